@@ -0,0 +1,143 @@
+//! The files a command's globs match, listed before it's confirmed: `rm
+//! *.log` says which logs go, `chmod -R 644 src/*` which files change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::shell;
+
+/// Programs whose glob arguments are expanded for the preview.
+const FILE_PROGRAMS: &[&str] = &["rm", "rmdir", "mv", "cp", "chmod", "chown", "chgrp", "shred", "truncate", "unlink"];
+
+/// At most this many files are kept per command; the rest are only counted.
+pub const MAX_LISTED: usize = 50;
+
+/// The files one file-changing command in a line matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Affected {
+    /// e.g. `rm`
+    pub program: String,
+    /// first [`MAX_LISTED`] matches, sorted
+    pub files: Vec<PathBuf>,
+    /// matches beyond the listed ones
+    pub more: usize,
+}
+
+/// What the globs of file-changing commands in `command` match in `dir`.
+/// Commands without globs, or whose globs match nothing, aren't listed.
+pub fn affected(command: &str, dir: &Path) -> Vec<Affected> {
+    let mut found = Vec::new();
+    for words in shell::simple_commands(command) {
+        // simple commands have at least one word
+        let program = shell::program_name(words[0]);
+        // `sudo rm ...` changes the same files
+        let (program, args) = match program {
+            "sudo" | "doas" => match words.get(1) {
+                Some(program) => (shell::program_name(program), &words[2..]),
+                None => continue,
+            },
+            _ => (program, &words[1..]),
+        };
+        if !FILE_PROGRAMS.contains(&program) {
+            continue;
+        }
+
+        let mut files: Vec<PathBuf> = args
+            .iter()
+            .filter(|arg| is_glob(arg))
+            .flat_map(|pattern| expand(pattern, dir))
+            .map(|path| path.strip_prefix(dir).map(Path::to_path_buf).unwrap_or(path))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        files.sort();
+        files.dedup();
+        let more = files.len().saturating_sub(MAX_LISTED);
+        files.truncate(MAX_LISTED);
+        found.push(Affected { program: program.to_string(), files, more });
+    }
+    found
+}
+
+/// An unquoted argument with `*`, `?` or `[`, which the shell expands.
+fn is_glob(arg: &str) -> bool {
+    !arg.starts_with(['\'', '"', '-']) && arg.contains(['*', '?', '['])
+}
+
+/// The paths an argument stands for: what it matches if it's a glob, the
+/// path it names otherwise.
+pub(crate) fn expand_arg(arg: &str, dir: &Path) -> Vec<PathBuf> {
+    if is_glob(arg) {
+        return expand(arg, dir);
+    }
+    let arg = arg.trim_matches(['"', '\'']);
+    match arg.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map(|home| home.join(rest)).into_iter().collect(),
+        None => vec![dir.join(arg)],
+    }
+}
+
+/// The existing paths `pattern` matches, like the shell would expand it.
+fn expand(pattern: &str, dir: &Path) -> Vec<PathBuf> {
+    let (mut paths, rest) = match pattern.strip_prefix("~/") {
+        Some(rest) => (dirs::home_dir().into_iter().collect::<Vec<_>>(), rest),
+        None if pattern.starts_with('/') => (vec![PathBuf::from("/")], &pattern[1..]),
+        None => (vec![dir.to_path_buf()], pattern),
+    };
+    for component in rest.split('/').filter(|c| !c.is_empty()) {
+        if !is_glob(component) {
+            paths = paths.into_iter().map(|path| path.join(component)).filter(|path| path.exists()).collect();
+            continue;
+        }
+        let pattern: Vec<char> = component.chars().collect();
+        paths = paths
+            .iter()
+            .filter_map(|path| fs::read_dir(path).ok())
+            .flatten()
+            .flatten()
+            .filter(|entry| {
+                let name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+                // hidden files only match a pattern that starts with a dot
+                (name.first() != Some(&'.') || pattern.first() == Some(&'.')) && matches(&pattern, &name)
+            })
+            .map(|entry| entry.path())
+            .collect();
+    }
+    paths
+}
+
+/// Whether `name` matches a glob with `*`, `?` and `[...]`/`[!...]` classes.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                // no closing bracket: a literal `[`
+                return name.first() == Some(&'[') && matches(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negated, class) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            let mut in_class = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    in_class |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_class |= class[i] == c;
+                    i += 1;
+                }
+            }
+            in_class != negated && matches(&pattern[end + 1..], &name[1..])
+        }
+        Some(&literal) => name.first() == Some(&literal) && matches(&pattern[1..], &name[1..]),
+    }
+}
@@ -0,0 +1,161 @@
+/// A color selected by an SGR escape sequence.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AnsiColor {
+    /// Entry of the 256 color palette; 0-15 are the standard and bright colors.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Text attributes in effect for a run of output.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct AnsiStyle {
+    pub fg: Option<AnsiColor>,
+    pub bg: Option<AnsiColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// A run of text sharing one style.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub text: String,
+    pub style: AnsiStyle,
+}
+
+/// Splits `input` into styled spans, interpreting SGR color codes and
+/// dropping every other escape sequence.
+///
+/// Carriage returns are treated the way a terminal would show them: the text
+/// after the last `\r` on a line replaces what came before it, so progress
+/// bars collapse to their final state.
+pub fn parse(input: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut style = AnsiStyle::default();
+    let mut text = String::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {}
+            '\r' if chars.peek() == Some(&'\n') => continue,
+            '\r' => {
+                carriage_return(&mut spans, &mut text);
+                continue;
+            }
+            _ => {
+                text.push(c);
+                continue;
+            }
+        }
+
+        match chars.next() {
+            // CSI: parameters up to a final byte in 0x40..=0x7e
+            Some('[') => {
+                let mut params = String::new();
+                let mut final_byte = None;
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        final_byte = Some(c);
+                        break;
+                    }
+                    params.push(c);
+                }
+
+                if final_byte == Some('m') {
+                    let mut next = style;
+                    apply_sgr(&mut next, &params);
+                    if next != style && !text.is_empty() {
+                        spans.push(Span { text: std::mem::take(&mut text), style });
+                    }
+                    style = next;
+                }
+            }
+            // OSC (window titles, hyperlinks): terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // character set designation, e.g. `ESC ( B`
+            Some('(' | ')' | '*' | '+') => {
+                chars.next();
+            }
+            // any other two character escape
+            _ => {}
+        }
+    }
+
+    if !text.is_empty() {
+        spans.push(Span { text, style });
+    }
+    spans
+}
+
+/// Returns `input` with all escape sequences removed.
+pub fn strip(input: &str) -> String {
+    parse(input).into_iter().map(|span| span.text).collect()
+}
+
+/// Discards everything since the last newline, as a terminal would overwrite it.
+fn carriage_return(spans: &mut Vec<Span>, text: &mut String) {
+    if let Some(pos) = text.rfind('\n') {
+        text.truncate(pos + 1);
+        return;
+    }
+    text.clear();
+
+    while let Some(span) = spans.last_mut() {
+        if let Some(pos) = span.text.rfind('\n') {
+            span.text.truncate(pos + 1);
+            return;
+        }
+        spans.pop();
+    }
+}
+
+fn apply_sgr(style: &mut AnsiStyle, params: &str) {
+    // `ESC[m` is the same as `ESC[0m`
+    if params.is_empty() {
+        *style = AnsiStyle::default();
+        return;
+    }
+
+    let mut codes = params.split(';').map(|p| p.parse::<u16>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        match code {
+            0 => *style = AnsiStyle::default(),
+            1 => style.bold = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            22 => style.bold = false,
+            23 => style.italic = false,
+            24 => style.underline = false,
+            30..=37 => style.fg = Some(AnsiColor::Indexed(code as u8 - 30)),
+            38 => style.fg = extended_color(&mut codes),
+            39 => style.fg = None,
+            40..=47 => style.bg = Some(AnsiColor::Indexed(code as u8 - 40)),
+            48 => style.bg = extended_color(&mut codes),
+            49 => style.bg = None,
+            90..=97 => style.fg = Some(AnsiColor::Indexed(code as u8 - 90 + 8)),
+            100..=107 => style.bg = Some(AnsiColor::Indexed(code as u8 - 100 + 8)),
+            _ => {}
+        }
+    }
+}
+
+/// Reads the `5;n` or `2;r;g;b` tail of a 38/48 code.
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<AnsiColor> {
+    let mut next = || codes.next().map(|c| c.min(255) as u8);
+    match next()? {
+        5 => Some(AnsiColor::Indexed(next()?)),
+        2 => Some(AnsiColor::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
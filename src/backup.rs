@@ -0,0 +1,246 @@
+//! Snapshots of the files a risky command is about to change, so they can
+//! be put back afterwards. Tracked files of a git repository are kept as a
+//! stash commit (`git stash create`, which leaves the working tree alone);
+//! anything else is copied under `~/.llm_term_backups/<id>/`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::affected;
+use crate::shell;
+
+/// More than this is too much to copy before every command.
+pub const MAX_BACKUP_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Snapshots kept; older ones, and their copies, are removed.
+const KEPT_SNAPSHOTS: usize = 20;
+
+/// Programs that change or remove the files they're given.
+const CHANGING_PROGRAMS: &[&str] = &["rm", "rmdir", "mv", "chmod", "chown", "chgrp", "shred", "truncate", "unlink"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub id: String,
+    /// the command it was taken for
+    pub command: String,
+    pub created: DateTime<Utc>,
+    /// backed up paths, absolute
+    pub paths: Vec<PathBuf>,
+    /// repository and commit holding the files, when they're all tracked in one
+    #[serde(default)]
+    pub git: Option<(PathBuf, String)>,
+}
+
+pub fn backups_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".llm_term_backups"))
+}
+
+fn index_path() -> Option<PathBuf> {
+    backups_dir().map(|dir| dir.join("index.json"))
+}
+
+/// The snapshots taken so far, oldest first.
+pub fn list() -> Vec<Snapshot> {
+    index_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn find(id: &str) -> Option<Snapshot> {
+    list().into_iter().find(|snapshot| snapshot.id == id)
+}
+
+/// The existing files and directories `command` would change or remove,
+/// run in `dir`: the arguments of `rm`, `mv`, `chmod` and the like, files
+/// edited with `sed -i`, and files overwritten with `>`. Deletions are
+/// left out when `trashing`, as the trash keeps those already.
+pub fn targets(command: &str, dir: &Path, trashing: bool) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+    for words in shell::simple_commands(command) {
+        let (program, args) = match shell::program_name(words[0]) {
+            "sudo" | "doas" if words.len() > 1 => (shell::program_name(words[1]), &words[2..]),
+            program => (program, &words[1..]),
+        };
+        let changes_args = match program {
+            "rm" | "rmdir" | "unlink" | "shred" => !trashing,
+            "sed" | "perl" => args.iter().any(|arg| arg.starts_with("-i") || *arg == "--in-place"),
+            _ => CHANGING_PROGRAMS.contains(&program),
+        };
+        if changes_args {
+            for arg in args.iter().filter(|arg| !arg.starts_with('-')) {
+                targets.extend(affected::expand_arg(arg, dir));
+            }
+        }
+        // `> file` and `>file`, but not `>>`, which only appends
+        for (i, word) in words.iter().enumerate() {
+            let file = match word.strip_prefix('>') {
+                Some("") => words.get(i + 1).copied(),
+                Some(file) if !file.starts_with(['>', '&']) => Some(file),
+                _ => None,
+            };
+            targets.extend(file.map(|file| dir.join(file.trim_matches(['"', '\'']))).filter(|path| path.is_file()));
+        }
+    }
+    // `> /dev/null` changes nothing worth keeping
+    targets.retain(|path| path.exists() && !path.starts_with("/dev"));
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Backs up `paths` before `command` runs.
+pub fn take(command: &str, paths: &[PathBuf]) -> Result<Snapshot, String> {
+    let paths: Vec<PathBuf> = paths.iter().map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone())).collect();
+    let mut snapshot = Snapshot { id: new_id(), command: command.to_string(), created: Utc::now(), paths, git: None };
+
+    match git_snapshot(&snapshot.paths) {
+        Some(git) => snapshot.git = Some(git),
+        None => {
+            let size: u64 = snapshot.paths.iter().map(|path| size_of(path)).sum();
+            if size > MAX_BACKUP_BYTES {
+                return Err(format!("{} MiB is too much to back up", size / (1024 * 1024)));
+            }
+            let dir = snapshot_dir(&snapshot.id)?;
+            for path in &snapshot.paths {
+                copy(path, &dir.join(stored_name(path))).map_err(|e| format!("Can't back up {}: {}", path.display(), e))?;
+            }
+        }
+    }
+
+    let mut snapshots = list();
+    snapshots.push(snapshot.clone());
+    let excess = snapshots.len().saturating_sub(KEPT_SNAPSHOTS);
+    for old in snapshots.drain(..excess) {
+        if let (None, Ok(dir)) = (&old.git, snapshot_dir(&old.id)) {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+    save(&snapshots)?;
+    Ok(snapshot)
+}
+
+/// Puts the files of `snapshot` back as they were when it was taken.
+pub fn restore(snapshot: &Snapshot) -> Result<(), String> {
+    if let Some((repo, commit)) = &snapshot.git {
+        let mut git = Command::new("git");
+        git.arg("-C").arg(repo).args(["checkout", commit, "--"]);
+        for path in &snapshot.paths {
+            git.arg(path.strip_prefix(repo).unwrap_or(path));
+        }
+        let output = git.stdin(Stdio::null()).output().map_err(|e| format!("Can't run git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("git checkout failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        return Ok(());
+    }
+
+    let dir = snapshot_dir(&snapshot.id)?;
+    for path in &snapshot.paths {
+        if path.is_dir() {
+            fs::remove_dir_all(path).map_err(|e| format!("Can't replace {}: {}", path.display(), e))?;
+        }
+        copy(&dir.join(stored_name(path)), path).map_err(|e| format!("Can't restore {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// A commit with the current content of `paths` if they're all tracked files
+/// of one repository: its stash commit, or HEAD when nothing is modified.
+fn git_snapshot(paths: &[PathBuf]) -> Option<(PathBuf, String)> {
+    let first = paths.first()?;
+    let repo = git(first.parent()?, &["rev-parse", "--show-toplevel"]).map(PathBuf::from)?;
+    let repo = fs::canonicalize(&repo).unwrap_or(repo);
+    let all_tracked = paths.iter().all(|path| {
+        path.is_file()
+            && path.starts_with(&repo)
+            && Command::new("git").arg("-C").arg(&repo).args(["ls-files", "--error-unmatch", "--"]).arg(path).stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok_and(|status| status.success())
+    });
+    if !all_tracked {
+        return None;
+    }
+    let commit = match git(&repo, &["stash", "create"]) {
+        // kept reachable, so `git gc` doesn't collect it
+        Some(stash) => {
+            git(&repo, &["stash", "store", "-m", "llm-term backup", &stash]);
+            stash
+        }
+        None => git(&repo, &["rev-parse", "HEAD"])?,
+    };
+    Some((repo, commit))
+}
+
+/// The trimmed output of a successful `git` run in `dir`, `None` if empty.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+fn save(snapshots: &[Snapshot]) -> Result<(), String> {
+    let path = index_path().ok_or("No home directory")?;
+    fs::create_dir_all(path.parent().ok_or("No home directory")?).map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(snapshots).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+fn snapshot_dir(id: &str) -> Result<PathBuf, String> {
+    backups_dir().map(|dir| dir.join(id)).ok_or_else(|| "No home directory".to_string())
+}
+
+/// A timestamp like `20260314-091530`, with `-2`, `-3`... when taken twice a second.
+fn new_id() -> String {
+    let base = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let taken: Vec<String> = list().into_iter().map(|snapshot| snapshot.id).collect();
+    (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}-{}", base, n) })
+        .find(|id| !taken.contains(id) && snapshot_dir(id).is_ok_and(|dir| !dir.exists()))
+        .unwrap_or(base)
+}
+
+/// Where a backed up path is kept inside its snapshot directory: the whole
+/// path, so two `config.json`s from different places don't clash.
+fn stored_name(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_os_string()),
+            Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().replace(':', "").into()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Copies a file, or a directory with everything in it. Symbolic links are
+/// copied as links, not followed.
+fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let meta = fs::symlink_metadata(from)?;
+    if meta.file_type().is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+        return Ok(());
+    }
+    if meta.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path).into_iter().flatten().flatten().map(|entry| size_of(&entry.path())).sum(),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::remote_cache::RemoteCache;
+use crate::vault;
+
+/// Prompt -> command cache, optionally backed by a JSON file.
+#[derive(Default)]
+pub struct Cache {
+    /// where [`Cache::save`] writes to; `None` keeps the cache in memory only
+    path: Option<PathBuf>,
+    entries: HashMap<String, String>,
+    remote: Option<RemoteCache>,
+    /// inserted since the last save, still to be shared with `remote`
+    unsynced: Vec<(String, String)>,
+}
+
+impl Cache {
+    /// A cache that is never written to disk.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Reads the cache stored at `path`. A missing file gives an empty cache
+    /// that will be created there on the first save.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(content) => serde_json::from_str(&vault::open_to_string(&content)?)?,
+            Err(_) => HashMap::new(),
+        };
+        Ok(Self { path: Some(path), entries, ..Self::default() })
+    }
+
+    /// Falls back to `remote` on local misses and shares new entries with it.
+    /// The remote cache is best effort: when it can't be reached it is ignored.
+    pub fn with_remote(mut self, remote: RemoteCache) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn get(&self, prompt: &str) -> Option<&str> {
+        self.entries.get(prompt).map(String::as_str)
+    }
+
+    /// Like [`get`](Self::get), but asks the remote cache when the prompt
+    /// isn't cached locally, and keeps what it finds.
+    pub fn lookup(&mut self, prompt: &str) -> Option<String> {
+        if let Some(command) = self.get(prompt) {
+            log::info!("cache hit");
+            return Some(command.to_string());
+        }
+        let Some(remote) = &self.remote else {
+            log::info!("cache miss");
+            return None;
+        };
+        let Some(command) = remote.get(prompt).ok().flatten() else {
+            log::info!("cache miss remote=true");
+            return None;
+        };
+        log::info!("cache hit remote=true");
+        self.entries.insert(prompt.to_string(), command.clone());
+        let _ = self.write();
+        Some(command)
+    }
+
+    pub fn insert(&mut self, prompt: impl Into<String>, command: impl Into<String>) {
+        let (prompt, command) = (prompt.into(), command.into());
+        if self.remote.is_some() {
+            self.unsynced.push((prompt.clone(), command.clone()));
+        }
+        self.entries.insert(prompt, command);
+    }
+
+    pub fn remove(&mut self, prompt: &str) -> Option<String> {
+        self.entries.remove(prompt)
+    }
+
+    /// All `(prompt, command)` pairs, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(prompt, command)| (prompt.as_str(), command.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes the cache back to its file, encrypted if a vault is unlocked,
+    /// and shares new entries with the remote cache. The file isn't written
+    /// for an in-memory cache.
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.write()?;
+        if let Some(remote) = &self.remote {
+            for (prompt, command) in self.unsynced.drain(..) {
+                let _ = remote.put(&prompt, &command);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = &self.path {
+            fs::write(path, vault::seal(serde_json::to_string_pretty(&self.entries)?.as_bytes()))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! What a command actually changed in its directory: the files are listed,
+//! with `git status` in a repository, before and after a file-changing
+//! command runs, and compared afterwards.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use crate::shell;
+
+/// Programs that usually change files; any command writing to a file with `>` does too.
+const FILE_CHANGING: &[&str] = &[
+    "rm", "rmdir", "mv", "cp", "mkdir", "touch", "ln", "chmod", "chown", "truncate", "shred", "unlink", "sed", "perl", "tar", "unzip", "gzip",
+    "gunzip", "zip", "rsync", "git", "patch", "npm", "yarn", "pnpm", "cargo", "make", "pip", "go", "curl", "wget", "Remove-Item", "Move-Item",
+    "Copy-Item", "New-Item", "Rename-Item", "del", "move", "copy", "ren", "md",
+];
+
+/// A listing stops after this many files, so a huge tree doesn't hold up the command.
+const MAX_FILES: usize = 20_000;
+
+/// Directories left out of the listing.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// The state of a directory at one moment.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub dir: PathBuf,
+    /// every file under `dir`, relative to it, with its size and modification time
+    files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+    /// the listing stopped at `MAX_FILES`
+    truncated: bool,
+    /// `git status --porcelain` lines, if `dir` is in a repository
+    git_status: Option<Vec<String>>,
+}
+
+/// How a directory differs between two snapshots.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    pub dir: PathBuf,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    /// `git status` lines that appeared
+    pub git_new: Vec<String>,
+    /// `git status` lines that went away, e.g. changes that were committed
+    pub git_gone: Vec<String>,
+    /// a listing was cut short, so changes deep in a big tree can be missing
+    pub truncated: bool,
+}
+
+/// Whether `command` looks like it changes files, so it's worth a snapshot.
+pub fn affects_files(command: &str) -> bool {
+    shell::redirects_output(command)
+        || shell::simple_commands(command).iter().any(|words| {
+            words.iter().take(2).any(|word| FILE_CHANGING.iter().any(|program| program.eq_ignore_ascii_case(shell::program_name(word))))
+        })
+}
+
+impl Snapshot {
+    /// Lists `dir` and asks git about it.
+    pub fn take(dir: &Path) -> Snapshot {
+        let mut snapshot = Snapshot { dir: dir.to_path_buf(), git_status: git_status(dir), ..Snapshot::default() };
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&current) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    if !SKIPPED_DIRS.iter().any(|skipped| entry.file_name() == *skipped) {
+                        pending.push(path.clone());
+                    }
+                    continue;
+                }
+                if snapshot.files.len() >= MAX_FILES {
+                    snapshot.truncated = true;
+                    return snapshot;
+                }
+                let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+                snapshot.files.insert(relative, (metadata.len(), metadata.modified().ok()));
+            }
+        }
+        snapshot
+    }
+
+    /// What changed between this snapshot and `after`.
+    pub fn compare(&self, after: &Snapshot) -> Changes {
+        let mut changes = Changes { dir: self.dir.clone(), truncated: self.truncated || after.truncated, ..Changes::default() };
+        for (path, state) in &after.files {
+            match self.files.get(path) {
+                None => changes.added.push(path.clone()),
+                Some(before) if before != state => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = self.files.keys().filter(|path| !after.files.contains_key(*path)).cloned().collect();
+        if let (Some(before), Some(after)) = (&self.git_status, &after.git_status) {
+            changes.git_new = after.iter().filter(|line| !before.contains(line)).cloned().collect();
+            changes.git_gone = before.iter().filter(|line| !after.contains(line)).cloned().collect();
+        }
+        changes
+    }
+}
+
+/// At most this many files are named per kind of change; the rest are counted.
+const MAX_NAMED: usize = 20;
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() && self.git_new.is_empty() && self.git_gone.is_empty()
+    }
+
+    /// The changes in words, one kind per line.
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            let note = if self.truncated { " (only part of it was listed)" } else { "" };
+            return format!("Nothing changed in {}{}.", self.dir.display(), note);
+        }
+        let mut lines = vec![format!("What changed in {}:", self.dir.display())];
+        for (label, paths) in [("Added", &self.added), ("Removed", &self.removed), ("Modified", &self.modified)] {
+            if paths.is_empty() {
+                continue;
+            }
+            let mut named: Vec<String> = paths.iter().take(MAX_NAMED).map(|path| path.display().to_string()).collect();
+            if paths.len() > MAX_NAMED {
+                named.push(format!("and {} more", paths.len() - MAX_NAMED));
+            }
+            lines.push(format!("{} ({}): {}", label, paths.len(), named.join(", ")));
+        }
+        if !self.git_new.is_empty() {
+            lines.push(format!("git status now shows: {}", self.git_new.join(", ")));
+        }
+        if !self.git_gone.is_empty() {
+            lines.push(format!("git status no longer shows: {}", self.git_gone.join(", ")));
+        }
+        if self.truncated {
+            lines.push(format!("Only the first {} files were compared.", MAX_FILES));
+        }
+        lines.join("\n")
+    }
+}
+
+/// `git status --porcelain` in `dir`; `None` outside a repository or without git.
+fn git_status(dir: &Path) -> Option<Vec<String>> {
+    let output = Command::new("git").args(["status", "--porcelain"]).current_dir(dir).stdin(Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+}
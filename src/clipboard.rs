@@ -0,0 +1,43 @@
+use std::process::Command as ProcessCommand;
+
+/// Clipboard text beyond this is cut off before it's sent to the model.
+const MAX_CONTEXT_CHARS: usize = 8000;
+
+/// Programs that print the clipboard, tried in order.
+fn paste_commands() -> &'static [(&'static str, &'static [&'static str])] {
+    if cfg!(target_os = "windows") {
+        &[("powershell", &["-NoProfile", "-Command", "Get-Clipboard -Raw"])]
+    } else if cfg!(target_os = "macos") {
+        &[("pbpaste", &[])]
+    } else {
+        &[
+            ("wl-paste", &["--no-newline"]),
+            ("xclip", &["-selection", "clipboard", "-o"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    }
+}
+
+/// Reads the current clipboard contents as text.
+pub fn read() -> Result<String, String> {
+    for (program, args) in paste_commands() {
+        if let Ok(output) = ProcessCommand::new(program).args(*args).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+        }
+    }
+
+    let tried: Vec<&str> = paste_commands().iter().map(|(program, _)| *program).collect();
+    Err(format!("Could not read the clipboard (tried {})", tried.join(", ")))
+}
+
+/// Appends clipboard `contents` to `prompt` as context for the model.
+pub fn attach(prompt: &str, contents: &str) -> String {
+    let contents = contents.trim();
+    let mut context: String = contents.chars().take(MAX_CONTEXT_CHARS).collect();
+    if context.len() < contents.len() {
+        context.push_str("\n[... truncated]");
+    }
+    format!("{}\n\nContext from my clipboard:\n```\n{}\n```", prompt, context)
+}
@@ -0,0 +1,155 @@
+//! Guardrails for the `aws`, `gcloud` and `az` CLIs: which account a command
+//! runs against, and a typed confirmation before one that deletes things.
+
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::guard::Guard;
+use crate::shell;
+
+/// How long a looked up profile setting or project is trusted.
+const TTL: Duration = Duration::from_secs(30);
+
+/// Parts of a subcommand that mean something goes away for good, as in
+/// `terminate-instances` or `gcloud compute instances delete`; `rm` and
+/// `rb` are `aws s3`'s.
+const DESTRUCTIVE_VERBS: &[&str] = &["delete", "terminate", "destroy", "rm", "rb"];
+
+/// Global flags of the CLIs that are followed by a value, which isn't a subcommand.
+const VALUE_FLAGS: &[&str] = &["--profile", "--region", "--output", "--endpoint-url", "--query", "--project", "--account", "--configuration", "--format", "--subscription", "-o"];
+
+/// The account a cloud CLI command runs against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    /// "AWS", "Google Cloud" or "Azure"
+    pub cloud: &'static str,
+    /// e.g. "profile `default`, region `eu-west-1`"
+    pub description: String,
+    /// the profile, project or subscription, if known
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.cloud, self.description)
+    }
+}
+
+/// The account of the first cloud CLI call in `command`, if there's one.
+pub fn account(command: &str) -> Option<Account> {
+    shell::simple_commands(command).iter().find_map(|words| account_for(words))
+}
+
+/// A typed confirmation for cloud CLI calls that delete, terminate or
+/// destroy something; the name of the account has to be typed.
+pub fn guard(command: &str) -> Option<Guard> {
+    for words in shell::simple_commands(command) {
+        let Some(verb) = destructive_verb(&words) else {
+            continue;
+        };
+        let Some(account) = account_for(&words) else {
+            continue;
+        };
+        let warning = format!("`{}` can't be undone. It runs against {}.", verb, account);
+        let confirm = account.name.unwrap_or_else(|| verb.to_string());
+        return Some(Guard { warning, confirm });
+    }
+    None
+}
+
+fn account_for(words: &[&str]) -> Option<Account> {
+    match shell::program_name(words.first()?) {
+        "aws" => {
+            let profile = shell::flag_value(words, &["--profile"]).or_else(|| env("AWS_PROFILE")).unwrap_or_else(|| "default".to_string());
+            let region = shell::flag_value(words, &["--region"])
+                .or_else(|| env("AWS_REGION"))
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .or_else(|| output("aws", &["configure", "get", "region", "--profile", &profile]));
+            let description = match region {
+                Some(region) => format!("profile `{}`, region `{}`", profile, region),
+                None => format!("profile `{}`", profile),
+            };
+            Some(Account { cloud: "AWS", description, name: Some(profile) })
+        }
+        "gcloud" => {
+            let project = shell::flag_value(words, &["--project"])
+                .or_else(|| env("CLOUDSDK_CORE_PROJECT"))
+                .or_else(|| output("gcloud", &["config", "get-value", "project"]));
+            let description = project.as_ref().map_or_else(|| "with no project set".to_string(), |project| format!("project `{}`", project));
+            Some(Account { cloud: "Google Cloud", description, name: project })
+        }
+        "az" => {
+            let subscription = shell::flag_value(words, &["--subscription"]).or_else(|| output("az", &["account", "show", "--query", "name", "-o", "tsv"]));
+            let description = subscription.as_ref().map_or_else(|| "while not logged in".to_string(), |subscription| format!("subscription `{}`", subscription));
+            Some(Account { cloud: "Azure", description, name: subscription })
+        }
+        _ => None,
+    }
+}
+
+/// The subcommand word of a cloud CLI call that destroys something, e.g. `delete-stack`.
+/// Only the service and subcommand words are looked at, not the names,
+/// paths and values after them, so `aws s3 cp rm-me.txt s3://bucket/` is fine.
+fn destructive_verb<'a>(words: &[&'a str]) -> Option<&'a str> {
+    // `aws <service> <operation>`, `gcloud compute instances delete`, `az network nsg rule delete`
+    let (program, depth) = match shell::program_name(words.first()?) {
+        "aws" => ("aws", 2),
+        "gcloud" => ("gcloud", 3),
+        "az" => ("az", 4),
+        _ => return None,
+    };
+    let mut subcommands = Vec::new();
+    let mut rest = words[1..].iter().copied();
+    while let Some(word) = rest.next() {
+        if word.starts_with('-') {
+            if VALUE_FLAGS.contains(&word) {
+                rest.next();
+            }
+            continue;
+        }
+        // a path, URL, ARN, key=value or file name: the operands have started
+        if word.contains(['/', '=', ':', '.']) || subcommands.len() == depth {
+            break;
+        }
+        if program == "gcloud" && subcommands.is_empty() && matches!(word, "alpha" | "beta" | "preview") {
+            continue;
+        }
+        subcommands.push(word);
+    }
+    subcommands.into_iter().find(|word| match program {
+        "aws" => word.split('-').any(|part| DESTRUCTIVE_VERBS.contains(&part)),
+        // `delete` or `delete-batch`; `rm` and `rb` are only `aws s3`'s
+        _ => word.split('-').next().is_some_and(|verb| DESTRUCTIVE_VERBS.contains(&verb) && !matches!(verb, "rm" | "rb")),
+    })
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// CLI outputs by program and arguments, with when they were looked up.
+type Outputs = HashMap<Vec<String>, (Instant, Option<String>)>;
+
+static OUTPUTS: Mutex<Option<Outputs>> = Mutex::new(None);
+
+/// The trimmed output of `program args`, `None` if it fails, prints nothing
+/// or says the value is `(unset)`. Cached for [`TTL`], as the CLIs are slow to start.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let key: Vec<String> = std::iter::once(program).chain(args.iter().copied()).map(String::from).collect();
+    let mut outputs = OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+    let outputs = outputs.get_or_insert_with(HashMap::new);
+    if let Some((_, value)) = outputs.get(&key).filter(|(at, _)| at.elapsed() < TTL) {
+        return value.clone();
+    }
+
+    let value = shell::on_path(program)
+        .then(|| Command::new(program).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output().ok())
+        .flatten()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|text| !text.is_empty() && text != "(unset)");
+    outputs.insert(key, (Instant::now(), value.clone()));
+    value
+}
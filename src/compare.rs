@@ -0,0 +1,67 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::context::estimate_tokens;
+use crate::generator;
+use crate::model::Model;
+use crate::shell::Shell;
+use crate::Config;
+
+/// One model's answer to a [`compare`] run.
+pub struct Candidate {
+    pub model: Model,
+    /// the command, `Ok(None)` if the model gave none, or the error message
+    pub command: Result<Option<String>, String>,
+    pub latency: Duration,
+    /// estimated from the prompt and answer length, in USD
+    pub estimated_cost: f64,
+}
+
+/// Asks every model in `models` for a command for `prompt` at the same time,
+/// using `config` for everything but the model. Results are in the order of `models`.
+pub fn compare(config: &Config, models: &[Model], prompt: &str) -> Vec<Candidate> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = models
+            .iter()
+            .map(|model| {
+                // each model answers by itself, without routing or fallbacks
+                let config = Config { model: model.clone(), routing: None, fallback: Vec::new(), ..config.clone() };
+                scope.spawn(move || {
+                    let started = Instant::now();
+                    let command = config.model.llm_get_command(&config, prompt).map_err(|e| e.to_string()).and_then(|reply| {
+                        // a question isn't something to run
+                        match reply.as_deref().and_then(generator::question) {
+                            Some(question) => Err(format!("Asks: {}", question)),
+                            None => Ok(reply),
+                        }
+                    });
+                    let latency = started.elapsed();
+
+                    let input_tokens = estimate_tokens(&config.model.get_system_prompt(&Shell::detect())) + estimate_tokens(prompt);
+                    let output_tokens = match &command {
+                        Ok(Some(command)) => estimate_tokens(command),
+                        _ => 0,
+                    };
+                    let (input_price, output_price) = config.model.price_per_million_tokens();
+                    let estimated_cost = (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000_000.0;
+
+                    Candidate { model: config.model, command, latency, estimated_cost }
+                })
+            })
+            .collect();
+
+        // a missing OPENAI_API_KEY panics inside the request; keep the other answers
+        handles
+            .into_iter()
+            .zip(models)
+            .map(|(handle, model)| {
+                handle.join().unwrap_or_else(|_| Candidate {
+                    model: model.clone(),
+                    command: Err("the request failed; is OPENAI_API_KEY set?".to_string()),
+                    latency: Duration::ZERO,
+                    estimated_cost: 0.0,
+                })
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::executor::ResourceLimits;
+use crate::model::Model;
+use crate::postprocess::{self, Rule};
+use crate::provider::{OllamaOptions, ReasoningEffort};
+use crate::rag::Rag;
+use crate::rate_limit::RateLimit;
+use crate::router::Routing;
+use crate::speech::Speech;
+use crate::voice::Transcriber;
+
+/// The config format this llm-term writes; older files are migrated when read.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// What a file's `max_tokens` is when it has none.
+pub const DEFAULT_MAX_TOKENS: i32 = 1000;
+
+/// Model names from before `version`, and what they're called now.
+const LEGACY_MODELS: &[(&str, &str)] = &[
+    ("gpt-3.5-turbo", "gpt-4o-mini"),
+    ("gpt-4", "gpt-4o"),
+    ("gpt-4-turbo", "gpt-4o"),
+    ("gpt4o", "gpt-4o"),
+    ("gpt4o-mini", "gpt-4o-mini"),
+    ("llama3", "ollama:llama3"),
+    ("llama3.1", "ollama:llama3.1"),
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Config {
+    /// the format of the file, [`CONFIG_VERSION`] once migrated; files without one are 0
+    #[serde(default)]
+    pub version: u32,
+    pub model: Model,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: i32,
+    /// command output beyond this many bytes is kept in a separate file
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    /// language for conversational replies, e.g. "Spanish" or "de"; commands stay as they are
+    #[serde(default)]
+    pub language: Option<String>,
+    /// GUI: desktop notification when a command or answer finishes while the window is unfocused
+    #[serde(default = "default_notifications")]
+    pub notifications: bool,
+    /// GUI: only notify for work that took at least this many seconds
+    #[serde(default = "default_notify_after_secs")]
+    pub notify_after_secs: u64,
+    /// encrypt sessions, caches and history with a passphrase asked for on start
+    #[serde(default)]
+    pub encrypt_storage: bool,
+    /// mask API keys, tokens, emails and the like before they are sent to the model
+    #[serde(default = "default_redact_secrets")]
+    pub redact_secrets: bool,
+    /// extra regexes whose matches are masked too
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// team-shared cache server asked when the local cache misses
+    #[serde(default)]
+    pub remote_cache_url: Option<String>,
+    /// bearer token for `remote_cache_url`
+    #[serde(default)]
+    pub remote_cache_token: Option<String>,
+    /// never run commands, only show and copy them; for locked-down machines
+    #[serde(default)]
+    pub suggest_only: bool,
+    /// CPU, memory and output caps for the commands llm-term runs
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// package manager for install commands, e.g. "brew"; the first one found otherwise
+    #[serde(default)]
+    pub package_manager: Option<String>,
+    /// ask a cheap model first and escalate to a stronger one when needed; `model` is then unused
+    #[serde(default)]
+    pub routing: Option<Routing>,
+    /// models tried in order when the configured one errors or times out
+    #[serde(default)]
+    pub fallback: Vec<Model>,
+    /// give up on a model request after this many seconds; 0 waits forever
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// proxy for model and remote cache requests; `HTTPS_PROXY` and friends otherwise
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM file with extra CA certificates to trust, e.g. a corporate proxy's
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+    /// accept any TLS certificate; only for proxies whose CA isn't available
+    #[serde(default)]
+    pub tls_skip_verify: bool,
+    /// move files `rm` would delete to the trash instead; `--permanent` skips it once
+    #[serde(default = "default_use_trash")]
+    pub use_trash: bool,
+    /// how dictated prompts are transcribed: "openai", or {"whisper_cpp": {"model": "ggml-base.bin"}}
+    #[serde(default)]
+    pub transcriber: Transcriber,
+    /// reading answers aloud in the GUI: engine and speed
+    #[serde(default)]
+    pub speech: Speech,
+    /// commands run before for the most similar prompts, shown to the model as examples; 0 turns them off
+    #[serde(default = "default_few_shot_examples")]
+    pub few_shot_examples: usize,
+    /// notes and dotfiles searched for excerpts to send along with prompts, once indexed with `llm-term index`
+    #[serde(default)]
+    pub rag: Option<Rag>,
+    /// check generated commands' flags against the local man pages or `--help`, with a second request
+    #[serde(default)]
+    pub check_flags: bool,
+    /// requests and tokens per minute sent to all models together
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    /// the same per provider, keyed "openai", "ollama" or "mock"
+    #[serde(default)]
+    pub provider_rate_limits: HashMap<String, RateLimit>,
+    /// the model stops a command reply at any of these, e.g. "\n\n" before an explanation; up to 4
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    /// clean-up applied to command replies, in order
+    #[serde(default = "postprocess::default_rules")]
+    pub post_process: Vec<Rule>,
+    /// Ollama's own API and its model options: keep_alive, num_ctx and seed
+    #[serde(default)]
+    pub ollama: OllamaOptions,
+    /// the Ollama server, e.g. "gpu-box:11434" or "http://192.168.1.20:11434"; `OLLAMA_HOST` or localhost otherwise
+    #[serde(default)]
+    pub ollama_host: Option<String>,
+    /// "low", "medium" or "high" thinking for o1, o3-mini and o4-mini; OpenAI's medium otherwise
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl Config {
+    /// A config for `model` with every optional setting at its default.
+    pub fn new(model: Model, max_tokens: i32) -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            model,
+            max_tokens,
+            max_output_bytes: default_max_output_bytes(),
+            language: None,
+            notifications: default_notifications(),
+            notify_after_secs: default_notify_after_secs(),
+            encrypt_storage: false,
+            redact_secrets: default_redact_secrets(),
+            redact_patterns: Vec::new(),
+            remote_cache_url: None,
+            remote_cache_token: None,
+            suggest_only: false,
+            limits: ResourceLimits::default(),
+            package_manager: None,
+            routing: None,
+            fallback: Vec::new(),
+            request_timeout_secs: default_request_timeout_secs(),
+            proxy: None,
+            ca_bundle: None,
+            tls_skip_verify: false,
+            use_trash: default_use_trash(),
+            transcriber: Transcriber::default(),
+            speech: Speech::default(),
+            few_shot_examples: default_few_shot_examples(),
+            rag: None,
+            check_flags: false,
+            rate_limit: RateLimit::default(),
+            provider_rate_limits: HashMap::new(),
+            stop_sequences: Vec::new(),
+            post_process: postprocess::default_rules(),
+            ollama: OllamaOptions::default(),
+            ollama_host: None,
+            reasoning_effort: None,
+        }
+    }
+}
+
+/// What [`Config::parse`] did to a file to read it.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// the file's version, when it was older and was migrated
+    pub migrated_from: Option<u32>,
+    /// settings migrated, e.g. an old model name
+    pub changes: Vec<String>,
+    /// settings that couldn't be read, and were ignored or left at their default
+    pub problems: Vec<String>,
+}
+
+impl LoadReport {
+    /// Whether the file can be saved as migrated without losing anything.
+    pub fn should_rewrite(&self) -> bool {
+        self.migrated_from.is_some() && self.problems.is_empty()
+    }
+}
+
+impl Config {
+    /// Reads a config file as hand edits and older versions leave it:
+    /// unknown settings are ignored, ones that can't be read are left at
+    /// their default, and old model names are migrated, all noted in the
+    /// report. Fails only when the text isn't a JSON object.
+    pub fn parse(text: &str) -> Result<(Config, LoadReport), String> {
+        let value: Value = serde_json::from_str(text).map_err(|e| format!("isn't valid JSON: {}", e))?;
+        let Value::Object(mut file) = value else {
+            return Err("isn't a JSON object".to_string());
+        };
+        let mut report = LoadReport::default();
+
+        let version = file.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+        if version < CONFIG_VERSION {
+            migrate(&mut file, version, &mut report);
+            report.migrated_from = Some(version);
+        } else if version > CONFIG_VERSION {
+            report.problems.push(format!("it's version {}, from a newer llm-term; this one reads version {}", version, CONFIG_VERSION));
+        }
+        file.remove("version");
+        if !file.contains_key("model") {
+            report.problems.push("no `model` is set; using gpt-4o-mini".to_string());
+        }
+
+        // each setting over the defaults, so one bad value doesn't lose the rest
+        let defaults = serde_json::to_value(Config::new(Model::OpenAiGpt4oMini, DEFAULT_MAX_TOKENS)).map_err(|e| e.to_string())?;
+        let Value::Object(mut merged) = defaults else {
+            return Err("the defaults aren't a JSON object".to_string());
+        };
+        for (key, value) in file {
+            let Some(default) = merged.insert(key.clone(), value) else {
+                merged.remove(&key);
+                report.problems.push(format!("unknown setting `{}` ignored", key));
+                continue;
+            };
+            if let Err(e) = serde_json::from_value::<Config>(Value::Object(merged.clone())) {
+                report.problems.push(format!("`{}` can't be read ({}); using the default", key, e));
+                merged.insert(key, default);
+            }
+        }
+        let config = serde_json::from_value(Value::Object(merged)).map_err(|e| e.to_string())?;
+        Ok((config, report))
+    }
+}
+
+/// Settings that name a model, written as on the command line, e.g. `ollama:llama3.1`.
+const MODEL_SETTINGS: &[&str] = &["model", "routing.first", "routing.escalate_to"];
+
+impl Config {
+    /// Every setting as `key`, dotted for nested ones like
+    /// `rate_limit.requests_per_minute`, and its value as [`Config::get`] shows it.
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = Vec::new();
+        if let Ok(Value::Object(values)) = serde_json::to_value(self) {
+            for (key, value) in values.into_iter().filter(|(key, _)| key != "version") {
+                flatten(&key, value, &mut settings);
+            }
+        }
+        settings
+    }
+
+    /// The value of the setting `key`: strings and model names as they are,
+    /// `fallback` as a comma-separated list, anything else as JSON.
+    pub fn get(&self, key: &str) -> Result<String, String> {
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        let value = key.split('.').try_fold(&value, |value, part| value.get(part)).filter(|_| key != "version");
+        let value = value.ok_or_else(|| format!("There's no setting `{}`; `llm-term config list` shows them", key))?;
+        Ok(show(key, value))
+    }
+
+    /// Sets `key` to `value`, read as JSON when it is, e.g. `2000`, `true`
+    /// or `null`, and as a string otherwise. Model settings take names like
+    /// `ollama:llama3.1`, and `fallback` a comma-separated list of them.
+    /// Nothing changes if the result isn't a valid config.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "version" {
+            return Err("`version` is kept by llm-term itself".to_string());
+        }
+        let unknown = || format!("There's no setting `{}`; `llm-term config list` shows them", key);
+        let mut values = serde_json::to_value(&*self).map_err(|e| e.to_string())?;
+        let parts: Vec<&str> = key.split('.').collect();
+        if values.get(parts[0]).is_none() {
+            return Err(unknown());
+        }
+
+        let mut target = &mut values;
+        for part in &parts[..parts.len() - 1] {
+            let Value::Object(group) = target else {
+                return Err(format!("`{}` isn't a group of settings; set the whole of it as JSON", key));
+            };
+            let next = group.entry(part.to_string()).or_insert(Value::Null);
+            if next.is_null() {
+                *next = Value::Object(Map::new());
+            }
+            target = next;
+        }
+        let Value::Object(group) = target else {
+            return Err(format!("`{}` isn't a group of settings; set the whole of it as JSON", key));
+        };
+        group.insert(parts[parts.len() - 1].to_string(), parse_setting(key, value)?);
+
+        let config: Config = serde_json::from_value(values).map_err(|e| format!("`{}` can't be {}: {}", key, value, e))?;
+        // a key inside a setting that doesn't have it would be dropped silently
+        let written = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+        if parts.iter().try_fold(&written, |value, part| value.get(*part)).is_none() {
+            return Err(unknown());
+        }
+        *self = config;
+        Ok(())
+    }
+}
+
+/// `value` given on the command line for `key`, as the config stores it.
+fn parse_setting(key: &str, value: &str) -> Result<Value, String> {
+    if MODEL_SETTINGS.contains(&key) {
+        let model: Model = value.parse()?;
+        return serde_json::to_value(model).map_err(|e| e.to_string());
+    }
+    if key == "fallback" && !value.trim_start().starts_with('[') {
+        let models = value.split(',').filter(|name| !name.trim().is_empty()).map(str::parse).collect::<Result<Vec<Model>, _>>()?;
+        return serde_json::to_value(models).map_err(|e| e.to_string());
+    }
+    Ok(serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())))
+}
+
+/// `value` of `key` the way [`parse_setting`] reads it back.
+fn show(key: &str, value: &Value) -> String {
+    if MODEL_SETTINGS.contains(&key) {
+        if let Ok(model) = serde_json::from_value::<Model>(value.clone()) {
+            return model.to_string();
+        }
+    }
+    if key == "fallback" {
+        if let Ok(models) = serde_json::from_value::<Vec<Model>>(value.clone()) {
+            return models.iter().map(Model::to_string).collect::<Vec<_>>().join(",");
+        }
+    }
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `value` of `key` as one line per setting, going into groups of them.
+fn flatten(key: &str, value: Value, settings: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(group) if !group.is_empty() && !MODEL_SETTINGS.contains(&key) => {
+            for (part, value) in group {
+                flatten(&format!("{}.{}", key, part), value, settings);
+            }
+        }
+        value => settings.push((key.to_string(), show(key, &value))),
+    }
+}
+
+/// Brings a file written as `version` up to [`CONFIG_VERSION`].
+fn migrate(file: &mut Map<String, Value>, version: u32, report: &mut LoadReport) {
+    // version 0, before there was one: models could go by older names
+    if version < 1 {
+        if let Some(model) = file.get_mut("model") {
+            migrate_model(model, "model", report);
+        }
+        if let Some(Value::Array(models)) = file.get_mut("fallback") {
+            for model in models {
+                migrate_model(model, "fallback", report);
+            }
+        }
+        if let Some(Value::Object(routing)) = file.get_mut("routing") {
+            for key in ["first", "escalate_to"] {
+                if let Some(model) = routing.get_mut(key) {
+                    migrate_model(model, &format!("routing.{}", key), report);
+                }
+            }
+        }
+    }
+}
+
+/// A model given by a name, as in `"ollama:llama3.1"` or an old one like
+/// `"gpt-4"`, rewritten as the config stores it.
+fn migrate_model(model: &mut Value, setting: &str, report: &mut LoadReport) {
+    let Value::String(name) = model else {
+        return;
+    };
+    let current = LEGACY_MODELS.iter().find(|(old, _)| old == name).map_or(name.as_str(), |(_, new)| new);
+    let Ok(migrated) = current.parse::<Model>() else {
+        return;
+    };
+    let Ok(value) = serde_json::to_value(&migrated) else {
+        return;
+    };
+    if current != name.as_str() {
+        report.changes.push(format!("`{}`: {} is now {}", setting, name, migrated));
+    }
+    *model = value;
+}
+
+fn default_max_tokens() -> i32 {
+    DEFAULT_MAX_TOKENS
+}
+
+fn default_max_output_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_notifications() -> bool {
+    true
+}
+
+fn default_notify_after_secs() -> u64 {
+    10
+}
+
+fn default_redact_secrets() -> bool {
+    true
+}
+
+fn default_request_timeout_secs() -> u64 {
+    120
+}
+
+fn default_use_trash() -> bool {
+    true
+}
+
+fn default_few_shot_examples() -> usize {
+    3
+}
@@ -0,0 +1,92 @@
+use crate::model::ConversationMessage;
+use crate::provider::{Message, Role};
+
+/// Tokens set aside for the system prompt when budgeting a request.
+pub const SYSTEM_PROMPT_RESERVE: usize = 512;
+
+/// Tokens each message costs on top of its text, for the role and separators.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Estimated token count for `text`, as a message.
+///
+/// Splits the text the way tiktoken's encodings pre-tokenize it: words and
+/// symbols with the space before them, digits in groups of three, runs of
+/// punctuation and of whitespace. Each piece is then counted by length,
+/// erring on the high side for long words and symbols, which BPE splits
+/// more; text outside ASCII counts a token a character. Close enough to
+/// stay clear of the context limit without shipping a tokenizer's vocabulary.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = MESSAGE_OVERHEAD;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens += if !c.is_ascii() {
+            1
+        } else if c.is_ascii_alphabetic() {
+            run_length(&mut chars, |next| next.is_ascii_alphabetic()).div_ceil(5)
+        } else if c.is_ascii_digit() {
+            run_length(&mut chars, |next| next.is_ascii_digit()).div_ceil(3)
+        } else if c.is_ascii_whitespace() {
+            if c == ' ' && chars.peek().is_some_and(|next| !next.is_whitespace()) {
+                // a single space goes with the word or symbol after it
+                0
+            } else {
+                run_length(&mut chars, |next| next.is_ascii_whitespace());
+                1
+            }
+        } else {
+            run_length(&mut chars, |next| next.is_ascii_punctuation()).div_ceil(2)
+        };
+    }
+    tokens
+}
+
+/// The length of the run of characters that started with the one just
+/// taken from `chars`, taking the rest of it.
+fn run_length(chars: &mut std::iter::Peekable<std::str::Chars>, matches: fn(char) -> bool) -> usize {
+    let mut len = 1;
+    while chars.next_if(|&next| matches(next)).is_some() {
+        len += 1;
+    }
+    len
+}
+
+pub fn history_tokens(history: &[ConversationMessage]) -> usize {
+    history.iter().map(|msg| estimate_tokens(&msg.content)).sum()
+}
+
+/// Returns the index of the oldest message that can be kept so that
+/// `history[index..]` fits into `budget` tokens.
+pub fn split_point(history: &[ConversationMessage], budget: usize) -> usize {
+    let mut used = 0;
+    for (idx, msg) in history.iter().enumerate().rev() {
+        used += estimate_tokens(&msg.content);
+        if used > budget {
+            return idx + 1;
+        }
+    }
+    0
+}
+
+/// Drops the oldest messages after the system prompt until `messages`, with
+/// an answer of up to `max_tokens`, fit into a `window` token context.
+/// Returns how many were dropped; an error if even the system prompt and
+/// the last message don't fit.
+pub fn fit_to_window(messages: &mut Vec<Message>, window: usize, max_tokens: usize) -> Result<usize, String> {
+    let tokens = |messages: &[Message]| messages.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>();
+    let system = messages.iter().take_while(|message| message.role == Role::System).count();
+    let mut dropped = 0;
+    while tokens(messages) + max_tokens > window && messages.len() > system + 1 {
+        messages.remove(system);
+        dropped += 1;
+    }
+    let needed = tokens(messages) + max_tokens;
+    if needed > window {
+        return Err(format!(
+            "the prompt comes to about {} tokens and the answer may take {}, more than the {} tokens the model can take; shorten it or lower max_tokens",
+            needed - max_tokens,
+            max_tokens,
+            window
+        ));
+    }
+    Ok(dropped)
+}
@@ -0,0 +1,187 @@
+//! `llm-term schedule`: requests like "run backup.sh every weekday at 6pm"
+//! become a crontab line, or a Windows Task Scheduler task, installed in
+//! the system's scheduler so they run without llm-term.
+//!
+//! The entries it installed are listed in `~/.llm_term_cron.json`; crontab
+//! lines also carry a `# llm-term:<id>` comment, so removing one leaves the
+//! rest of the crontab alone.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::Config;
+
+/// A schedule the model came up with, not installed yet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Draft {
+    pub id: String,
+    /// five crontab fields, or the schtasks options like `/SC DAILY /ST 18:00`
+    pub schedule: String,
+    pub command: String,
+    /// what the schedule means, in words
+    pub explanation: String,
+}
+
+/// A schedule llm-term installed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub id: String,
+    /// what the user asked for
+    pub request: String,
+    pub schedule: String,
+    pub command: String,
+    pub installed_at: DateTime<Utc>,
+}
+
+impl Draft {
+    /// The crontab line or the schtasks command that installs it, as it's shown before.
+    pub fn installed_form(&self) -> String {
+        if cfg!(target_os = "windows") {
+            format!("schtasks /Create /TN \"{}\" /TR \"{}\" {} /F", task_name(&self.id), self.command.replace('"', "\\\""), self.schedule)
+        } else {
+            cron_line(&self.schedule, &self.command, &self.id)
+        }
+    }
+}
+
+fn cron_line(schedule: &str, command: &str, id: &str) -> String {
+    format!("{} {} {}", schedule, command, tag(id))
+}
+
+/// Marks the crontab lines llm-term installed.
+fn tag(id: &str) -> String {
+    format!("# llm-term:{}", id)
+}
+
+fn task_name(id: &str) -> String {
+    format!("llm-term {}", id)
+}
+
+fn cron_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_cron.json");
+    Ok(path)
+}
+
+/// Every schedule llm-term installed, oldest first.
+pub fn list() -> Vec<Entry> {
+    cron_file_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(entries: &[Entry]) -> std::io::Result<()> {
+    fs::write(cron_file_path()?, serde_json::to_string_pretty(entries)?)
+}
+
+/// Asks the model for the schedule and command behind `request`.
+pub fn draft(config: &Config, request: &str) -> Result<Draft, Box<dyn std::error::Error>> {
+    let reply = config.model.llm_schedule_entry(config, request)?.unwrap_or_default();
+    let field = |name: &str| {
+        reply
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(|value| value.trim().trim_matches('`').trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let (Some(schedule), Some(command)) = (field("SCHEDULE:"), field("COMMAND:")) else {
+        return Err(format!("The model didn't come up with a schedule for that: {}", reply.trim()).into());
+    };
+    if !cfg!(target_os = "windows") && !schedule.starts_with('@') && schedule.split_whitespace().count() != 5 {
+        return Err(format!("`{}` isn't a crontab schedule", schedule).into());
+    }
+    let id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+    Ok(Draft { id, schedule, command, explanation: field("EXPLANATION:").unwrap_or_default() })
+}
+
+/// Installs `draft` in the system's scheduler and remembers it.
+pub fn install(request: &str, draft: &Draft) -> Result<Entry, Box<dyn std::error::Error>> {
+    if cfg!(target_os = "windows") {
+        let output = Command::new("schtasks")
+            .args(["/Create", "/TN", &task_name(&draft.id), "/TR", &draft.command])
+            .args(draft.schedule.split_whitespace())
+            .arg("/F")
+            .stdin(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(format!("schtasks failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+        }
+    } else {
+        let mut crontab = read_crontab()?;
+        if !crontab.is_empty() && !crontab.ends_with('\n') {
+            crontab.push('\n');
+        }
+        crontab.push_str(&cron_line(&draft.schedule, &draft.command, &draft.id));
+        crontab.push('\n');
+        write_crontab(&crontab)?;
+    }
+
+    let entry = Entry {
+        id: draft.id.clone(),
+        request: request.to_string(),
+        schedule: draft.schedule.clone(),
+        command: draft.command.clone(),
+        installed_at: Utc::now(),
+    };
+    let mut entries = list();
+    entries.push(entry.clone());
+    save(&entries)?;
+    Ok(entry)
+}
+
+/// Takes the schedule `id` out of the system's scheduler and the list. Its
+/// crontab line is removed even if it was edited by hand, as long as the tag is left.
+pub fn remove(id: &str) -> Result<Entry, Box<dyn std::error::Error>> {
+    let mut entries = list();
+    let pos = entries.iter().position(|entry| entry.id == id).ok_or_else(|| format!("No schedule `{}`; `llm-term schedule list` shows them", id))?;
+
+    if cfg!(target_os = "windows") {
+        let output = Command::new("schtasks").args(["/Delete", "/TN", &task_name(id), "/F"]).stdin(Stdio::null()).output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // one deleted by hand is gone too
+        if !output.status.success() && !stderr.contains("cannot find") {
+            return Err(format!("schtasks failed: {}", stderr.trim()).into());
+        }
+    } else {
+        let tag = tag(id);
+        let crontab = read_crontab()?;
+        let kept: String = crontab.lines().filter(|line| !line.trim_end().ends_with(&tag)).map(|line| format!("{}\n", line)).collect();
+        if kept.len() != crontab.len() {
+            write_crontab(&kept)?;
+        }
+    }
+
+    let entry = entries.remove(pos);
+    save(&entries)?;
+    Ok(entry)
+}
+
+/// The user's crontab; empty if they don't have one yet.
+fn read_crontab() -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("crontab").arg("-l").stdin(Stdio::null()).output().map_err(|e| format!("Can't run crontab: {}", e))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("no crontab") {
+        Ok(String::new())
+    } else {
+        Err(format!("crontab -l failed: {}", stderr.trim()).into())
+    }
+}
+
+fn write_crontab(content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = Command::new("crontab").arg("-").stdin(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|e| format!("Can't run crontab: {}", e))?;
+    child.stdin.take().expect("stdin is piped").write_all(content.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!("crontab failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(())
+}
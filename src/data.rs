@@ -0,0 +1,88 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::gui::LlmTermApp;
+use crate::{feedback, history, persist, quick_actions, schedule, snippets, Vault};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Folder inside the archive holding the per-session files.
+const SESSIONS_PREFIX: &str = "sessions/";
+
+/// Single files that make up the app's state, with their names in the archive.
+fn data_files() -> Result<Vec<(&'static str, PathBuf)>> {
+    Ok(vec![
+        ("config.json", crate::get_default_config_path()?),
+        ("cache.json", crate::get_cache_path()?),
+        ("last_command.json", crate::get_last_command_path()?),
+        ("gui_cache.json", LlmTermApp::cache_file_path()?),
+        ("snippets.json", snippets::snippets_file_path()?),
+        ("schedules.json", schedule::schedules_file_path()?),
+        ("quick_actions.json", quick_actions::quick_actions_file_path()?),
+        ("history.json", history::history_file_path()?),
+        ("feedback.json", feedback::feedback_file_path()?),
+        // encrypted files can only be read with the salt they were written with
+        ("vault.json", Vault::file_path().ok_or("no home dir")?),
+    ])
+}
+
+/// Writes config, sessions, caches, snippets and schedules into the zip file
+/// at `archive`. Returns the number of files stored.
+pub fn export(archive: &Path) -> Result<usize> {
+    let mut zip = zip::ZipWriter::new(fs::File::create(archive)?);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut count = 0;
+
+    for (name, path) in data_files()? {
+        if let Ok(content) = fs::read(&path) {
+            zip.start_file(name, options)?;
+            zip.write_all(&content)?;
+            count += 1;
+        }
+    }
+
+    for entry in fs::read_dir(LlmTermApp::sessions_dir()?)? {
+        let path = entry?.path();
+        // backups (`.json.1`) and temp files stay behind
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            zip.start_file(format!("{}{}", SESSIONS_PREFIX, name), options)?;
+            zip.write_all(&fs::read(&path)?)?;
+            count += 1;
+        }
+    }
+
+    zip.finish()?;
+    Ok(count)
+}
+
+/// Restores what [`export`] wrote, replacing the current files. Entries the
+/// app doesn't know are skipped. Returns the number of files restored.
+pub fn import(archive: &Path) -> Result<usize> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    let files = data_files()?;
+    let sessions_dir = LlmTermApp::sessions_dir()?;
+    let mut count = 0;
+
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let name = entry.name().to_string();
+
+        let target = match name.strip_prefix(SESSIONS_PREFIX) {
+            // plain file names only, so nothing lands outside the sessions folder
+            Some(file) if !file.is_empty() && !file.contains(['/', '\\']) && file != ".." => sessions_dir.join(file),
+            Some(_) => continue,
+            None => match files.iter().find(|(known, _)| *known == name) {
+                Some((_, path)) => path.clone(),
+                None => continue,
+            },
+        };
+
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        persist::write_atomic(&target, &content)?;
+        count += 1;
+    }
+    Ok(count)
+}
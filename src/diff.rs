@@ -0,0 +1,249 @@
+//! Previews of in-place edits: a `sed -i`, `perl -pi` or `Set-Content` step
+//! is run on temporary copies of its files, and the result is shown as a
+//! unified diff before the command touches the real ones.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::executor::Executor;
+use crate::shell;
+
+/// Lines of unchanged context around each change, as `diff -u` shows.
+const CONTEXT: usize = 3;
+
+/// Files with more differing lines than this are shown as replaced wholesale.
+const MAX_COMPARED_LINES: usize = 2000;
+
+/// What an in-place edit would do to one file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    /// in unified format; empty if the edit changes nothing
+    pub diff: String,
+}
+
+/// The existing files in `dir` that in-place edits in `command` would change.
+pub fn edited_files(command: &str, dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = steps(command).into_iter().flat_map(|step| edit_targets(step, dir)).map(|(_, path)| path).collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Runs the in-place edits of `command` on copies of their files and
+/// compares the copies with the originals. Other steps aren't run.
+pub fn preview(executor: &Executor, command: &str, dir: &Path) -> Result<Vec<FileDiff>, String> {
+    static PREVIEWS: AtomicUsize = AtomicUsize::new(0);
+    let scratch = std::env::temp_dir().join(format!("llm-term-preview-{}-{}", std::process::id(), PREVIEWS.fetch_add(1, Ordering::Relaxed)));
+    let result = preview_in(executor, command, dir, &scratch);
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+fn preview_in(executor: &Executor, command: &str, dir: &Path, scratch: &Path) -> Result<Vec<FileDiff>, String> {
+    // each file's copy, kept across steps so a second edit sees the first
+    let mut copies: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for step in steps(command) {
+        let targets = edit_targets(step, dir);
+        if targets.is_empty() {
+            continue;
+        }
+
+        // the step again, with each file swapped for its copy
+        let mut rewritten = String::new();
+        let mut end = 0;
+        for (slice, path) in &targets {
+            let copy = match copies.iter().find(|(original, _)| original == path) {
+                Some((_, copy)) => copy.clone(),
+                None => {
+                    let copy = scratch.join(copies.len().to_string()).join(path.file_name().unwrap_or_default());
+                    fs::create_dir_all(copy.parent().unwrap_or(scratch)).map_err(|e| e.to_string())?;
+                    fs::copy(path, &copy).map_err(|e| format!("Can't copy {}: {}", path.display(), e))?;
+                    copies.push((path.clone(), copy.clone()));
+                    copy
+                }
+            };
+            let start = slice.as_ptr() as usize - step.as_ptr() as usize;
+            rewritten.push_str(&step[end..start]);
+            rewritten.push_str(&format!("'{}'", copy.display()));
+            end = start + slice.len();
+        }
+        rewritten.push_str(&step[end..]);
+
+        let execution = executor.run(&rewritten).map_err(|e| format!("Can't run the preview: {}", e))?;
+        if !execution.status.success() {
+            return Err(format!("The edit failed on a copy: {}", execution.stderr.trim()));
+        }
+    }
+
+    copies
+        .into_iter()
+        .map(|(path, copy)| {
+            let old = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+            let new = fs::read_to_string(&copy).map_err(|e| format!("Can't read the edited copy of {}: {}", path.display(), e))?;
+            let name = path.strip_prefix(dir).unwrap_or(&path).display().to_string();
+            Ok(FileDiff { diff: unified(&old, &new, &name), path })
+        })
+        .collect()
+}
+
+/// The steps of `command`: split at unquoted `;`, `&&`, `||` and newlines,
+/// but not at `|`, which PowerShell edits pipe through (`(Get-Content f) -replace ... | Set-Content f`).
+fn steps(command: &str) -> Vec<&str> {
+    let mut steps = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = command.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (';' | '\n', None) => {
+                steps.push(&command[start..i]);
+                start = i + 1;
+            }
+            ('&' | '|', None) if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                chars.next();
+                steps.push(&command[start..i]);
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    steps.push(&command[start..]);
+    steps.into_iter().filter(|step| !step.trim().is_empty()).collect()
+}
+
+/// The words of `step` naming files it edits in place, as slices of `step`
+/// (quotes included), with the files they name.
+fn edit_targets<'a>(step: &'a str, dir: &Path) -> Vec<(&'a str, PathBuf)> {
+    let words = shell::words(step);
+    let Some(program) = words.first().map(|word| shell::program_name(word.trim_start_matches('('))) else {
+        return Vec::new();
+    };
+    let in_place = match program {
+        "sed" | "gsed" => words.iter().any(|word| word.starts_with("-i") || word.starts_with("--in-place")),
+        "perl" => words.iter().any(|word| word.starts_with('-') && !word.starts_with("--") && word.contains('i')),
+        _ => words.iter().any(|word| word.eq_ignore_ascii_case("Set-Content") || word.eq_ignore_ascii_case("Out-File")),
+    };
+    if !in_place {
+        return Vec::new();
+    }
+
+    let mut targets: Vec<(&str, PathBuf)> = Vec::new();
+    for word in &words[1..] {
+        // `(Get-Content app.conf)` names app.conf too
+        let slice = word.trim_matches(['(', ')']);
+        let name = slice.trim_matches(['"', '\'']);
+        if name.is_empty() || name.starts_with('-') {
+            continue;
+        }
+        let path = dir.join(name);
+        if path.is_file() {
+            targets.push((slice, path));
+        }
+    }
+    targets
+}
+
+/// A unified diff of `old` and `new`, both called `name`. Empty if they're the same.
+pub fn unified(old: &str, new: &str, name: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old, &new);
+    if ops.iter().all(|op| matches!(op, Op::Same(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", name, name);
+    let changed: Vec<usize> = ops.iter().enumerate().filter(|(_, op)| !matches!(op, Op::Same(..))).map(|(i, _)| i).collect();
+    let mut i = 0;
+    while i < changed.len() {
+        // a hunk runs until a gap of more than twice the context between changes
+        let mut j = i;
+        while j + 1 < changed.len() && changed[j + 1] - changed[j] <= 2 * CONTEXT + 1 {
+            j += 1;
+        }
+        let start = changed[i].saturating_sub(CONTEXT);
+        let end = (changed[j] + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let (old_start, new_start) = ops[..start].iter().fold((1, 1), |(o, n), op| match op {
+            Op::Same(..) => (o + 1, n + 1),
+            Op::Removed(_) => (o + 1, n),
+            Op::Added(_) => (o, n + 1),
+        });
+        let old_len = hunk.iter().filter(|op| !matches!(op, Op::Added(_))).count();
+        let new_len = hunk.iter().filter(|op| !matches!(op, Op::Removed(_))).count();
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+        for op in hunk {
+            match op {
+                Op::Same(line) => out.push_str(&format!(" {}\n", line)),
+                Op::Removed(line) => out.push_str(&format!("-{}\n", line)),
+                Op::Added(line) => out.push_str(&format!("+{}\n", line)),
+            }
+        }
+        i = j + 1;
+    }
+    out
+}
+
+/// `diff` colored like `git diff`: removals red, additions green, hunk headers cyan.
+pub fn colorize(diff: &str) -> String {
+    diff.lines()
+        .map(|line| match line.chars().next() {
+            _ if line.starts_with("---") || line.starts_with("+++") => format!("\x1b[1m{}\x1b[0m", line),
+            Some('-') => format!("\x1b[31m{}\x1b[0m", line),
+            Some('+') => format!("\x1b[32m{}\x1b[0m", line),
+            Some('@') => format!("\x1b[36m{}\x1b[0m", line),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+enum Op<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The edit from `old` to `new`, line by line: a longest common subsequence
+/// of what's left after the common start and end.
+fn line_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Op<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut ops: Vec<Op> = old[..prefix].iter().map(|line| Op::Same(line)).collect();
+    if a.len().max(b.len()) > MAX_COMPARED_LINES {
+        ops.extend(a.iter().map(|line| Op::Removed(line)));
+        ops.extend(b.iter().map(|line| Op::Added(line)));
+    } else {
+        // lengths[i][j]: the longest common subsequence of a[i..] and b[j..]
+        let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push(Op::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+                // removals first, as `diff -u` shows them
+                ops.push(Op::Removed(a[i]));
+                i += 1;
+            } else {
+                ops.push(Op::Added(b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|line| Op::Same(line)));
+    ops
+}
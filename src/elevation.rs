@@ -0,0 +1,283 @@
+//! Spotting commands that need administrator rights and running them elevated.
+
+use std::process::{Command, Stdio};
+
+use crate::shell::{self, Shell};
+
+/// Programs that elevate the command they're given.
+const ELEVATORS: &[&str] = &["sudo", "doas", "pkexec"];
+
+/// (program, subcommands) that only work as root; no subcommands means every use.
+const ADMIN_PROGRAMS: &[(&str, &[&str])] = &[
+    ("apt", &["install", "remove", "purge", "upgrade", "update", "autoremove"]),
+    ("apt-get", &["install", "remove", "purge", "upgrade", "update", "dist-upgrade", "autoremove"]),
+    ("dnf", &["install", "remove", "upgrade", "update"]),
+    ("yum", &["install", "remove", "update"]),
+    ("zypper", &["install", "remove", "update"]),
+    ("pacman", &["-S", "-Syu", "-R", "-Rs"]),
+    ("systemctl", &["start", "stop", "restart", "reload", "enable", "disable"]),
+    ("mount", &[]),
+    ("umount", &[]),
+    ("useradd", &[]),
+    ("userdel", &[]),
+    ("usermod", &[]),
+    ("groupadd", &[]),
+    ("visudo", &[]),
+    ("fdisk", &[]),
+    ("mkfs", &[]),
+    ("modprobe", &[]),
+    ("iptables", &[]),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Elevation {
+    /// the command elevates itself, e.g. `sudo apt install ripgrep`
+    Requested,
+    /// the command runs an admin-only program as is, e.g. `apt install ripgrep`
+    Required,
+}
+
+/// Whether `command` needs administrator rights, and how it asks for them.
+pub fn detect(command: &str) -> Option<Elevation> {
+    let lower = command.to_ascii_lowercase();
+    // Windows: Start-Process ... -Verb RunAs
+    if lower.contains("-verb runas") {
+        return Some(Elevation::Requested);
+    }
+
+    let mut required = false;
+    for words in shell::simple_commands(command) {
+        let Some(program) = words.first().map(|word| word.rsplit('/').next().unwrap_or(word)) else {
+            continue;
+        };
+        if ELEVATORS.contains(&program) || program == "su" {
+            return Some(Elevation::Requested);
+        }
+        let program = program.split('.').next().unwrap_or(program);
+        required |= ADMIN_PROGRAMS
+            .iter()
+            .any(|(admin, subcommands)| *admin == program && (subcommands.is_empty() || words[1..].iter().any(|w| subcommands.contains(w))));
+    }
+    required.then_some(Elevation::Required)
+}
+
+/// Whether this process already runs as root or as an elevated administrator.
+pub fn is_elevated() -> bool {
+    if cfg!(target_os = "windows") {
+        // only succeeds in an elevated prompt
+        Command::new("net")
+            .arg("session")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    } else {
+        Command::new("id")
+            .arg("-u")
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+    }
+}
+
+/// `command` for a terminal, where sudo can ask for the password itself.
+/// Commands that elevate themselves are left alone.
+pub fn for_terminal(command: &str, elevation: Elevation, shell: &Shell) -> String {
+    match (elevation, shell) {
+        (_, Shell::Powershell) => for_desktop(command, shell),
+        (Elevation::Requested, _) => command.to_string(),
+        (Elevation::Required, _) => {
+            let (shell_cmd, shell_arg) = shell.to_shell_command_and_command_arg();
+            format!("sudo {} {} {}", shell_cmd, shell_arg, single_quote(command))
+        }
+    }
+}
+
+/// `command` rewritten to ask for the password in a system dialog, for when
+/// there's no terminal: pkexec on Linux, the administrator prompt on macOS and
+/// UAC on Windows. `sudo` in front of the command's parts is dropped.
+pub fn for_desktop(command: &str, shell: &Shell) -> String {
+    let command = strip_sudo(command);
+    if matches!(shell, Shell::Powershell) {
+        return format!(
+            "Start-Process {} -Verb RunAs -Wait -ArgumentList '-NoProfile','-Command',{}",
+            shell::powershell_program(),
+            powershell_quote(&command)
+        );
+    }
+
+    let (shell_cmd, shell_arg) = shell.to_shell_command_and_command_arg();
+    if cfg!(target_os = "macos") {
+        let script = format!("do shell script \"{}\" with administrator privileges", command.replace('\\', "\\\\").replace('"', "\\\""));
+        format!("osascript -e {}", single_quote(&script))
+    } else {
+        format!("pkexec {} {} {}", shell_cmd, shell_arg, single_quote(&command))
+    }
+}
+
+/// `sudo` options that make no difference once the whole command runs as
+/// root: keeping the environment or `HOME`, not prompting, and the prompt.
+const DROPPABLE_SUDO_FLAGS: &[&str] = &["preserve-env", "set-home", "non-interactive", "stdin", "reset-timestamp", "askpass", "prompt"];
+const DROPPABLE_SUDO_SHORT_FLAGS: &str = "EHnSkA";
+
+/// Drops `sudo` (and `doas`) where it starts a simple command, with its
+/// options. One with an option that matters, like `-u postgres`, is kept;
+/// it runs as root, so it doesn't ask for a password again.
+fn strip_sudo(command: &str) -> String {
+    let mut out = String::with_capacity(command.len());
+    // true where a new simple command starts
+    let mut at_start = true;
+    let mut rest = command;
+    while !rest.is_empty() {
+        let trimmed = rest.trim_start();
+        out.push_str(&rest[..rest.len() - trimmed.len()]);
+        rest = trimmed;
+        if rest.is_empty() {
+            break;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        if at_start && (word == "sudo" || word == "doas") {
+            if let Some(options) = droppable_sudo_options(&rest[end..]) {
+                rest = rest[end + options..].trim_start();
+                continue;
+            }
+        }
+        out.push_str(word);
+        at_start = word.ends_with([';', '|', '&', '(']);
+        rest = &rest[end..];
+    }
+    out
+}
+
+/// How much of `args`, what follows a `sudo`, is options [`strip_sudo`] can
+/// drop, up to the command. `None` if there's one it can't.
+fn droppable_sudo_options(args: &str) -> Option<usize> {
+    let mut len = 0;
+    let mut value_next = false;
+    loop {
+        let rest = &args[len..];
+        let word = rest.trim_start();
+        let word = &word[..word.find(char::is_whitespace).unwrap_or(word.len())];
+        let next = len + (rest.len() - rest.trim_start().len()) + word.len();
+        if std::mem::take(&mut value_next) {
+            len = next;
+            continue;
+        }
+        if word == "--" {
+            return Some(next);
+        }
+        if !word.starts_with('-') || word == "-" {
+            return Some(len);
+        }
+        if let Some(long) = word.strip_prefix("--") {
+            let (name, value) = long.split_once('=').map_or((long, None), |(name, value)| (name, Some(value)));
+            if !DROPPABLE_SUDO_FLAGS.contains(&name) {
+                return None;
+            }
+            value_next = name == "prompt" && value.is_none();
+        } else {
+            for (i, flag) in word.char_indices().skip(1) {
+                if flag == 'p' {
+                    // the prompt, here or in the next word
+                    value_next = i + 1 == word.len();
+                    break;
+                }
+                if !DROPPABLE_SUDO_SHORT_FLAGS.contains(flag) {
+                    return None;
+                }
+            }
+        }
+        len = next;
+    }
+}
+
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn powershell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, for_desktop, for_terminal, strip_sudo, Elevation};
+    use crate::shell::Shell;
+
+    #[test]
+    fn sudo_is_requested() {
+        assert_eq!(detect("sudo apt install ripgrep"), Some(Elevation::Requested));
+        assert_eq!(detect("cd /etc && doas vi hosts"), Some(Elevation::Requested));
+    }
+
+    #[test]
+    fn runas_is_requested() {
+        assert_eq!(detect("Start-Process pwsh -Verb RunAs"), Some(Elevation::Requested));
+    }
+
+    #[test]
+    fn admin_program_is_required() {
+        assert_eq!(detect("apt install ripgrep"), Some(Elevation::Required));
+        assert_eq!(detect("/usr/bin/systemctl restart nginx"), Some(Elevation::Required));
+        assert_eq!(detect("mkfs.ext4 /dev/sdb1"), Some(Elevation::Required));
+    }
+
+    #[test]
+    fn admin_program_in_a_later_step() {
+        assert_eq!(detect("lsblk && mount /dev/sdb1 /mnt"), Some(Elevation::Required));
+    }
+
+    #[test]
+    fn read_only_subcommands_need_nothing() {
+        assert_eq!(detect("apt list --installed"), None);
+        assert_eq!(detect("systemctl status nginx"), None);
+        assert_eq!(detect("ls -la"), None);
+    }
+
+    #[test]
+    fn terminal_wraps_required_commands_in_sudo() {
+        assert_eq!(
+            for_terminal("apt install ripgrep && echo 'done'", Elevation::Required, &Shell::BornAgainShell),
+            "sudo sh -c 'apt install ripgrep && echo '\\''done'\\'''"
+        );
+    }
+
+    #[test]
+    fn terminal_leaves_requested_commands_alone() {
+        assert_eq!(for_terminal("sudo apt update", Elevation::Requested, &Shell::Zsh), "sudo apt update");
+    }
+
+    #[test]
+    fn sudo_is_dropped_where_a_command_starts() {
+        assert_eq!(strip_sudo("sudo apt update && sudo apt upgrade -y"), "apt update && apt upgrade -y");
+        assert_eq!(strip_sudo("echo sudo"), "echo sudo");
+    }
+
+    #[test]
+    fn sudo_options_that_dont_matter_are_dropped_too() {
+        assert_eq!(strip_sudo("sudo -E make install"), "make install");
+        assert_eq!(strip_sudo("sudo -nH -p pw: -- apt update"), "apt update");
+        assert_eq!(strip_sudo("sudo --preserve-env=PATH make install"), "make install");
+    }
+
+    #[test]
+    fn sudo_to_another_user_is_kept() {
+        assert_eq!(strip_sudo("sudo -u postgres psql"), "sudo -u postgres psql");
+        assert_eq!(strip_sudo("sudo -Eu postgres psql && sudo systemctl reload postgresql"), "sudo -Eu postgres psql && systemctl reload postgresql");
+        assert_eq!(strip_sudo("sudo --user=postgres psql"), "sudo --user=postgres psql");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn desktop_asks_with_pkexec() {
+        assert_eq!(for_desktop("sudo mount /dev/sdb1 /mnt", &Shell::BornAgainShell), "pkexec sh -c 'mount /dev/sdb1 /mnt'");
+    }
+
+    #[test]
+    fn powershell_asks_with_uac() {
+        let elevated = for_desktop("Set-Service -Name 'wuauserv' -StartupType Disabled", &Shell::Powershell);
+        assert!(elevated.starts_with("Start-Process "), "{}", elevated);
+        assert!(elevated.ends_with("-Verb RunAs -Wait -ArgumentList '-NoProfile','-Command','Set-Service -Name ''wuauserv'' -StartupType Disabled'"), "{}", elevated);
+    }
+}
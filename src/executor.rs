@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::shell::{self, Shell};
+
+/// Caps on what a command may use, from the `limits` config field. Unset
+/// fields are unlimited. Enforced with rlimits on Unix and a job object on Windows.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct ResourceLimits {
+    /// CPU time in seconds
+    #[serde(default)]
+    pub cpu_secs: Option<u64>,
+    /// address space (Unix) or committed memory (Windows), in MiB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// bytes of captured output
+    #[serde(default)]
+    pub output_bytes: Option<u64>,
+    /// the largest file the command may write, in bytes (Unix)
+    #[serde(default)]
+    pub file_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.cpu_secs.is_none() && self.memory_mb.is_none() && self.output_bytes.is_none() && self.file_bytes.is_none()
+    }
+}
+
+/// How much of an attached command's stderr [`Executor::run_attached_keeping_stderr`] keeps.
+const KEPT_STDERR_BYTES: usize = 16 * 1024;
+
+/// Gets the output of a command started with [`Executor::run_watched`] as it's printed.
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Result of [`Executor::run`].
+pub struct Execution {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+    /// directory the shell ended up in, e.g. after a `cd`
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Runs commands through a shell, the way llm-term runs approved commands.
+pub struct Executor {
+    shell: Shell,
+    working_dir: Option<PathBuf>,
+    limits: ResourceLimits,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new(Shell::detect())
+    }
+}
+
+impl Executor {
+    pub fn new(shell: Shell) -> Self {
+        Self { shell, working_dir: None, limits: ResourceLimits::default() }
+    }
+
+    /// Applies `limits` to every command started from now on.
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Starts commands in `dir` instead of the current directory.
+    /// Directories that don't exist (anymore) are ignored.
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    pub fn shell(&self) -> &Shell {
+        &self.shell
+    }
+
+    /// A process that runs `command` in the shell, ready to be customized and
+    /// spawned. Start it with [`spawn`](Self::spawn) so the limits apply on every platform.
+    pub fn command(&self, command: &str) -> Command {
+        let (shell_cmd, shell_arg) = self.shell.to_shell_command_and_command_arg();
+        let mut process = Command::new(shell_cmd);
+        process.arg(shell_arg).arg(command);
+        if let Some(dir) = self.working_dir.as_deref().filter(|dir| dir.is_dir()) {
+            process.current_dir(dir);
+        }
+        #[cfg(unix)]
+        limits::apply_rlimits(&mut process, self.limits);
+        process
+    }
+
+    /// Spawns `process`, made by [`command`](Self::command), under the limits.
+    pub fn spawn(&self, process: &mut Command) -> io::Result<Child> {
+        let child = process.spawn()?;
+        #[cfg(windows)]
+        if !self.limits.is_unlimited() {
+            if let Err(e) = limits::assign_job(&child, self.limits) {
+                let mut child = child;
+                let _ = child.kill();
+                return Err(e);
+            }
+        }
+        Ok(child)
+    }
+
+    /// Runs `command` without input and captures its output and final working directory.
+    /// A command that prints more than `output_bytes` is stopped, with
+    /// everything it started, and its working directory isn't known.
+    pub fn run(&self, command: &str) -> io::Result<Execution> {
+        let child = self.spawn(&mut self.captured(command))?;
+        self.collect(child, None)
+    }
+
+    /// Like [`run`](Self::run), for a command running in the background:
+    /// `on_start` gets its process id as soon as it runs, for [`kill`], and
+    /// `on_output` what it prints to stdout and stderr as it arrives.
+    pub fn run_watched(&self, command: &str, on_start: impl FnOnce(u32), on_output: OutputSink) -> io::Result<Execution> {
+        let child = self.spawn(&mut self.captured(command))?;
+        on_start(child.id());
+        self.collect(child, Some(on_output))
+    }
+
+    /// The process for `command` with its output piped, in its own process
+    /// group so a [`kill`] stops what it started too.
+    fn captured(&self, command: &str) -> Command {
+        let mut process = self.command(&self.shell.wrap_with_cwd_report(command));
+        process.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        process
+    }
+
+    /// Reads the piped output of `child` and waits for it, killing it once
+    /// it goes over the output limit so the wait can't hang.
+    fn collect(&self, mut child: Child, on_output: Option<OutputSink>) -> io::Result<Execution> {
+        let limit = self.limits.output_bytes.unwrap_or(u64::MAX);
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let pid = child.id();
+        let limited = Arc::new(AtomicBool::new(false));
+        let stop = {
+            let limited = limited.clone();
+            move || {
+                if !limited.swap(true, Ordering::SeqCst) {
+                    kill(pid);
+                }
+            }
+        };
+        let stderr_sink = on_output.clone();
+        let stderr_stop = stop.clone();
+        let stderr = thread::spawn(move || read_limited(stderr, limit, stderr_sink.as_deref(), &stderr_stop));
+        let stdout = read_limited(stdout, limit, on_output.as_deref(), &stop);
+        let stderr = stderr.join().unwrap_or_default();
+        let status = child.wait()?;
+
+        let (mut stdout, working_dir) = shell::split_cwd_report(&String::from_utf8_lossy(&stdout));
+        if limited.load(Ordering::SeqCst) {
+            stdout.push_str("\n[output limit reached]");
+        }
+        Ok(Execution {
+            stdout,
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            status,
+            working_dir,
+        })
+    }
+
+    /// Runs `command` attached to this process's terminal, so it can prompt the user.
+    pub fn run_attached(&self, command: &str) -> io::Result<ExitStatus> {
+        self.spawn(&mut self.command(command))?.wait()
+    }
+
+    /// Like [`run_attached`](Self::run_attached), but what the command writes
+    /// to stderr is also kept, up to its last `KEPT_STDERR_BYTES`, while it's
+    /// passed on to this process's stderr.
+    pub fn run_attached_keeping_stderr(&self, command: &str) -> io::Result<(ExitStatus, String)> {
+        let mut child = self.spawn(self.command(command).stderr(Stdio::piped()))?;
+        let mut pipe = child.stderr.take().expect("stderr is piped");
+        let reader = thread::spawn(move || {
+            let mut kept = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while let Ok(read) = pipe.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                let _ = io::stderr().write_all(&buffer[..read]);
+                kept.extend_from_slice(&buffer[..read]);
+                let excess = kept.len().saturating_sub(KEPT_STDERR_BYTES);
+                kept.drain(..excess);
+            }
+            String::from_utf8_lossy(&kept).into_owned()
+        });
+        let status = child.wait()?;
+        Ok((status, reader.join().unwrap_or_default()))
+    }
+
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+}
+
+/// Reads `pipe` until it ends, keeping the first `limit` bytes. Past that
+/// `on_limit` is called and the rest is read and dropped until the pipe ends.
+/// `on_output` gets each kept chunk as it's read.
+fn read_limited(
+    mut pipe: impl Read,
+    limit: u64,
+    on_output: Option<&(dyn Fn(&str) + Send + Sync)>,
+    on_limit: &dyn Fn(),
+) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while let Ok(read) = pipe.read(&mut buffer) {
+        if read == 0 {
+            break;
+        }
+        let room = (limit - data.len() as u64).min(read as u64) as usize;
+        if room > 0 {
+            if let Some(on_output) = on_output {
+                on_output(&String::from_utf8_lossy(&buffer[..room]));
+            }
+            data.extend_from_slice(&buffer[..room]);
+        }
+        if room < read {
+            on_limit();
+        }
+    }
+    data
+}
+
+/// Stops the process `pid` started by [`Executor::run_watched`], with
+/// everything it started.
+pub fn kill(pid: u32) {
+    #[cfg(unix)]
+    // SAFETY: signals the process group the command was started in
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+#[cfg(unix)]
+mod limits {
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    use super::ResourceLimits;
+
+    pub fn apply_rlimits(process: &mut Command, limits: ResourceLimits) {
+        if limits.is_unlimited() {
+            return;
+        }
+        let rlimits = [
+            (libc::RLIMIT_CPU, limits.cpu_secs),
+            (libc::RLIMIT_AS, limits.memory_mb.map(|mb| mb * 1024 * 1024)),
+            (libc::RLIMIT_FSIZE, limits.file_bytes),
+        ];
+        // SAFETY: setrlimit is async-signal-safe and only touches the child
+        unsafe {
+            process.pre_exec(move || {
+                for (resource, value) in rlimits {
+                    if let Some(value) = value {
+                        let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+                        if libc::setrlimit(resource, &limit) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(windows)]
+mod limits {
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_PROCESS_MEMORY, JOB_OBJECT_LIMIT_PROCESS_TIME,
+    };
+
+    use super::ResourceLimits;
+
+    /// Puts `child` in a new job object that enforces the CPU and memory limits.
+    pub fn assign_job(child: &Child, limits: ResourceLimits) -> io::Result<()> {
+        // SAFETY: plain Win32 calls on handles owned here or by `child`
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            if let Some(secs) = limits.cpu_secs {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_TIME;
+                // in 100 ns ticks
+                info.BasicLimitInformation.PerProcessUserTimeLimit = (secs * 10_000_000) as i64;
+            }
+            if let Some(mb) = limits.memory_mb {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                info.ProcessMemoryLimit = (mb * 1024 * 1024) as usize;
+            }
+
+            let ok = SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const _,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            ) != 0
+                && AssignProcessToJobObject(job, child.as_raw_handle() as _) != 0;
+            let result = if ok { Ok(()) } else { Err(io::Error::last_os_error()) };
+            // the job lives on as long as the process is in it
+            CloseHandle(job);
+            result
+        }
+    }
+}
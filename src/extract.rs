@@ -0,0 +1,142 @@
+//! Finds the commands a chat answer suggests. The chat prompt asks for
+//! `` `COMMAND: <command>` ``, but models also write the marker bare or in
+//! bold, put the command in a fenced block under it, leave out the closing
+//! backtick or use backticks inside the command, as in
+//! `` `COMMAND: echo `date`` ``.
+
+use std::ops::Range;
+
+const MARKER: &str = "COMMAND:";
+
+/// A suggested command and where it is in the answer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Found {
+    pub command: String,
+    /// the byte range of `command` in the answer
+    pub span: Range<usize>,
+}
+
+/// The commands `reply` suggests, in order.
+pub fn commands(reply: &str) -> Vec<String> {
+    find(reply).into_iter().map(|found| found.command).collect()
+}
+
+/// The commands `reply` suggests, in order, with where each is.
+pub fn find(reply: &str) -> Vec<Found> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(at) = reply[from..].find(MARKER).map(|at| from + at) {
+        from = at + MARKER.len();
+        // e.g. SUBCOMMAND:
+        if reply[..at].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        if let Some(command) = command_after(reply, at) {
+            from = from.max(command.span.end);
+            found.push(command);
+        }
+    }
+    found
+}
+
+/// The command for the marker at `at`.
+fn command_after(reply: &str, at: usize) -> Option<Found> {
+    let ticks = reply[..at].bytes().rev().take_while(|&b| b == b'`').count();
+    let after = at + MARKER.len();
+    let line_end = reply[after..].find('\n').map_or(reply.len(), |end| after + end);
+
+    // `COMMAND: ls`, with the marker in the code span
+    if ticks > 0 {
+        let end = span_end(reply, after, line_end, ticks);
+        if let Some(found) = trimmed(reply, after..end.unwrap_or(line_end)) {
+            return Some(found);
+        }
+        // `COMMAND:` `ls`: the span held only the marker
+        return command_after_marker(reply, end.map_or(line_end, |end| end + ticks), line_end);
+    }
+    command_after_marker(reply, after, line_end)
+}
+
+/// The command after a marker that isn't in a code span, from `start`:
+/// in a code span or fenced block of its own, or the rest of the line.
+fn command_after_marker(reply: &str, start: usize, line_end: usize) -> Option<Found> {
+    // the rest of **COMMAND:** ls
+    let rest = reply[start..line_end].trim_start().trim_start_matches('*').trim_start();
+    let start = line_end - rest.len();
+
+    if rest.trim().is_empty() {
+        return fenced_block(reply, line_end);
+    }
+    if rest.starts_with("```") {
+        return fenced_block(reply, start);
+    }
+    let ticks = rest.bytes().take_while(|&b| b == b'`').count();
+    if ticks > 0 {
+        let end = span_end(reply, start + ticks, line_end, ticks).unwrap_or(line_end);
+        return trimmed(reply, start + ticks..end);
+    }
+    // no code span: the line is the command, less a stray closing backtick
+    let end = start + rest.trim_end().trim_end_matches('`').len();
+    trimmed(reply, start..end)
+}
+
+/// The code of the fenced block starting on the line after `from`, blank
+/// lines aside; `None` if there's other text first.
+fn fenced_block(reply: &str, from: usize) -> Option<Found> {
+    let rest = &reply[from..];
+    let open = from + (rest.len() - rest.trim_start().len());
+    if !reply[open..].starts_with("```") {
+        return None;
+    }
+    // the info string, e.g. bash
+    let code_start = reply[open..].find('\n').map(|end| open + end + 1)?;
+    let code_end = reply[code_start..].find("```").map_or(reply.len(), |end| code_start + end);
+    trimmed(reply, code_start..code_end)
+}
+
+/// Where the code span opened by `ticks` backticks before `start` closes,
+/// looking no further than `line_end`. With one backtick, backticks inside
+/// the command pair up, as in `` `echo `date`` ``.
+fn span_end(reply: &str, start: usize, line_end: usize, ticks: usize) -> Option<usize> {
+    let bytes = reply.as_bytes();
+    let mut inner_open = false;
+    let mut i = start;
+    while i < line_end {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let run = bytes[i..line_end].iter().take_while(|&&b| b == b'`').count();
+        if ticks > 1 {
+            if run == ticks {
+                return Some(i);
+            }
+        } else {
+            // a backtick after a space and before a word opens one inside the command
+            let before_space = i == start || bytes[i - 1].is_ascii_whitespace() || bytes[i - 1] == b'(' || bytes[i - 1] == b'=';
+            let after_word = bytes.get(i + 1).is_some_and(|&b| !b.is_ascii_whitespace() && b != b'`');
+            if inner_open {
+                inner_open = false;
+            } else if before_space && after_word && (i + 1..line_end).any(|j| bytes[j] == b'`') {
+                inner_open = true;
+            } else {
+                return Some(i);
+            }
+            i += 1;
+            continue;
+        }
+        i += run;
+    }
+    None
+}
+
+/// `range` of `reply` without the whitespace around it, unless that leaves nothing.
+fn trimmed(reply: &str, range: Range<usize>) -> Option<Found> {
+    let text = &reply[range.clone()];
+    let command = text.trim();
+    if command.is_empty() {
+        return None;
+    }
+    let start = range.start + (text.len() - text.trim_start().len());
+    Some(Found { command: command.to_string(), span: start..start + command.len() })
+}
@@ -0,0 +1,103 @@
+//! 👍/👎 ratings of suggested commands, and the corrections given with a 👎.
+//! Recent corrections go back into the system prompt, so the model doesn't
+//! repeat a mistake the user already fixed.
+//!
+//! Kept in `~/.llm_term_feedback.json`, encrypted like the caches when the
+//! vault is unlocked.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::vault;
+
+/// Oldest ratings are dropped beyond this many.
+const MAX_RATINGS: usize = 500;
+
+/// At most this many corrections are put in the prompt.
+const MAX_NOTED: usize = 5;
+
+/// How much a rated prompt's words must overlap (Dice coefficient) with the
+/// current one to be noted before more recent ones.
+const MIN_SIMILARITY: f64 = 0.3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub prompt: String,
+    pub command: String,
+    /// 👍 or 👎
+    pub good: bool,
+    /// what the command should have been, given with a 👎
+    #[serde(default)]
+    pub correction: Option<String>,
+    pub rated_at: DateTime<Utc>,
+}
+
+pub fn feedback_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_feedback.json");
+    Ok(path)
+}
+
+/// Every rating, oldest first.
+pub fn load() -> Vec<Rating> {
+    feedback_file_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|content| vault::open_to_string(&content).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Stores `rating`, replacing an earlier one of the same command for the same prompt.
+pub fn record(rating: Rating) -> std::io::Result<()> {
+    let mut ratings = load();
+    ratings.retain(|r| !(r.prompt == rating.prompt && r.command == rating.command));
+    ratings.push(rating);
+    let excess = ratings.len().saturating_sub(MAX_RATINGS);
+    ratings.drain(..excess);
+
+    let content = serde_json::to_string_pretty(&ratings)?;
+    fs::write(feedback_file_path()?, vault::seal(content.as_bytes()))
+}
+
+/// Tells the model about commands the user rated 👎, those for prompts like
+/// `prompt` first; empty if there are none.
+pub fn prompt_note(prompt: &str) -> String {
+    let words = significant_words(prompt);
+    let mut bad: Vec<Rating> = load().into_iter().filter(|r| !r.good).rev().collect();
+    // a stable sort keeps the most recent first within each half
+    bad.sort_by_key(|r| similarity(&words, &significant_words(&r.prompt)) < MIN_SIMILARITY);
+    if bad.is_empty() {
+        return String::new();
+    }
+
+    let mut note = String::from("The user corrected some of your earlier suggestions; don't repeat these mistakes:");
+    for rating in bad.iter().take(MAX_NOTED) {
+        match &rating.correction {
+            Some(fixed) => note.push_str(&format!("\n- Last time you suggested `{}` for \"{}\", the user fixed it to `{}`.", rating.command, rating.prompt, fixed)),
+            None => note.push_str(&format!("\n- Last time you suggested `{}` for \"{}\", the user said it was wrong.", rating.command, rating.prompt)),
+        }
+    }
+    note
+}
+
+/// How much two sets of words overlap, from 0 to 1 (Dice coefficient).
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn significant_words(prompt: &str) -> HashSet<String> {
+    const STOP_WORDS: &[&str] = &["a", "an", "the", "of", "in", "on", "for", "to", "and", "or", "by", "with", "me", "my", "all", "this", "that", "please"];
+    prompt
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+        .map(str::to_string)
+        .collect()
+}
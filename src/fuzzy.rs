@@ -0,0 +1,41 @@
+/// Scores how well `query` matches `candidate` as a case-insensitive
+/// subsequence, or `None` if some query character doesn't appear in order.
+///
+/// Consecutive matches and matches at the start of a word score higher, so
+/// "lsf" ranks "list files" above "false".
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + candidate[pos..].iter().position(|&c| c == q)?;
+
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        // gaps cost a little so tighter matches win
+        score -= (found - pos).min(10) as i64;
+
+        previous = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Returns the items whose key matches `query`, best first. An empty query
+/// keeps every item in its original order.
+pub fn rank<'a, T>(query: &str, items: &'a [T], key: impl Fn(&T) -> &str) -> Vec<&'a T> {
+    let mut scored: Vec<(i64, usize, &T)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| score(query, key(item)).map(|s| (s, idx, item)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, _, item)| item).collect()
+}
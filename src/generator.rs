@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cache::Cache;
+use crate::model::ConversationMessage;
+use crate::{history, stats, Config};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// What a [`CommandGenerator::warm`] run did.
+#[derive(Default)]
+pub struct WarmSummary {
+    /// new commands stored in the cache
+    pub generated: usize,
+    pub already_cached: usize,
+    /// prompts that failed or got no command
+    pub failed: usize,
+}
+
+/// What [`CommandGenerator::fix`] suggests for a command that failed.
+pub struct Fix {
+    /// why it failed
+    pub explanation: String,
+    /// what to run instead, if anything
+    pub command: Option<String>,
+}
+
+/// What the model answered a request for a command.
+pub enum Answer {
+    /// `None` if it wasn't confident
+    Command(Option<String>),
+    /// the request was ambiguous; the command depends on the answer
+    Question(String),
+}
+
+/// A turn of [`CommandGenerator::clarify`].
+pub struct Clarification {
+    /// the model's question, or what it says about the command
+    pub reply: String,
+    /// the command, once the model knows enough
+    pub command: Option<String>,
+}
+
+/// One prompt of a [`CommandGenerator::batch`].
+pub struct BatchItem {
+    pub prompt: String,
+    /// `None` if no command could be generated, the error if the request failed
+    pub command: std::result::Result<Option<String>, String>,
+    pub from_cache: bool,
+}
+
+/// Turns natural-language requests into commands for the user's shell.
+pub struct CommandGenerator {
+    config: Config,
+}
+
+impl CommandGenerator {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Asks the model for a command; `None` if it couldn't come up with one,
+    /// or asked a question about the request instead.
+    pub fn generate(&self, prompt: &str) -> Result<Option<String>> {
+        Ok(match self.ask(prompt)? {
+            Answer::Command(command) => command,
+            Answer::Question(_) => None,
+        })
+    }
+
+    /// Asks the model for a command, which it may answer with a question
+    /// when the request is ambiguous; see [`answer`](Self::answer).
+    pub fn ask(&self, prompt: &str) -> Result<Answer> {
+        let reply = self.config.model.llm_get_command(&self.config, prompt)?;
+        Ok(match reply.as_deref().and_then(question) {
+            Some(question) => Answer::Question(question),
+            None => Answer::Command(reply),
+        })
+    }
+
+    /// The command for `prompt` now that the user answered the model's
+    /// `question` about it. It only gets to ask once: another question is `None`.
+    pub fn answer(&self, prompt: &str, question: &str, answer: &str) -> Result<Option<String>> {
+        let reply = self.config.model.llm_get_command_with_answer(&self.config, prompt, question, answer)?;
+        Ok(reply.filter(|reply| self::question(reply).is_none()))
+    }
+
+    /// Like [`generate`](Self::generate), but answers from `cache` when it can
+    /// and records new, non-empty commands there. The flag says whether it was a cache hit.
+    pub fn generate_cached(&self, cache: &mut Cache, prompt: &str) -> Result<Option<(String, bool)>> {
+        let cached = cache.lookup(prompt);
+        stats::record_cache_lookup(cached.is_some());
+        if let Some(command) = cached {
+            return Ok(Some((command, true)));
+        }
+        match self.generate(prompt)? {
+            // an empty answer means the model wasn't confident; ask again next time
+            Some(command) if !command.trim().is_empty() => {
+                cache.insert(prompt, command.clone());
+                cache.save()?;
+                Ok(Some((command, false)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Modifies `previous_command`, generated for `previous_prompt`, as `change` describes.
+    pub fn refine(&self, previous_prompt: &str, previous_command: &str, change: &str) -> Result<Option<String>> {
+        self.config.model.llm_refine_command(&self.config, previous_prompt, previous_command, change)
+    }
+
+    /// Why the run in `entry` failed, given its exit code and stderr, and
+    /// what to run instead; `None` if the model didn't answer.
+    pub fn fix(&self, entry: &history::Entry) -> Result<Option<Fix>> {
+        let reply = self.config.model.llm_fix_failed_command(&self.config, &entry.prompt, &entry.command, entry.exit_code, entry.stderr.as_deref().unwrap_or(""))?;
+        let Some(reply) = reply.filter(|reply| !reply.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let (explanation, command) = split_command_line(&reply);
+        Ok(Some(Fix { explanation, command }))
+    }
+
+    /// The model's next turn in talking `prompt`, too unclear for a command,
+    /// through: `conversation` holds its questions and the user's answers so far.
+    pub fn clarify(&self, prompt: &str, conversation: &[ConversationMessage]) -> Result<Option<Clarification>> {
+        let reply = self.config.model.llm_clarify_request(&self.config, prompt, conversation)?;
+        let Some(reply) = reply.filter(|reply| !reply.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let (reply, command) = split_command_line(&reply);
+        Ok(Some(Clarification { reply, command }))
+    }
+
+    /// A short plain-text explanation of what `command` does.
+    pub fn explain(&self, command: &str) -> Result<Option<String>> {
+        self.config.model.llm_explain_command(&self.config, command)
+    }
+
+    /// Fills `cache` with commands for those `prompts` it doesn't have yet,
+    /// asking the model for up to `jobs` of them at a time. Calls `on_done`
+    /// as each prompt finishes, from the worker thread that handled it.
+    pub fn warm(
+        &self,
+        cache: &mut Cache,
+        prompts: &[String],
+        jobs: usize,
+        on_done: impl Fn(&str, &std::result::Result<Option<String>, String>) + Sync,
+    ) -> Result<WarmSummary> {
+        let mut summary = WarmSummary::default();
+        for item in self.batch(cache, prompts, jobs, on_done)? {
+            match item.command {
+                _ if item.from_cache => summary.already_cached += 1,
+                Ok(Some(_)) => summary.generated += 1,
+                _ => summary.failed += 1,
+            }
+        }
+        Ok(summary)
+    }
+
+    /// A command for each of `prompts`, in their order: from `cache` when it
+    /// has one, otherwise from the model, up to `jobs` at a time. Blank and
+    /// repeated prompts are skipped. New commands are stored in `cache`, and
+    /// `on_done` is called as each request finishes, from its worker thread.
+    pub fn batch(
+        &self,
+        cache: &mut Cache,
+        prompts: &[String],
+        jobs: usize,
+        on_done: impl Fn(&str, &std::result::Result<Option<String>, String>) + Sync,
+    ) -> Result<Vec<BatchItem>> {
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        let mut todo = Vec::new();
+        for prompt in prompts.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            if !seen.insert(prompt) {
+                continue;
+            }
+            match cache.lookup(prompt) {
+                Some(command) => items.push(BatchItem { prompt: prompt.to_string(), command: Ok(Some(command)), from_cache: true }),
+                None => {
+                    todo.push((items.len(), prompt));
+                    items.push(BatchItem { prompt: prompt.to_string(), command: Ok(None), from_cache: false });
+                }
+            }
+        }
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, todo.len().max(1)) {
+                scope.spawn(|| {
+                    while let Some(&(idx, prompt)) = todo.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        // a missing OPENAI_API_KEY panics inside the request; carry on with the rest
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| self.generate(prompt)))
+                            .unwrap_or_else(|_| Err("the request failed; is OPENAI_API_KEY set?".into()))
+                            .map_err(|e| e.to_string());
+                        on_done(prompt, &result);
+                        results.lock().unwrap().push((idx, result));
+                    }
+                });
+            }
+        });
+
+        let mut generated = false;
+        for (idx, result) in results.into_inner().unwrap() {
+            let item = &mut items[idx];
+            item.command = match result {
+                Ok(Some(command)) if !command.trim().is_empty() => {
+                    cache.insert(item.prompt.as_str(), command.trim());
+                    generated = true;
+                    Ok(Some(command.trim().to_string()))
+                }
+                // the model answers with an empty string when it isn't confident
+                Ok(_) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+        if generated {
+            cache.save()?;
+        }
+        Ok(items)
+    }
+}
+
+/// The question in a `{"type":"question","question":"..."}` reply, also
+/// when it's wrapped in a code fence.
+pub fn question(reply: &str) -> Option<String> {
+    let json = reply.trim().trim_start_matches("```json").trim_matches('`').trim();
+    if !json.starts_with('{') {
+        return None;
+    }
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    if value.get("type")?.as_str()? != "question" {
+        return None;
+    }
+    Some(value.get("question")?.as_str()?.trim().to_string()).filter(|question| !question.is_empty())
+}
+
+/// `reply` split at its last `COMMAND: ` line into the text before it and the command.
+fn split_command_line(reply: &str) -> (String, Option<String>) {
+    let (text, command) = match reply.rfind("COMMAND:") {
+        Some(at) => (&reply[..at], reply[at + "COMMAND:".len()..].lines().next().map(|line| line.trim().trim_matches('`').trim().to_string())),
+        None => (reply, None),
+    };
+    (text.trim().to_string(), command.filter(|command| !command.is_empty()))
+}
@@ -0,0 +1,25 @@
+//! Extra confirmations for commands that reach beyond this machine, like
+//! a production cluster or a cloud account: the user types a word to show
+//! they mean it.
+
+use crate::{cloud, kube};
+
+/// A warning shown before a command runs, and what has to be typed to go ahead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Guard {
+    pub warning: String,
+    /// e.g. the name of the context the command targets
+    pub confirm: String,
+}
+
+impl Guard {
+    /// Whether `input` matches what has to be typed.
+    pub fn accepts(&self, input: &str) -> bool {
+        input.trim() == self.confirm
+    }
+}
+
+/// The confirmations `command` needs, in the order they're asked for.
+pub fn check(command: &str) -> Vec<Guard> {
+    kube::guard(command).into_iter().chain(cloud::guard(command)).collect()
+}
@@ -4,13 +4,43 @@ use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command as ProcessCommand;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::io::{self, Write};
+use std::time::{Duration, Instant};
 
-use crate::model::{Model, ConversationMessage};
-use crate::Config;
-use crate::shell::Shell;
+use crate::model::{self, Model, ConversationMessage, DEFAULT_TEMPERATURE};
+use crate::{changes, extract, generator, manual, stats, Cache, Config, Execution, Executor, Project, Redactor, ResourceLimits, Script};
+use crate::ansi::{self, AnsiColor};
+use crate::context;
+use crate::pty::{self, PtySession};
+use crate::schedule::{self, Schedule};
+use crate::snippets::{self, Snippet};
+use crate::quick_actions::{self, QuickAction};
+use crate::history;
+use crate::compare::{self, Candidate};
+use crate::clipboard;
+use crate::i18n::{tr, Lang};
+use crate::notify;
+use crate::ipc;
+use crate::persist;
+use crate::data;
+use crate::elevation::{self, Elevation};
+use crate::guard::{self, Guard};
+use crate::affected::{self, Affected};
+use crate::backup;
+use crate::diff;
+use crate::feedback::{self, Rating};
+use crate::speech;
+use crate::voice;
+use crate::window::WindowState;
+use crate::cloud;
+use crate::iac;
+use crate::trash;
+use crate::{vault, Vault};
+use crate::shell::{self, Shell};
+use crate::{logging, provider};
+use crate::jobs::{self, Job};
 
 /// One message in the chat log.
 #[derive(Serialize, Deserialize, Clone)]
@@ -18,8 +48,77 @@ pub struct ChatMessage {
     pub content: String,
     pub is_user: bool,
     pub timestamp: DateTime<Utc>,
+    /// an answer suggesting commands, shown as command cards
     pub is_command: bool,
+    /// its command has been run, or for output messages, finished
     pub executed: bool,
+    /// how the command it's about exited, when known
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// shown in the "Pinned" strip at the top of the chat
+    #[serde(default)]
+    pub pinned: bool,
+    /// set on messages reporting the result of an executed command
+    #[serde(default)]
+    pub output: Option<CommandOutput>,
+    /// backup taken before the command this message is about, offered for a restore
+    #[serde(default)]
+    pub backup: Option<String>,
+}
+
+impl ChatMessage {
+    /// A prompt the user sent.
+    fn user(content: String) -> Self {
+        Self { is_user: true, ..Self::note(content) }
+    }
+
+    /// A message on llm-term's side, like an answer or a note about a
+    /// command, with nothing attached.
+    fn note(content: String) -> Self {
+        Self {
+            content,
+            is_user: false,
+            timestamp: Utc::now(),
+            is_command: false,
+            executed: false,
+            exit_code: None,
+            pinned: false,
+            output: None,
+            backup: None,
+        }
+    }
+
+    /// Text sent to the model for this message, including any command output.
+    fn history_text(&self) -> String {
+        match &self.output {
+            Some(output) => format!("{}\n\nOutput:\n{}", self.content, ansi::strip(&output.text)),
+            None => self.content.clone(),
+        }
+    }
+}
+
+/// Captured result of a command run from the chat.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    /// output as stored in the session; cut at `max_output_bytes`
+    pub text: String,
+    /// spillover file holding the complete output when `text` was truncated
+    #[serde(default)]
+    pub full_output_path: Option<PathBuf>,
+    /// what the command changed in its directory, for file-changing commands
+    #[serde(default)]
+    pub changes: Option<changes::Changes>,
+}
+
+/// Transient UI state of the command output blocks.
+#[derive(Default)]
+pub struct OutputViewState {
+    /// full outputs loaded via "Show more", keyed by spillover file
+    pub expanded: HashMap<PathBuf, String>,
+    /// "Save full output" in progress: (spillover file, destination draft)
+    pub save_target: Option<(PathBuf, String)>,
+    pub status: Option<String>,
 }
 
 /// A full conversation with a unique id and title (first user prompt).
@@ -29,6 +128,64 @@ pub struct ChatSession {
     pub title: String,
     pub messages: Vec<ChatMessage>,
     pub created_at: DateTime<Utc>,
+    /// id of the session this one was branched from, if any
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// rolling summary standing in for the oldest messages
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// number of leading messages folded into `summary`
+    #[serde(default)]
+    pub summarized_count: usize,
+    /// instructions added to the unified system prompt for this session
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// directory the last command left the shell in; commands start here
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    /// always ask the model instead of answering repeated prompts from the cache
+    #[serde(default)]
+    pub bypass_cache: bool,
+    /// hidden from the sidebar; listed in the Archive view
+    #[serde(default)]
+    pub archived: bool,
+    /// answers aren't read aloud in this chat, whatever `speech.enabled` says
+    #[serde(default)]
+    pub muted: bool,
+    /// model answering in this chat instead of the configured one
+    #[serde(default)]
+    pub model: Option<Model>,
+    /// suggestions waiting in this chat while another tab is open
+    #[serde(skip)]
+    pending: PendingState,
+    /// false for a session listed in the index whose messages are still on disk
+    #[serde(skip)]
+    pub loaded: bool,
+    /// changed since its file was last written
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// What the sessions index keeps about each session; enough for the sidebar.
+#[derive(Serialize, Deserialize)]
+struct SessionIndexEntry {
+    id: String,
+    title: String,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    archived: bool,
+}
+
+impl ChatSession {
+    /// Drops the summary if it covers messages past `len`.
+    fn invalidate_summary_after(&mut self, len: usize) {
+        if self.summarized_count > len {
+            self.summary = None;
+            self.summarized_count = 0;
+        }
+    }
 }
 
 impl Default for ChatSession {
@@ -38,10 +195,241 @@ impl Default for ChatSession {
             title: "New Chat".into(),
             messages: Vec::new(),
             created_at: Utc::now(),
+            parent_id: None,
+            summary: None,
+            summarized_count: 0,
+            system_prompt: None,
+            working_dir: None,
+            bypass_cache: false,
+            archived: false,
+            muted: false,
+            model: None,
+            pending: PendingState::default(),
+            loaded: true,
+            dirty: true,
+        }
+    }
+}
+
+/// Changes are written once the sessions have been left alone this long.
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+/// Rotating backups kept of each session file, taken at most every `BACKUP_INTERVAL`.
+const SESSION_BACKUPS: usize = 5;
+const BACKUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Lines of live output shown for each job in the Jobs panel.
+const JOB_TAIL_LINES: usize = 8;
+
+/// Temperature used by "Regenerate (more creative)".
+const CREATIVE_TEMPERATURE: f32 = 0.9;
+
+/// What a chat has suggested and not yet run, kept with the chat while
+/// another tab is open.
+#[derive(Clone, Default)]
+struct PendingState {
+    commands: Vec<PendingCommand>,
+    script: Option<Script>,
+    planned_command: Option<String>,
+}
+
+/// A command the model suggested, waiting to be run or dismissed.
+#[derive(Clone)]
+pub struct PendingCommand {
+    pub command: String,
+    /// "Back up" unticked for it
+    skip_backup: bool,
+    /// "Delete permanently" ticked for it, skipping `use_trash` once
+    delete_permanently: bool,
+    /// file its output should be written to
+    save_output_path: String,
+}
+
+impl PendingCommand {
+    fn new(command: String) -> Self {
+        Self { command, skip_backup: false, delete_permanently: false, save_output_path: String::new() }
+    }
+}
+
+/// A model answer being worked out in the background for one chat.
+struct InFlight {
+    session_id: String,
+    cache_key: String,
+    started: Instant,
+    rx: Receiver<Update>,
+    /// what has been streamed of the answer so far
+    partial: String,
+    placement: Placement,
+}
+
+/// Where an answer goes in its chat once it arrives.
+#[derive(Clone, Copy)]
+enum Placement {
+    /// after the prompt it answers
+    Reply,
+    /// in place of the answer at this index, whose suggestions go with it
+    Replace(usize),
+    /// an explanation of a command, which suggests nothing to run
+    Explanation,
+}
+
+/// What a check too slow for the UI thread found about a command.
+enum Checked {
+    /// the cloud account it runs against
+    Account(cloud::Account),
+    /// what its in-place edits would change, run on copies
+    Preview { command: String, diffs: Result<Vec<diff::FileDiff>, String> },
+    /// the backup taken before it; it runs once this is in
+    Backup {
+        /// the command as approved, before it was rewritten to use the trash
+        original: String,
+        command: String,
+        files: usize,
+        /// the answer that suggested it, as in `running_suggestion`
+        suggestion: Option<(String, usize)>,
+        snapshot: Result<backup::Snapshot, String>,
+    },
+}
+
+/// The messages a request's history is made of, before they're fitted to
+/// the context window on the background thread.
+struct PendingHistory {
+    messages: Vec<ConversationMessage>,
+    /// index in the session of the first of `messages`
+    start: usize,
+    /// the summary of the messages before `start`
+    summary: Option<String>,
+    /// tokens the history may take
+    budget: usize,
+}
+
+impl PendingHistory {
+    /// The history to send. When it no longer fits, the oldest messages are
+    /// folded into the rolling summary, which is sent back as
+    /// [`Update::Summary`]; anything that still doesn't fit (e.g. the summary
+    /// request failed) is dropped oldest first.
+    fn build(self, config: &Config, tx: &Sender<Update>) -> Vec<ConversationMessage> {
+        let PendingHistory { messages: mut history, start, mut summary, budget } = self;
+        if context::history_tokens(&history) > budget {
+            // keep the most recent half of the budget verbatim
+            let fold = context::split_point(&history, budget / 2);
+            if let Ok(Some(folded)) = config.model.llm_summarize(config, summary.as_deref(), &history[..fold]) {
+                let _ = tx.send(Update::Summary { summary: folded.clone(), summarized_count: start + fold });
+                summary = Some(folded);
+                history.drain(..fold);
+            }
+        }
+
+        if let Some(summary) = summary {
+            history.insert(0, ConversationMessage {
+                content: format!("Summary of the earlier conversation: {}", summary),
+                is_user: false,
+            });
+        }
+
+        let keep_from = context::split_point(&history, budget);
+        history.drain(..keep_from);
+        history
+    }
+}
+
+/// What the background thread sends back while it answers.
+enum Update {
+    /// older messages were folded into the session's summary, up to `summarized_count`
+    Summary { summary: String, summarized_count: usize },
+    /// a model starts answering; what was streamed before is dropped
+    Started,
+    /// the next piece of the answer
+    Text(String),
+    Done(Answer),
+}
+
+/// Sends the pieces of a streamed answer over `tx`.
+fn stream_updates(tx: Sender<Update>, repaint: egui::Context) -> impl FnMut(model::Streamed) {
+    move |streamed| {
+        let _ = tx.send(match streamed {
+            model::Streamed::Started => Update::Started,
+            model::Streamed::Text(text) => Update::Text(text.to_string()),
+        });
+        repaint.request_repaint();
+    }
+}
+
+struct Answer {
+    text: String,
+    /// a real answer, not an error, so it's worth caching
+    cacheable: bool,
+    /// a fallback model answered; said above the answer
+    fallback_note: Option<String>,
+}
+
+/// The headings chat history is grouped under, newest first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DateGroup {
+    Today,
+    Yesterday,
+    ThisWeek,
+    Older,
+}
+
+impl DateGroup {
+    const ALL: [DateGroup; 4] = [DateGroup::Today, DateGroup::Yesterday, DateGroup::ThisWeek, DateGroup::Older];
+
+    /// The group of a session started at `created`, in local time; "this week" is the last seven days.
+    fn of(created: DateTime<Utc>, today: chrono::NaiveDate) -> Self {
+        let days = (today - created.with_timezone(&chrono::Local).date_naive()).num_days();
+        match days {
+            ..=0 => DateGroup::Today,
+            1 => DateGroup::Yesterday,
+            2..=6 => DateGroup::ThisWeek,
+            _ => DateGroup::Older,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::Yesterday => "Yesterday",
+            DateGroup::ThisWeek => "This Week",
+            DateGroup::Older => "Older",
         }
     }
 }
 
+/// What a pending command would touch, for the hints under it.
+#[derive(Clone, Default)]
+struct PendingFiles {
+    command: String,
+    /// the files its globs match
+    affected: Vec<Affected>,
+    /// what it would change that can be backed up first
+    backup_targets: Vec<PathBuf>,
+    /// files it edits in place, whose diff can be previewed
+    edits: Vec<PathBuf>,
+}
+
+/// Options for re-sending the conversation behind the last assistant message.
+pub struct RegenerateRequest {
+    /// Model to ask instead of the configured one.
+    pub model: Option<Model>,
+    pub temperature: f32,
+    /// Replace the previous answer instead of appending a new one.
+    pub replace: bool,
+}
+
+/// Entries of the Data menu.
+pub enum DataAction {
+    Export,
+    Import,
+}
+
+/// What to do with an edited user message.
+pub enum EditAction {
+    /// Drop everything from the edited message onwards and resend.
+    Truncate(usize, String),
+    /// Copy the history before the edited message into a new linked session and resend there.
+    Branch(usize, String),
+}
+
 /// Main GUI application state.
 pub struct LlmTermApp {
     pub config: Config,
@@ -49,10 +437,142 @@ pub struct LlmTermApp {
     pub current_session: ChatSession,
     pub chat_sessions: Vec<ChatSession>,
     pub selected_session_id: Option<String>,
-    /// simple on‑disk cache; maps prompt -> response
-    pub cache: HashMap<String, String>,
+    /// maps prompt -> response, kept in `~/.llm_term_gui_cache.json`
+    pub cache: Cache,
     pub is_loading: bool,
-    pub pending_command: Option<String>,
+    /// commands suggested and not yet run or dismissed, oldest first
+    pub pending_commands: Vec<PendingCommand>,
+    /// keep the old answer when regenerating instead of replacing it
+    pub keep_previous_answer: bool,
+    /// user message currently being edited: (index, draft text)
+    pub editing: Option<(usize, String)>,
+    pub output_view: OutputViewState,
+    /// run commands in a pseudo terminal even if they don't look interactive
+    pub run_interactive: bool,
+    /// interactive command currently shown in the terminal window
+    pub terminal: Option<PtySession>,
+    pub terminal_input: String,
+    /// whether the finished terminal session was already added to the chat
+    pub terminal_recorded: bool,
+    /// the directory as it was before the terminal's command, if it changes files
+    pub terminal_before: Option<changes::Snapshot>,
+    /// commands run from the chat in the background
+    pub jobs: Vec<Job>,
+    /// show the Jobs panel on the right
+    pub show_jobs: bool,
+    /// show the persistent shell pane on the right
+    pub show_shell_pane: bool,
+    /// debug console with recent log lines and the last API exchange, toggled with F12
+    pub show_debug_console: bool,
+    /// live shell hosted in the pane; approved commands are typed into it
+    pub shell_pane: Option<PtySession>,
+    pub shell_pane_input: String,
+    pub show_schedules: bool,
+    pub schedules: Vec<Schedule>,
+    pub schedule_input: String,
+    /// generated schedule waiting for approval
+    pub schedule_draft: Option<Schedule>,
+    pub schedule_error: Option<String>,
+    /// results of scheduled runs: (schedule id, output)
+    schedule_tx: Sender<(String, String)>,
+    schedule_rx: Receiver<(String, String)>,
+    pub show_snippets: bool,
+    pub quick_actions: Vec<QuickAction>,
+    /// quick action whose placeholders are being filled in, with the values so far
+    pub quick_action_form: Option<(QuickAction, HashMap<String, String>)>,
+    pub snippets: Vec<Snippet>,
+    /// starred command waiting for a name before it's saved
+    pub snippet_draft: Option<Snippet>,
+    /// placeholder values typed into the snippets window, per snippet name
+    pub snippet_values: HashMap<String, HashMap<String, String>>,
+    pub show_compare: bool,
+    pub compare_prompt: String,
+    /// comma separated, as for `llm-term compare --models`
+    pub compare_models: String,
+    pub compare_results: Vec<Candidate>,
+    pub compare_error: Option<String>,
+    /// clipboard text sent along with the next prompt
+    pub clipboard_context: Option<String>,
+    /// language of the static labels, from the `language` config field
+    pub lang: Lang,
+    /// widgets F6 moves focus between: sidebar, chat log, composer
+    focus_anchors: [Option<egui::Id>; 3],
+    focus_region: usize,
+    /// move the cursor back into the composer on the next frame
+    composer_refocus: bool,
+    /// the chat history sidebar, hidden with ☰ to make room on small screens
+    show_sidebar: bool,
+    /// window geometry, sidebar width and open chat as last saved
+    window_state: WindowState,
+    /// when the window state first differed from the saved one
+    window_changed_since: Option<Instant>,
+    /// audio recorded while the 🎤 button is held
+    dictation: Option<voice::Recording>,
+    /// the transcription of the last dictation, once it's done
+    dictation_rx: Option<Receiver<Result<String, String>>>,
+    dictation_error: Option<String>,
+    /// the answer being read aloud
+    speaking: Option<speech::Speaking>,
+    /// sessions open as tabs, in tab order; the current one among them
+    open_tabs: Vec<String>,
+    /// model requests still running, at most one per chat
+    in_flight: Vec<InFlight>,
+    /// titles the model came up with: (session id, title)
+    title_tx: Sender<(String, String)>,
+    title_rx: Receiver<(String, String)>,
+    /// session being renamed in the sidebar, with the draft title
+    renaming: Option<(String, String)>,
+    /// 👍/👎 given to suggested commands, as stored in `~/.llm_term_feedback.json`
+    ratings: Vec<Rating>,
+    /// command rated 👎 whose correction is being typed: (prompt, command, draft)
+    correcting: Option<(String, String, String)>,
+    /// prompts handed over by `llm-term --gui "prompt"`; `None` only asks for focus
+    handoff_rx: Receiver<Option<String>>,
+    _instance: Option<ipc::Instance>,
+    /// when the sessions first changed since the last save
+    unsaved_since: Option<Instant>,
+    /// list archived sessions in the sidebar instead of the active ones
+    pub show_archived: bool,
+    /// age for "Delete sessions older than N days"
+    pub cleanup_days: u32,
+    /// zip file for the Data menu's export and import
+    pub data_path: String,
+    pub data_status: Option<String>,
+    /// the Ollama menu's server field
+    ollama_host_input: String,
+    /// the connection test running for it: the models found, or why it failed
+    ollama_test_rx: Option<Receiver<Result<Vec<String>, String>>>,
+    ollama_status: Option<String>,
+    /// checks run off the UI thread before or alongside a command: (session id, what was found)
+    check_tx: Sender<(String, Checked)>,
+    check_rx: Receiver<(String, Checked)>,
+    /// `encrypt_storage` is on and the passphrase hasn't been entered yet
+    locked: bool,
+    passphrase_input: String,
+    unlock_error: Option<String>,
+    /// from `redact_secrets`; shows what a prompt looked like when it was sent
+    redactor: Option<Redactor>,
+    /// approved command waiting for the extra administrator-rights confirmation
+    pub elevation_request: Option<(String, Elevation)>,
+    /// approved command waiting for typed confirmations, e.g. of a production
+    /// cluster: the command, the guards left and what's been typed so far
+    pub guard_request: Option<(String, Vec<Guard>, String)>,
+    /// chat and index of the answer whose command is running, marked executed when it finishes
+    running_suggestion: Option<(String, usize)>,
+    /// `terraform apply` or `pulumi up` whose preview is in the chat; running it again applies
+    planned_command: Option<String>,
+    /// what the pending commands would touch, looked up once per command
+    pending_files: Vec<PendingFiles>,
+    /// "Back up" unticked for the command being run
+    skip_backup: bool,
+    /// "Delete permanently" ticked for the command being run, skipping `use_trash` once
+    delete_permanently: bool,
+    /// multi-line script from the last answer, shown in the script editor
+    pub pending_script: Option<Script>,
+    /// approved command with `<PORT>`/`{filename}` placeholders left to fill in, with the values so far
+    pub placeholder_request: Option<(String, HashMap<String, String>)>,
+    /// command run before for a prompt like the last one, offered for a rerun above the composer
+    pub history_hint: Option<history::Entry>,
 }
 
 impl LlmTermApp {
@@ -60,318 +580,3535 @@ impl LlmTermApp {
     /*                 ─── construction / persistence ───                    */
     /* --------------------------------------------------------------------- */
 
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let chat_sessions = Self::load_chat_sessions().unwrap_or_default();
+    pub fn new(cc: &eframe::CreationContext<'_>, initial_prompt: Option<String>) -> Self {
+        // the CLI's config, so settings like `language` apply here too
+        let config = Self::load_config().unwrap_or_else(|| Config::new(Model::OpenAiGpt4oMini, 1000));
+        let lang = Lang::from_config(config.language.as_deref());
+        let redactor = Redactor::from_config(&config).ok().flatten();
+        let ollama_host_input = config.ollama_host.clone().unwrap_or_default();
 
-        Self {
-            config: Config {
-                model: Model::OpenAiGpt4oMini,
-                max_tokens: 1000,
-            },
+        if config.encrypt_storage && !vault::is_unlocked() {
+            if let Ok(vault) = std::env::var("LLM_TERM_PASSPHRASE").map_err(|e| e.to_string()).and_then(|p| Vault::unlock(&p)) {
+                vault::install(vault);
+            }
+        }
+        // nothing encrypted is read (or overwritten) before the unlock screen is passed
+        let locked = config.encrypt_storage && !vault::is_unlocked();
+        let (chat_sessions, cache) = if locked {
+            (Vec::new(), None)
+        } else {
+            (
+                Self::load_chat_sessions().unwrap_or_default(),
+                Self::cache_file_path().ok().and_then(|path| Cache::load(path).ok()),
+            )
+        };
+
+        let schedules = schedule::load_schedules().unwrap_or_default();
+        let (schedule_tx, schedule_rx) = mpsc::channel();
+        let (title_tx, title_rx) = mpsc::channel();
+        let (check_tx, check_rx) = mpsc::channel();
+        let snippets = snippets::load_snippets().unwrap_or_default();
+
+        // the startup prompt goes through the same queue as later handoffs
+        let (handoff_tx, handoff_rx) = mpsc::channel();
+        if let Some(prompt) = initial_prompt {
+            let _ = handoff_tx.send(Some(prompt));
+        }
+        let instance = ipc::Instance::listen(cc.egui_ctx.clone(), handoff_tx).ok();
+        // the size and position were applied when the window was created
+        let window_state = WindowState::load();
+
+        let mut app = Self {
+            config,
             current_input: String::new(),
             current_session: ChatSession::default(),
             chat_sessions,
             selected_session_id: None,
-            cache: HashMap::new(),
+            cache: cache.unwrap_or_else(Cache::in_memory),
             is_loading: false,
-            pending_command: None,
+            pending_commands: Vec::new(),
+            keep_previous_answer: false,
+            editing: None,
+            output_view: OutputViewState::default(),
+            run_interactive: false,
+            terminal: None,
+            terminal_input: String::new(),
+            terminal_recorded: false,
+            terminal_before: None,
+            jobs: Vec::new(),
+            show_jobs: false,
+            show_shell_pane: false,
+            show_debug_console: false,
+            shell_pane: None,
+            shell_pane_input: String::new(),
+            show_schedules: false,
+            schedules,
+            schedule_input: String::new(),
+            schedule_draft: None,
+            schedule_error: None,
+            schedule_tx,
+            schedule_rx,
+            show_snippets: false,
+            quick_actions: quick_actions::load_quick_actions().unwrap_or_default(),
+            quick_action_form: None,
+            snippets,
+            snippet_draft: None,
+            snippet_values: HashMap::new(),
+            show_compare: false,
+            compare_prompt: String::new(),
+            compare_models: "gpt-4o,gpt-4o-mini".to_string(),
+            compare_results: Vec::new(),
+            compare_error: None,
+            clipboard_context: None,
+            lang,
+            focus_anchors: [None; 3],
+            composer_refocus: false,
+            show_sidebar: true,
+            window_state: window_state.clone(),
+            window_changed_since: None,
+            focus_region: 2,
+            handoff_rx,
+            dictation: None,
+            dictation_rx: None,
+            dictation_error: None,
+            speaking: None,
+            open_tabs: Vec::new(),
+            in_flight: Vec::new(),
+            title_tx,
+            title_rx,
+            renaming: None,
+            ratings: feedback::load(),
+            correcting: None,
+            _instance: instance,
+            unsaved_since: None,
+            show_archived: false,
+            cleanup_days: 30,
+            data_path: "llm-term-data.zip".to_string(),
+            data_status: None,
+            ollama_host_input,
+            ollama_test_rx: None,
+            ollama_status: None,
+            check_tx,
+            check_rx,
+            locked,
+            passphrase_input: String::new(),
+            unlock_error: None,
+            redactor,
+            elevation_request: None,
+            guard_request: None,
+            planned_command: None,
+            pending_files: Vec::new(),
+            running_suggestion: None,
+            skip_backup: false,
+            delete_permanently: false,
+            pending_script: None,
+            placeholder_request: None,
+            history_hint: None,
+        };
+        if let Some(id) = &window_state.session_id {
+            app.load_session(id);
         }
+        app.open_tabs = vec![app.current_session.id.clone()];
+        app
     }
 
-    fn sessions_file_path() -> std::io::Result<PathBuf> {
+    /// Sessions written before they moved into `~/.llm_term_sessions/`.
+    fn legacy_sessions_file_path() -> std::io::Result<PathBuf> {
         let mut path = dirs::home_dir()
             .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
         path.push(".llm_term_sessions.json");
         Ok(path)
     }
 
+    /// One `<id>.json` per session plus an `index.json` listing them.
+    pub(crate) fn sessions_dir() -> std::io::Result<PathBuf> {
+        let mut path = dirs::home_dir()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+        path.push(".llm_term_sessions");
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    fn session_file_path(id: &str) -> std::io::Result<PathBuf> {
+        Ok(Self::sessions_dir()?.join(format!("{}.json", id)))
+    }
+
+    pub(crate) fn cache_file_path() -> std::io::Result<PathBuf> {
+        let mut path = dirs::home_dir()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+        path.push(".llm_term_gui_cache.json");
+        Ok(path)
+    }
+
+    /// Directory for spillover files of long command outputs.
+    fn outputs_dir() -> std::io::Result<PathBuf> {
+        let mut path = dirs::home_dir()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+        path.push(".llm_term_outputs");
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    /// Reads the sessions index; messages are loaded when a session is opened.
     fn load_chat_sessions() -> std::io::Result<Vec<ChatSession>> {
-        let path = Self::sessions_file_path()?;
-        if path.exists() {
-            let content = fs::read_to_string(path)?;
-            Ok(serde_json::from_str(&content).unwrap_or_default())
+        let index_path = Self::sessions_dir()?.join("index.json");
+        if !index_path.exists() {
+            return Self::migrate_legacy_sessions();
+        }
+
+        let index: Vec<SessionIndexEntry> = persist::read_json_with_backups(&index_path, SESSION_BACKUPS).unwrap_or_default();
+        Ok(index
+            .into_iter()
+            .map(|entry| ChatSession {
+                id: entry.id,
+                title: entry.title,
+                created_at: entry.created_at,
+                parent_id: entry.parent_id,
+                archived: entry.archived,
+                loaded: false,
+                ..ChatSession::default()
+            })
+            .collect())
+    }
+
+    /// Moves the sessions of the old single `.llm_term_sessions.json` into
+    /// per-session files. The old file is kept as `.json.migrated`.
+    fn migrate_legacy_sessions() -> std::io::Result<Vec<ChatSession>> {
+        let legacy_path = Self::legacy_sessions_file_path()?;
+        if !legacy_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions: Vec<ChatSession> = persist::read_json_with_backups(&legacy_path, SESSION_BACKUPS).unwrap_or_default();
+        for session in &mut sessions {
+            session.loaded = true;
+            session.dirty = true;
+        }
+        Self::write_sessions(&mut sessions)?;
+        fs::rename(&legacy_path, legacy_path.with_extension("json.migrated"))?;
+        Ok(sessions)
+    }
+
+    /// Reads the messages and settings of a session listed in the index.
+    fn load_session_file(session: &mut ChatSession) {
+        if session.loaded {
+            return;
+        }
+        let stored = Self::session_file_path(&session.id)
+            .ok()
+            .and_then(|path| persist::read_json_with_backups::<ChatSession>(&path, SESSION_BACKUPS));
+        if let Some(stored) = stored {
+            *session = stored;
+        }
+        session.loaded = true;
+    }
+
+    /// Writes the index and the file of every session that changed since it was last written.
+    fn write_sessions<'a>(sessions: impl IntoIterator<Item = &'a mut ChatSession>) -> std::io::Result<()> {
+        let mut index = Vec::new();
+        for session in sessions {
+            if session.loaded && session.dirty {
+                let path = Self::session_file_path(&session.id)?;
+                persist::rotate_backups(&path, SESSION_BACKUPS, BACKUP_INTERVAL)?;
+                persist::write_atomic(&path, &vault::seal(serde_json::to_string_pretty(session)?.as_bytes()))?;
+                session.dirty = false;
+            }
+            index.push(SessionIndexEntry {
+                id: session.id.clone(),
+                title: session.title.clone(),
+                created_at: session.created_at,
+                parent_id: session.parent_id.clone(),
+                archived: session.archived,
+            });
+        }
+
+        let index_path = Self::sessions_dir()?.join("index.json");
+        persist::rotate_backups(&index_path, SESSION_BACKUPS, BACKUP_INTERVAL)?;
+        persist::write_atomic(&index_path, &vault::seal(serde_json::to_string_pretty(&index)?.as_bytes()))
+    }
+
+    /// Writes the changed sessions, including the open one, to disk right away.
+    fn save_chat_sessions(&mut self) -> std::io::Result<()> {
+        let current = Some(&mut self.current_session).filter(|session| !session.messages.is_empty());
+        Self::write_sessions(self.chat_sessions.iter_mut().chain(current))
+    }
+
+    /// Marks the open session as changed; the changes are saved after
+    /// `SAVE_DEBOUNCE` without further ones, so a burst of edits is written
+    /// once. Other sessions that change are marked `dirty` where they do.
+    fn schedule_save(&mut self) {
+        self.current_session.dirty = true;
+        self.unsaved_since = Some(Instant::now());
+    }
+
+    /// Saves scheduled changes that are due, or all of them when `force` is set.
+    fn flush_sessions(&mut self, ctx: &egui::Context, force: bool) {
+        let Some(since) = self.unsaved_since.filter(|_| !self.locked) else {
+            return;
+        };
+        let waited = since.elapsed();
+        if force || waited >= SAVE_DEBOUNCE {
+            if self.save_chat_sessions().is_ok() {
+                self.unsaved_since = None;
+            }
         } else {
-            Ok(Vec::new())
+            ctx.request_repaint_after(SAVE_DEBOUNCE - waited);
         }
     }
 
-    fn save_chat_sessions(&self) -> std::io::Result<()> {
-        let path = Self::sessions_file_path()?;
-        let content = serde_json::to_string_pretty(&self.chat_sessions)?;
-        fs::write(path, content)
+    /// Saves the window's geometry, sidebar width and open chat once they've
+    /// stayed the same for `SAVE_DEBOUNCE`, or right away when it's closing.
+    fn track_window_state(&mut self, ctx: &egui::Context, sidebar_width: Option<f32>) {
+        let (viewport, closing) = ctx.input(|i| (i.viewport().clone(), i.viewport().close_requested()));
+        let session_id = Some(&self.current_session.id).filter(|_| !self.current_session.messages.is_empty());
+        let state = self.window_state.observe(&viewport, sidebar_width, session_id);
+        if state == self.window_state {
+            self.window_changed_since = None;
+            return;
+        }
+        let since = *self.window_changed_since.get_or_insert_with(Instant::now);
+        if closing || since.elapsed() >= SAVE_DEBOUNCE {
+            let _ = state.save();
+            self.window_state = state;
+            self.window_changed_since = None;
+        } else {
+            ctx.request_repaint_after(SAVE_DEBOUNCE.saturating_sub(since.elapsed()));
+        }
     }
 
     /* --------------------------------------------------------------------- */
     /*                          session management                           */
     /* --------------------------------------------------------------------- */
 
+    /// Starts a new chat in a new tab.
     fn new_chat(&mut self) {
+        self.stash_pending();
         // push current session if it has any messages
         if !self.current_session.messages.is_empty() {
             self.chat_sessions.push(self.current_session.clone());
+        } else {
+            // an empty chat isn't kept, nor its tab
+            self.open_tabs.retain(|id| id != &self.current_session.id);
         }
 
         self.current_session = ChatSession::default();
         self.selected_session_id = None;
         self.current_input.clear();
+        self.open_tabs.push(self.current_session.id.clone());
+        self.unstash_pending();
     }
 
+    /// Switches to a session, opening a tab for it if it has none.
     fn load_session(&mut self, session_id: &str) {
         if let Some(pos) = self.chat_sessions.iter().position(|s| s.id == session_id) {
+            self.stash_pending();
             // swap out
             let mut session = self.chat_sessions.remove(pos);
+            Self::load_session_file(&mut session);
             std::mem::swap(&mut self.current_session, &mut session);
             self.chat_sessions.push(session); // save the replaced session back
             self.selected_session_id = Some(self.current_session.id.clone());
+            self.unstash_pending();
+            if !self.open_tabs.iter().any(|id| id == session_id) {
+                self.open_tabs.push(session_id.to_string());
+            }
         }
     }
 
-    /* --------------------------------------------------------------------- */
-    /*               helper: execute a command                               */
-    /* --------------------------------------------------------------------- */
-    fn execute_command(&self, command: &str) -> String {
-        let (shell_cmd, shell_arg) = Shell::detect().to_shell_command_and_command_arg();
-        
-        match ProcessCommand::new(shell_cmd)
-            .arg(shell_arg)
-            .arg(command)
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-        {
-            Ok(child) => {
-                match child.wait_with_output() {
-                    Ok(output) => {
-                        let mut result = String::new();
-                        if !output.stdout.is_empty() {
-                            result.push_str(&String::from_utf8_lossy(&output.stdout));
-                        }
-                        if !output.stderr.is_empty() {
-                            if !result.is_empty() {
-                                result.push_str("\n");
-                            }
-                            result.push_str(&String::from_utf8_lossy(&output.stderr));
-                        }
-                        if result.is_empty() {
-                            result = "Command executed successfully (no output)".to_string();
-                        }
-                        result
-                    }
-                    Err(e) => format!("Command execution failed: {}", e),
-                }
-            }
-            Err(e) => format!("Failed to start command: {}", e),
+    /// Closes the tab of `session_id`; the session stays in the history.
+    fn close_tab(&mut self, session_id: &str) {
+        let Some(pos) = self.open_tabs.iter().position(|id| id == session_id) else {
+            return;
+        };
+        self.open_tabs.remove(pos);
+        if session_id != self.current_session.id {
+            return;
+        }
+        match self.open_tabs.get(pos.min(self.open_tabs.len().saturating_sub(1))).cloned() {
+            Some(next) => self.load_session(&next),
+            None => self.new_chat(),
         }
     }
 
-    /* --------------------------------------------------------------------- */
-    /*               helper: handle a user submitting a prompt               */
-    /* --------------------------------------------------------------------- */
-    fn handle_user_prompt(&mut self, ctx: &egui::Context, prompt: String) {
-        // push user message
-        self.current_session.messages.push(ChatMessage {
-            content: prompt.clone(),
-            is_user: true,
-            timestamp: Utc::now(),
-            is_command: false,
-            executed: false,
-        });
+    /// Keeps the pending suggestions with the current session before it's switched out.
+    fn stash_pending(&mut self) {
+        self.current_session.pending = PendingState {
+            commands: std::mem::take(&mut self.pending_commands),
+            script: self.pending_script.take(),
+            planned_command: self.planned_command.take(),
+        };
+    }
 
-        if self.current_session.title == "New Chat" {
-            self.current_session.title = prompt
-                .chars()
-                .take(30)
-                .collect::<String>()
-                .trim_end()
-                .to_string();
-        }
+    /// Brings back the pending suggestions of the session just switched in.
+    fn unstash_pending(&mut self) {
+        let pending = std::mem::take(&mut self.current_session.pending);
+        self.pending_commands = pending.commands;
+        self.pending_script = pending.script;
+        self.planned_command = pending.planned_command;
+    }
 
-        // Check if this is a "yes" response to execute the last command
-        if let Some(ref pending_cmd) = self.pending_command.clone() {
-            let trimmed_prompt = prompt.trim().to_lowercase();
-            if matches!(trimmed_prompt.as_str(), "yes" | "y" | "sure" | "go ahead" | "execute" | "run it" | "do it") {
-                // Execute the pending command
-                let output = self.execute_command(pending_cmd);
-                self.pending_command = None;
-                
-                self.current_session.messages.push(ChatMessage {
-                    content: format!("Executing: {}\n\nOutput:\n{}", pending_cmd, output),
-                    is_user: false,
-                    timestamp: Utc::now(),
-                    is_command: false,
-                    executed: false,
-                });
-                
-                let _ = self.save_chat_sessions();
-                ctx.request_repaint();
-                return;
-            }
+    fn session_title(&self, session_id: &str) -> Option<&str> {
+        std::iter::once(&self.current_session).chain(&self.chat_sessions).find(|s| s.id == session_id).map(|s| s.title.as_str())
+    }
+
+    fn is_answering(&self, session_id: &str) -> bool {
+        self.in_flight.iter().any(|request| request.session_id == session_id)
+    }
+
+    /// The config the current chat's requests go out with: its own model, if
+    /// it has one, answering by itself.
+    fn chat_config(&self) -> Config {
+        match &self.current_session.model {
+            Some(model) => Config { model: model.clone(), routing: None, fallback: Vec::new(), ..self.config.clone() },
+            None => self.config.clone(),
         }
+    }
 
-        // Convert current session messages to conversation history format
-        let conversation_history: Vec<ConversationMessage> = self.current_session.messages
-            .iter()
-            .map(|msg| ConversationMessage {
-                content: msg.content.clone(),
-                is_user: msg.is_user,
-            })
-            .collect();
+    /// Asks a cheap model for a title once a chat has its first answer. The
+    /// first prompt, cut short, stays the title if that fails.
+    fn request_title(&self, ctx: &egui::Context, session_id: &str, answer: &str) {
+        let Some(session) = std::iter::once(&self.current_session).chain(&self.chat_sessions).find(|s| s.id == session_id) else {
+            return;
+        };
+        let mut prompts = session.messages.iter().filter(|m| m.is_user);
+        let (Some(prompt), None) = (prompts.next(), prompts.next()) else {
+            return;
+        };
+        if session.title != provisional_title(&prompt.content) || answer.starts_with("Error") {
+            return;
+        }
 
-        let cache_key = format!("unified:{}", prompt);
-        let response = match self.cache.get(&cache_key) {
-            Some(cached) => cached.clone(),
-            None => {
-                match self.config.model.llm_get_response(&self.config, &prompt, true, &conversation_history) {
-                    Ok(Some(reply)) => {
-                        self.cache.insert(cache_key, reply.clone());
-                        reply
-                    }
-                    Ok(None) => {
-                        let reply = "I'm not sure how to respond to that.".to_string();
-                        self.cache.insert(cache_key, reply.clone());
-                        reply
-                    }
-                    Err(e) => {
-                        let reply = format!("Error: {}", e);
-                        self.cache.insert(cache_key, reply.clone());
-                        reply
-                    }
+        let model = match &self.config.routing {
+            Some(routing) if session.model.is_none() => routing.first.clone(),
+            _ => session.model.as_ref().unwrap_or(&self.config.model).cheap(),
+        };
+        let config = Config { model: model.clone(), routing: None, fallback: Vec::new(), ..self.config.clone() };
+        let (tx, ctx) = (self.title_tx.clone(), ctx.clone());
+        let (session_id, prompt, answer) = (session_id.to_string(), prompt.content.clone(), answer.to_string());
+        std::thread::spawn(move || match model.llm_title(&config, &prompt, &answer) {
+            Ok(Some(title)) => {
+                let title = title.lines().next().unwrap_or_default().trim().trim_matches(['"', '\'', '*']).trim_end_matches('.').trim();
+                if !title.is_empty() {
+                    let _ = tx.send((session_id, title.chars().take(60).collect()));
+                    ctx.request_repaint();
                 }
             }
-        };
+            Ok(None) => {}
+            Err(e) => log::warn!("Can't title the chat: {}", e),
+        });
+    }
 
-        // Check if response contains a command or execute instruction
-        if response.contains("EXECUTE_LAST_COMMAND") {
-            if let Some(pending_cmd) = self.pending_command.clone() {
-                let output = self.execute_command(&pending_cmd);
-                self.pending_command = None;
-                
-                self.current_session.messages.push(ChatMessage {
-                    content: format!("Executing: {}\n\nOutput:\n{}", pending_cmd, output),
-                    is_user: false,
-                    timestamp: Utc::now(),
-                    is_command: false,
-                    executed: false,
-                });
+    /// Takes up the titles the model has come up with, unless the chat was
+    /// renamed in the meantime.
+    fn receive_titles(&mut self) {
+        while let Ok((session_id, title)) = self.title_rx.try_recv() {
+            let Some(session) = std::iter::once(&mut self.current_session).chain(&mut self.chat_sessions).find(|s| s.id == session_id) else {
+                continue;
+            };
+            let first_prompt = session.messages.iter().find(|m| m.is_user).map(|m| provisional_title(&m.content));
+            if first_prompt.as_ref() == Some(&session.title) {
+                session.title = title;
+                session.dirty = true;
+                self.schedule_save();
             }
-        } else {
-            // Look for COMMAND: pattern in the response
-            if let Some(cmd_start) = response.find("COMMAND: ") {
-                let cmd_part = &response[cmd_start + 9..];
-                if let Some(cmd_end) = cmd_part.find('`') {
-                    let command = cmd_part[..cmd_end].trim();
-                    self.pending_command = Some(command.to_string());
-                }
-            }
-            
-            self.current_session.messages.push(ChatMessage {
-                content: response,
-                is_user: false,
-                timestamp: Utc::now(),
-                is_command: false,
-                executed: false,
-            });
         }
+    }
 
-        // persist
-        let _ = self.save_chat_sessions();
+    fn rename_session(&mut self, session_id: &str, title: &str) {
+        let title = title.trim();
+        if title.is_empty() {
+            return;
+        }
+        if let Some(session) = std::iter::once(&mut self.current_session).chain(&mut self.chat_sessions).find(|s| s.id == session_id) {
+            session.title = title.to_string();
+            session.dirty = true;
+            self.schedule_save();
+        }
+    }
 
-        // keep UI responsive
-        ctx.request_repaint();
+    /// Models offered for a chat: the built-in ones and those in the config.
+    fn model_choices(&self) -> Vec<Model> {
+        let mut choices = vec![Model::OpenAiGpt4o, Model::OpenAiGpt4oMini, Model::OpenAiO3Mini, Model::Ollama("llama3.1".to_string()), self.config.model.clone()];
+        choices.extend(self.config.fallback.iter().cloned());
+        choices.extend(self.current_session.model.clone());
+        let mut seen = Vec::new();
+        choices.retain(|model| {
+            let name = model.to_string();
+            let new = !seen.contains(&name);
+            seen.push(name);
+            new
+        });
+        choices
     }
-}
 
-/* ------------------------------------------------------------------------- */
-/*                             egui integration                              */
-/* ------------------------------------------------------------------------- */
+    fn set_archived(&mut self, session_id: &str, archived: bool) {
+        if let Some(session) = self.chat_sessions.iter_mut().find(|s| s.id == session_id) {
+            // the flag lives in the session file too, so it has to be rewritten
+            Self::load_session_file(session);
+            session.archived = archived;
+            session.dirty = true;
+            self.schedule_save();
+        }
+    }
 
-impl eframe::App for LlmTermApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        /* --------------- LEFT: history ---------------- */
-        let mut new_chat_clicked = false;
-        let mut session_to_load: Option<String> = None;
+    /// Removes sessions for good, including their files and backups.
+    fn delete_sessions(&mut self, ids: &[String]) {
+        self.chat_sessions.retain(|s| !ids.contains(&s.id));
+        self.open_tabs.retain(|id| !ids.contains(id));
+        for id in ids {
+            if let Ok(path) = Self::session_file_path(id) {
+                for file in std::iter::once(path.clone()).chain(persist::backups(&path, SESSION_BACKUPS)) {
+                    let _ = fs::remove_file(file);
+                }
+            }
+        }
+        self.schedule_save();
+    }
 
-        egui::SidePanel::left("history").show(ctx, |ui| {
-            ui.heading("Chat History");
-            if ui.button("+ New Chat").clicked() {
-                new_chat_clicked = true;
+    fn run_data_action(&mut self, action: DataAction) {
+        let path = PathBuf::from(self.data_path.trim());
+        self.data_status = Some(match action {
+            DataAction::Export => {
+                // include the open chat
+                let _ = self.save_chat_sessions();
+                match data::export(&path) {
+                    Ok(count) => format!("Exported {} files.", count),
+                    Err(e) => format!("Export failed: {}", e),
+                }
             }
+            DataAction::Import => match data::import(&path) {
+                Ok(count) => {
+                    self.reload_data();
+                    format!("Imported {} files.", count)
+                }
+                Err(e) => format!("Import failed: {}", e),
+            },
+        });
+    }
 
-            ui.separator();
+    /// Asks the server in the Ollama menu for its models, off the UI thread.
+    fn test_ollama(&mut self, ctx: &egui::Context) {
+        let mut config = self.config.clone();
+        config.ollama_host = Some(self.ollama_host_input.trim().to_string()).filter(|host| !host.is_empty());
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(provider::ollama_models(&config));
+            ctx.request_repaint();
+        });
+        self.ollama_test_rx = Some(rx);
+        self.ollama_status = None;
+    }
+
+    fn receive_ollama_test(&mut self) {
+        let Some(result) = self.ollama_test_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+            return;
+        };
+        self.ollama_test_rx = None;
+        self.ollama_status = Some(match result {
+            Ok(models) if models.is_empty() => "Connected; no models pulled yet.".to_string(),
+            Ok(models) => format!("Connected: {}", models.join(", ")),
+            Err(e) => format!("Can't connect: {}", e),
+        });
+    }
 
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for session in &self.chat_sessions {
-                    let selected = self
-                        .selected_session_id
-                        .as_ref()
-                        .map(|id| id == &session.id)
-                        .unwrap_or(false);
+    /// Uses the server in the Ollama menu from now on, and saves it in config.json.
+    fn save_ollama_host(&mut self) {
+        let host = Some(self.ollama_host_input.trim().to_string()).filter(|host| !host.is_empty());
+        self.config.ollama_host = host.clone();
+        let mut config = Self::load_config().unwrap_or_else(|| self.config.clone());
+        config.ollama_host = host;
+        let saved = crate::get_default_config_path()
+            .and_then(|path| Ok(persist::write_atomic(&path, serde_json::to_string_pretty(&config)?.as_bytes())?));
+        self.ollama_status = Some(match saved {
+            Ok(()) => format!("Saved; Ollama is at {}", provider::ollama_url(&self.config)),
+            Err(e) => format!("Using it until the app closes; saving failed: {}", e),
+        });
+    }
 
-                    if ui.selectable_label(selected, &session.title).clicked() {
-                        session_to_load = Some(session.id.clone());
-                    }
+    fn show_unlock_screen(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let mut unlock = false;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.heading(tr(lang, "🔒 Storage is encrypted"));
+                let input = ui.add(
+                    egui::TextEdit::singleline(&mut self.passphrase_input)
+                        .password(true)
+                        .hint_text(tr(lang, "Passphrase")),
+                );
+                input.request_focus();
+                unlock = ui.button(tr(lang, "Unlock")).clicked()
+                    || (input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                if let Some(error) = &self.unlock_error {
+                    ui.colored_label(Color32::RED, error);
                 }
             });
         });
 
-        if new_chat_clicked {
-            self.new_chat();
+        if unlock {
+            match Vault::unlock(&std::mem::take(&mut self.passphrase_input)) {
+                Ok(vault) => {
+                    vault::install(vault);
+                    self.locked = false;
+                    self.unlock_error = None;
+                    self.reload_data();
+                }
+                Err(e) => self.unlock_error = Some(e),
+            }
         }
-        if let Some(id) = session_to_load {
-            self.load_session(&id);
+    }
+
+    /// Rereads everything an import may have replaced.
+    fn reload_data(&mut self) {
+        self.chat_sessions = Self::load_chat_sessions().unwrap_or_default();
+        self.current_session = ChatSession::default();
+        self.selected_session_id = None;
+        self.unsaved_since = None;
+        self.snippets = snippets::load_snippets().unwrap_or_default();
+        self.quick_actions = quick_actions::load_quick_actions().unwrap_or_default();
+        self.schedules = schedule::load_schedules().unwrap_or_default();
+        if let Some(cache) = Self::cache_file_path().ok().and_then(|path| Cache::load(path).ok()) {
+            self.cache = cache;
         }
+        if let Some(config) = Self::load_config() {
+            self.lang = Lang::from_config(config.language.as_deref());
+            self.redactor = Redactor::from_config(&config).ok().flatten();
+            self.config = config;
+        }
+    }
 
-        /* --------------- CENTRAL: chat log ------------ */
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("LLM Terminal");
+    /// The CLI's config, read as it reads it. What it had to ignore goes to the log.
+    fn load_config() -> Option<Config> {
+        let path = crate::get_default_config_path().ok()?;
+        let content = fs::read_to_string(&path).ok()?;
+        match Config::parse(&content) {
+            Ok((config, report)) => {
+                for problem in report.problems {
+                    log::warn!("{}: {}", path.display(), problem);
+                }
+                Some(config)
+            }
+            Err(e) => {
+                log::warn!("{} {}; using the defaults", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Sessions, other than the open one, created more than `days` days ago.
+    fn sessions_older_than(&self, days: u32) -> Vec<String> {
+        let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+        self.chat_sessions
+            .iter()
+            .filter(|s| s.created_at < cutoff)
+            .map(|s| s.id.clone())
+            .collect()
+    }
+
+    /// Starts a new session holding the history before `idx`, linked to the
+    /// current one, in a tab of its own.
+    fn branch_from(&mut self, idx: usize) {
+        self.stash_pending();
+        let mut branch = ChatSession {
+            title: format!("{} (branch)", self.current_session.title),
+            messages: self.current_session.messages[..idx].to_vec(),
+            parent_id: Some(self.current_session.id.clone()),
+            summary: self.current_session.summary.clone(),
+            summarized_count: self.current_session.summarized_count,
+            system_prompt: self.current_session.system_prompt.clone(),
+            working_dir: self.current_session.working_dir.clone(),
+            model: self.current_session.model.clone(),
+            ..ChatSession::default()
+        };
+        branch.invalidate_summary_after(idx);
+
+        let parent = std::mem::replace(&mut self.current_session, branch);
+        self.chat_sessions.push(parent);
+        self.selected_session_id = Some(self.current_session.id.clone());
+        self.open_tabs.push(self.current_session.id.clone());
+    }
+
+    /// "Fork from here": a new session with the history up to and including
+    /// message `idx`, to try another approach while the original stays as it was.
+    fn fork_from(&mut self, idx: usize) {
+        self.branch_from(idx + 1);
+        self.current_session.title = format!("{} (fork)", self.chat_sessions.last().map_or("", |parent| parent.title.as_str()));
+        self.current_input.clear();
+        self.schedule_save();
+    }
+
+    fn apply_edit(&mut self, ctx: &egui::Context, action: EditAction) {
+        self.editing = None;
+        self.pending_commands.clear();
+
+        let prompt = match action {
+            EditAction::Truncate(idx, prompt) => {
+                self.current_session.messages.truncate(idx);
+                self.current_session.invalidate_summary_after(idx);
+                prompt
+            }
+            EditAction::Branch(idx, prompt) => {
+                self.branch_from(idx);
+                prompt
+            }
+        };
+
+        self.handle_user_prompt(ctx, prompt);
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*               helper: execute a command                               */
+    /* --------------------------------------------------------------------- */
+    /// Executes `command` and records its output in the chat.
+    fn run_pending_command(&mut self, ctx: &egui::Context, command: &str) {
+        self.running_suggestion = self.suggestion_of(command).map(|idx| (self.current_session.id.clone(), idx));
+        let pending = self.dismiss_command(command);
+        self.skip_backup = pending.as_ref().is_some_and(|p| p.skip_backup);
+        self.delete_permanently = pending.as_ref().is_some_and(|p| p.delete_permanently);
+
+        if !snippets::placeholders(command).is_empty() {
+            // filled in by the placeholder window, which calls this again
+            self.placeholder_request = Some((command.to_string(), HashMap::new()));
+            return;
+        }
+
+        if self.config.suggest_only {
+            ctx.copy_text(command.to_string());
+            self.current_session.messages.push(ChatMessage::note(format!("Suggest-only mode: `{}` wasn't run. It was copied to the clipboard.", command)));
+            return;
+        }
+
+        let dir = self.current_session.working_dir.clone().or_else(|| std::env::current_dir().ok());
+        let project = dir.and_then(|dir| Project::discover(&dir)).and_then(Result::ok).unwrap_or_default();
+        if let Some(forbidden) = project.forbids(command) {
+            self.current_session.messages.push(ChatMessage::note(format!("Not running `{}`: `{}` is forbidden by {}.", command, forbidden, project.path.display())));
+            return;
+        }
+
+        if self.planned_command.take().as_deref() != Some(command) {
+            if let Some(plan) = iac::plan_for(command) {
+                self.show_plan(ctx, command, &plan);
+                return;
+            }
+        }
+
+        let guards = guard::check(command);
+        if !guards.is_empty() {
+            // confirmed in the guard window, which then calls `run_guarded_command`
+            self.guard_request = Some((command.to_string(), guards, String::new()));
+            return;
+        }
+        self.run_guarded_command(ctx, command);
+    }
+
+    /// Runs the preview of an apply as a background job. Once it's done,
+    /// `receive_job_outcomes` puts it in the chat and offers the apply again
+    /// if it succeeded.
+    fn show_plan(&mut self, ctx: &egui::Context, command: &str, plan: &str) {
+        let mut job = Job::start(ctx, plan, self.current_session.id.clone(), None, self.current_session.working_dir.clone(), self.config.limits, None);
+        job.previewing = Some(command.to_string());
+        self.jobs.push(job);
+    }
+
+    /// Runs `command` once its guards, if any, are confirmed: elevated if it
+    /// needs to be, and recorded in the command history.
+    fn run_guarded_command(&mut self, ctx: &egui::Context, command: &str) {
+        if let Some(prompt) = self.prompt_for_command(command) {
+            history::record(&prompt, command);
+        }
+        {
+            // looking the account up asks the cloud CLI, which takes a while
+            let (tx, session_id, ctx, command) = (self.check_tx.clone(), self.current_session.id.clone(), ctx.clone(), command.to_string());
+            std::thread::spawn(move || {
+                if let Some(account) = cloud::account(&command) {
+                    let _ = tx.send((session_id, Checked::Account(account)));
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        let shell = Shell::detect();
+        let use_trash = self.config.use_trash && !std::mem::take(&mut self.delete_permanently);
+        let trashed = use_trash.then(|| trash::rewrite(command, &shell)).flatten();
+        if trashed.is_none() && use_trash && trash::deletes(command, &shell) {
+            self.current_session.messages.push(ChatMessage::note(format!("Not running `{}`: {}. Tick \"Delete permanently\" to delete for good.", command, trash::not_rewritten_reason(command, &shell))));
+            let skip_backup = std::mem::take(&mut self.skip_backup);
+            self.queue_command(PendingCommand { skip_backup, ..PendingCommand::new(command.to_string()) });
+            return;
+        }
+        let original = command;
+        let command = trashed.as_deref().unwrap_or(command);
+
+        let dir = self.current_session.working_dir.clone().or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        let targets = if std::mem::take(&mut self.skip_backup) { Vec::new() } else { backup::targets(command, &dir, false) };
+        if !targets.is_empty() {
+            // runs in `receive_checks` once the backup is taken
+            let (tx, session_id, ctx) = (self.check_tx.clone(), self.current_session.id.clone(), ctx.clone());
+            let (original, command, suggestion) = (original.to_string(), command.to_string(), self.running_suggestion.take());
+            std::thread::spawn(move || {
+                let snapshot = backup::take(&command, &targets);
+                let _ = tx.send((session_id, Checked::Backup { original, command, files: targets.len(), suggestion, snapshot }));
+                ctx.request_repaint();
+            });
+            return;
+        }
+        self.run_backed_up_command(ctx, command);
+    }
+
+    /// Runs `command` once any backup of its files is taken, after the extra
+    /// confirmation if it needs administrator rights.
+    fn run_backed_up_command(&mut self, ctx: &egui::Context, command: &str) {
+        match elevation::detect(command).filter(|_| !elevation::is_elevated()) {
+            // asked again in the elevation window, which then calls `start_command`
+            Some(needed) => self.elevation_request = Some((command.to_string(), needed)),
+            None => self.start_command(ctx, command, false),
+        }
+    }
+
+    /// The last answer suggesting `command`, or a command wrapping it, e.g. to save its output.
+    fn suggestion_of(&self, command: &str) -> Option<usize> {
+        self.current_session
+            .messages
+            .iter()
+            .rposition(|m| m.is_command && extract::commands(&m.content).iter().any(|c| command.contains(c.as_str())))
+    }
+
+    /// Marks the answer whose command just ran as executed, with how it exited.
+    fn mark_executed(&mut self, exit_code: Option<i32>) {
+        let Some((session_id, idx)) = self.running_suggestion.take() else {
+            return;
+        };
+        if session_id != self.current_session.id {
+            return;
+        }
+        if let Some(message) = self.current_session.messages.get_mut(idx).filter(|m| m.is_command) {
+            message.executed = true;
+            message.exit_code = exit_code;
+        }
+    }
+
+    /// Adds `pending` to the commands waiting to be run, unless it's there already.
+    fn queue_command(&mut self, pending: PendingCommand) {
+        if !self.pending_commands.iter().any(|p| p.command == pending.command) {
+            self.pending_commands.push(pending);
+        }
+    }
+
+    /// Offers `command` to be run again in the chat `session_id`, whether it's open or not.
+    fn offer_again(&mut self, session_id: &str, command: &str) {
+        if session_id == self.current_session.id {
+            self.queue_command(PendingCommand::new(command.to_string()));
+        } else if let Some(session) = self.chat_sessions.iter_mut().find(|s| s.id == session_id) {
+            if !session.pending.commands.iter().any(|p| p.command == command) {
+                session.pending.commands.push(PendingCommand::new(command.to_string()));
+            }
+        }
+    }
+
+    /// Takes `command` off the pending ones, returning it with its options.
+    fn dismiss_command(&mut self, command: &str) -> Option<PendingCommand> {
+        let idx = self.pending_commands.iter().position(|p| p.command == command)?;
+        Some(self.pending_commands.remove(idx))
+    }
+
+    /// The command suggested last, which "yes" runs.
+    fn latest_pending_command(&self) -> Option<String> {
+        self.pending_commands.last().map(|p| p.command.clone())
+    }
+
+    /// What each pending command would touch, in the order they're queued;
+    /// looked up once per command.
+    fn pending_files(&mut self) -> Vec<PendingFiles> {
+        let commands: Vec<String> = self.pending_commands.iter().map(|p| p.command.clone()).collect();
+        self.pending_files.retain(|files| commands.contains(&files.command));
+        let dir = self.command_dir();
+        commands
+            .into_iter()
+            .map(|command| {
+                if let Some(files) = self.pending_files.iter().find(|files| files.command == command) {
+                    return files.clone();
+                }
+                let trashing = self.config.use_trash && trash::deletes(&command, &Shell::detect());
+                let files = PendingFiles {
+                    affected: affected::affected(&command, &dir),
+                    backup_targets: backup::targets(&command, &dir, trashing),
+                    edits: diff::edited_files(&command, &dir),
+                    command,
+                };
+                self.pending_files.push(files.clone());
+                files
+            })
+            .collect()
+    }
+
+    /// The directory commands of this chat run in.
+    fn command_dir(&self) -> PathBuf {
+        self.current_session.working_dir.clone().or_else(|| std::env::current_dir().ok()).unwrap_or_default()
+    }
+
+    fn start_dictation(&mut self) {
+        self.dictation_error = None;
+        match voice::start() {
+            Ok(recording) => self.dictation = Some(recording),
+            Err(e) => self.dictation_error = Some(e),
+        }
+    }
+
+    /// Stops recording and transcribes it in the background; the text lands in the composer.
+    fn finish_dictation(&mut self, ctx: &egui::Context) {
+        let Some(recording) = self.dictation.take() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let config = self.config.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(recording.finish(&config).map_err(|e| e.to_string()));
+            ctx.request_repaint();
+        });
+        self.dictation_rx = Some(rx);
+    }
+
+    /// Runs the in-place edits of `command` on copies of their files and puts
+    /// the diff in the chat; the command itself stays pending.
+    fn preview_edits(&mut self, ctx: &egui::Context, command: &str) {
+        let dir = self.command_dir();
+        let executor = Executor::default().with_limits(self.config.limits).in_dir(&dir);
+        let (tx, session_id, ctx, command) = (self.check_tx.clone(), self.current_session.id.clone(), ctx.clone(), command.to_string());
+        std::thread::spawn(move || {
+            let diffs = diff::preview(&executor, &command, &dir);
+            let _ = tx.send((session_id, Checked::Preview { command, diffs }));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Puts what the checks run in the background found in their chats, and
+    /// runs the commands whose backups are done.
+    fn receive_checks(&mut self, ctx: &egui::Context) {
+        while let Ok((session_id, checked)) = self.check_rx.try_recv() {
+            let is_current = session_id == self.current_session.id;
+            let (content, output, backup) = match checked {
+                Checked::Account(account) => (format!("Runs against {}.", account), None, None),
+                Checked::Preview { command, diffs } => match diffs {
+                    Ok(diffs) => {
+                        let text = diffs.iter().filter(|d| !d.diff.is_empty()).map(|d| diff::colorize(&d.diff)).collect::<Vec<_>>().join("\n");
+                        let unchanged = diffs.iter().filter(|d| d.diff.is_empty()).count();
+                        let mut content = format!("Preview of the edit, made on copies of {} file(s); nothing has been changed yet.", diffs.len());
+                        if unchanged > 0 {
+                            content.push_str(&format!(" {} of them would stay the same.", unchanged));
+                        }
+                        let output = (!text.is_empty()).then(|| self.truncate_output(&format!("preview: {}", command), text));
+                        (content, output, None)
+                    }
+                    Err(e) => (format!("Couldn't preview the edit: {}", e), None, None),
+                },
+                Checked::Backup { original, command, files, suggestion, snapshot } => match snapshot {
+                    Ok(snapshot) if is_current => {
+                        self.current_session.messages.push(ChatMessage {
+                            backup: Some(snapshot.id),
+                            ..ChatMessage::note(format!("Backed up {} file(s) before running `{}`.", files, command))
+                        });
+                        self.running_suggestion = suggestion;
+                        self.run_backed_up_command(ctx, &command);
+                        self.schedule_save();
+                        continue;
+                    }
+                    Ok(snapshot) => {
+                        // it'd run in another chat's directory; it's offered again there instead
+                        self.offer_again(&session_id, &original);
+                        (format!("Backed up {} file(s), but `{}` wasn't run because another chat was open by then.", files, original), None, Some(snapshot.id))
+                    }
+                    Err(e) => {
+                        self.offer_again(&session_id, &original);
+                        (format!("Not running `{}`: the backup failed: {}. Untick \"Back up\" to run it anyway.", original, e), None, None)
+                    }
+                },
+            };
+            let Some(session) = std::iter::once(&mut self.current_session).chain(&mut self.chat_sessions).find(|s| s.id == session_id) else {
+                // the chat was deleted in the meantime
+                continue;
+            };
+            Self::load_session_file(session);
+            session.dirty = true;
+            session.messages.push(ChatMessage {
+                output,
+                backup,
+                ..ChatMessage::note(content)
+            });
+            self.schedule_save();
+        }
+    }
+
+    /// Puts the files of backup `id` back and says how it went in the chat.
+    fn restore_backup(&mut self, id: &str) {
+        let result = backup::find(id).ok_or_else(|| format!("the backup {} is gone", id)).and_then(|snapshot| backup::restore(&snapshot).map(|()| snapshot));
+        let content = match result {
+            Ok(snapshot) => format!("Restored {} file(s) as they were before `{}`.", snapshot.paths.len(), snapshot.command),
+            Err(e) => format!("Couldn't restore the backup: {}", e),
+        };
+        self.current_session.messages.push(ChatMessage::note(content));
+    }
+
+    /// The user message that led to the answer suggesting `command`, if it's in this chat.
+    fn prompt_for_command(&self, command: &str) -> Option<String> {
+        let messages = &self.current_session.messages;
+        let answer = messages.iter().rposition(|m| !m.is_user && m.content.contains(command))?;
+        messages[..answer].iter().rev().find(|m| m.is_user).map(|m| m.content.clone())
+    }
+
+    /// Runs an approved `command`: in the terminal pane if it's open, in a
+    /// terminal window if it's interactive (or `in_terminal` is set), and
+    /// captured into the chat otherwise.
+    fn start_command(&mut self, ctx: &egui::Context, command: &str, in_terminal: bool) {
+        if let Some(shell) = self.shell_pane.as_mut().filter(|_| self.show_shell_pane) {
+            let (content, sent) = match shell.send_line(command) {
+                Ok(()) => (format!("Sent to terminal pane: {}", command), true),
+                Err(e) => (format!("Failed to send `{}` to the terminal pane: {}", command, e), false),
+            };
+            if sent {
+                // how it exits isn't known in the pane
+                self.mark_executed(None);
+            }
+            self.current_session.messages.push(ChatMessage::note(content));
+            return;
+        }
+
+        if in_terminal || self.run_interactive || pty::needs_terminal(command) {
+            let before = self.snapshot_before(command);
+            match PtySession::spawn(command, self.current_session.working_dir.as_deref()) {
+                Ok(session) => {
+                    self.terminal = Some(session);
+                    self.terminal_input.clear();
+                    self.terminal_recorded = false;
+                    self.terminal_before = before;
+                }
+                Err(e) => self.current_session.messages.push(ChatMessage::note(format!("Failed to start terminal for `{}`: {}", command, e))),
+            }
+            return;
+        }
+
+        // finished in `receive_job_outcomes`
+        let suggestion = self.running_suggestion.take().filter(|(session_id, _)| *session_id == self.current_session.id).map(|(_, idx)| idx);
+        let before = self.snapshot_before(command);
+        let job = Job::start(ctx, command, self.current_session.id.clone(), suggestion, self.current_session.working_dir.clone(), self.config.limits, before);
+        self.jobs.push(job);
+    }
+
+    /// Adds the output of the background jobs that finished to the chats they were run from.
+    fn receive_job_outcomes(&mut self, ctx: &egui::Context) {
+        let finished: Vec<(usize, jobs::Outcome)> = self.jobs.iter().enumerate().filter_map(|(i, job)| job.take_outcome().map(|outcome| (i, outcome))).collect();
+        for (i, outcome) in finished {
+            let job = &self.jobs[i];
+            let (command, session_id, suggestion, previewing) = (job.command.clone(), job.session_id.clone(), job.suggestion, job.previewing.clone());
+            self.notify_finished(ctx, job.started, if previewing.is_some() { "Preview finished" } else { "Command finished" }, &command);
+            let mut output = self.truncate_output(&command, outcome.output);
+            output.changes = outcome.changes;
+
+            let is_current = session_id == self.current_session.id;
+            let Some(session) = std::iter::once(&mut self.current_session).chain(&mut self.chat_sessions).find(|s| s.id == session_id) else {
+                // the chat was deleted while it ran
+                continue;
+            };
+            Self::load_session_file(session);
+            session.dirty = true;
+            if let Some(apply) = previewing {
+                let succeeded = outcome.exit_code == Some(0);
+                let content = if succeeded {
+                    format!("Previewed the changes with `{}`. Run the command again to apply them.", command)
+                } else {
+                    format!("`{}` failed, so the changes weren't applied.", command)
+                };
+                session.messages.push(ChatMessage { output: Some(output), ..ChatMessage::note(content) });
+                if succeeded && !is_current {
+                    session.pending.planned_command = Some(apply.clone());
+                } else if succeeded {
+                    self.planned_command = Some(apply.clone());
+                }
+                if succeeded {
+                    self.offer_again(&session_id, &apply);
+                }
+                self.schedule_save();
+                continue;
+            }
+            if outcome.working_dir.is_some() {
+                session.working_dir = outcome.working_dir;
+            }
+            if let Some(message) = suggestion.and_then(|idx| session.messages.get_mut(idx)).filter(|m| m.is_command) {
+                message.executed = true;
+                message.exit_code = outcome.exit_code;
+            }
+            session.messages.push(ChatMessage {
+                executed: true,
+                exit_code: outcome.exit_code,
+                output: Some(output),
+                ..ChatMessage::note(format!("Executing: {}", command))
+            });
+            self.schedule_save();
+        }
+    }
+
+    /// The Jobs panel: every command run in the background, with its status,
+    /// time and last lines of output, and a kill button while it runs.
+    fn show_jobs_panel(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let mut kill = None;
+        let mut clear = false;
+
+        egui::SidePanel::right("jobs_panel")
+            .resizable(true)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(tr(lang, "⚙ Jobs"));
+                    let any_done = self.jobs.iter().any(|job| job.status() != jobs::Status::Running);
+                    if ui.add_enabled(any_done, egui::Button::new(tr(lang, "Clear finished"))).clicked() {
+                        clear = true;
+                    }
+                    if icon_button(ui, "✕", tr(lang, "Close")).clicked() {
+                        self.show_jobs = false;
+                    }
+                });
+                ui.separator();
+                if self.jobs.is_empty() {
+                    ui.weak(tr(lang, "Commands run from the chat show up here while they run."));
+                }
+
+                egui::ScrollArea::vertical().id_salt("jobs_list").show(ui, |ui| {
+                    for (i, job) in self.jobs.iter().enumerate().rev() {
+                        let (color, status) = match job.status() {
+                            jobs::Status::Running => (Color32::YELLOW, tr(lang, "running")),
+                            jobs::Status::Finished => (Color32::LIGHT_GREEN, tr(lang, "finished")),
+                            jobs::Status::Failed => (Color32::LIGHT_RED, tr(lang, "failed")),
+                            jobs::Status::Killed => (Color32::GRAY, tr(lang, "killed")),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.colored_label(color, status);
+                            if let (jobs::Status::Failed, Some(code)) = (job.status(), job.exit_code()) {
+                                ui.weak(format!("({})", code));
+                            }
+                            ui.weak(format!("{:.1}s", job.elapsed().as_secs_f32()));
+                            if job.status() == jobs::Status::Running && ui.small_button(tr(lang, "⏹ Kill")).clicked() {
+                                kill = Some(i);
+                            }
+                        });
+                        ui.label(egui::RichText::new(format!("$ {}", job.command)).monospace());
+                        let tail = job.tail(JOB_TAIL_LINES);
+                        if !tail.is_empty() {
+                            ui.label(ansi_layout_job(ui, &tail));
+                        }
+                        ui.separator();
+                    }
+                });
+            });
+
+        if let Some(job) = kill.and_then(|i| self.jobs.get(i)) {
+            job.kill();
+        }
+        if clear {
+            self.jobs.retain(|job| job.status() == jobs::Status::Running);
+        }
+        if self.jobs.iter().any(|job| job.status() == jobs::Status::Running) {
+            // the elapsed times keep counting
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+    }
+
+    /// Asks for the placeholders left in an approved command before it runs.
+    fn show_placeholder_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let suggest_only = self.config.suggest_only;
+        let Some((command, values)) = &mut self.placeholder_request else {
+            return;
+        };
+        let mut run = None;
+        let mut cancel = false;
+
+        egui::Window::new(tr(lang, "✏ Fill in the command"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.monospace(snippets::fill(command, values));
+                egui::Grid::new("placeholder_form").num_columns(2).show(ui, |ui| {
+                    for placeholder in snippets::placeholders(command) {
+                        ui.label(format!("{}:", placeholder));
+                        ui.text_edit_singleline(values.entry(placeholder).or_default());
+                        ui.end_row();
+                    }
+                });
+                let rendered = snippets::render(command, values);
+                ui.horizontal(|ui| {
+                    let label = if suggest_only { tr(lang, "📋 Copy") } else { tr(lang, "▶ Run") };
+                    if ui.add_enabled(rendered.is_ok(), egui::Button::new(label)).clicked() {
+                        run = rendered.ok();
+                    }
+                    cancel = ui.button(tr(lang, "Cancel")).clicked();
+                });
+            });
+
+        if let Some(command) = run {
+            self.placeholder_request = None;
+            self.run_pending_command(ctx, &command);
+            self.schedule_save();
+        } else if cancel {
+            self.placeholder_request = None;
+        }
+    }
+
+    /// Saves the pending script to a temporary file and runs it with its interpreter.
+    fn run_pending_script(&mut self, ctx: &egui::Context) {
+        let Some(script) = self.pending_script.take() else {
+            return;
+        };
+        let line = script
+            .save_temp()
+            .and_then(|path| script.command_line(&path, &Shell::detect()).ok_or_else(|| io::Error::other("no interpreter")));
+        match line {
+            Ok(line) => match elevation::detect(&script.body).filter(|_| !self.config.suggest_only && !elevation::is_elevated()) {
+                Some(needed) => self.elevation_request = Some((line, needed)),
+                None => self.run_pending_command(ctx, &line),
+            },
+            Err(e) => self.current_session.messages.push(ChatMessage::note(format!("Couldn't run the {} script: {}", script.language, e))),
+        }
+    }
+
+    /// Editor for the script from the last answer, with Run, Copy and Discard.
+    fn show_script_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let suggest_only = self.config.suggest_only;
+        let Some(script) = &mut self.pending_script else {
+            return;
+        };
+        let language = script.language.clone();
+        let mut run = false;
+        let mut discard = false;
+
+        egui::Window::new(tr(lang, "📜 Script"))
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr(lang, "Language:"));
+                    ui.monospace(&language);
+                    if script.interpreter().is_none() {
+                        ui.colored_label(Color32::YELLOW, tr(lang, "no interpreter known for this language"));
+                    }
+                });
+                let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                    let mut job = script_layout_job(ui, text, &language);
+                    job.wrap.max_width = wrap_width;
+                    ui.fonts(|fonts| fonts.layout_job(job))
+                };
+                egui::ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut script.body)
+                            .code_editor()
+                            .desired_rows(12)
+                            .desired_width(f32::INFINITY)
+                            .layouter(&mut layouter),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if !suggest_only && ui.add_enabled(script.interpreter().is_some(), egui::Button::new(tr(lang, "▶ Run script"))).clicked() {
+                        run = true;
+                    }
+                    if ui.button(tr(lang, "📋 Copy")).clicked() {
+                        ui.ctx().copy_text(script.body.clone());
+                    }
+                    if ui.button(tr(lang, "Discard")).clicked() {
+                        discard = true;
+                    }
+                });
+            });
+
+        if run {
+            self.run_pending_script(ctx);
+            self.schedule_save();
+        } else if discard {
+            self.pending_script = None;
+        }
+    }
+
+    /// Shows the first guard left for the pending command and asks the user
+    /// to type what it wants before going on to the next one.
+    fn show_guard_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let Some((command, guards, input)) = &mut self.guard_request else {
+            return;
+        };
+        let Some(guard) = guards.first().cloned() else {
+            self.guard_request = None;
+            return;
+        };
+        let mut run = false;
+        let mut cancel = false;
+
+        egui::Window::new(tr(lang, "⚠ Confirm command"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.colored_label(Color32::from_rgb(230, 90, 70), &guard.warning);
+                ui.monospace(command.as_str());
+                ui.label(format!("{} `{}`", tr(lang, "To run it, type"), guard.confirm));
+                let response = ui.text_edit_singleline(input);
+                let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                ui.horizontal(|ui| {
+                    let accepted = guard.accepts(input);
+                    if ui.add_enabled(accepted, egui::Button::new(tr(lang, "▶ Run"))).clicked() || (entered && accepted) {
+                        run = true;
+                    }
+                    if ui.button(tr(lang, "Cancel")).clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if run {
+            guards.remove(0);
+            input.clear();
+            if guards.is_empty() {
+                let command = command.clone();
+                self.guard_request = None;
+                self.run_guarded_command(ctx, &command);
+                self.schedule_save();
+            }
+        } else if cancel {
+            self.guard_request = None;
+        }
+    }
+
+    /// Asks again before a command that needs administrator rights runs, and
+    /// lets the user pick how the password is asked for.
+    fn show_elevation_window(&mut self, ctx: &egui::Context) {
+        let Some((command, needed)) = self.elevation_request.clone() else {
+            return;
+        };
+        let lang = self.lang;
+        let shell = Shell::detect();
+        let mut run: Option<(String, bool)> = None;
+        let mut cancel = false;
+
+        egui::Window::new(tr(lang, "⚠ Administrator rights"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(match needed {
+                    Elevation::Requested => tr(lang, "This command runs with administrator rights:"),
+                    Elevation::Required => tr(lang, "This command needs administrator rights to work:"),
+                });
+                ui.monospace(&command);
+                ui.horizontal(|ui| {
+                    if ui.button(tr(lang, "🔑 Run with system prompt")).on_hover_text(tr(lang, "Ask for the password in a system dialog (pkexec, UAC)")).clicked() {
+                        run = Some((elevation::for_desktop(&command, &shell), false));
+                    }
+                    if !matches!(shell, Shell::Powershell)
+                        && ui.button(tr(lang, "🖥 Run in terminal")).on_hover_text(tr(lang, "Type the sudo password in the terminal window")).clicked()
+                    {
+                        run = Some((elevation::for_terminal(&command, needed, &shell), true));
+                    }
+                    if ui.button(tr(lang, "Cancel")).clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if let Some((elevated, in_terminal)) = run {
+            self.elevation_request = None;
+            self.start_command(ctx, &elevated, in_terminal);
+            self.schedule_save();
+        } else if cancel {
+            self.elevation_request = None;
+        }
+    }
+
+    /// Keeps the first `max_output_bytes` of `text`, moving the complete output
+    /// into a spillover file so it doesn't bloat the chat view and sessions file.
+    fn truncate_output(&self, command: &str, mut text: String) -> CommandOutput {
+        let limit = self.config.max_output_bytes;
+        if text.len() <= limit {
+            return CommandOutput { command: command.to_string(), text, full_output_path: None, changes: None };
+        }
+
+        let full_output_path = Self::outputs_dir()
+            .map(|dir| dir.join(format!("{}.txt", uuid::Uuid::new_v4())))
+            .and_then(|path| fs::write(&path, &text).map(|_| path))
+            .ok();
+
+        let mut cut = limit;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+
+        CommandOutput { command: command.to_string(), text, full_output_path, changes: None }
+    }
+
+    /// The session's directory as it is before `command`, if it looks like it changes files.
+    fn snapshot_before(&self, command: &str) -> Option<changes::Snapshot> {
+        if !changes::affects_files(command) {
+            return None;
+        }
+        let dir = self.current_session.working_dir.clone().or_else(|| std::env::current_dir().ok())?;
+        Some(changes::Snapshot::take(&dir))
+    }
+
+    /// Adds the output of a finished terminal session to the chat.
+    fn record_terminal_output(&mut self, command: String, text: String, exit_code: Option<i32>) {
+        let mut output = self.truncate_output(&command, text);
+        output.changes = self.terminal_before.take().map(|before| before.compare(&changes::Snapshot::take(&before.dir)));
+        self.mark_executed(exit_code);
+        self.current_session.messages.push(ChatMessage {
+            executed: true,
+            exit_code,
+            output: Some(output),
+            ..ChatMessage::note(format!("Executing: {}", command))
+        });
+        self.schedule_save();
+    }
+
+    /// Sends a desktop notification for work started at `started` if it took
+    /// long enough and the window isn't focused.
+    fn notify_finished(&self, ctx: &egui::Context, started: Instant, summary: &str, body: &str) {
+        if !self.config.notifications || started.elapsed() < Duration::from_secs(self.config.notify_after_secs) {
+            return;
+        }
+        if !ctx.input(|i| i.focused) {
+            notify::send(summary, body);
+        }
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*                          scheduled commands                           */
+    /* --------------------------------------------------------------------- */
+
+    /// Turns a request like "check disk usage every hour" into a schedule draft.
+    fn draft_schedule(&mut self) {
+        let description = self.schedule_input.trim().to_string();
+        self.schedule_error = None;
+
+        let Some(interval) = schedule::parse_interval(&description) else {
+            self.schedule_error = Some("Say how often to run it, e.g. \"every 15 minutes\" or \"hourly\".".to_string());
+            return;
+        };
+
+        match self.config.model.llm_get_command(&self.config, &description) {
+            Ok(Some(reply)) if !reply.trim().is_empty() => match generator::question(&reply) {
+                // there's no one to answer it when the schedule runs
+                Some(question) => self.schedule_error = Some(format!("{} Add that to the request.", question)),
+                None => self.schedule_draft = Some(Schedule::new(description, reply.trim().to_string(), interval)),
+            },
+            Ok(_) => self.schedule_error = Some("No command could be generated.".to_string()),
+            Err(e) => self.schedule_error = Some(format!("Error: {}", e)),
+        }
+    }
+
+    /// Starts every due schedule on a background thread and collects finished runs.
+    fn run_due_schedules(&mut self, ctx: &egui::Context) {
+        let mut changed = false;
+
+        while let Ok((id, output)) = self.schedule_rx.try_recv() {
+            if let Some(schedule) = self.schedules.iter_mut().find(|s| s.id == id) {
+                schedule.last_output = Some(output);
+                changed = true;
+            }
+        }
+
+        let now = Utc::now();
+        let suggest_only = self.config.suggest_only;
+        let limits = self.config.limits;
+        for schedule in self.schedules.iter_mut().filter(|s| !suggest_only && s.is_due(now)) {
+            schedule.mark_ran(now);
+            changed = true;
+
+            let tx = self.schedule_tx.clone();
+            let ctx = ctx.clone();
+            let (id, command) = (schedule.id.clone(), schedule.command.clone());
+            std::thread::spawn(move || {
+                let (output, _, _) = execute_in_shell(&command, None, limits);
+                let _ = tx.send((id, output));
+                ctx.request_repaint();
+            });
+        }
+
+        if changed {
+            let _ = schedule::save_schedules(&self.schedules);
+        }
+        if self.schedules.iter().any(|s| s.enabled) {
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+    }
+
+    fn show_schedules_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_schedules;
+        let mut generate = false;
+        let mut approve = false;
+        let mut discard = false;
+        let mut remove: Option<String> = None;
+        let mut changed = false;
+
+        egui::Window::new("⏰ Schedules")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let input = ui.add(
+                        egui::TextEdit::singleline(&mut self.schedule_input)
+                            .hint_text("e.g. check disk usage every hour")
+                            .desired_width(400.0),
+                    );
+                    generate = ui.add_enabled(!self.schedule_input.trim().is_empty(), egui::Button::new("Generate")).clicked()
+                        || (input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                });
+                if let Some(error) = &self.schedule_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+
+                if let Some(draft) = &mut self.schedule_draft {
+                    ui.separator();
+                    ui.label(format!("Runs {}:", schedule::describe_interval(draft.interval_secs)));
+                    ui.add(egui::TextEdit::singleline(&mut draft.command).code_editor().desired_width(f32::INFINITY));
+
+                    let entry = if cfg!(target_os = "windows") { draft.task_scheduler_entry() } else { draft.cron_entry() };
+                    ui.horizontal(|ui| {
+                        ui.weak(if cfg!(target_os = "windows") { "Task Scheduler:" } else { "crontab:" });
+                        ui.monospace(&entry);
+                        if icon_button(ui, "📋", "Copy the entry to run it without the GUI").clicked() {
+                            ui.ctx().copy_text(entry.clone());
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        approve = ui.button("Schedule in app").clicked();
+                        discard = ui.button("Discard").clicked();
+                    });
+                }
+
+                ui.separator();
+                if self.schedules.is_empty() {
+                    ui.weak("No schedules yet.");
+                }
+                for schedule in &mut self.schedules {
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut schedule.enabled, "").changed();
+                        ui.monospace(&schedule.command);
+                        ui.weak(schedule::describe_interval(schedule.interval_secs));
+                        if icon_button(ui, "🗑", "Remove schedule").clicked() {
+                            remove = Some(schedule.id.clone());
+                        }
+                    });
+                    ui.indent(&schedule.id, |ui| {
+                        ui.weak(&schedule.description);
+                        match schedule.last_run {
+                            Some(last_run) => ui.weak(format!("Last run {}", last_run.format("%Y-%m-%d %H:%M"))),
+                            None => ui.weak(format!("Next run {}", schedule.next_run.format("%Y-%m-%d %H:%M"))),
+                        };
+                        if let Some(output) = &schedule.last_output {
+                            egui::CollapsingHeader::new("Last output")
+                                .id_salt(("schedule_output", &schedule.id))
+                                .show(ui, |ui| {
+                                    ui.label(ansi_layout_job(ui, output));
+                                });
+                        }
+                    });
+                }
+            });
+
+        self.show_schedules = open;
+        if generate {
+            self.draft_schedule();
+        }
+        if approve {
+            if let Some(draft) = self.schedule_draft.take() {
+                self.schedules.push(draft);
+                self.schedule_input.clear();
+                changed = true;
+            }
+        }
+        if discard {
+            self.schedule_draft = None;
+        }
+        if let Some(id) = remove {
+            self.schedules.retain(|s| s.id != id);
+            changed = true;
+        }
+        if changed {
+            let _ = schedule::save_schedules(&self.schedules);
+        }
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*                            snippets library                           */
+    /* --------------------------------------------------------------------- */
+
+    /// Opens the snippets window with `command` ready to be named and saved.
+    /// The prompt that produced it becomes the description.
+    fn star_command(&mut self, idx: usize, command: String) {
+        let description = self.current_session.messages[..idx]
+            .iter()
+            .rev()
+            .find(|m| m.is_user)
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+
+        self.snippet_draft = Some(Snippet { name: String::new(), description, command });
+        self.show_snippets = true;
+    }
+
+    fn show_snippets_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_snippets;
+        let mut save = false;
+        let mut discard = false;
+        let mut run: Option<String> = None;
+        let mut remove: Option<String> = None;
+        let suggest_only = self.config.suggest_only;
+
+        egui::Window::new("⭐ Snippets")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                if let Some(draft) = &mut self.snippet_draft {
+                    egui::Grid::new("snippet_draft").num_columns(2).show(ui, |ui| {
+                        ui.label("Name:");
+                        ui.add(egui::TextEdit::singleline(&mut draft.name).hint_text("e.g. kill-port"));
+                        ui.end_row();
+                        ui.label("Description:");
+                        ui.text_edit_singleline(&mut draft.description);
+                        ui.end_row();
+                        ui.label("Command:");
+                        ui.add(egui::TextEdit::singleline(&mut draft.command).code_editor().desired_width(400.0));
+                        ui.end_row();
+                    });
+                    ui.weak("Use {name} for values filled in when running, e.g. lsof -ti :{port} | xargs kill");
+                    ui.horizontal(|ui| {
+                        let valid = !draft.name.trim().is_empty() && !draft.command.trim().is_empty();
+                        save = ui.add_enabled(valid, egui::Button::new("Save")).clicked();
+                        discard = ui.button("Discard").clicked();
+                    });
+                    ui.separator();
+                }
+
+                if self.snippets.is_empty() {
+                    ui.weak("No snippets yet. Star a command with ⭐ to save it here.");
+                }
+                for snippet in &self.snippets {
+                    let values = self.snippet_values.entry(snippet.name.clone()).or_default();
+                    ui.horizontal(|ui| {
+                        ui.strong(&snippet.name);
+                        ui.monospace(&snippet.command);
+                    });
+                    ui.indent(("snippet", &snippet.name), |ui| {
+                        if !snippet.description.is_empty() {
+                            ui.weak(&snippet.description);
+                        }
+                        ui.horizontal(|ui| {
+                            for placeholder in snippet.placeholders() {
+                                ui.label(format!("{}:", placeholder));
+                                ui.add(egui::TextEdit::singleline(values.entry(placeholder).or_default()).desired_width(80.0));
+                            }
+                            let rendered = snippet.render(values);
+                            if suggest_only {
+                                if ui.add_enabled(rendered.is_ok(), egui::Button::new("📋 Copy")).clicked() {
+                                    if let Ok(command) = rendered {
+                                        ui.ctx().copy_text(command);
+                                    }
+                                }
+                            } else if ui.add_enabled(rendered.is_ok(), egui::Button::new("▶ Run")).clicked() {
+                                run = rendered.ok();
+                            }
+                            if icon_button(ui, "🗑", "Remove snippet").clicked() {
+                                remove = Some(snippet.name.clone());
+                            }
+                        });
+                    });
+                }
+            });
+
+        self.show_snippets = open;
+        let mut changed = false;
+        if save {
+            if let Some(mut draft) = self.snippet_draft.take() {
+                draft.name = draft.name.trim().to_string();
+                snippets::upsert(&mut self.snippets, draft);
+                changed = true;
+            }
+        }
+        if discard {
+            self.snippet_draft = None;
+        }
+        if let Some(name) = remove {
+            self.snippets.retain(|s| s.name != name);
+            self.snippet_values.remove(&name);
+            changed = true;
+        }
+        if changed {
+            let _ = snippets::save_snippets(&self.snippets);
+        }
+        if let Some(command) = run {
+            self.run_pending_command(ctx, &command);
+            self.schedule_save();
+        }
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*                             debug console                             */
+    /* --------------------------------------------------------------------- */
+
+    /// Recent log lines, the last raw API exchange and the app's state, for
+    /// bug reports. Nothing here contains the API key.
+    fn show_debug_console(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let exchange = provider::last_exchange();
+        let state = format!(
+            "version: {}\nmodel: {}\nshell: {}\nos: {}\ncache entries: {}\nlog file: {}",
+            env!("CARGO_PKG_VERSION"),
+            self.config.model,
+            Shell::detect().to_shell_command_and_command_arg().0,
+            std::env::consts::OS,
+            self.cache.len(),
+            logging::log_file_path().map_or_else(|| "-".to_string(), |path| path.display().to_string()),
+        );
+        let lines = logging::recent();
+
+        egui::SidePanel::right("debug_console")
+            .resizable(true)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(tr(lang, "🐞 Debug"));
+                    if ui.button(tr(lang, "📋 Copy report")).on_hover_text(tr(lang, "State, log lines and the last request, for a bug report")).clicked() {
+                        let exchange = exchange.as_ref().map_or_else(String::new, |e| format!("\n\n{}\n{}\n\n{}", e.url, e.request, e.response));
+                        ui.ctx().copy_text(format!("{}\n\n{}{}", state, lines.join("\n"), exchange));
+                    }
+                    if icon_button(ui, "✕", "Close (F12)").clicked() {
+                        self.show_debug_console = false;
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::CollapsingHeader::new(tr(lang, "State")).default_open(true).show(ui, |ui| {
+                        ui.label(egui::RichText::new(&state).monospace());
+                    });
+                    egui::CollapsingHeader::new(tr(lang, "Recent log")).default_open(true).show(ui, |ui| {
+                        if lines.is_empty() {
+                            ui.weak(tr(lang, "Nothing logged yet; start with -vv for request details"));
+                        }
+                        egui::ScrollArea::vertical().id_salt("debug_log").max_height(240.0).stick_to_bottom(true).show(ui, |ui| {
+                            for line in &lines {
+                                ui.label(egui::RichText::new(line).monospace().small());
+                            }
+                        });
+                    });
+                    match &exchange {
+                        Some(exchange) => {
+                            egui::CollapsingHeader::new(tr(lang, "Last request")).show(ui, |ui| {
+                                ui.weak(&exchange.url);
+                                ui.label(egui::RichText::new(&exchange.request).monospace().small());
+                            });
+                            egui::CollapsingHeader::new(tr(lang, "Last response")).show(ui, |ui| {
+                                ui.label(egui::RichText::new(&exchange.response).monospace().small());
+                            });
+                        }
+                        None => {
+                            ui.weak(tr(lang, "No API request made yet"));
+                        }
+                    }
+                });
+            });
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*                             quick actions                             */
+    /* --------------------------------------------------------------------- */
+
+    /// Puts `prompt` into the composer, ready to be edited or sent.
+    fn insert_prompt(&mut self, prompt: &str) {
+        self.current_input = prompt.to_string();
+        self.composer_refocus = true;
+    }
+
+    /// Asks for the placeholders of the quick action that was clicked.
+    fn show_quick_action_form(&mut self, ctx: &egui::Context) {
+        let lang = self.lang;
+        let Some((action, values)) = &mut self.quick_action_form else {
+            return;
+        };
+        let mut open = true;
+        let mut insert = None;
+
+        egui::Window::new(&action.label)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("quick_action_form").num_columns(2).show(ui, |ui| {
+                    for placeholder in snippets::placeholders(&action.prompt) {
+                        ui.label(format!("{}:", placeholder));
+                        ui.text_edit_singleline(values.entry(placeholder).or_default());
+                        ui.end_row();
+                    }
+                });
+                let rendered = snippets::render(&action.prompt, values);
+                if ui.add_enabled(rendered.is_ok(), egui::Button::new(tr(lang, "Insert prompt"))).clicked() {
+                    insert = rendered.ok();
+                }
+            });
+
+        if let Some(prompt) = insert {
+            self.insert_prompt(&prompt);
+            self.quick_action_form = None;
+        } else if !open {
+            self.quick_action_form = None;
+        }
+    }
+
+    fn show_compare_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_compare;
+        let mut run_compare = false;
+        let mut use_command: Option<String> = None;
+
+        egui::Window::new("⚖ Compare models")
+            .open(&mut open)
+            .default_width(800.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("compare_inputs").num_columns(2).show(ui, |ui| {
+                    ui.label("Prompt:");
+                    ui.add(egui::TextEdit::singleline(&mut self.compare_prompt).desired_width(500.0));
+                    ui.end_row();
+                    ui.label("Models:");
+                    ui.add(egui::TextEdit::singleline(&mut self.compare_models).hint_text("gpt-4o,gpt-4o-mini,ollama:llama3.1"));
+                    ui.end_row();
+                });
+                run_compare = ui.add_enabled(!self.compare_prompt.trim().is_empty(), egui::Button::new("Compare")).clicked();
+
+                if let Some(error) = &self.compare_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                if self.compare_results.is_empty() {
+                    return;
+                }
+                ui.separator();
+                ui.columns(self.compare_results.len(), |columns| {
+                    for (column, candidate) in columns.iter_mut().zip(&self.compare_results) {
+                        column.strong(candidate.model.to_string());
+                        column.weak(format!("{:.2}s · ~${:.5}", candidate.latency.as_secs_f64(), candidate.estimated_cost));
+                        match &candidate.command {
+                            Ok(Some(command)) => {
+                                column.monospace(command.trim());
+                                if column.button("Use").clicked() {
+                                    use_command = Some(command.trim().to_string());
+                                }
+                            }
+                            Ok(None) => {
+                                column.weak("(no command)");
+                            }
+                            Err(e) => {
+                                column.colored_label(Color32::RED, e);
+                            }
+                        }
+                    }
+                });
+            });
+
+        self.show_compare = open;
+        if run_compare {
+            let models: Result<Vec<Model>, String> = self.compare_models.split(',').map(str::parse).collect();
+            match models {
+                Ok(models) => {
+                    self.compare_error = None;
+                    self.compare_results = compare::compare(&self.config, &models, self.compare_prompt.trim());
+                }
+                Err(e) => self.compare_error = Some(e),
+            }
+        }
+        if let Some(command) = use_command {
+            // approve it like any other suggested command
+            self.queue_command(PendingCommand::new(command));
+            self.show_compare = false;
+        }
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*               helper: handle a user submitting a prompt               */
+    /* --------------------------------------------------------------------- */
+    fn handle_user_prompt(&mut self, ctx: &egui::Context, prompt: String) {
+        let prompt = match self.clipboard_context.take() {
+            Some(contents) => clipboard::attach(&prompt, &contents),
+            None => prompt,
+        };
+
+        // push user message
+        self.current_session.messages.push(ChatMessage::user(prompt.clone()));
+
+        if self.current_session.title == "New Chat" {
+            // until the model has titled it
+            self.current_session.title = provisional_title(&prompt);
+        }
+
+        // Check if this is a "yes" response to execute the last command
+        let trimmed_prompt = prompt.trim().to_lowercase();
+        let is_yes = matches!(trimmed_prompt.as_str(), "yes" | "y" | "sure" | "go ahead" | "execute" | "run it" | "do it");
+        if is_yes && self.pending_script.is_some() {
+            self.run_pending_script(ctx);
+            self.schedule_save();
+            ctx.request_repaint();
+            return;
+        }
+        if let Some(ref pending_cmd) = self.latest_pending_command() {
+            if is_yes {
+                // Execute the pending command
+                self.run_pending_command(ctx, pending_cmd);
+                
+                self.schedule_save();
+                ctx.request_repaint();
+                return;
+            }
+        }
+
+        // everything before the message we just pushed
+        let upto = self.current_session.messages.len() - 1;
+        let conversation_history = self.conversation_history(upto, &prompt);
+
+        let started = Instant::now();
+        let cache_key = answer_cache_key(&prompt);
+        let cached = if self.current_session.bypass_cache {
+            None
+        } else {
+            let cached = self.cache.get(&cache_key).map(str::to_string);
+            stats::record_cache_lookup(cached.is_some());
+            cached
+        };
+        self.history_hint = None;
+        let session_id = self.current_session.id.clone();
+        match cached {
+            Some(cached) => self.finish_answer(ctx, &session_id, started, cached, Placement::Reply),
+            None => {
+                self.history_hint = history::similar(&prompt);
+                // asked in the background, so other tabs can be used while this one waits
+                let (tx, rx) = mpsc::channel();
+                let config = self.chat_config();
+                let summary_config = self.config.clone();
+                let system_prompt = self.current_session.system_prompt.clone();
+                let repaint = ctx.clone();
+                std::thread::spawn(move || {
+                    let conversation_history = conversation_history.build(&summary_config, &tx);
+                    let response = model::stream_to(
+                        stream_updates(tx.clone(), repaint.clone()),
+                        || config.model.llm_get_response(&config, &prompt, true, &conversation_history, DEFAULT_TEMPERATURE, system_prompt.as_deref()),
+                    );
+                    let (text, cacheable) = match response {
+                        Ok(Some(reply)) if !reply.trim().is_empty() && config.check_flags => (Self::check_flags(&config, &prompt, &reply), true),
+                        Ok(Some(reply)) if !reply.trim().is_empty() => (reply, true),
+                        // only real answers are cached; errors and empty replies are usually temporary
+                        Ok(_) => ("I'm not sure how to respond to that.".to_string(), false),
+                        Err(e) if e.is::<model::Timeout>() => (format!("Error: {}. Use 🔄 Regenerate to try again.", e), false),
+                        Err(e) => (format!("Error: {}", e), false),
+                    };
+                    let _ = tx.send(Update::Done(Answer { text, cacheable, fallback_note: model::take_fallback_note() }));
+                    repaint.request_repaint();
+                });
+                self.in_flight.push(InFlight { session_id, cache_key, started, rx, partial: String::new(), placement: Placement::Reply });
+            }
+        }
+
+        // persist
+        self.schedule_save();
+
+        // keep UI responsive
+        ctx.request_repaint();
+    }
+
+    /// Takes in the answers of background requests that have come back.
+    fn receive_answers(&mut self, ctx: &egui::Context) {
+        let mut arrived = Vec::new();
+        let mut summaries = Vec::new();
+        self.in_flight.retain_mut(|request| loop {
+            match request.rx.try_recv() {
+                Ok(Update::Summary { summary, summarized_count }) => summaries.push((request.session_id.clone(), summary, summarized_count)),
+                Ok(Update::Started) => request.partial.clear(),
+                Ok(Update::Text(text)) => request.partial.push_str(&text),
+                Ok(Update::Done(answer)) => {
+                    arrived.push((request.session_id.clone(), request.cache_key.clone(), request.started, request.placement, answer));
+                    break false;
+                }
+                Err(mpsc::TryRecvError::Empty) => break true,
+                Err(mpsc::TryRecvError::Disconnected) => break false,
+            }
+        });
+        for (session_id, summary, summarized_count) in summaries {
+            self.receive_summary(&session_id, summary, summarized_count);
+        }
+        for (session_id, cache_key, started, placement, answer) in arrived {
+            if answer.cacheable {
+                self.cache.insert(cache_key, answer.text.clone());
+                let _ = self.cache.save();
+            }
+            let response = match answer.fallback_note {
+                Some(note) => format!("ℹ {}\n\n{}", note, answer.text),
+                None => answer.text,
+            };
+            self.finish_answer(ctx, &session_id, started, response, placement);
+            self.schedule_save();
+        }
+    }
+
+    /// Adds an answer to the chat it was asked in, taking up the command or
+    /// script it suggests. A chat in another tab keeps them until it's opened.
+    fn finish_answer(&mut self, ctx: &egui::Context, session_id: &str, started: Instant, response: String, placement: Placement) {
+        self.notify_finished(ctx, started, "Answer ready", &response);
+        if let Placement::Explanation = placement {
+            if let Some(session) = self.session_mut(session_id) {
+                session.messages.push(ChatMessage::note(response));
+            }
+            return;
+        }
+        self.request_title(ctx, session_id, &response);
+
+        // the replaced answer's suggestions go with it
+        let replaced = match placement {
+            Placement::Replace(idx) => std::iter::once(&self.current_session).chain(&self.chat_sessions).find(|s| s.id == session_id).and_then(|session| session.messages.get(idx)).filter(|m| !m.is_user).map(|m| m.content.clone()),
+            _ => None,
+        };
+        let replaced_commands = replaced.as_deref().map(extract::commands).unwrap_or_default();
+
+        if session_id != self.current_session.id {
+            let Some(session) = self.chat_sessions.iter_mut().find(|s| s.id == session_id) else {
+                // deleted while it was answering
+                return;
+            };
+            session.dirty = true;
+            session.pending.commands.retain(|p| !replaced_commands.contains(&p.command));
+            if replaced.as_deref().is_some_and(|old| Script::extract(old).is_some()) {
+                session.pending.script = None;
+            }
+            let content = if response.contains("EXECUTE_LAST_COMMAND") {
+                // nothing runs in a tab that isn't open
+                "Open this chat and answer yes to run the suggested command.".to_string()
+            } else {
+                if let Some(script) = Script::extract(&response) {
+                    session.pending.script = Some(script);
+                } else {
+                    for command in extract::commands(&response) {
+                        if !session.pending.commands.iter().any(|p| p.command == command) {
+                            session.pending.commands.push(PendingCommand::new(command));
+                        }
+                    }
+                }
+                response
+            };
+            let message = ChatMessage {
+                is_command: Self::suggests_commands(&content),
+                ..ChatMessage::note(content)
+            };
+            Self::place_answer(&mut session.messages, message, replaced.is_some().then_some(placement));
+            return;
+        }
+
+        for command in &replaced_commands {
+            self.dismiss_command(command);
+        }
+        if replaced.as_deref().is_some_and(|old| Script::extract(old).is_some()) {
+            self.pending_script = None;
+        }
+
+        // Check if response contains a command or execute instruction
+        if response.contains("EXECUTE_LAST_COMMAND") {
+            if self.pending_script.is_some() {
+                self.run_pending_script(ctx);
+            } else if let Some(pending_cmd) = self.latest_pending_command() {
+                self.run_pending_command(ctx, &pending_cmd);
+            }
+        } else {
+            let commands = extract::commands(&response);
+            if let Some(script) = Script::extract(&response) {
+                self.pending_script = Some(script);
+            } else if !commands.is_empty() {
+                for command in commands {
+                    self.queue_command(PendingCommand::new(command));
+                }
+            } else if self.config.speech.enabled && !self.current_session.muted {
+                // a new answer cuts off the one being read
+                self.speaking = Some(speech::speak(&self.config, &response));
+            }
+            
+            let message = ChatMessage {
+                is_command: Self::suggests_commands(&response),
+                ..ChatMessage::note(response)
+            };
+            Self::place_answer(&mut self.current_session.messages, message, replaced.is_some().then_some(placement));
+        }
+    }
+
+    /// Puts `message` where `placement` says, or at the end.
+    fn place_answer(messages: &mut Vec<ChatMessage>, message: ChatMessage, placement: Option<Placement>) {
+        match placement {
+            Some(Placement::Replace(idx)) => messages[idx] = message,
+            _ => messages.push(message),
+        }
+    }
+
+    fn session_mut(&mut self, session_id: &str) -> Option<&mut ChatSession> {
+        let session = std::iter::once(&mut self.current_session).chain(&mut self.chat_sessions).find(|s| s.id == session_id)?;
+        session.dirty = true;
+        Some(session)
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*          helper: history that fits the context window                 */
+    /* --------------------------------------------------------------------- */
+
+    /// What the history sent along with `prompt` is built from: the messages
+    /// before `upto` that aren't in the session's summary yet.
+    fn conversation_history(&self, upto: usize, prompt: &str) -> PendingHistory {
+        let session = &self.current_session;
+        let system_tokens = session.system_prompt.as_deref().map_or(0, context::estimate_tokens);
+        let budget = self.config.model.context_window_in(&self.config).saturating_sub(
+            self.config.max_tokens.max(0) as usize
+                + context::SYSTEM_PROMPT_RESERVE
+                + system_tokens
+                + context::estimate_tokens(prompt),
+        );
+
+        let start = if session.summarized_count <= upto { session.summarized_count } else { 0 };
+        PendingHistory {
+            messages: session.messages[start..upto]
+                .iter()
+                .map(|msg| ConversationMessage {
+                    content: msg.history_text(),
+                    is_user: msg.is_user,
+                })
+                .collect(),
+            start,
+            summary: if start > 0 { session.summary.clone() } else { None },
+            budget,
+        }
+    }
+
+    /// Takes up a summary the background thread folded older messages into.
+    fn receive_summary(&mut self, session_id: &str, summary: String, summarized_count: usize) {
+        if let Some(session) = self.session_mut(session_id) {
+            session.summary = Some(summary);
+            session.summarized_count = summarized_count;
+        }
+    }
+
+    /// Whether `response` suggests commands to run, rather than a script or nothing.
+    fn suggests_commands(response: &str) -> bool {
+        Script::extract(response).is_none() && !extract::commands(response).is_empty()
+    }
+
+    /// `reply` with the flags of each suggested command checked against the local documentation.
+    fn check_flags(config: &Config, prompt: &str, reply: &str) -> String {
+        let mut reply = reply.to_string();
+        // last first, so the spans before stay where they are
+        for found in extract::find(&reply).into_iter().rev() {
+            let checked = manual::check(config, prompt, &found.command);
+            if checked != found.command {
+                reply.replace_range(found.span, &checked);
+            }
+        }
+        reply
+    }
+
+    /// Stores a 👍/👎 for `command`, suggested for `prompt`.
+    fn rate_command(&mut self, prompt: &str, command: &str, good: bool, correction: Option<String>) {
+        let rating = Rating { prompt: prompt.to_string(), command: command.to_string(), good, correction, rated_at: Utc::now() };
+        self.ratings.retain(|r| !(r.prompt == rating.prompt && r.command == rating.command));
+        self.ratings.push(rating.clone());
+        if let Err(e) = feedback::record(rating) {
+            log::warn!("Can't store the rating: {}", e);
+        }
+    }
+
+    /// Asks the model what `command` does, in the background, and puts the
+    /// explanation in the chat.
+    fn explain_command(&mut self, ctx: &egui::Context, command: &str) {
+        let (tx, rx) = mpsc::channel();
+        let config = self.chat_config();
+        let command = command.to_string();
+        let repaint = ctx.clone();
+        std::thread::spawn(move || {
+            let text = match config.model.llm_explain_command(&config, &command) {
+                Ok(Some(explanation)) if !explanation.trim().is_empty() => format!("`{}`: {}", command, explanation.trim()),
+                Ok(_) => format!("No explanation came back for `{}`.", command),
+                Err(e) => format!("Error: {}", e),
+            };
+            let _ = tx.send(Update::Done(Answer { text, cacheable: false, fallback_note: None }));
+            repaint.request_repaint();
+        });
+        let session_id = self.current_session.id.clone();
+        self.in_flight.push(InFlight { session_id, cache_key: String::new(), started: Instant::now(), rx, partial: String::new(), placement: Placement::Explanation });
+    }
+
+    /* --------------------------------------------------------------------- */
+    /*          helper: regenerate the last assistant message                */
+    /* --------------------------------------------------------------------- */
+    fn regenerate_last_response(&mut self, ctx: &egui::Context, request: RegenerateRequest) {
+        let session_id = self.current_session.id.clone();
+        if self.is_answering(&session_id) {
+            return;
+        }
+        let messages = &self.current_session.messages;
+        let Some(answer_idx) = messages.iter().rposition(|m| !m.is_user) else {
+            return;
+        };
+        let Some(prompt_idx) = messages[..answer_idx].iter().rposition(|m| m.is_user) else {
+            return;
+        };
+        let prompt = messages[prompt_idx].content.clone();
+
+        let conversation_history = self.conversation_history(prompt_idx, &prompt);
+
+        let config = match request.model {
+            // a model picked by hand answers by itself
+            Some(model) => Config { model, routing: None, fallback: Vec::new(), ..self.config.clone() },
+            None => self.chat_config(),
+        };
+
+        // regenerating always bypasses the cache
+        let (tx, rx) = mpsc::channel();
+        let summary_config = self.config.clone();
+        let system_prompt = self.current_session.system_prompt.clone();
+        let temperature = request.temperature;
+        let repaint = ctx.clone();
+        let cache_key = answer_cache_key(&prompt);
+        std::thread::spawn(move || {
+            let conversation_history = conversation_history.build(&summary_config, &tx);
+            let response = model::stream_to(
+                stream_updates(tx.clone(), repaint.clone()),
+                || config.model.llm_get_response(&config, &prompt, true, &conversation_history, temperature, system_prompt.as_deref()),
+            );
+            let (text, cacheable) = match response {
+                Ok(Some(reply)) if !reply.trim().is_empty() => (reply, true),
+                Ok(_) => ("I'm not sure how to respond to that.".to_string(), false),
+                Err(e) if e.is::<model::Timeout>() => (format!("Error: {}. Use 🔄 Regenerate to try again.", e), false),
+                Err(e) => (format!("Error: {}", e), false),
+            };
+            let _ = tx.send(Update::Done(Answer { text, cacheable, fallback_note: model::take_fallback_note() }));
+            repaint.request_repaint();
+        });
+        let placement = if request.replace { Placement::Replace(answer_idx) } else { Placement::Reply };
+        self.in_flight.push(InFlight { session_id, cache_key, started: Instant::now(), rx, partial: String::new(), placement });
+        ctx.request_repaint();
+    }
+}
+
+/* ------------------------------------------------------------------------- */
+/*                             egui integration                              */
+/* ------------------------------------------------------------------------- */
+
+impl eframe::App for LlmTermApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let lang = self.lang;
+
+        /* --------------- unlock screen ---------------- */
+        if self.locked {
+            self.show_unlock_screen(ctx);
+            return;
+        }
+
+        /* --------------- saving ----------------------- */
+        let closing = ctx.input(|i| i.viewport().close_requested());
+        self.flush_sessions(ctx, closing);
+
+        /* --------------- handoffs from the CLI -------- */
+        while let Ok(prompt) = self.handoff_rx.try_recv() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            if let Some(prompt) = prompt {
+                self.new_chat();
+                self.handle_user_prompt(ctx, prompt);
+            }
+        }
+
+        /* --------------- background answers ---------- */
+        self.receive_answers(ctx);
+        self.receive_titles();
+        self.receive_job_outcomes(ctx);
+        self.receive_checks(ctx);
+        self.receive_ollama_test();
+
+        /* --------------- dictation ------------------- */
+        if let Some(result) = self.dictation_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.dictation_rx = None;
+            match result {
+                Ok(text) => {
+                    if !self.current_input.trim().is_empty() {
+                        self.current_input.push(' ');
+                    }
+                    self.current_input.push_str(&text);
+                    self.composer_refocus = true;
+                }
+                Err(e) => self.dictation_error = Some(e),
+            }
+        }
+        if self.dictation.is_some() {
+            // notice the button being let go even without other input
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        /* --------------- keyboard navigation ---------- */
+        let (cycle, new_chat_shortcut) = ctx.input_mut(|i| {
+            let cycle = if i.consume_key(egui::Modifiers::SHIFT, egui::Key::F6) {
+                2
+            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::F6) {
+                1
+            } else {
+                0
+            };
+            let new_chat = i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::N));
+            if i.consume_key(egui::Modifiers::NONE, egui::Key::F12) {
+                self.show_debug_console = !self.show_debug_console;
+            }
+            (cycle, new_chat)
+        });
+        let mut rerun_hint = self.history_hint.is_some()
+            && ctx.input_mut(|i| i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::R)));
+        if cycle != 0 {
+            self.focus_region = (self.focus_region + cycle) % self.focus_anchors.len();
+            if let Some(id) = self.focus_anchors[self.focus_region] {
+                ctx.memory_mut(|m| m.request_focus(id));
+            }
+        }
+
+        /* --------------- LEFT: history ---------------- */
+        // Ctrl+N works with the sidebar hidden too
+        let mut new_chat_clicked = new_chat_shortcut;
+        let mut session_to_load: Option<String> = None;
+        let mut set_archived: Option<(String, bool)> = None;
+        let mut delete_sessions: Vec<String> = Vec::new();
+        let mut start_renaming: Option<(String, String)> = None;
+        let mut rename: Option<(String, String)> = None;
+        let mut stop_renaming = false;
+
+        let sidebar = egui::SidePanel::left("history")
+            .resizable(true)
+            .default_width(self.window_state.sidebar_width.unwrap_or(220.0))
+            .width_range(160.0..=480.0)
+            .show_animated(ctx, self.show_sidebar, |ui| {
+                ui.heading(tr(lang, "Chat History"));
+                let new_chat = ui.button(tr(lang, "+ New Chat")).on_hover_text("Ctrl+N");
+                self.focus_anchors[0] = Some(new_chat.id);
+                if new_chat.clicked() {
+                    new_chat_clicked = true;
+                }
+
+                ui.separator();
+
+                let archived_count = self.chat_sessions.iter().filter(|s| s.archived).count();
+                ui.toggle_value(&mut self.show_archived, tr(lang, "🗄 Archive ({})").replace("{}", &archived_count.to_string()));
+
+                egui::ScrollArea::vertical().max_height(ui.available_height() - 60.0).show(ui, |ui| {
+                    let mut sessions: Vec<&ChatSession> = self.chat_sessions.iter().filter(|s| s.archived == self.show_archived).collect();
+                    sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+                    let today = chrono::Local::now().date_naive();
+                    for group in DateGroup::ALL {
+                        let in_group: Vec<&ChatSession> = sessions.iter().copied().filter(|s| DateGroup::of(s.created_at, today) == group).collect();
+                        if in_group.is_empty() {
+                            continue;
+                        }
+                        // egui remembers which groups were collapsed
+                        egui::CollapsingHeader::new(format!("{} ({})", tr(lang, group.label()), in_group.len()))
+                            .id_salt(("session_group", group.label()))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                for session in in_group {
+                                    let selected = self
+                                        .selected_session_id
+                                        .as_ref()
+                                        .map(|id| id == &session.id)
+                                        .unwrap_or(false);
+
+                                    let label = match session.parent_id {
+                                        Some(_) => format!("↳ {}", session.title),
+                                        None => session.title.clone(),
+                                    };
+
+                                    if let Some((_, draft)) = self.renaming.as_mut().filter(|(id, _)| id == &session.id) {
+                                        let edit = ui.text_edit_singleline(draft);
+                                        edit.request_focus();
+                                        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                            stop_renaming = true;
+                                        } else if edit.lost_focus() {
+                                            rename = Some((session.id.clone(), draft.clone()));
+                                        }
+                                        continue;
+                                    }
+
+                                    let response = ui.selectable_label(selected, label);
+                                    if response.clicked() {
+                                        session_to_load = Some(session.id.clone());
+                                    }
+                                    response.context_menu(|ui| {
+                                        if ui.button(tr(lang, "Rename")).clicked() {
+                                            start_renaming = Some((session.id.clone(), session.title.clone()));
+                                            ui.close_menu();
+                                        }
+                                        let text = if session.archived { tr(lang, "Unarchive") } else { tr(lang, "Archive") };
+                                        if ui.button(text).clicked() {
+                                            set_archived = Some((session.id.clone(), !session.archived));
+                                            ui.close_menu();
+                                        }
+                                        if ui.button(tr(lang, "Delete")).clicked() {
+                                            delete_sessions = vec![session.id.clone()];
+                                            ui.close_menu();
+                                        }
+                                    });
+                                }
+                            });
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(tr(lang, "Older than"));
+                    ui.add(egui::DragValue::new(&mut self.cleanup_days).range(1..=3650).suffix(tr(lang, " days")));
+                });
+                let old = self.sessions_older_than(self.cleanup_days);
+                let label = tr(lang, "🗑 Delete {} sessions").replace("{}", &old.len().to_string());
+                if ui.add_enabled(!old.is_empty(), egui::Button::new(label)).clicked() {
+                    delete_sessions = old;
+                }
+            });
+        let sidebar_width = sidebar.map(|panel| panel.response.rect.width());
+        self.track_window_state(ctx, sidebar_width);
+
+        if start_renaming.is_some() {
+            self.renaming = start_renaming;
+        }
+        if let Some((id, title)) = rename {
+            self.rename_session(&id, &title);
+            self.renaming = None;
+        }
+        if stop_renaming {
+            self.renaming = None;
+        }
+        if let Some((id, archived)) = set_archived {
+            self.set_archived(&id, archived);
+        }
+        if !delete_sessions.is_empty() {
+            self.delete_sessions(&delete_sessions);
+        }
+
+        if new_chat_clicked {
+            self.new_chat();
+        }
+        if let Some(id) = session_to_load {
+            self.load_session(&id);
+        }
+
+        /* --------------- RIGHT: shell pane ------------ */
+        if self.show_shell_pane {
+            if self.shell_pane.is_none() {
+                self.shell_pane = PtySession::spawn_shell().ok();
+            }
+
+            let mut restart_shell = false;
+
+            egui::SidePanel::right("shell_pane")
+                .resizable(true)
+                .default_width(450.0)
+                .show(ctx, |ui| {
+                    ui.heading("Terminal");
+                    ui.separator();
+
+                    let Some(shell) = &mut self.shell_pane else {
+                        ui.colored_label(Color32::RED, tr(lang, "Could not start a shell."));
+                        return;
+                    };
+                    let exited = shell.exit_code();
+                    let output = shell.output();
+
+                    egui::ScrollArea::vertical()
+                        .id_salt("shell_pane_scroll")
+                        .stick_to_bottom(true)
+                        .max_height(ui.available_height() - 40.0)
+                        .show(ui, |ui| {
+                            ui.label(ansi_layout_job(ui, &output));
+                        });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        if let Some(code) = exited {
+                            ui.label(tr(lang, "Shell exited with code {}").replace("{}", &code.to_string()));
+                            restart_shell = ui.button(tr(lang, "Restart")).clicked();
+                            return;
+                        }
+
+                        let input = ui.add(
+                            egui::TextEdit::singleline(&mut self.shell_pane_input)
+                                .hint_text(tr(lang, "Type a command…"))
+                                .desired_width(ui.available_width() - 70.0),
+                        );
+                        if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            let line = std::mem::take(&mut self.shell_pane_input);
+                            let _ = shell.send_line(&line);
+                            input.request_focus();
+                        }
+                        if ui.button("Ctrl+C").clicked() {
+                            let _ = shell.write_input("\x03");
+                        }
+                    });
+                });
+
+            if restart_shell {
+                self.shell_pane = None;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        /* --------------- RIGHT: debug console (F12) --- */
+        if self.show_debug_console {
+            self.show_debug_console(ctx);
+        }
+        if self.show_jobs {
+            self.show_jobs_panel(ctx);
+        }
+
+        /* --------------- CENTRAL: chat log ------------ */
+        let mut regenerate: Option<RegenerateRequest> = None;
+        let mut edit_action: Option<EditAction> = None;
+        let mut start_editing: Option<(usize, String)> = None;
+        let mut stop_editing = false;
+        let mut save_sessions = false;
+        let mut star: Option<(usize, String)> = None;
+        let mut what_changed: Option<String> = None;
+        let mut run_pending: Option<String> = None;
+        let mut run_saving_output: Option<(String, String)> = None;
+        let mut dismiss: Option<String> = None;
+        let mut explain: Option<String> = None;
+        let mut fork: Option<usize> = None;
+        let mut toggle_pin: Option<usize> = None;
+        let mut rate: Option<(String, String, bool)> = None;
+        let mut save_correction = false;
+        let mut skip_correction = false;
+        let mut forget_cached: Option<String> = None;
+        let mut data_action: Option<DataAction> = None;
+        let (mut test_ollama, mut save_ollama) = (false, false);
+        let pending_files = self.pending_files();
+        let mut restore: Option<String> = None;
+        let mut preview_edits: Option<String> = None;
+
+        let mut switch_tab: Option<String> = None;
+        let mut close_tab: Option<String> = None;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.open_tabs.len() > 1 {
+                ui.horizontal_wrapped(|ui| {
+                    for id in &self.open_tabs {
+                        let title = self.session_title(id).unwrap_or("New Chat");
+                        let label = if self.is_answering(id) { format!("⏳ {}", title) } else { title.to_string() };
+                        if ui.selectable_label(id == &self.current_session.id, label).clicked() {
+                            switch_tab = Some(id.clone());
+                        }
+                        if icon_button(ui, "✕", tr(lang, "Close tab")).clicked() {
+                            close_tab = Some(id.clone());
+                        }
+                        ui.separator();
+                    }
+                });
+                ui.separator();
+            }
+            ui.horizontal(|ui| {
+                let hamburger = if self.show_sidebar { tr(lang, "Hide chat history") } else { tr(lang, "Show chat history") };
+                if icon_button(ui, "☰", hamburger).clicked() {
+                    self.show_sidebar = !self.show_sidebar;
+                }
+                ui.heading("LLM Terminal");
+                // nothing that runs commands is offered in suggest-only mode
+                if !self.config.suggest_only {
+                    let toggle = ui.toggle_value(&mut self.show_shell_pane, tr(lang, "🖥 Terminal pane"))
+                        .on_hover_text(tr(lang, "Run approved commands in a persistent shell"));
+                    self.focus_anchors[1] = Some(toggle.id);
+                    ui.toggle_value(&mut self.show_schedules, tr(lang, "⏰ Schedules"));
+                    let running = self.jobs.iter().filter(|job| job.status() == jobs::Status::Running).count();
+                    let jobs_label = if running > 0 { format!("{} ({})", tr(lang, "⚙ Jobs"), running) } else { tr(lang, "⚙ Jobs").to_string() };
+                    ui.toggle_value(&mut self.show_jobs, jobs_label);
+                }
+                ui.toggle_value(&mut self.show_snippets, tr(lang, "⭐ Snippets"));
+                ui.toggle_value(&mut self.show_compare, tr(lang, "⚖ Compare"));
+                let default_model = format!("{} ({})", tr(lang, "Default model"), self.config.model);
+                let choices = self.model_choices();
+                let selected = self.current_session.model.as_ref().map_or_else(|| default_model.clone(), Model::to_string);
+                egui::ComboBox::from_id_salt("chat_model").selected_text(&selected).show_ui(ui, |ui| {
+                    if ui.selectable_label(self.current_session.model.is_none(), &default_model).clicked() {
+                        self.current_session.model = None;
+                        save_sessions = true;
+                    }
+                    for model in choices {
+                        let name = model.to_string();
+                        if ui.selectable_label(self.current_session.model.is_some() && name == selected, &name).clicked() {
+                            self.current_session.model = Some(model);
+                            save_sessions = true;
+                        }
+                    }
+                }).response.on_hover_text(tr(lang, "Model answering in this chat"));
+                if self.config.speech.enabled {
+                    ui.menu_button(tr(lang, "🔊 Speech"), |ui| {
+                        if ui.checkbox(&mut self.current_session.muted, tr(lang, "Mute this chat")).changed() {
+                            if self.current_session.muted {
+                                self.speaking = None;
+                            }
+                            save_sessions = true;
+                        }
+                        ui.add(egui::Slider::new(&mut self.config.speech.speed, 0.5..=2.0).step_by(0.1).text(tr(lang, "Speed")))
+                            .on_hover_text(tr(lang, "Until the app is closed; set \"speed\" under \"speech\" in config.json to keep it"));
+                    });
+                    if self.speaking.as_ref().is_some_and(|speaking| speaking.is_speaking()) && ui.button(tr(lang, "⏹ Stop reading")).clicked() {
+                        self.speaking = None;
+                    }
+                }
+                ui.menu_button(tr(lang, "📦 Data"), |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.data_path).hint_text("llm-term-data.zip"));
+                    let path = self.data_path.trim();
+                    if ui.add_enabled(!path.is_empty(), egui::Button::new(tr(lang, "Export all data"))).clicked() {
+                        data_action = Some(DataAction::Export);
+                    }
+                    if ui
+                        .add_enabled(!path.is_empty(), egui::Button::new(tr(lang, "Import data")))
+                        .on_hover_text(tr(lang, "Replaces the current sessions, caches, snippets and settings"))
+                        .clicked()
+                    {
+                        data_action = Some(DataAction::Import);
+                    }
+                    if let Some(status) = &self.data_status {
+                        ui.label(status);
+                    }
+                });
+                ui.menu_button(tr(lang, "🦙 Ollama"), |ui| {
+                    ui.label(tr(lang, "Server"));
+                    ui.add(egui::TextEdit::singleline(&mut self.ollama_host_input).hint_text(provider::DEFAULT_OLLAMA_HOST))
+                        .on_hover_text(tr(lang, "Host and port, e.g. gpu-box:11434; empty for OLLAMA_HOST or this machine"));
+                    ui.horizontal(|ui| {
+                        let testing = self.ollama_test_rx.is_some();
+                        if ui.add_enabled(!testing, egui::Button::new(tr(lang, "Test connection"))).clicked() {
+                            test_ollama = true;
+                        }
+                        if ui.button(tr(lang, "Save")).clicked() {
+                            save_ollama = true;
+                        }
+                        if testing {
+                            ui.spinner();
+                        }
+                    });
+                    if let Some(status) = &self.ollama_status {
+                        ui.label(status);
+                    }
+                });
+                if let Some(dir) = &self.current_session.working_dir {
+                    ui.weak(format!("📁 {}", dir.display()));
+                }
+            });
+
+            egui::CollapsingHeader::new(tr(lang, "⚙ System")).show(ui, |ui| {
+                let session = &mut self.current_session;
+                ui.label(egui::RichText::new(self.config.model.get_unified_conversational_prompt()).monospace().weak());
+                match &mut session.system_prompt {
+                    Some(instructions) => {
+                        let response = ui.add(
+                            egui::TextEdit::multiline(instructions)
+                                .hint_text(tr(lang, "Instructions added to the system prompt in this chat"))
+                                .desired_rows(4)
+                                .desired_width(f32::INFINITY),
+                        );
+                        if response.lost_focus() {
+                            save_sessions = true;
+                        }
+                        if ui.button(tr(lang, "Reset to default")).clicked() {
+                            session.system_prompt = None;
+                            save_sessions = true;
+                        }
+                    }
+                    None => {
+                        if ui.button(tr(lang, "Add instructions for this chat")).clicked() {
+                            session.system_prompt = Some(String::new());
+                        }
+                    }
+                }
+            });
             ui.separator();
 
+            // pinned messages, kept in reach however long the chat gets
+            if self.current_session.messages.iter().any(|m| m.pinned) {
+                ui.label(egui::RichText::new(tr(lang, "📌 Pinned")).strong());
+                egui::ScrollArea::vertical().id_salt("pinned").max_height(120.0).show(ui, |ui| {
+                    for (idx, msg) in self.current_session.messages.iter().enumerate().filter(|(_, m)| m.pinned) {
+                        if msg.is_command {
+                            for command in extract::commands(&msg.content) {
+                                ui.horizontal(|ui| {
+                                    ui.monospace(&command);
+                                    if !self.config.suggest_only && ui.small_button(tr(lang, "▶ Run")).clicked() {
+                                        run_pending = Some(command.clone());
+                                    }
+                                    if ui.small_button(tr(lang, "📋 Copy")).clicked() {
+                                        ui.ctx().copy_text(command.clone());
+                                    }
+                                });
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            let first_line = msg.content.lines().next().unwrap_or_default();
+                            let shown: String = first_line.chars().take(80).collect();
+                            let ellipsis = if shown.len() < msg.content.trim_end().len() { "…" } else { "" };
+                            ui.label(egui::RichText::new(format!("{}{}", shown, ellipsis)).weak()).on_hover_text(&msg.content);
+                            if !msg.is_command && ui.small_button(tr(lang, "📋 Copy")).clicked() {
+                                ui.ctx().copy_text(msg.content.clone());
+                            }
+                            if icon_button(ui, "✕", tr(lang, "Unpin")).clicked() {
+                                toggle_pin = Some(idx);
+                            }
+                        });
+                    }
+                });
+                ui.separator();
+            }
+
             egui::ScrollArea::vertical()
                 .stick_to_bottom(true)
                 .show(ui, |ui| {
-                    for msg in &self.current_session.messages {
-                        ui.horizontal(|ui| {
-                            if msg.is_user {
-                                ui.colored_label(Color32::LIGHT_BLUE, "You:");
-                            } else {
-                                ui.colored_label(Color32::LIGHT_GREEN, "Assistant:");
+                    if let Some(summary) = &self.current_session.summary {
+                        let count = self.current_session.summarized_count;
+                        egui::CollapsingHeader::new(format!("📝 Summary of {} earlier messages", count))
+                            .show(ui, |ui| {
+                                ui.label(summary);
+                            });
+                        ui.separator();
+                    }
+
+                    let last_answer_idx = self.current_session.messages.iter().rposition(|m| !m.is_user);
+
+                    for (idx, msg) in self.current_session.messages.iter().enumerate() {
+                        let is_editing = matches!(&self.editing, Some((i, _)) if *i == idx);
+
+                        if is_editing {
+                            if let Some((_, draft)) = &mut self.editing {
+                                ui.colored_label(Color32::LIGHT_BLUE, tr(lang, "You (editing):"));
+                                ui.add(egui::TextEdit::multiline(draft).desired_rows(2));
+                                ui.horizontal(|ui| {
+                                    let can_send = !draft.trim().is_empty();
+                                    if ui.add_enabled(can_send, egui::Button::new(tr(lang, "Resend"))).on_hover_text(tr(lang, "Discard everything after this message")).clicked() {
+                                        edit_action = Some(EditAction::Truncate(idx, draft.clone()));
+                                    }
+                                    if ui.add_enabled(can_send, egui::Button::new(tr(lang, "Resend as branch"))).on_hover_text(tr(lang, "Keep this chat and continue in a new linked one")).clicked() {
+                                        edit_action = Some(EditAction::Branch(idx, draft.clone()));
+                                    }
+                                    if ui.button(tr(lang, "Cancel")).clicked() {
+                                        stop_editing = true;
+                                    }
+                                });
                             }
-                            ui.label(&msg.content);
-                        });
+                        } else if let Some(output) = &msg.output {
+                            if show_command_output(ui, idx, output, &mut self.output_view) {
+                                star = Some((idx, output.command.clone()));
+                            }
+                            if let Some(changes) = &output.changes {
+                                if ui.small_button(tr(lang, "🔍 What changed")).on_hover_text(tr(lang, "Compare the files and git status from before and after the command")).clicked() {
+                                    what_changed = Some(changes.summary());
+                                }
+                            }
+                        } else {
+                            ui.horizontal(|ui| {
+                                if msg.is_user {
+                                    ui.colored_label(Color32::LIGHT_BLUE, tr(lang, "You:"));
+                                } else {
+                                    ui.colored_label(Color32::LIGHT_GREEN, tr(lang, "Assistant:"));
+                                }
+                                let label = ui.label(&msg.content);
+                                if !msg.is_user && Some(idx) == last_answer_idx {
+                                    // screen readers announce the newest answer when it arrives
+                                    ui.ctx().accesskit_node_builder(label.id, |node| node.set_live(egui::accesskit::Live::Polite));
+                                }
+
+                                if msg.is_user && icon_button(ui, "✏", tr(lang, "Edit and resend")).clicked() {
+                                    start_editing = Some((idx, msg.content.clone()));
+                                }
+                                if icon_button(ui, "🔀", tr(lang, "Fork from here")).clicked() {
+                                    fork = Some(idx);
+                                }
+                                let pin_label = if msg.pinned { tr(lang, "Unpin") } else { tr(lang, "Pin to the top of the chat") };
+                                if icon_button(ui, "📌", pin_label).clicked() {
+                                    toggle_pin = Some(idx);
+                                }
+                                if !msg.is_user {
+                                    let prompt = self.current_session.messages[..idx].iter().rev().find(|m| m.is_user);
+                                    if let Some(key) = prompt.map(|m| answer_cache_key(&m.content)) {
+                                        if self.cache.get(&key).is_some()
+                                            && icon_button(ui, "🧹", tr(lang, "Forget the cached answer to this prompt")).clicked()
+                                        {
+                                            forget_cached = Some(key);
+                                        }
+                                    }
+                                }
+                                if let Some(id) = &msg.backup {
+                                    if ui.small_button(tr(lang, "↩ Restore backup")).on_hover_text(tr(lang, "Put the files back as they were before the command")).clicked() {
+                                        restore = Some(id.clone());
+                                    }
+                                }
+                            });
+
+                            if msg.is_command {
+                                let prompt = self.current_session.messages[..idx].iter().rev().find(|m| m.is_user).map(|m| m.content.clone()).unwrap_or_default();
+                                for command in extract::commands(&msg.content) {
+                                    let rating = self.ratings.iter().find(|r| r.prompt == prompt && r.command == command).map(|r| r.good);
+                                    egui::Frame::group(ui.style()).show(ui, |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.monospace(&command);
+                                            if !self.config.suggest_only && ui.small_button(tr(lang, "▶ Run")).clicked() {
+                                                run_pending = Some(command.clone());
+                                            }
+                                            if ui.small_button(tr(lang, "📋 Copy")).clicked() {
+                                                ui.ctx().copy_text(command.clone());
+                                            }
+                                            if ui.small_button(tr(lang, "💡 Explain")).on_hover_text(tr(lang, "Ask the model what this command does")).clicked() {
+                                                explain = Some(command.clone());
+                                            }
+                                            if ui.selectable_label(rating == Some(true), "👍").on_hover_text(tr(lang, "Good suggestion")).clicked() {
+                                                rate = Some((prompt.clone(), command.clone(), true));
+                                            }
+                                            if ui.selectable_label(rating == Some(false), "👎").on_hover_text(tr(lang, "Wrong suggestion; say what it should have been")).clicked() {
+                                                rate = Some((prompt.clone(), command.clone(), false));
+                                            }
+                                        });
+                                        if let Some((_, _, draft)) = self.correcting.as_mut().filter(|(p, c, _)| *p == prompt && *c == command) {
+                                            ui.horizontal(|ui| {
+                                                ui.label(tr(lang, "Should have been:"));
+                                                ui.add(egui::TextEdit::singleline(draft).code_editor().desired_width(300.0));
+                                                if ui.small_button(tr(lang, "Save")).clicked() {
+                                                    save_correction = true;
+                                                }
+                                                if ui.small_button(tr(lang, "Skip")).clicked() {
+                                                    skip_correction = true;
+                                                }
+                                            });
+                                        }
+                                    });
+                                }
+                                if msg.executed {
+                                    match msg.exit_code {
+                                        Some(0) => ui.colored_label(Color32::LIGHT_GREEN, tr(lang, "executed ✓ (exit {})").replace("{}", "0")),
+                                        Some(code) => ui.colored_label(Color32::LIGHT_RED, tr(lang, "executed ✗ (exit {})").replace("{}", &code.to_string())),
+                                        None => ui.colored_label(Color32::GRAY, tr(lang, "executed ✓")),
+                                    };
+                                }
+                            }
+
+                            if let Some(redactor) = self.redactor.as_ref().filter(|_| msg.is_user) {
+                                let (masked, redactions) = redactor.redact(&msg.content);
+                                if !redactions.is_empty() {
+                                    egui::CollapsingHeader::new(tr(lang, "🔒 {} masked before sending").replace("{}", &redactions.len().to_string()))
+                                        .id_salt(("redacted", idx))
+                                        .show(ui, |ui| {
+                                            for redaction in &redactions {
+                                                ui.monospace(format!("{}: {}", redaction.kind, redaction.secret));
+                                            }
+                                            ui.label(tr(lang, "Sent as:"));
+                                            ui.label(egui::RichText::new(masked).weak());
+                                        });
+                                }
+                            }
+                        }
+
+                        if Some(idx) == last_answer_idx {
+                            ui.horizontal(|ui| {
+                                let replace = !self.keep_previous_answer;
+                                let regenerate_button = ui.small_button(tr(lang, "🔄 Regenerate"));
+                                self.focus_anchors[1] = Some(regenerate_button.id);
+                                if regenerate_button.clicked() {
+                                    regenerate = Some(RegenerateRequest { model: None, temperature: DEFAULT_TEMPERATURE, replace });
+                                }
+                                let menu = ui.menu_button("▾", |ui| {
+                                    if ui.button(tr(lang, "Regenerate (more creative)")).clicked() {
+                                        regenerate = Some(RegenerateRequest { model: None, temperature: CREATIVE_TEMPERATURE, replace });
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    for model in [Model::OpenAiGpt4oMini, Model::OpenAiGpt4o, Model::OpenAiO3Mini, Model::Ollama("llama3.1".to_string())] {
+                                        if ui.button(tr(lang, "Regenerate with {}").replace("{}", &model.get_model_name())).clicked() {
+                                            regenerate = Some(RegenerateRequest { model: Some(model), temperature: DEFAULT_TEMPERATURE, replace });
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    ui.separator();
+                                    ui.checkbox(&mut self.keep_previous_answer, tr(lang, "Keep previous answer"));
+                                });
+                                menu.response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, tr(lang, "More regenerate options")));
+                            });
+                        }
                         ui.separator();
                     }
-                    
-                    // Show pending command indicator
-                    if self.pending_command.is_some() {
+
+                    // the answer as it streams in
+                    if let Some(request) = self.in_flight.iter().find(|r| r.session_id == self.current_session.id && !r.partial.is_empty()) {
                         ui.horizontal(|ui| {
-                            ui.colored_label(Color32::YELLOW, "💬 Waiting for your response...");
+                            ui.colored_label(Color32::LIGHT_GREEN, tr(lang, "Assistant:"));
+                            ui.label(&request.partial);
+                            ui.spinner();
                         });
+                        ui.separator();
+                    }
+                    
+                    // Show pending commands, each with its own controls
+                    if self.config.suggest_only {
+                        for pending in &self.pending_commands {
+                            ui.horizontal(|ui| {
+                                ui.monospace(&pending.command);
+                                if ui.button(tr(lang, "📋 Copy command")).clicked() {
+                                    ui.ctx().copy_text(pending.command.clone());
+                                }
+                            });
+                        }
+                    } else if !self.pending_commands.is_empty() {
+                        ui.colored_label(Color32::YELLOW, tr(lang, "💬 Waiting for your response..."));
+                        let use_trash = self.config.use_trash;
+                        for (idx, (pending, files)) in self.pending_commands.iter_mut().zip(&pending_files).enumerate() {
+                            egui::Frame::group(ui.style()).show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.monospace(&pending.command);
+                                    if ui.button(tr(lang, "▶ Run")).clicked() {
+                                        run_pending = Some(pending.command.clone());
+                                    }
+                                    if ui.button(tr(lang, "✕ Dismiss")).clicked() {
+                                        dismiss = Some(pending.command.clone());
+                                    }
+                                    if !files.edits.is_empty() && ui.button(tr(lang, "🔍 Preview edit")).on_hover_text(tr(lang, "Run the edit on copies of the files and show what would change")).clicked() {
+                                        preview_edits = Some(pending.command.clone());
+                                    }
+                                });
+                                if !files.backup_targets.is_empty() {
+                                    let mut back_up = !pending.skip_backup;
+                                    let label = tr(lang, "💾 Back up the {} file(s) it changes first").replace("{}", &files.backup_targets.len().to_string());
+                                    if ui.checkbox(&mut back_up, label).changed() {
+                                        pending.skip_backup = !back_up;
+                                    }
+                                }
+                                if use_trash && trash::deletes(&pending.command, &Shell::detect()) {
+                                    ui.checkbox(&mut pending.delete_permanently, tr(lang, "🗑 Delete permanently instead of moving to the trash"));
+                                }
+                                for (i, affected) in files.affected.iter().enumerate() {
+                                    let count = affected.files.len() + affected.more;
+                                    egui::CollapsingHeader::new(format!("{} `{}`: {}", tr(lang, "Files affected by"), affected.program, count))
+                                        .id_salt(("pending_affected", idx, i))
+                                        .default_open(true)
+                                        .show(ui, |ui| {
+                                            for file in &affected.files {
+                                                ui.monospace(file.display().to_string());
+                                            }
+                                            if affected.more > 0 {
+                                                ui.weak(format!("{} {}", affected.more, tr(lang, "more")));
+                                            }
+                                        });
+                                }
+                                if !shell::redirects_output(&pending.command) {
+                                    ui.horizontal(|ui| {
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut pending.save_output_path)
+                                                .hint_text(tr(lang, "output file, e.g. report.txt"))
+                                                .desired_width(250.0),
+                                        );
+                                        let path = pending.save_output_path.trim();
+                                        if ui.add_enabled(!path.is_empty(), egui::Button::new(tr(lang, "💾 Run and save output"))).clicked() {
+                                            run_saving_output = Some((pending.command.clone(), Shell::detect().redirect_to_file(&pending.command, Path::new(path))));
+                                        }
+                                    });
+                                }
+                            });
+                        }
                     }
                 });
         });
 
+        if let Some(request) = regenerate {
+            self.regenerate_last_response(ctx, request);
+        }
+        if let Some(id) = switch_tab {
+            self.load_session(&id);
+        }
+        if let Some(id) = close_tab {
+            self.close_tab(&id);
+        }
+        if let Some(id) = restore {
+            self.restore_backup(&id);
+            self.schedule_save();
+        }
+        if let Some(command) = preview_edits {
+            self.preview_edits(ctx, &command);
+        }
+        if save_sessions {
+            self.schedule_save();
+        }
+        if stop_editing {
+            self.editing = None;
+        }
+        if start_editing.is_some() {
+            self.editing = start_editing;
+        }
+        if let Some(action) = edit_action {
+            self.apply_edit(ctx, action);
+        }
+        if let Some((idx, command)) = star {
+            self.star_command(idx, command);
+        }
+        if let Some(summary) = what_changed {
+            self.current_session.messages.push(ChatMessage::note(summary));
+            self.schedule_save();
+        }
+        if let Some(key) = forget_cached {
+            self.cache.remove(&key);
+            let _ = self.cache.save();
+        }
+        if let Some(action) = data_action {
+            self.run_data_action(action);
+        }
+        if save_ollama {
+            self.save_ollama_host();
+        }
+        if test_ollama {
+            self.test_ollama(ctx);
+        }
+        if let Some(command) = dismiss {
+            self.dismiss_command(&command);
+        }
+        if let Some(message) = toggle_pin.and_then(|idx| self.current_session.messages.get_mut(idx)) {
+            message.pinned = !message.pinned;
+            self.schedule_save();
+        }
+        if let Some((prompt, command, good)) = rate {
+            self.rate_command(&prompt, &command, good, None);
+            // a 👎 asks for the right command, which is noted in later prompts
+            self.correcting = (!good).then(|| (prompt, command.clone(), command));
+        }
+        if save_correction {
+            if let Some((prompt, command, draft)) = self.correcting.take() {
+                let draft = draft.trim();
+                let correction = (!draft.is_empty() && draft != command).then(|| draft.to_string());
+                if let Some(fixed) = &correction {
+                    // the fixed command is an example of what the user wants, like one they ran
+                    history::record(&prompt, fixed);
+                }
+                self.rate_command(&prompt, &command, false, correction);
+            }
+        }
+        if skip_correction {
+            self.correcting = None;
+        }
+        if let Some(idx) = fork {
+            self.fork_from(idx);
+        }
+        if let Some(command) = explain {
+            self.explain_command(ctx, &command);
+            self.schedule_save();
+        }
+        if let Some(command) = run_pending {
+            self.run_pending_command(ctx, &command);
+            self.schedule_save();
+        }
+        if let Some((command, redirected)) = run_saving_output {
+            // run under its new name, keeping its options
+            if let Some(pending) = self.pending_commands.iter_mut().find(|p| p.command == command) {
+                pending.command = redirected.clone();
+            }
+            self.run_pending_command(ctx, &redirected);
+            self.schedule_save();
+        }
+
+        /* --------------- WINDOW: schedules ------------ */
+        self.run_due_schedules(ctx);
+        if self.show_schedules {
+            self.show_schedules_window(ctx);
+        }
+        self.show_script_window(ctx);
+        self.show_placeholder_window(ctx);
+        self.show_guard_window(ctx);
+        self.show_elevation_window(ctx);
+
+        /* --------------- WINDOW: snippets -------------- */
+        if self.show_snippets {
+            self.show_snippets_window(ctx);
+        }
+
+        /* --------------- WINDOW: compare models -------- */
+        if self.show_compare {
+            self.show_compare_window(ctx);
+        }
+
+        /* --------------- WINDOW: interactive terminal -- */
+        let mut close_terminal = false;
+        let mut finished: Option<(String, String, i32)> = None;
+
+        if let Some(term) = &mut self.terminal {
+            let exit_code = term.exit_code();
+            let output = term.output();
+
+            egui::Window::new("🖥 Terminal")
+                .default_width(700.0)
+                .show(ctx, |ui| {
+                    ui.monospace(format!("$ {}", term.command));
+                    egui::ScrollArea::vertical()
+                        .id_salt("terminal_scroll")
+                        .stick_to_bottom(true)
+                        .max_height(400.0)
+                        .show(ui, |ui| {
+                            ui.label(ansi_layout_job(ui, &output));
+                        });
+                    ui.separator();
+
+                    match exit_code {
+                        None => {
+                            ui.horizontal(|ui| {
+                                let input = ui.add(
+                                    egui::TextEdit::singleline(&mut self.terminal_input)
+                                        .hint_text(tr(lang, "Input (Enter to send)"))
+                                        .desired_width(400.0),
+                                );
+                                if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                    let line = std::mem::take(&mut self.terminal_input);
+                                    let _ = term.send_line(&line);
+                                    input.request_focus();
+                                }
+                                if ui.button("Ctrl+C").clicked() {
+                                    let _ = term.write_input("\x03");
+                                }
+                                if ui.button(tr(lang, "Kill")).clicked() {
+                                    term.kill();
+                                }
+                            });
+                        }
+                        Some(code) => {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Exited with code {}", code));
+                                if ui.button(tr(lang, "Close")).clicked() {
+                                    close_terminal = true;
+                                }
+                            });
+                        }
+                    }
+                });
+
+            match exit_code {
+                None => ctx.request_repaint_after(std::time::Duration::from_millis(100)),
+                Some(code) if !self.terminal_recorded => {
+                    self.terminal_recorded = true;
+                    finished = Some((term.command.clone(), format!("{}\n[exited with code {}]", output, code), code as i32));
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some((command, text, code)) = finished {
+            if let Some(term) = &self.terminal {
+                self.notify_finished(ctx, term.started, "Command finished", &command);
+            }
+            self.record_terminal_output(command, text, Some(code));
+        }
+        if close_terminal {
+            self.terminal = None;
+        }
+
         /* --------------- BOTTOM: composer ------------- */
+        let mut paste_context = false;
+        let mut quick_action = None;
+        let mut dismiss_hint = false;
+        let rows = 1 + usize::from(!self.quick_actions.is_empty()) + usize::from(self.history_hint.is_some());
         egui::TopBottomPanel::bottom("composer")
-            .exact_height(40.0)
+            .exact_height(8.0 + 32.0 * rows as f32)
             .resizable(false)
             .show(ctx, |ui| {
+                if let Some(entry) = &self.history_hint {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("💡 {}", tr(lang, "You ran {} for a similar request {}").replacen("{}", &format!("`{}`", entry.command), 1).replacen("{}", &entry.ago(), 1)))
+                            .on_hover_text(&entry.prompt);
+                        if !self.config.suggest_only && ui.small_button(tr(lang, "▶ Rerun (Ctrl+R)")).clicked() {
+                            rerun_hint = true;
+                        }
+                        if icon_button(ui, "✕", "Dismiss").clicked() {
+                            dismiss_hint = true;
+                        }
+                    });
+                }
+                if !self.quick_actions.is_empty() {
+                    ui.horizontal(|ui| {
+                        for action in &self.quick_actions {
+                            if ui.small_button(&action.label).on_hover_text(&action.prompt).clicked() {
+                                quick_action = Some(action.clone());
+                            }
+                        }
+                    });
+                }
                 ui.horizontal(|ui| {
                     ui.label("💬");
 
                     let input =
-                        ui.add(egui::TextEdit::singleline(&mut self.current_input).hint_text("Type here…"));
+                        ui.add(egui::TextEdit::singleline(&mut self.current_input).hint_text(tr(lang, "Type here…")));
+                    if self.focus_anchors[2].is_none() {
+                        // start with the cursor in the composer
+                        input.request_focus();
+                    }
+                    self.focus_anchors[2] = Some(input.id);
 
+                    // push to talk: recording while the button is held down
+                    let transcribing = self.dictation_rx.is_some();
+                    let label = if self.dictation.is_some() { "🔴" } else if transcribing { "⏳" } else { "🎤" };
+                    let mic = ui
+                        .add_enabled(!transcribing, egui::Button::new(label))
+                        .on_hover_text(tr(lang, "Hold to dictate a prompt"));
+                    let held = mic.is_pointer_button_down_on();
+                    if held && self.dictation.is_none() && !transcribing {
+                        self.start_dictation();
+                    } else if !held && self.dictation.is_some() {
+                        self.finish_dictation(ctx);
+                    }
+                    if let Some(error) = &self.dictation_error {
+                        ui.colored_label(Color32::RED, error);
+                    }
+
+                    // one question at a time per chat; other tabs can ask meanwhile
+                    let answering = self.is_answering(&self.current_session.id);
                     let send_pressed = ui
-                        .add_enabled(!self.current_input.trim().is_empty(), egui::Button::new("Send"))
+                        .add_enabled(!answering && !self.current_input.trim().is_empty(), egui::Button::new(tr(lang, "Send")))
                         .clicked()
-                        || (input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                        || (!answering && input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                    if answering {
+                        ui.spinner();
+                    }
+
+                    if !self.config.suggest_only {
+                        ui.checkbox(&mut self.run_interactive, tr(lang, "🖥 Interactive"))
+                            .on_hover_text(tr(lang, "Run commands in a terminal window (ssh, sudo, prompts)"));
+                    }
+                    if ui
+                        .checkbox(&mut self.current_session.bypass_cache, tr(lang, "Skip cache"))
+                        .on_hover_text(tr(lang, "Always ask the model in this chat, even for prompts it answered before"))
+                        .changed()
+                    {
+                        self.schedule_save();
+                    }
+
+                    match &self.clipboard_context {
+                        Some(contents) => {
+                            let label = tr(lang, "📎 Clipboard ({} chars) ✕").replace("{}", &contents.chars().count().to_string());
+                            if ui.small_button(label).on_hover_text(tr(lang, "Remove the clipboard context")).clicked() {
+                                self.clipboard_context = None;
+                            }
+                        }
+                        None => {
+                            if ui.button(tr(lang, "📋 Paste context")).on_hover_text(tr(lang, "Send the clipboard contents along with the next prompt")).clicked() {
+                                paste_context = true;
+                            }
+                        }
+                    }
 
                     if send_pressed {
                         let prompt = std::mem::take(&mut self.current_input);
                         self.handle_user_prompt(ctx, prompt);
+                        // Enter moved focus out of the text field; keep typing without the mouse
+                        input.request_focus();
                     }
+                    if self.composer_refocus {
+                        self.composer_refocus = false;
+                        input.request_focus();
+                    }
+                });
+            });
+
+        if rerun_hint && !self.config.suggest_only {
+            if let Some(entry) = self.history_hint.take() {
+                self.run_pending_command(ctx, &entry.command);
+                self.schedule_save();
+            }
+        } else if dismiss_hint {
+            self.history_hint = None;
+        }
+        if let Some(action) = quick_action {
+            if snippets::placeholders(&action.prompt).is_empty() {
+                self.insert_prompt(&action.prompt);
+            } else {
+                self.quick_action_form = Some((action, HashMap::new()));
+            }
+        }
+        self.show_quick_action_form(ctx);
+
+        if paste_context {
+            match clipboard::read() {
+                Ok(contents) if !contents.trim().is_empty() => self.clipboard_context = Some(contents),
+                Ok(_) => {}
+                Err(e) => self.current_session.messages.push(ChatMessage::note(e)),
+            }
+        }
+    }
+}
+
+/// Runs `command` in the detected shell, starting in `working_dir`, and returns
+/// its combined output together with the directory the command finished in
+/// and its exit code (`None` if it was killed or didn't start).
+pub(crate) fn execute_in_shell(command: &str, working_dir: Option<&Path>, limits: ResourceLimits) -> (String, Option<PathBuf>, Option<i32>) {
+    let mut executor = Executor::default().with_limits(limits);
+    if let Some(dir) = working_dir {
+        executor = executor.in_dir(dir);
+    }
+    describe_execution(executor.run(command))
+}
+
+/// The output of a command as the chat shows it, with the directory it
+/// finished in and its exit code.
+pub(crate) fn describe_execution(result: io::Result<Execution>) -> (String, Option<PathBuf>, Option<i32>) {
+    match result {
+        Ok(execution) => {
+            let mut result = execution.stdout;
+            if !execution.stderr.is_empty() {
+                if !result.is_empty() {
+                    result.push_str("\n");
+                }
+                result.push_str(&execution.stderr);
+            }
+            if result.is_empty() {
+                result = "Command executed successfully (no output)".to_string();
+            }
+            (result, execution.working_dir, execution.status.code())
+        }
+        Err(e) => (format!("Failed to start command: {}", e), None, None),
+    }
+}
+
+/// The title a chat gets from its first prompt, until the model has named it.
+fn provisional_title(prompt: &str) -> String {
+    prompt.chars().take(30).collect::<String>().trim_end().to_string()
+}
+
+/// Key under which the conversational answer to `prompt` is cached.
+fn answer_cache_key(prompt: &str) -> String {
+    format!("unified:{}", prompt)
+}
+
+/// Small icon-only button; `label` is shown on hover and read out by screen readers.
+fn icon_button(ui: &mut egui::Ui, icon: &str, label: &str) -> egui::Response {
+    let response = ui.small_button(icon).on_hover_text(label);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, ui.is_enabled(), label));
+    response
+}
+
+/// Renders an executed command and its output as a collapsible monospace block.
+///
+/// Returns true when the user starred the command.
+fn show_command_output(ui: &mut egui::Ui, idx: usize, output: &CommandOutput, view: &mut OutputViewState) -> bool {
+    let OutputViewState { expanded, save_target, status } = view;
+    let text = output
+        .full_output_path
+        .as_ref()
+        .and_then(|path| expanded.get(path))
+        .unwrap_or(&output.text);
+    let mut toggle_expanded = false;
+    let mut starred = false;
+
+    egui::Frame::group(ui.style())
+        .fill(ui.visuals().extreme_bg_color)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::LIGHT_GREEN, egui::RichText::new(format!("$ {}", output.command)).monospace());
+                if ui.small_button("📋 Copy").on_hover_text("Copy output").clicked() {
+                    ui.ctx().copy_text(ansi::strip(text));
+                }
+                starred = icon_button(ui, "⭐", "Save to snippets").clicked();
+            });
+
+            egui::CollapsingHeader::new("Output")
+                .id_salt(("command_output", idx))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_salt(("command_output_scroll", idx))
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            ui.label(ansi_layout_job(ui, text));
+                        });
                 });
+
+            if let Some(path) = &output.full_output_path {
+                let is_expanded = expanded.contains_key(path);
+                toggle_expanded = show_truncation_controls(ui, path, is_expanded, save_target, status);
+            }
+        });
+
+    if let (true, Some(path)) = (toggle_expanded, &output.full_output_path) {
+        if expanded.remove(path).is_none() {
+            match fs::read_to_string(path) {
+                Ok(full) => {
+                    expanded.insert(path.clone(), full);
+                }
+                Err(e) => *status = Some(format!("Could not read {}: {}", path.display(), e)),
+            }
+        }
+    }
+    starred
+}
+
+/// "Show more" / "Save full output" controls for a truncated output.
+///
+/// Returns true when the user asked to show or hide the complete output.
+fn show_truncation_controls(
+    ui: &mut egui::Ui,
+    path: &PathBuf,
+    is_expanded: bool,
+    save_target: &mut Option<(PathBuf, String)>,
+    status: &mut Option<String>,
+) -> bool {
+    let mut toggle_expanded = false;
+
+    ui.horizontal(|ui| {
+        if is_expanded {
+            toggle_expanded = ui.small_button("Show less").clicked();
+        } else {
+            ui.weak("Output truncated.");
+            toggle_expanded = ui.small_button("Show more").clicked();
+        }
+
+        if ui.small_button("💾 Save full output to file").clicked() {
+            let default_target = dirs::home_dir()
+                .unwrap_or_default()
+                .join(path.file_name().unwrap_or_default());
+            *save_target = Some((path.clone(), default_target.display().to_string()));
+        }
+    });
+
+    let mut finished = false;
+    if let Some((source, target)) = save_target {
+        if source == path {
+            ui.horizontal(|ui| {
+                ui.label("Save to:");
+                ui.text_edit_singleline(target);
+                if ui.button("Save").clicked() {
+                    *status = Some(match fs::copy(&*source, &*target) {
+                        Ok(_) => format!("Saved output to {}", target),
+                        Err(e) => format!("Could not save to {}: {}", target, e),
+                    });
+                    finished = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    finished = true;
+                }
             });
+        }
+    }
+    if finished {
+        *save_target = None;
+    }
+
+    if let Some(status) = status {
+        ui.weak(status.as_str());
+    }
+
+    toggle_expanded
+}
+
+/// Converts ANSI colored text into an egui layout job.
+fn ansi_layout_job(ui: &egui::Ui, text: &str) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let default_color = ui.visuals().text_color();
+    let mut job = egui::text::LayoutJob::default();
+
+    for span in ansi::parse(text) {
+        let color = span.style.fg.map_or(default_color, ansi_color);
+        job.append(&span.text, 0.0, egui::TextFormat {
+            font_id: font_id.clone(),
+            color,
+            background: span.style.bg.map_or(Color32::TRANSPARENT, ansi_color),
+            italics: span.style.italic,
+            underline: if span.style.underline { egui::Stroke::new(1.0, color) } else { egui::Stroke::NONE },
+            ..Default::default()
+        });
+    }
+    job
+}
+
+fn ansi_color(color: AnsiColor) -> Color32 {
+    const PALETTE: [Color32; 16] = [
+        Color32::from_rgb(0, 0, 0),
+        Color32::from_rgb(205, 49, 49),
+        Color32::from_rgb(13, 188, 121),
+        Color32::from_rgb(229, 229, 16),
+        Color32::from_rgb(36, 114, 200),
+        Color32::from_rgb(188, 63, 188),
+        Color32::from_rgb(17, 168, 205),
+        Color32::from_rgb(229, 229, 229),
+        Color32::from_rgb(102, 102, 102),
+        Color32::from_rgb(241, 76, 76),
+        Color32::from_rgb(35, 209, 139),
+        Color32::from_rgb(245, 245, 67),
+        Color32::from_rgb(59, 142, 234),
+        Color32::from_rgb(214, 112, 214),
+        Color32::from_rgb(41, 184, 219),
+        Color32::from_rgb(255, 255, 255),
+    ];
+
+    match color {
+        AnsiColor::Indexed(idx @ 0..=15) => PALETTE[idx as usize],
+        // 6x6x6 color cube
+        AnsiColor::Indexed(idx @ 16..=231) => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let idx = idx - 16;
+            Color32::from_rgb(level(idx / 36), level(idx / 6 % 6), level(idx % 6))
+        }
+        // grayscale ramp
+        AnsiColor::Indexed(idx) => Color32::from_gray(8 + (idx - 232) * 10),
+        AnsiColor::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+    }
+}
+
+/// Keywords highlighted in the script editor, per language family.
+fn script_keywords(language: &str) -> &'static [&'static str] {
+    match language {
+        "python" | "python3" | "py" => &["def", "return", "if", "elif", "else", "for", "while", "in", "import", "from", "as", "with", "try", "except", "finally", "raise", "class", "pass", "break", "continue", "and", "or", "not", "None", "True", "False", "lambda"],
+        "powershell" | "ps1" | "pwsh" => &["function", "param", "return", "if", "elseif", "else", "foreach", "for", "while", "in", "switch", "try", "catch", "finally", "throw", "begin", "process", "end"],
+        "node" | "javascript" | "js" => &["function", "return", "if", "else", "for", "while", "const", "let", "var", "of", "in", "try", "catch", "finally", "throw", "new", "async", "await", "import", "from", "require", "null", "true", "false"],
+        "ruby" | "rb" => &["def", "end", "return", "if", "elsif", "else", "unless", "for", "while", "in", "do", "begin", "rescue", "ensure", "class", "module", "require", "nil", "true", "false"],
+        "perl" | "pl" => &["sub", "my", "our", "return", "if", "elsif", "else", "unless", "for", "foreach", "while", "use", "die"],
+        _ => &["if", "then", "else", "elif", "fi", "for", "in", "do", "done", "while", "until", "case", "esac", "function", "return", "local", "export", "set", "end", "exit"],
+    }
+}
+
+/// Colors comments, strings, variables and keywords for the script editor.
+fn script_layout_job(ui: &egui::Ui, text: &str, language: &str) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let default_color = ui.visuals().text_color();
+    let comment = if matches!(language, "node" | "javascript" | "js") { "//" } else { "#" };
+    let keywords = script_keywords(language);
+    let mut job = egui::text::LayoutJob::default();
+    let push = |job: &mut egui::text::LayoutJob, text: &str, color: Color32| {
+        job.append(text, 0.0, egui::TextFormat { font_id: font_id.clone(), color, ..Default::default() });
+    };
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with(comment) {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            push(&mut job, &rest[..len], Color32::from_rgb(106, 153, 85));
+            len
+        } else if c == '"' || c == '\'' {
+            // up to the closing quote, or the end of the line if there is none
+            let close = rest[1..].find(c).map(|i| i + 2);
+            let len = close.unwrap_or_else(|| rest.find('\n').unwrap_or(rest.len())).max(1);
+            push(&mut job, &rest[..len], Color32::from_rgb(206, 145, 120));
+            len
+        } else if c == '$' || c.is_alphanumeric() || c == '_' {
+            let start = usize::from(c == '$');
+            let len = start + rest[start..].find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).unwrap_or(rest.len() - start);
+            let word = &rest[..len.max(1)];
+            let color = if c == '$' {
+                Color32::from_rgb(156, 220, 254)
+            } else if keywords.contains(&word) {
+                Color32::from_rgb(197, 134, 192)
+            } else {
+                default_color
+            };
+            push(&mut job, word, color);
+            word.len()
+        } else {
+            push(&mut job, &rest[..c.len_utf8()], default_color);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
     }
+    job
 }
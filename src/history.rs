@@ -0,0 +1,178 @@
+//! Commands that were run, with the prompt they came from, so a similar
+//! prompt can offer to rerun one instead of asking the model again. The runs
+//! for the most similar prompts also go to the model as examples of the
+//! user's own commands.
+//!
+//! Kept in `~/.llm_term_history.json`, encrypted like the caches when the
+//! vault is unlocked.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::vault;
+
+/// Oldest entries are dropped beyond this many.
+const MAX_ENTRIES: usize = 500;
+
+/// How much of a run's stderr is kept.
+const MAX_STDERR_BYTES: usize = 4000;
+
+/// How much two prompts' words must overlap (Dice coefficient) to count as similar.
+const MIN_SIMILARITY: f64 = 0.6;
+
+/// How much a prompt's words must overlap with the current one for its run to be an example.
+const MIN_EXAMPLE_SIMILARITY: f64 = 0.3;
+
+/// Words that say nothing about what a prompt asks for.
+const STOP_WORDS: &[&str] = &["a", "an", "the", "of", "in", "on", "for", "to", "and", "or", "by", "with", "me", "my", "all", "this", "that", "please"];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub prompt: String,
+    pub command: String,
+    pub ran_at: DateTime<Utc>,
+    /// how the CLI's run ended, if it was seen
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// the end of what the run wrote to stderr
+    #[serde(default)]
+    pub stderr: Option<String>,
+}
+
+impl Entry {
+    /// How long ago the command ran, e.g. "2 days ago".
+    pub fn ago(&self) -> String {
+        let elapsed = Utc::now().signed_duration_since(self.ran_at);
+        let (count, unit) = match elapsed.num_minutes() {
+            minutes if minutes < 1 => return "just now".to_string(),
+            minutes if minutes < 60 => (minutes, "minute"),
+            minutes if minutes < 60 * 24 => (elapsed.num_hours(), "hour"),
+            _ => (elapsed.num_days(), "day"),
+        };
+        format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+    }
+}
+
+pub fn history_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_history.json");
+    Ok(path)
+}
+
+/// Every recorded run, oldest first.
+pub fn load() -> Vec<Entry> {
+    history_file_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|content| vault::open_to_string(&content).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Remembers that `command` was run for `prompt`. Failures are ignored; the
+/// history only feeds hints.
+pub fn record(prompt: &str, command: &str) {
+    let mut entries = load();
+    entries.retain(|entry| !(entry.prompt == prompt && entry.command == command));
+    entries.push(Entry { prompt: prompt.to_string(), command: command.to_string(), ran_at: Utc::now(), exit_code: None, stderr: None });
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.drain(..excess);
+    save(&entries);
+}
+
+/// Notes how the latest run of `command` ended, for `--last-error`.
+pub fn record_outcome(command: &str, exit_code: Option<i32>, stderr: &str) {
+    let mut entries = load();
+    let Some(entry) = entries.iter_mut().rev().find(|entry| entry.command == command) else {
+        return;
+    };
+    entry.exit_code = exit_code;
+    let start = stderr.len().saturating_sub(MAX_STDERR_BYTES);
+    // the end is where the error usually is
+    let start = (start..stderr.len()).find(|&i| stderr.is_char_boundary(i)).unwrap_or(stderr.len());
+    entry.stderr = Some(stderr[start..].to_string()).filter(|stderr| !stderr.trim().is_empty());
+    save(&entries);
+}
+
+/// The command run last, if any.
+pub fn last() -> Option<Entry> {
+    load().pop()
+}
+
+fn save(entries: &[Entry]) {
+    if let (Ok(path), Ok(content)) = (history_file_path(), serde_json::to_string_pretty(entries)) {
+        let _ = fs::write(path, vault::seal(content.as_bytes()));
+    }
+}
+
+/// The most recent run whose prompt is worded much like `prompt`.
+pub fn similar(prompt: &str) -> Option<Entry> {
+    let words = significant_words(prompt);
+    if words.is_empty() {
+        return None;
+    }
+    load().into_iter().rev().find(|entry| {
+        let other = significant_words(&entry.prompt);
+        let shared = words.intersection(&other).count();
+        2.0 * shared as f64 / (words.len() + other.len()) as f64 >= MIN_SIMILARITY
+    })
+}
+
+/// Up to `count` runs for the prompts most like `prompt`, most similar first;
+/// each command only once.
+pub fn examples(prompt: &str, count: usize) -> Vec<Entry> {
+    let words = significant_words(prompt);
+    if words.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let mut scored: Vec<(f64, Entry)> = load()
+        .into_iter()
+        .rev()
+        .map(|entry| {
+            let other = significant_words(&entry.prompt);
+            let shared = words.intersection(&other).count();
+            (2.0 * shared as f64 / (words.len() + other.len()) as f64, entry)
+        })
+        .filter(|(score, _)| *score >= MIN_EXAMPLE_SIMILARITY)
+        .collect();
+    // stable, so the most recent run wins a tie
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    let mut examples: Vec<Entry> = Vec::new();
+    for (_, entry) in scored {
+        if !examples.iter().any(|example| example.command == entry.command) {
+            examples.push(entry);
+        }
+        if examples.len() == count {
+            break;
+        }
+    }
+    examples
+}
+
+/// Shows the model commands the user ran for prompts like `prompt`, so it
+/// keeps to their tools and style; empty if there are none.
+pub fn prompt_note(prompt: &str, count: usize) -> String {
+    let examples = examples(prompt, count);
+    if examples.is_empty() {
+        return String::new();
+    }
+    let mut note = String::from("Commands this user approved before for similar requests; keep to the tools, paths and style they show:");
+    for example in examples {
+        note.push_str(&format!("\n- \"{}\" → `{}`", example.prompt, example.command));
+    }
+    note
+}
+
+fn significant_words(prompt: &str) -> HashSet<String> {
+    prompt
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,196 @@
+//! HTTP agents for the model providers and the remote cache, set up for
+//! corporate networks: proxies, `NO_PROXY` and custom CA bundles.
+//!
+//! They're ureq agents rather than reqwest: every model request already
+//! runs on a thread of its own, so a blocking client fits, and ureq pools
+//! connections per agent, streams response bodies and takes the rustls
+//! config below as it is, without an async runtime in the binary.
+
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::Config;
+
+/// Hosts that are never sent through a proxy, like a local Ollama.
+const LOOPBACK: &[&str] = &["localhost", "127.0.0.1", "::1", "[::1]"];
+
+/// Connecting gives up after this long, whatever `request_timeout_secs` says.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What makes two agents differ: proxy, CA bundle, skip-verify and read timeout.
+type AgentKey = (Option<String>, Option<PathBuf>, bool, u64);
+
+/// Agents handed out by [`agent`], kept so connections are reused between requests.
+static AGENTS: Mutex<Option<HashMap<AgentKey, ureq::Agent>>> = Mutex::new(None);
+
+/// A shared agent for the model requests to `url`. Agents pool their
+/// connections, so later requests to the same host skip the TCP and TLS
+/// handshakes. Reads time out after `request_timeout_secs`.
+pub fn agent(config: &Config, url: &str) -> Result<ureq::Agent, Box<dyn std::error::Error>> {
+    let key = (proxy_for(config, url), config.ca_bundle.clone(), config.tls_skip_verify, config.request_timeout_secs);
+    let mut agents = AGENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(agent) = agents.get_or_insert_with(HashMap::new).get(&key) {
+        return Ok(agent.clone());
+    }
+
+    let mut builder = agent_builder(config, url)?.timeout_connect(CONNECT_TIMEOUT);
+    if config.request_timeout_secs > 0 {
+        builder = builder.timeout_read(Duration::from_secs(config.request_timeout_secs));
+    }
+    let agent = builder.build();
+    agents.get_or_insert_with(HashMap::new).insert(key, agent.clone());
+    Ok(agent)
+}
+
+/// Whether `error`, or an error causing it, is a connect or read timeout.
+pub fn timed_out(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut error = Some(error);
+    while let Some(e) = error {
+        if e.downcast_ref::<std::io::Error>().is_some_and(|e| matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)) {
+            return true;
+        }
+        error = e.source();
+    }
+    false
+}
+
+/// An agent builder for requests to `url`, with the proxy and TLS settings
+/// from `config` and the environment applied.
+pub fn agent_builder(config: &Config, url: &str) -> Result<ureq::AgentBuilder, Box<dyn std::error::Error>> {
+    let mut builder = ureq::AgentBuilder::new().try_proxy_from_env(false);
+    if let Some(proxy) = proxy_for(config, url) {
+        builder = builder.proxy(ureq::Proxy::new(&proxy).map_err(|e| format!("Invalid proxy `{}`: {}", proxy, e))?);
+    }
+    if config.ca_bundle.is_some() || config.tls_skip_verify {
+        builder = builder.tls_config(Arc::new(tls_config(config)?));
+    }
+    Ok(builder)
+}
+
+/// The proxy for `url`: `proxy` from the config, else `HTTPS_PROXY`,
+/// `HTTP_PROXY` or `ALL_PROXY`. `None` for hosts matched by `NO_PROXY`.
+fn proxy_for(config: &Config, url: &str) -> Option<String> {
+    let host = host(url);
+    let no_proxy = env(&["NO_PROXY", "no_proxy"]).unwrap_or_default();
+    let excluded = no_proxy.split(',').map(str::trim).filter(|entry| !entry.is_empty()).any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*" || host == entry || host.ends_with(&format!(".{}", entry))
+    });
+    if excluded || LOOPBACK.contains(&host) {
+        return None;
+    }
+
+    let names: &[&str] = if url.starts_with("https://") {
+        &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+    } else {
+        &["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+    };
+    config.proxy.clone().or_else(|| env(names))
+}
+
+/// The first of the environment variables `names` that is set and not empty.
+fn env(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| std::env::var(name).ok().filter(|value| !value.trim().is_empty()))
+}
+
+/// The host part of `url`, without user info or port.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or(ipv6),
+        None => host.split(':').next().unwrap_or(host),
+    }
+}
+
+/// Trusts the usual public roots plus the certificates in `ca_bundle`, or
+/// anything at all with `tls_skip_verify`.
+fn tls_config(config: &Config) -> Result<rustls::ClientConfig, Box<dyn std::error::Error>> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    if config.tls_skip_verify {
+        return Ok(builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(SkipVerify(provider)))
+            .with_no_client_auth());
+    }
+
+    let mut roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    if let Some(path) = &config.ca_bundle {
+        let pem = fs::read_to_string(path).map_err(|e| format!("Can't read ca_bundle {}: {}", path.display(), e))?;
+        let certificates = pem_certificates(&pem)?;
+        if certificates.is_empty() {
+            return Err(format!("No certificates in ca_bundle {}", path.display()).into());
+        }
+        for certificate in certificates {
+            roots.add(certificate)?;
+        }
+    }
+    Ok(builder.with_root_certificates(roots).with_no_client_auth())
+}
+
+/// The `CERTIFICATE` blocks of a PEM file.
+fn pem_certificates(pem: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(BEGIN) {
+        let body = &rest[start + BEGIN.len()..];
+        let end = body.find(END).ok_or("Unterminated certificate in ca_bundle")?;
+        let base64: String = body[..end].chars().filter(|c| !c.is_whitespace()).collect();
+        certificates.push(CertificateDer::from(base64::engine::general_purpose::STANDARD.decode(base64)?));
+        rest = &body[end + END.len()..];
+    }
+    Ok(certificates)
+}
+
+/// Accepts any server certificate, for TLS-intercepting proxies whose CA
+/// isn't at hand. Signatures are still checked so the handshake is sound.
+#[derive(Debug)]
+struct SkipVerify(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for SkipVerify {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
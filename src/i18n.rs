@@ -0,0 +1,266 @@
+/// Language of the GUI's static labels.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+    De,
+    Fr,
+}
+
+impl Lang {
+    /// Picks the UI language for the configured `language`, which may be a
+    /// name ("Spanish", "Deutsch") or a locale ("fr", "es_MX.UTF-8").
+    /// Languages without translations fall back to English.
+    pub fn from_config(language: Option<&str>) -> Self {
+        let Some(language) = language else {
+            return Lang::En;
+        };
+        let language = language.trim().to_lowercase();
+        let code = language.split(['_', '-', '.']).next().unwrap_or("");
+
+        match code {
+            "es" | "spanish" | "español" | "espanol" => Lang::Es,
+            "de" | "german" | "deutsch" => Lang::De,
+            "fr" | "french" | "français" | "francais" => Lang::Fr,
+            _ => Lang::En,
+        }
+    }
+}
+
+/// Rows of [English, Spanish, German, French]. The English text doubles as the key.
+const TRANSLATIONS: &[[&str; 4]] = &[
+    ["🔒 Storage is encrypted", "🔒 El almacenamiento está cifrado", "🔒 Der Speicher ist verschlüsselt", "🔒 Le stockage est chiffré"],
+    ["Passphrase", "Frase de contraseña", "Passphrase", "Phrase secrète"],
+    ["Unlock", "Desbloquear", "Entsperren", "Déverrouiller"],
+    ["Chat History", "Historial de chats", "Chatverlauf", "Historique des discussions"],
+    ["+ New Chat", "+ Nuevo chat", "+ Neuer Chat", "+ Nouvelle discussion"],
+    ["🗄 Archive ({})", "🗄 Archivo ({})", "🗄 Archiv ({})", "🗄 Archives ({})"],
+    ["Archive", "Archivar", "Archivieren", "Archiver"],
+    ["Unarchive", "Desarchivar", "Wiederherstellen", "Désarchiver"],
+    ["Delete", "Eliminar", "Löschen", "Supprimer"],
+    ["Older than", "Más antiguas que", "Älter als", "Plus anciennes que"],
+    [" days", " días", " Tage", " jours"],
+    ["🗑 Delete {} sessions", "🗑 Eliminar {} chats", "🗑 {} Chats löschen", "🗑 Supprimer {} discussions"],
+    ["Could not start a shell.", "No se pudo iniciar una shell.", "Konnte keine Shell starten.", "Impossible de démarrer un shell."],
+    ["Shell exited with code {}", "La shell terminó con el código {}", "Shell mit Code {} beendet", "Le shell s'est terminé avec le code {}"],
+    ["Restart", "Reiniciar", "Neu starten", "Redémarrer"],
+    ["Type a command…", "Escribe un comando…", "Befehl eingeben…", "Tapez une commande…"],
+    ["🖥 Terminal pane", "🖥 Panel de terminal", "🖥 Terminalbereich", "🖥 Panneau terminal"],
+    [
+        "Run approved commands in a persistent shell",
+        "Ejecutar los comandos aprobados en una shell persistente",
+        "Bestätigte Befehle in einer dauerhaften Shell ausführen",
+        "Exécuter les commandes approuvées dans un shell persistant",
+    ],
+    ["⏰ Schedules", "⏰ Programaciones", "⏰ Zeitpläne", "⏰ Planifications"],
+    ["⭐ Snippets", "⭐ Fragmentos", "⭐ Snippets", "⭐ Extraits"],
+    ["⚖ Compare", "⚖ Comparar", "⚖ Vergleichen", "⚖ Comparer"],
+    ["📦 Data", "📦 Datos", "📦 Daten", "📦 Données"],
+    ["Export all data", "Exportar todos los datos", "Alle Daten exportieren", "Exporter toutes les données"],
+    ["Import data", "Importar datos", "Daten importieren", "Importer des données"],
+    [
+        "Replaces the current sessions, caches, snippets and settings",
+        "Reemplaza los chats, cachés, fragmentos y ajustes actuales",
+        "Ersetzt die aktuellen Chats, Caches, Snippets und Einstellungen",
+        "Remplace les discussions, caches, extraits et réglages actuels",
+    ],
+    ["⚙ System", "⚙ Sistema", "⚙ System", "⚙ Système"],
+    ["Reset to default", "Restablecer", "Zurücksetzen", "Réinitialiser"],
+    ["Add instructions for this chat", "Añadir instrucciones para este chat", "Anweisungen für diesen Chat hinzufügen", "Ajouter des instructions pour cette discussion"],
+    ["Instructions added to the system prompt in this chat", "Instrucciones que se añaden al prompt del sistema en este chat", "Anweisungen, die in diesem Chat zum Systemprompt hinzukommen", "Instructions ajoutées au prompt système dans cette discussion"],
+    ["You (editing):", "Tú (editando):", "Du (bearbeiten):", "Vous (modification) :"],
+    ["Resend", "Reenviar", "Erneut senden", "Renvoyer"],
+    [
+        "Discard everything after this message",
+        "Descartar todo lo posterior a este mensaje",
+        "Alles nach dieser Nachricht verwerfen",
+        "Supprimer tout ce qui suit ce message",
+    ],
+    ["Resend as branch", "Reenviar como rama", "Als Zweig erneut senden", "Renvoyer comme branche"],
+    [
+        "Keep this chat and continue in a new linked one",
+        "Conservar este chat y continuar en uno nuevo vinculado",
+        "Diesen Chat behalten und in einem neuen, verknüpften fortfahren",
+        "Garder cette discussion et continuer dans une nouvelle liée",
+    ],
+    ["Cancel", "Cancelar", "Abbrechen", "Annuler"],
+    ["You:", "Tú:", "Du:", "Vous :"],
+    ["Assistant:", "Asistente:", "Assistent:", "Assistant :"],
+    ["Edit and resend", "Editar y reenviar", "Bearbeiten und erneut senden", "Modifier et renvoyer"],
+    [
+        "Forget the cached answer to this prompt",
+        "Olvidar la respuesta guardada para este mensaje",
+        "Zwischengespeicherte Antwort auf diese Eingabe vergessen",
+        "Oublier la réponse en cache pour ce message",
+    ],
+    ["🔄 Regenerate", "🔄 Regenerar", "🔄 Neu generieren", "🔄 Régénérer"],
+    ["Regenerate (more creative)", "Regenerar (más creativo)", "Neu generieren (kreativer)", "Régénérer (plus créatif)"],
+    ["Regenerate with {}", "Regenerar con {}", "Neu generieren mit {}", "Régénérer avec {}"],
+    ["More regenerate options", "Más opciones de regeneración", "Weitere Optionen zum Neugenerieren", "Plus d'options de régénération"],
+    ["Keep previous answer", "Conservar la respuesta anterior", "Vorherige Antwort behalten", "Garder la réponse précédente"],
+    ["💬 Waiting for your response...", "💬 Esperando tu respuesta...", "💬 Warte auf deine Antwort...", "💬 En attente de votre réponse..."],
+    ["output file, e.g. report.txt", "archivo de salida, p. ej. informe.txt", "Ausgabedatei, z. B. bericht.txt", "fichier de sortie, p. ex. rapport.txt"],
+    ["💾 Run and save output", "💾 Ejecutar y guardar la salida", "💾 Ausführen und Ausgabe speichern", "💾 Exécuter et enregistrer la sortie"],
+    ["Input (Enter to send)", "Entrada (Intro para enviar)", "Eingabe (Enter zum Senden)", "Saisie (Entrée pour envoyer)"],
+    ["Kill", "Terminar", "Beenden", "Tuer"],
+    ["Close", "Cerrar", "Schließen", "Fermer"],
+    ["Type here…", "Escribe aquí…", "Hier eingeben…", "Écrivez ici…"],
+    ["Send", "Enviar", "Senden", "Envoyer"],
+    ["🖥 Interactive", "🖥 Interactivo", "🖥 Interaktiv", "🖥 Interactif"],
+    [
+        "Run commands in a terminal window (ssh, sudo, prompts)",
+        "Ejecutar comandos en una ventana de terminal (ssh, sudo, preguntas)",
+        "Befehle in einem Terminalfenster ausführen (ssh, sudo, Eingaben)",
+        "Exécuter les commandes dans une fenêtre de terminal (ssh, sudo, invites)",
+    ],
+    ["Skip cache", "Omitir caché", "Cache umgehen", "Ignorer le cache"],
+    [
+        "Always ask the model in this chat, even for prompts it answered before",
+        "Preguntar siempre al modelo en este chat, incluso por mensajes ya respondidos",
+        "In diesem Chat immer das Modell fragen, auch bei bereits beantworteten Eingaben",
+        "Toujours interroger le modèle dans cette discussion, même pour les messages déjà traités",
+    ],
+    ["📎 Clipboard ({} chars) ✕", "📎 Portapapeles ({} caracteres) ✕", "📎 Zwischenablage ({} Zeichen) ✕", "📎 Presse-papiers ({} caractères) ✕"],
+    ["Remove the clipboard context", "Quitar el contexto del portapapeles", "Zwischenablage-Kontext entfernen", "Retirer le contexte du presse-papiers"],
+    ["📋 Paste context", "📋 Pegar contexto", "📋 Kontext einfügen", "📋 Coller le contexte"],
+    [
+        "Send the clipboard contents along with the next prompt",
+        "Enviar el contenido del portapapeles con el siguiente mensaje",
+        "Inhalt der Zwischenablage mit der nächsten Eingabe senden",
+        "Envoyer le contenu du presse-papiers avec le prochain message",
+    ],
+    ["🔒 {} masked before sending", "🔒 {} ocultados antes de enviar", "🔒 {} vor dem Senden maskiert", "🔒 {} masqués avant l'envoi"],
+    ["Sent as:", "Enviado como:", "Gesendet als:", "Envoyé ainsi :"],
+    ["📋 Copy command", "📋 Copiar comando", "📋 Befehl kopieren", "📋 Copier la commande"],
+    ["⚠ Administrator rights", "⚠ Permisos de administrador", "⚠ Administratorrechte", "⚠ Droits d'administrateur"],
+    [
+        "This command runs with administrator rights:",
+        "Este comando se ejecuta con permisos de administrador:",
+        "Dieser Befehl läuft mit Administratorrechten:",
+        "Cette commande s'exécute avec les droits d'administrateur :",
+    ],
+    [
+        "This command needs administrator rights to work:",
+        "Este comando necesita permisos de administrador:",
+        "Dieser Befehl braucht Administratorrechte:",
+        "Cette commande a besoin des droits d'administrateur :",
+    ],
+    ["🔑 Run with system prompt", "🔑 Ejecutar con diálogo del sistema", "🔑 Mit Systemdialog ausführen", "🔑 Exécuter avec la boîte du système"],
+    [
+        "Ask for the password in a system dialog (pkexec, UAC)",
+        "Pedir la contraseña en un diálogo del sistema (pkexec, UAC)",
+        "Passwort in einem Systemdialog abfragen (pkexec, UAC)",
+        "Demander le mot de passe dans une boîte du système (pkexec, UAC)",
+    ],
+    ["🖥 Run in terminal", "🖥 Ejecutar en terminal", "🖥 Im Terminal ausführen", "🖥 Exécuter dans le terminal"],
+    [
+        "Type the sudo password in the terminal window",
+        "Escribir la contraseña de sudo en la ventana de terminal",
+        "Das sudo-Passwort im Terminalfenster eingeben",
+        "Saisir le mot de passe sudo dans la fenêtre du terminal",
+    ],
+    ["📜 Script", "📜 Script", "📜 Skript", "📜 Script"],
+    ["Language:", "Lenguaje:", "Sprache:", "Langage :"],
+    [
+        "no interpreter known for this language",
+        "no se conoce un intérprete para este lenguaje",
+        "kein Interpreter für diese Sprache bekannt",
+        "aucun interpréteur connu pour ce langage",
+    ],
+    ["▶ Run script", "▶ Ejecutar script", "▶ Skript ausführen", "▶ Exécuter le script"],
+    ["📋 Copy", "📋 Copiar", "📋 Kopieren", "📋 Copier"],
+    ["Discard", "Descartar", "Verwerfen", "Abandonner"],
+    ["Insert prompt", "Insertar mensaje", "Eingabe einfügen", "Insérer le message"],
+    ["✏ Fill in the command", "✏ Completar el comando", "✏ Befehl ausfüllen", "✏ Compléter la commande"],
+    ["▶ Run", "▶ Ejecutar", "▶ Ausführen", "▶ Exécuter"],
+    [
+        "You ran {} for a similar request {}",
+        "Ejecutaste {} para una petición parecida {}",
+        "Du hast {} für eine ähnliche Anfrage ausgeführt ({})",
+        "Vous avez exécuté {} pour une demande similaire {}",
+    ],
+    ["▶ Rerun (Ctrl+R)", "▶ Repetir (Ctrl+R)", "▶ Erneut ausführen (Strg+R)", "▶ Relancer (Ctrl+R)"],
+    ["🐞 Debug", "🐞 Depuración", "🐞 Debug", "🐞 Débogage"],
+    ["📋 Copy report", "📋 Copiar informe", "📋 Bericht kopieren", "📋 Copier le rapport"],
+    [
+        "State, log lines and the last request, for a bug report",
+        "Estado, registro y la última petición, para informar de un error",
+        "Zustand, Protokoll und letzte Anfrage, für einen Fehlerbericht",
+        "État, journal et dernière requête, pour un rapport de bogue",
+    ],
+    ["State", "Estado", "Zustand", "État"],
+    ["Recent log", "Registro reciente", "Letzte Protokolleinträge", "Journal récent"],
+    [
+        "Nothing logged yet; start with -vv for request details",
+        "Nada registrado aún; inicia con -vv para ver las peticiones",
+        "Noch nichts protokolliert; mit -vv starten für Details zu Anfragen",
+        "Rien de journalisé ; lancez avec -vv pour le détail des requêtes",
+    ],
+    ["Last request", "Última petición", "Letzte Anfrage", "Dernière requête"],
+    ["Last response", "Última respuesta", "Letzte Antwort", "Dernière réponse"],
+    ["No API request made yet", "Aún no hay peticiones a la API", "Noch keine API-Anfrage", "Aucune requête API pour l'instant"],
+    ["⚠ Confirm command", "⚠ Confirmar comando", "⚠ Befehl bestätigen", "⚠ Confirmer la commande"],
+    ["To run it, type", "Para ejecutarlo, escribe", "Zum Ausführen eingeben:", "Pour l'exécuter, tapez"],
+    ["Files affected by", "Archivos afectados por", "Betroffene Dateien von", "Fichiers touchés par"],
+    ["more", "más", "weitere", "de plus"],
+    ["🗑 Delete permanently instead of moving to the trash", "🗑 Borrar definitivamente en lugar de mover a la papelera", "🗑 Endgültig löschen statt in den Papierkorb", "🗑 Supprimer définitivement au lieu de mettre à la corbeille"],
+    ["💾 Back up the {} file(s) it changes first", "💾 Copiar antes los {} archivo(s) que cambia", "💾 Vorher die {} geänderte(n) Datei(en) sichern", "💾 Sauvegarder d'abord les {} fichier(s) modifiés"],
+    ["↩ Restore backup", "↩ Restaurar copia", "↩ Sicherung wiederherstellen", "↩ Restaurer la sauvegarde"],
+    ["Put the files back as they were before the command", "Devolver los archivos a como estaban antes del comando", "Die Dateien auf den Stand vor dem Befehl zurücksetzen", "Remettre les fichiers comme avant la commande"],
+    ["🔍 Preview edit", "🔍 Vista previa de la edición", "🔍 Änderung vorab ansehen", "🔍 Aperçu de la modification"],
+    ["Run the edit on copies of the files and show what would change", "Aplica la edición a copias de los archivos y muestra qué cambiaría", "Führt die Änderung an Kopien der Dateien aus und zeigt, was sich ändern würde", "Applique la modification à des copies des fichiers et montre ce qui changerait"],
+    ["Hold to dictate a prompt", "Mantén pulsado para dictar una petición", "Gedrückt halten, um einen Prompt zu diktieren", "Maintenir pour dicter une demande"],
+    ["🔊 Speech", "🔊 Voz", "🔊 Sprachausgabe", "🔊 Synthèse vocale"],
+    ["Mute this chat", "Silenciar este chat", "Diesen Chat stummschalten", "Couper le son de cette discussion"],
+    ["Speed", "Velocidad", "Geschwindigkeit", "Vitesse"],
+    ["Until the app is closed; set \"speed\" under \"speech\" in config.json to keep it", "Hasta cerrar la aplicación; pon \"speed\" en \"speech\" de config.json para conservarla", "Bis die App geschlossen wird; \"speed\" unter \"speech\" in config.json setzen, um sie zu behalten", "Jusqu'à la fermeture de l'application ; réglez \"speed\" dans \"speech\" de config.json pour la garder"],
+    ["⏹ Stop reading", "⏹ Dejar de leer", "⏹ Vorlesen beenden", "⏹ Arrêter la lecture"],
+    ["Today", "Hoy", "Heute", "Aujourd'hui"],
+    ["Yesterday", "Ayer", "Gestern", "Hier"],
+    ["This Week", "Esta semana", "Diese Woche", "Cette semaine"],
+    ["Older", "Anteriores", "Älter", "Plus ancien"],
+    ["Hide chat history", "Ocultar historial de chats", "Chatverlauf ausblenden", "Masquer l'historique"],
+    ["Show chat history", "Mostrar historial de chats", "Chatverlauf einblenden", "Afficher l'historique"],
+    ["Close tab", "Cerrar pestaña", "Tab schließen", "Fermer l'onglet"],
+    ["Default model", "Modelo predeterminado", "Standardmodell", "Modèle par défaut"],
+    ["Model answering in this chat", "Modelo que responde en este chat", "Modell, das in diesem Chat antwortet", "Modèle qui répond dans cette discussion"],
+    ["✕ Dismiss", "✕ Descartar", "✕ Verwerfen", "✕ Ignorer"],
+    ["💡 Explain", "💡 Explicar", "💡 Erklären", "💡 Expliquer"],
+    ["Ask the model what this command does", "Preguntar al modelo qué hace este comando", "Das Modell fragen, was dieser Befehl tut", "Demander au modèle ce que fait cette commande"],
+    ["executed ✓ (exit {})", "ejecutado ✓ (salida {})", "ausgeführt ✓ (Exit {})", "exécutée ✓ (sortie {})"],
+    ["executed ✗ (exit {})", "ejecutado ✗ (salida {})", "ausgeführt ✗ (Exit {})", "exécutée ✗ (sortie {})"],
+    ["executed ✓", "ejecutado ✓", "ausgeführt ✓", "exécutée ✓"],
+    ["Fork from here", "Bifurcar desde aquí", "Ab hier abzweigen", "Bifurquer à partir d'ici"],
+    ["Rename", "Renombrar", "Umbenennen", "Renommer"],
+    ["📌 Pinned", "📌 Fijados", "📌 Angeheftet", "📌 Épinglés"],
+    ["Unpin", "Desfijar", "Nicht mehr anheften", "Désépingler"],
+    ["Pin to the top of the chat", "Fijar arriba del chat", "Oben im Chat anheften", "Épingler en haut de la discussion"],
+    ["Good suggestion", "Buena sugerencia", "Guter Vorschlag", "Bonne suggestion"],
+    ["Wrong suggestion; say what it should have been", "Sugerencia incorrecta; indica cuál debería haber sido", "Falscher Vorschlag; sag, wie er hätte lauten sollen", "Mauvaise suggestion ; indiquez ce qu'elle aurait dû être"],
+    ["Should have been:", "Debería haber sido:", "Hätte lauten sollen:", "Aurait dû être :"],
+    ["Save", "Guardar", "Speichern", "Enregistrer"],
+    ["Skip", "Omitir", "Überspringen", "Ignorer"],
+    ["🔍 What changed", "🔍 Qué cambió", "🔍 Was hat sich geändert", "🔍 Ce qui a changé"],
+    ["Compare the files and git status from before and after the command", "Comparar los archivos y el estado de git de antes y después del comando", "Dateien und git status vor und nach dem Befehl vergleichen", "Comparer les fichiers et le statut git d'avant et d'après la commande"],
+    ["⚙ Jobs", "⚙ Tareas", "⚙ Jobs", "⚙ Tâches"],
+    ["Clear finished", "Quitar terminadas", "Beendete entfernen", "Effacer les terminées"],
+    ["Commands run from the chat show up here while they run.", "Los comandos ejecutados desde el chat aparecen aquí mientras se ejecutan.", "Im Chat gestartete Befehle erscheinen hier, während sie laufen.", "Les commandes lancées depuis le chat apparaissent ici pendant leur exécution."],
+    ["running", "en curso", "läuft", "en cours"],
+    ["finished", "terminado", "beendet", "terminé"],
+    ["failed", "falló", "fehlgeschlagen", "échoué"],
+    ["killed", "terminado a la fuerza", "abgebrochen", "tué"],
+    ["⏹ Kill", "⏹ Terminar", "⏹ Beenden", "⏹ Tuer"],
+    ["🦙 Ollama", "🦙 Ollama", "🦙 Ollama", "🦙 Ollama"],
+    ["Server", "Servidor", "Server", "Serveur"],
+    ["Host and port, e.g. gpu-box:11434; empty for OLLAMA_HOST or this machine", "Host y puerto, p. ej. gpu-box:11434; vacío para OLLAMA_HOST o este equipo", "Host und Port, z. B. gpu-box:11434; leer für OLLAMA_HOST oder diesen Rechner", "Hôte et port, p. ex. gpu-box:11434 ; vide pour OLLAMA_HOST ou cette machine"],
+    ["Test connection", "Probar conexión", "Verbindung testen", "Tester la connexion"],
+];
+
+/// Translates a static label. Text without a translation is returned as is.
+pub fn tr(lang: Lang, text: &'static str) -> &'static str {
+    TRANSLATIONS
+        .iter()
+        .find(|row| row[0] == text)
+        .map_or(text, |row| row[lang as usize])
+}
@@ -0,0 +1,48 @@
+//! Plan before apply: `terraform apply` and `pulumi up` get their `plan` or
+//! `preview` run and shown first, so the changes are seen before they're made.
+
+use crate::shell;
+
+/// Flags that skip the interactive approval, dropped from the plan.
+const APPROVE_FLAGS: &[&str] = &["-auto-approve", "--auto-approve", "-auto-approve=true", "--yes", "-y", "--skip-preview", "-f"];
+
+/// The command that previews what `command` would change: everything before
+/// its `apply`/`up`, then that step as a `plan`/`preview`. `None` if it
+/// doesn't apply anything, or applies a saved plan that was reviewed already.
+pub fn plan_for(command: &str) -> Option<String> {
+    let mut start = 0;
+    for part in command.split_inclusive([';', '|', '&', '\n']) {
+        let step = part.trim_end_matches([';', '|', '&', '\n']);
+        if let Some(plan) = plan_step(&step.split_whitespace().collect::<Vec<_>>()) {
+            let indent = step.len() - step.trim_start().len();
+            return Some(format!("{}{}", &command[..start + indent], plan));
+        }
+        start += part.len();
+    }
+    None
+}
+
+/// `terraform apply`, `tofu apply` or `pulumi up` in `words` rewritten as a plan.
+fn plan_step(words: &[&str]) -> Option<String> {
+    // a redirection isn't part of the arguments
+    let words: Vec<&str> = words.iter().copied().take_while(|word| !word.contains('>') && !word.starts_with('<')).collect();
+    let position = words.iter().skip(1).position(|word| !word.starts_with('-'))? + 1;
+    let (head, args) = (&words[..position], &words[position + 1..]);
+    let plan = match (shell::program_name(words.first()?), words[position]) {
+        // `terraform apply tfplan` applies a plan that was saved, and shown, before
+        ("terraform" | "tofu", "apply") if !has_plan_file(args) => "plan",
+        ("pulumi", "up" | "update") => "preview",
+        _ => return None,
+    };
+    let args: Vec<&str> = args.iter().copied().filter(|arg| !APPROVE_FLAGS.contains(arg)).collect();
+    Some([head, &[plan], &args].concat().join(" "))
+}
+
+/// Whether `args` of `terraform apply` name a plan file: a word that isn't a
+/// flag or the value of one, like `prod` in `-var-file prod`.
+fn has_plan_file(args: &[&str]) -> bool {
+    args.iter().enumerate().any(|(i, arg)| {
+        let value_of_flag = i > 0 && args[i - 1].starts_with('-') && !args[i - 1].contains('=') && !APPROVE_FLAGS.contains(&args[i - 1]);
+        !arg.starts_with('-') && !value_of_flag
+    })
+}
@@ -0,0 +1,106 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// How long the CLI waits for a running GUI before starting a new one.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// One request from the CLI to the running GUI.
+#[derive(Serialize, Deserialize)]
+struct Handoff {
+    /// proves the request comes from someone who can read the instance file
+    token: String,
+    /// start a new chat with this prompt; `None` only brings the window forward
+    prompt: Option<String>,
+}
+
+/// Where a running GUI records `port token` for the CLI to find it.
+fn instance_file_path() -> io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_gui_instance");
+    Ok(path)
+}
+
+/// Hands `prompt` to an already running GUI. Returns false when there is none
+/// (or it doesn't answer), in which case the caller should start one.
+pub fn send_to_running(prompt: Option<&str>) -> bool {
+    let Ok(content) = instance_file_path().and_then(fs::read_to_string) else {
+        return false;
+    };
+    let Some((port, token)) = content.trim().split_once(' ') else {
+        return false;
+    };
+    let Ok(port) = port.parse::<u16>() else {
+        return false;
+    };
+
+    let handoff = Handoff { token: token.to_string(), prompt: prompt.map(str::to_string) };
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let exchange = || -> io::Result<bool> {
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        writeln!(stream, "{}", serde_json::to_string(&handoff)?)?;
+
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply.trim() == "ok")
+    };
+    exchange().unwrap_or(false)
+}
+
+/// Listening side, owned by the GUI. Dropping it removes the instance file
+/// so the next `--gui` starts a fresh window.
+pub struct Instance {
+    token: String,
+}
+
+impl Instance {
+    /// Starts accepting handoffs and forwards their prompts to `tx`.
+    pub fn listen(ctx: egui::Context, tx: Sender<Option<String>>) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        let token = uuid::Uuid::new_v4().to_string();
+        fs::write(instance_file_path()?, format!("{} {}", port, token))?;
+
+        let expected = token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve(stream, &expected, &tx, &ctx);
+            }
+        });
+
+        Ok(Self { token })
+    }
+}
+
+fn serve(stream: TcpStream, token: &str, tx: &Sender<Option<String>>, ctx: &egui::Context) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let handoff: Handoff = serde_json::from_str(&line)?;
+    if handoff.token != token {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "bad token"));
+    }
+    let _ = tx.send(handoff.prompt);
+    ctx.request_repaint();
+    writeln!(&stream, "ok")
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // only remove the file if a newer instance hasn't taken it over
+        if let Ok(path) = instance_file_path() {
+            if fs::read_to_string(&path).is_ok_and(|content| content.ends_with(&self.token)) {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
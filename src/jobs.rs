@@ -0,0 +1,151 @@
+//! Commands run from the chat in the background, so a long one doesn't hold
+//! up the window. The Jobs panel lists them with their status, how long
+//! they've run and the tail of their output, and can kill them; once one
+//! finishes, its output goes to the chat it was run from.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::gui::describe_execution;
+use crate::{changes, executor, shell, Executor, ResourceLimits};
+
+/// Output kept for the panel while a job runs; the chat gets all of it.
+const MAX_LIVE_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    /// exited with 0
+    Finished,
+    /// exited with another code, or didn't start
+    Failed,
+    Killed,
+}
+
+/// What a finished job leaves for the chat.
+pub struct Outcome {
+    /// stdout and stderr, as a command run in the chat shows them
+    pub output: String,
+    /// where the shell ended up, e.g. after a `cd`
+    pub working_dir: Option<PathBuf>,
+    pub exit_code: Option<i32>,
+    pub changes: Option<changes::Changes>,
+}
+
+#[derive(Default)]
+struct Progress {
+    live: String,
+    pid: Option<u32>,
+    killed: bool,
+    /// set when it finished, with its exit code
+    ended: Option<(Instant, Option<i32>)>,
+    /// until it's taken for the chat
+    outcome: Option<Outcome>,
+}
+
+pub struct Job {
+    pub command: String,
+    /// the chat it was run from
+    pub session_id: String,
+    /// the answer suggesting it, marked executed when it's done
+    pub suggestion: Option<usize>,
+    /// the `terraform apply` or `pulumi up` this is the plan of, offered
+    /// again once the plan succeeds
+    pub previewing: Option<String>,
+    pub started: Instant,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Job {
+    /// Runs `command` on a background thread. `before` is the directory as it
+    /// was before, for file-changing commands; it's compared with how it's left.
+    pub fn start(
+        ctx: &egui::Context,
+        command: &str,
+        session_id: String,
+        suggestion: Option<usize>,
+        working_dir: Option<PathBuf>,
+        limits: ResourceLimits,
+        before: Option<changes::Snapshot>,
+    ) -> Job {
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let job = Job { command: command.to_string(), session_id, suggestion, previewing: None, started: Instant::now(), progress: progress.clone() };
+
+        let ctx = ctx.clone();
+        let command = command.to_string();
+        std::thread::spawn(move || {
+            let mut executor = Executor::default().with_limits(limits);
+            if let Some(dir) = &working_dir {
+                executor = executor.in_dir(dir);
+            }
+            let (live, live_ctx) = (progress.clone(), ctx.clone());
+            let on_output: executor::OutputSink = Arc::new(move |text: &str| {
+                let mut progress = live.lock().unwrap();
+                progress.live.push_str(text);
+                let excess = progress.live.len().saturating_sub(MAX_LIVE_BYTES);
+                if excess > 0 {
+                    let cut = (excess..progress.live.len()).find(|&i| progress.live.is_char_boundary(i)).unwrap_or(progress.live.len());
+                    progress.live.drain(..cut);
+                }
+                live_ctx.request_repaint();
+            });
+            let started = progress.clone();
+            let result = executor.run_watched(&command, move |pid| started.lock().unwrap().pid = Some(pid), on_output);
+
+            let (output, working_dir, exit_code) = describe_execution(result);
+            let changes = before.map(|before| before.compare(&changes::Snapshot::take(&before.dir)));
+            let mut progress = progress.lock().unwrap();
+            progress.ended = Some((Instant::now(), exit_code));
+            progress.outcome = Some(Outcome { output, working_dir, exit_code, changes });
+            ctx.request_repaint();
+        });
+        job
+    }
+
+    pub fn status(&self) -> Status {
+        let progress = self.progress.lock().unwrap();
+        match progress.ended {
+            None => Status::Running,
+            Some(_) if progress.killed => Status::Killed,
+            Some((_, Some(0))) => Status::Finished,
+            Some(_) => Status::Failed,
+        }
+    }
+
+    /// How long it ran, or has been running.
+    pub fn elapsed(&self) -> Duration {
+        let progress = self.progress.lock().unwrap();
+        progress.ended.map_or_else(Instant::now, |(at, _)| at) - self.started
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        self.progress.lock().unwrap().ended.and_then(|(_, code)| code)
+    }
+
+    /// The last `lines` lines it printed.
+    pub fn tail(&self, lines: usize) -> String {
+        let progress = self.progress.lock().unwrap();
+        // the directory report at the very end isn't output
+        let (live, _) = shell::split_cwd_report(&progress.live);
+        let all: Vec<&str> = live.lines().collect();
+        all[all.len().saturating_sub(lines)..].join("\n")
+    }
+
+    /// Stops the command and everything it started.
+    pub fn kill(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        // once it's ended, the id may belong to another process
+        if let (None, Some(pid)) = (progress.ended, progress.pid) {
+            progress.killed = true;
+            executor::kill(pid);
+        }
+    }
+
+    /// How it ended, once, when it has.
+    pub fn take_outcome(&self) -> Option<Outcome> {
+        self.progress.lock().unwrap().outcome.take()
+    }
+}
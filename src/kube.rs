@@ -0,0 +1,93 @@
+//! The current `kubectl` context and namespace, for the prompt and for an
+//! extra confirmation before commands that target another context.
+
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::guard::Guard;
+use crate::shell;
+
+/// How long a looked up context is trusted; `kubectl config use-context`
+/// elsewhere shows up after this.
+const TTL: Duration = Duration::from_secs(30);
+
+/// Programs that talk to a cluster and the flags naming the context.
+const CLUSTER_PROGRAMS: &[(&str, &[&str])] = &[("kubectl", &["--context"]), ("helm", &["--kube-context"]), ("k9s", &["--context"])];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KubeContext {
+    pub name: String,
+    /// `None` means the `default` namespace
+    pub namespace: Option<String>,
+}
+
+static CURRENT: Mutex<Option<(Instant, Option<KubeContext>)>> = Mutex::new(None);
+
+/// The current context, or `None` without `kubectl` or a kubeconfig.
+pub fn current() -> Option<KubeContext> {
+    let mut cached = CURRENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, context)) = cached.as_ref().filter(|(at, _)| at.elapsed() < TTL) {
+        return context.clone();
+    }
+    let context = look_up();
+    *cached = Some((Instant::now(), context.clone()));
+    context
+}
+
+fn look_up() -> Option<KubeContext> {
+    let has_config = std::env::var_os("KUBECONFIG").is_some() || dirs::home_dir().is_some_and(|home| home.join(".kube").join("config").is_file());
+    if !has_config || !shell::on_path("kubectl") {
+        return None;
+    }
+    let name = kubectl(&["config", "current-context"])?;
+    let namespace = kubectl(&["config", "view", "--minify", "-o", "jsonpath={..namespace}"]);
+    Some(KubeContext { name, namespace })
+}
+
+/// The trimmed output of a successful `kubectl` run, `None` if empty.
+fn kubectl(args: &[&str]) -> Option<String> {
+    let output = Command::new("kubectl").args(args).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+/// Tells the model which cluster commands will hit; empty without a context.
+pub fn prompt_note() -> String {
+    let Some(context) = current() else {
+        return String::new();
+    };
+    format!(
+        "kubectl's current context is `{}` and its namespace `{}`. Rely on them for kubectl and helm commands instead of passing --context or --namespace, unless the user names another one.",
+        context.name,
+        context.namespace.as_deref().unwrap_or("default")
+    )
+}
+
+/// An extra confirmation for cluster commands that name a context other
+/// than the current one, or one that looks like production.
+pub fn guard(command: &str) -> Option<Guard> {
+    let current = current();
+    for words in shell::simple_commands(command) {
+        let Some(program) = words.first().map(|word| shell::program_name(word)) else {
+            continue;
+        };
+        let Some((_, flags)) = CLUSTER_PROGRAMS.iter().find(|(name, _)| *name == program) else {
+            continue;
+        };
+
+        let explicit = shell::flag_value(&words, flags);
+        let Some(target) = explicit.clone().or_else(|| current.as_ref().map(|c| c.name.clone())) else {
+            continue;
+        };
+        let other = explicit.is_some() && current.as_ref().is_some_and(|c| c.name != target);
+        if other || target.to_lowercase().contains("prod") {
+            let warning = match &current {
+                Some(current) if other => format!("This command targets the Kubernetes context `{}`, not the current `{}`.", target, current.name),
+                _ => format!("This command targets the Kubernetes context `{}`.", target),
+            };
+            return Some(Guard { warning, confirm: target });
+        }
+    }
+    None
+}
@@ -0,0 +1,76 @@
+//! Command generation and execution behind the `llm-term` CLI and GUI.
+//!
+//! ```no_run
+//! use llm_term::{Cache, CommandGenerator, Config, Executor, Model};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let generator = CommandGenerator::new(Config::new(Model::OpenAiGpt4oMini, 1000));
+//! let mut cache = Cache::load("cache.json")?;
+//!
+//! if let Some((command, _from_cache)) = generator.generate_cached(&mut cache, "list the 5 largest files")? {
+//!     let execution = Executor::default().run(&command)?;
+//!     print!("{}", execution.stdout);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod affected;
+pub mod ansi;
+pub mod backup;
+pub mod cache;
+pub mod changes;
+pub mod cloud;
+pub mod compare;
+pub mod config;
+pub mod context;
+pub mod cron;
+pub mod diff;
+pub mod elevation;
+pub mod executor;
+pub mod extract;
+pub mod feedback;
+pub mod generator;
+pub mod guard;
+pub mod history;
+pub mod http;
+pub mod iac;
+pub mod kube;
+pub mod logging;
+pub mod manual;
+pub mod model;
+pub mod packages;
+pub mod plan;
+pub mod postprocess;
+pub mod project;
+pub mod provider;
+pub mod rag;
+pub mod rate_limit;
+pub mod recording;
+pub mod redact;
+pub mod remote_cache;
+pub mod router;
+pub mod runbook;
+pub mod script;
+pub mod shell;
+pub mod speech;
+pub mod stats;
+pub mod tasks;
+pub mod trash;
+pub mod validate;
+pub mod vault;
+pub mod voice;
+pub mod watch;
+
+pub use cache::Cache;
+pub use config::Config;
+pub use executor::{Execution, Executor, ResourceLimits};
+pub use generator::CommandGenerator;
+pub use model::Model;
+pub use project::Project;
+pub use redact::Redactor;
+pub use remote_cache::RemoteCache;
+pub use script::Script;
+pub use shell::Shell;
+pub use stats::Stats;
+pub use vault::Vault;
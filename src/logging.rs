@@ -0,0 +1,129 @@
+//! Diagnostics: `-v` / `-vv` on stderr and a log file for post-mortems.
+//!
+//! Records are `<time> <LEVEL> <target>: <message>`, with details as
+//! `key=value` pairs in the message. `-v` shows the shell, cache decisions,
+//! model choices and timings; `-vv` adds the full request payloads. The
+//! file under `~/.llm_term_logs` always gets info records, and everything
+//! `-vv` shows when it's given; it's rotated at [`MAX_FILE_BYTES`].
+//!
+//! It's a `log` backend rather than `tracing`: ureq and rustls log through
+//! `log`, so their records end up here too without a bridge, and the
+//! details fit in `key=value` pairs without spans.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A log file grows to this size before it's rotated.
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Rotated files kept next to the current one: `llm-term.log.1` up to `.3`.
+const KEPT_FILES: u32 = 3;
+
+/// Lines kept in memory for [`recent`], e.g. for the GUI's debug panel.
+const RECENT_LINES: usize = 300;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The last log lines written, oldest first.
+pub fn recent() -> Vec<String> {
+    RECENT.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".llm_term_logs"))
+}
+
+pub fn log_file_path() -> Option<PathBuf> {
+    log_dir().map(|dir| dir.join("llm-term.log"))
+}
+
+struct Logger {
+    /// shown on stderr; `Off` without `-v`
+    stderr: LevelFilter,
+    file: Mutex<Option<File>>,
+    file_level: LevelFilter,
+}
+
+/// Installs the logger for `verbosity` (the number of `-v`s). The log file
+/// is best effort: if it can't be opened, only stderr gets records.
+pub fn init(verbosity: u8) {
+    let stderr = match verbosity {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let file_level = stderr.max(LevelFilter::Info);
+    let logger = Logger { stderr, file: Mutex::new(open_file()), file_level };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(file_level);
+    }
+}
+
+/// Opens the log file for appending, rotating it first if it's too big.
+fn open_file() -> Option<File> {
+    let path = log_file_path()?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() >= MAX_FILE_BYTES) {
+        rotate(&path);
+    }
+    OpenOptions::new().create(true).append(true).open(&path).ok()
+}
+
+/// `llm-term.log` becomes `.1`, `.1` becomes `.2` and so on; the oldest goes.
+fn rotate(path: &std::path::Path) {
+    let numbered = |n: u32| PathBuf::from(format!("{}.{}", path.display(), n));
+    let _ = fs::remove_file(numbered(KEPT_FILES));
+    for n in (1..KEPT_FILES).rev() {
+        let _ = fs::rename(numbered(n), numbered(n + 1));
+    }
+    let _ = fs::rename(path, numbered(1));
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.stderr.max(self.file_level)
+    }
+
+    fn log(&self, record: &Record) {
+        // other crates' records are only of interest with -vvv
+        let ours = record.target().starts_with("llm_term");
+        if !ours && self.stderr < LevelFilter::Trace {
+            return;
+        }
+
+        let line = format!("{} {:<5} {}: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"), record.level(), record.target(), record.args());
+        if record.level() <= self.stderr {
+            eprintln!("{}", line);
+        }
+        if record.level() <= self.file_level {
+            let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.clone());
+            drop(recent);
+
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            let too_big = file.as_ref().and_then(|f| f.metadata().ok()).is_some_and(|meta| meta.len() >= MAX_FILE_BYTES);
+            if too_big {
+                // closed before the rename, which Windows insists on
+                *file = None;
+                *file = open_file();
+            }
+            if let Some(file) = file.as_mut() {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
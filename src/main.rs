@@ -1,25 +1,33 @@
-mod shell;
-mod model;
 mod gui;
+mod pty;
+mod schedule;
+mod snippets;
+mod quick_actions;
+mod fuzzy;
+mod clipboard;
+mod i18n;
+mod notify;
+mod tray;
+mod ipc;
+mod persist;
+mod data;
+mod serve;
+mod rpc;
+mod window;
+mod jobs;
 
-use std::collections::HashMap;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::fs;
-use std::process::Command as ProcessCommand;
 use serde::{Deserialize, Serialize};
 use clap::{Command, Arg};
 use colored::*;
-use std::path::PathBuf;
-use shell::Shell;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use llm_term::{affected, ansi, backup, changes, cloud, compare, context, cron, diff, elevation, executor, extract, feedback, generator, guard, history, iac, logging, manual, model, plan, provider, rag, recording, runbook, shell, speech, stats, trash, validate, vault, voice, watch, Cache, CommandGenerator, Config, Execution, Executor, Redactor, RemoteCache, Project, ResourceLimits, Script, Stats, Vault};
+use llm_term::generator::Answer;
 use crate::model::Model;
 use crate::gui::LlmTermApp;
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Config {
-    pub model: Model,
-    pub max_tokens: i32
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = Command::new("llm-term")
         .version("1.0")
@@ -47,23 +55,339 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Launch GUI interface")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("tray")
+                .long("tray")
+                .help(format!("Run in the system tray; {} opens a quick prompt", tray::HOTKEY_LABEL))
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .help("Search previous prompts and rerun or tweak one")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("suggest-only")
+                .long("suggest-only")
+                .help("Only show commands, never run them (same as \"suggest_only\": true in the config)")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("permanent")
+                .long("permanent")
+                .help("Delete files for good instead of moving them to the trash (\"use_trash\": false in the config)")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-redacted")
+                .long("show-redacted")
+                .help("Print the prompt as it is sent, with secrets masked")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("refine")
+                .long("refine")
+                .value_name("CHANGE")
+                .help("Modify the last generated command, e.g. --refine \"also sort by size\"")
+                .conflicts_with("prompt"),
+        )
+        .arg(
+            Arg::new("last-error")
+                .long("last-error")
+                .help("Ask why the last command failed, from its exit code and stderr, and what to run instead")
+                .conflicts_with_all(["prompt", "refine"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clipboard")
+                .long("clipboard")
+                .help("Include the clipboard contents (e.g. a copied error message) as context")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .help("Dictate the prompt: records from the microphone until Enter, then transcribes it")
+                .conflicts_with("prompt")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("save-output")
+                .long("save-output")
+                .value_name("PATH")
+                .help("Write the command's output to PATH instead of the terminal"),
+        )
+        .arg(
+            Arg::new("star")
+                .long("star")
+                .value_name("NAME")
+                .help("Save the generated command to the snippets library under NAME"),
+        )
+        .arg(
+            Arg::new("stdio")
+                .long("stdio")
+                .help("Answer line-delimited JSON-RPC requests (generate, explain, execute) on stdin, for editor plugins")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Log the shell, cache decisions and timings to stderr; -vv adds request payloads. See ~/.llm_term_logs")
+                .action(clap::ArgAction::Count),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Save every model request and answer to FILE, e.g. to attach to a bug report"),
+        )
+        .arg(
+            Arg::new("replay")
+                .long("replay")
+                .value_name("FILE")
+                .conflicts_with("record")
+                .help("Answer model requests from a --record file instead of the models; needs no keys or network"),
+        )
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("snippet")
+                .about("Run a saved snippet, e.g. `llm-term snippet kill-port --port 8080`; lists snippets without a name")
+                .arg(Arg::new("name").help("Name of the snippet").index(1))
+                .arg(
+                    Arg::new("params")
+                        .help("Values for the snippet's placeholders as --name value")
+                        .index(2)
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("export-data")
+                .about("Bundle config, sessions, caches, snippets and schedules into a zip file")
+                .arg(Arg::new("file").value_name("FILE").required(true).index(1)),
+        )
+        .subcommand(
+            Command::new("import-data")
+                .about("Restore the data from an export-data zip file, replacing the current data")
+                .arg(Arg::new("file").value_name("FILE").required(true).index(1)),
+        )
+        .subcommand(
+            Command::new("warm")
+                .about("Pre-fill the cache from a file with one prompt per line; lines starting with # are skipped")
+                .arg(Arg::new("file").value_name("FILE").required(true).index(1))
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .default_value("4")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("How many prompts to send to the model at once"),
+                ),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Generate commands for every line of a file (lines starting with # are skipped), run them in order, and print a report")
+                .arg(Arg::new("file").value_name("FILE").required(true).index(1))
+                .arg(
+                    Arg::new("no-execute")
+                        .long("no-execute")
+                        .help("Only generate the commands, for a runbook or migration script")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("FORMAT")
+                        .value_parser(["text", "json", "markdown"])
+                        .default_value("text")
+                        .help("How to print the report"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .default_value("4")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("How many prompts to send to the model at once"),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show local cache hit rates and model latencies; nothing is ever sent anywhere")
+                .arg(
+                    Arg::new("reset")
+                        .long("reset")
+                        .help("Clear the recorded stats")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Ask several models for the same command and show their answers side by side")
+                .arg(Arg::new("prompt").help("The prompt describing the desired command").required(true).index(1))
+                .arg(
+                    Arg::new("models")
+                        .long("models")
+                        .value_name("MODELS")
+                        .default_value("gpt-4o,gpt-4o-mini")
+                        .help("Comma separated models, e.g. gpt-4o,gpt-4o-mini,ollama:llama3.1"),
+                ),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Watch for something, e.g. `llm-term watch \"tell me when disk usage exceeds 90%\"`: runs a generated check on an interval and notifies when its condition is met")
+                .arg(Arg::new("request").help("What to be told about").required(true).index(1))
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_name("SECS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seconds between checks; taken from \"every ...\" in the request, or 60"),
+                ),
+        )
+        .subcommand(
+            Command::new("schedule")
+                .about("Install a command in cron (Task Scheduler on Windows) from a request like `llm-term schedule \"run backup.sh every weekday at 6pm\"`")
+                .arg(Arg::new("request").help("What to run and when").index(1))
+                .args_conflicts_with_subcommands(true)
+                .subcommand(Command::new("list").about("List the schedules llm-term installed"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Take a schedule llm-term installed out of cron or Task Scheduler")
+                        .arg(Arg::new("id").help("The schedule's id, from `schedule list`").required(true).index(1)),
+                ),
+        )
+        .subcommand(
+            Command::new("plan")
+                .about("Plan a request that takes several commands, e.g. `llm-term plan \"rotate these certs\"`: runs the steps one by one and offers to roll back what ran if one fails")
+                .arg(Arg::new("request").help("What to do").required(true).index(1))
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .default_value("4")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("How many independent steps, e.g. checks of several hosts, run at the same time"),
+                ),
+        )
+        .subcommand(
+            Command::new("runbook")
+                .about("Record the commands you approve into a named runbook, and replay it step by step")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("record")
+                        .about("Add every command approved from now on to the runbook, until `runbook stop`")
+                        .arg(Arg::new("name").help("Name of the runbook, e.g. deploy").required(true).index(1))
+                        .arg(Arg::new("description").long("description").value_name("TEXT").help("What the runbook is for")),
+                )
+                .subcommand(Command::new("stop").about("Stop recording"))
+                .subcommand(Command::new("list").about("List the saved runbooks"))
+                .subcommand(
+                    Command::new("run")
+                        .about("Replay a runbook, confirming each step and stopping when one ends with an unexpected exit code")
+                        .arg(Arg::new("name").help("Name of the runbook").required(true).index(1)),
+                ),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Embed the notes and dotfiles listed under `rag` in the config, so relevant parts go along with prompts; only changed files are embedded again"),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Put back the files backed up before a command; lists the backups without an id")
+                .arg(Arg::new("id").help("The backup to restore, e.g. 20260314-091530").index(1)),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Check or change the config without the interactive setup")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("validate")
+                        .about("Check models, keys, token limits, regexes and the files the config points at, and say how to fix what's wrong")
+                        .arg(Arg::new("file").help("The config to check; the one next to the binary otherwise").index(1)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Change one setting, e.g. `config set model ollama:llama3.1` or `config set rate_limit.requests_per_minute 20`")
+                        .arg(Arg::new("key").help("The setting, dotted for nested ones").required(true).index(1))
+                        .arg(Arg::new("value").help("JSON, a plain string, or a model name for model settings").required(true).index(2)),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print one setting")
+                        .arg(Arg::new("key").help("The setting, dotted for nested ones").required(true).index(1)),
+                )
+                .subcommand(Command::new("list").about("Print every setting and its value")),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve a REST API on localhost: POST /generate, POST /execute, GET /history")
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .default_value("8080")
+                        .value_parser(clap::value_parser!(u16)),
+                )
+                .arg(
+                    Arg::new("token")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .help("Bearer token clients must send; random if not given"),
+                )
+                .arg(
+                    Arg::new("execute")
+                        .long("execute")
+                        .value_name("POLICY")
+                        .value_parser(["never", "generated", "any"])
+                        .default_value("never")
+                        .help("Which commands POST /execute may run: never, generated (only commands from the history) or any"),
+                ),
+        )
         .get_matches();
 
     let config_path = get_default_config_path().expect("Failed to get default config path");
 
+    logging::init(matches.get_count("verbose"));
+    log::info!("start version={} shell={} os={} config={}", env!("CARGO_PKG_VERSION"), shell::Shell::detect().to_shell_command_and_command_arg().0, std::env::consts::OS, config_path.display());
+
+    if let Some(file) = matches.get_one::<String>("record") {
+        recording::record_to(Path::new(file))?;
+    }
+    if let Some(file) = matches.get_one::<String>("replay") {
+        recording::replay_from(Path::new(file))?;
+    }
+
     // Launch GUI if requested
     if matches.get_flag("gui") {
+        let prompt = matches.get_one::<String>("prompt").cloned();
+        if ipc::send_to_running(prompt.as_deref()) {
+            println!("{}", "Sent to the running LLM Terminal window.".green());
+            return Ok(());
+        }
+
         let options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default()
-                .with_inner_size([1200.0, 800.0])
-                .with_title("LLM Terminal"),
+            // where the window was left, if it's been open before
+            viewport: window::WindowState::load().apply(
+                egui::ViewportBuilder::default()
+                    .with_inner_size([1200.0, 800.0])
+                    .with_title("LLM Terminal"),
+            ),
             ..Default::default()
         };
         
         return eframe::run_native(
             "LLM Terminal",
             options,
-            Box::new(|cc| Ok(Box::new(LlmTermApp::new(cc)))),
+            Box::new(|cc| Ok(Box::new(LlmTermApp::new(cc, prompt)))),
         ).map_err(|e| format!("Failed to run GUI: {}", e).into());
     }
 
@@ -75,25 +399,206 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    let config = load_or_create_config(&config_path)?;
+    if let Some(snippet_matches) = matches.subcommand_matches("snippet") {
+        // snippets don't need a config, but still honour suggest_only and limits if there is one
+        let saved_config = fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|content| Config::parse(&content).ok())
+            .map(|(config, _)| config);
+        let suggest_only = matches.get_flag("suggest-only") || saved_config.as_ref().is_some_and(|config| config.suggest_only);
+        let use_trash = !matches.get_flag("permanent") && saved_config.as_ref().is_none_or(|config| config.use_trash);
+        let limits = saved_config.map(|config| config.limits).unwrap_or_default();
+        return run_snippet(snippet_matches, suggest_only, limits, use_trash);
+    }
+
+    if let Some(export_matches) = matches.subcommand_matches("export-data") {
+        let file = export_matches.get_one::<String>("file").expect("file is required");
+        let count = data::export(Path::new(file))?;
+        println!("{}", format!("Exported {} files to {}.", count, file).green());
+        return Ok(());
+    }
+
+    if let Some(import_matches) = matches.subcommand_matches("import-data") {
+        let file = import_matches.get_one::<String>("file").expect("file is required");
+        let count = data::import(Path::new(file))?;
+        println!("{}", format!("Imported {} files from {}.", count, file).green());
+        return Ok(());
+    }
+
+    if let Some(stats_matches) = matches.subcommand_matches("stats") {
+        if stats_matches.get_flag("reset") {
+            Stats::reset()?;
+            println!("{}", "Stats cleared.".green());
+            return Ok(());
+        }
+        print_stats(&Stats::load());
+        return Ok(());
+    }
+
+    if let Some(restore_matches) = matches.subcommand_matches("restore") {
+        return restore_backup(restore_matches.get_one::<String>("id"));
+    }
+
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        return manage_config(&config_path, config_matches);
+    }
+
+    let mut config = load_or_create_config(&config_path)?;
+    if matches.get_flag("suggest-only") {
+        config.suggest_only = true;
+    }
+    if matches.get_flag("permanent") {
+        config.use_trash = false;
+    }
+    if config.encrypt_storage {
+        // stdin carries the requests in --stdio mode
+        unlock_storage(!matches.get_flag("stdio"))?;
+    }
+
+    if let Some(schedule_matches) = matches.subcommand_matches("schedule") {
+        return system_schedule(&config, schedule_matches);
+    }
+
+    if matches.subcommand_matches("index").is_some() {
+        println!("Indexing...");
+        let report = rag::index(&config)?;
+        println!("{}", format!("Indexed {} files ({} chunks); {} were unchanged.", report.files, report.chunks, report.unchanged).green());
+        return Ok(());
+    }
+
+    if matches.get_flag("tray") {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([560.0, 140.0])
+                .with_always_on_top()
+                .with_title("LLM Terminal quick prompt"),
+            ..Default::default()
+        };
+
+        return eframe::run_native(
+            "LLM Terminal quick prompt",
+            options,
+            Box::new(move |cc| Ok(Box::new(tray::QuickPromptApp::new(cc, config).map_err(|e| e.to_string())?))),
+        ).map_err(|e| format!("Failed to start the tray: {}", e).into());
+    }
+
+    let mut cache = Cache::load(get_cache_path()?)?;
+    if let Some(remote) = RemoteCache::from_config(&config) {
+        cache = cache.with_remote(remote);
+    }
+    let generator = CommandGenerator::new(config);
+
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        let port = *serve_matches.get_one::<u16>("port").expect("port has a default");
+        let token = serve_matches.get_one::<String>("token").cloned();
+        let policy = serve_matches
+            .get_one::<String>("execute")
+            .and_then(|value| serve::ExecutePolicy::parse(value))
+            .expect("execute has a default");
+        if generator.config().suggest_only && policy != serve::ExecutePolicy::Never {
+            return Err("--execute can't be used in suggest-only mode".into());
+        }
+        return serve::run(generator, cache, port, token, policy);
+    }
+
+    if let Some(warm_matches) = matches.subcommand_matches("warm") {
+        let file = warm_matches.get_one::<String>("file").expect("file is required");
+        let jobs = *warm_matches.get_one::<usize>("jobs").expect("jobs has a default");
+        return warm_cache(&generator, &mut cache, Path::new(file), jobs);
+    }
+
+    if let Some(batch_matches) = matches.subcommand_matches("batch") {
+        let file = batch_matches.get_one::<String>("file").expect("file is required");
+        let jobs = *batch_matches.get_one::<usize>("jobs").expect("jobs has a default");
+        let format = batch_matches.get_one::<String>("output").expect("output has a default");
+        return run_batch(&generator, &mut cache, Path::new(file), jobs, !batch_matches.get_flag("no-execute"), format);
+    }
+
+    if let Some(compare_matches) = matches.subcommand_matches("compare") {
+        let prompt = compare_matches.get_one::<String>("prompt").expect("prompt is required");
+        let models = compare_matches
+            .get_one::<String>("models")
+            .expect("models has a default")
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Model>, String>>()?;
+        return compare_models(generator.config(), &models, prompt);
+    }
+
+    if let Some(watch_matches) = matches.subcommand_matches("watch") {
+        let request = watch_matches.get_one::<String>("request").expect("request is required");
+        let interval = watch_matches.get_one::<u64>("interval").copied().or_else(|| schedule::parse_interval(request)).unwrap_or(DEFAULT_WATCH_SECS);
+        return watch_for(generator.config(), request, interval);
+    }
+
+    if matches.get_flag("stdio") {
+        return rpc::run(&generator, &mut cache);
+    }
+    let options = RunOptions {
+        star: matches.get_one::<String>("star").map(String::as_str),
+        save_output: matches.get_one::<String>("save-output").map(Path::new),
+        show_redacted: matches.get_flag("show-redacted"),
+        suggest_only: generator.config().suggest_only,
+        limits: generator.config().limits,
+        use_trash: generator.config().use_trash,
+    };
+
+    if let Some(plan_matches) = matches.subcommand_matches("plan") {
+        let request = plan_matches.get_one::<String>("request").expect("request is required");
+        let max_parallel = *plan_matches.get_one::<usize>("jobs").unwrap_or(&plan::DEFAULT_PARALLEL);
+        return run_plan(generator.config(), request, max_parallel, &options);
+    }
+
+    if let Some(runbook_matches) = matches.subcommand_matches("runbook") {
+        return manage_runbooks(runbook_matches, &options);
+    }
+
+    if matches.get_flag("history") {
+        return pick_from_history(&generator, &mut cache, &options);
+    }
+
+    if let Some(refinement) = matches.get_one::<String>("refine") {
+        return refine_last_command(&generator, &mut cache, refinement, &options);
+    }
 
-    let cache_path = get_cache_path()?;
-    let mut cache = load_cache(&cache_path)?;
+    if matches.get_flag("last-error") {
+        return fix_last_error(&generator, &mut cache, &options);
+    }
 
-    if let Some(prompt) = matches.get_one::<String>("prompt") {
-        let disable_cache = matches.get_flag("disable-cache");
+    let dictated = if matches.get_flag("listen") { Some(listen(generator.config())?) } else { None };
+    if let Some(prompt) = matches.get_one::<String>("prompt").or(dictated.as_ref()) {
+        let use_clipboard = matches.get_flag("clipboard");
+        // the clipboard changes between runs, so its answers can't come from the cache
+        let disable_cache = matches.get_flag("disable-cache") || use_clipboard;
+        let prompt = &if use_clipboard {
+            clipboard::attach(prompt, &clipboard::read()?)
+        } else {
+            prompt.clone()
+        };
 
         if !disable_cache {
-            if let Some(cached_command) = cache.get(prompt) {
+            let cached = cache.lookup(prompt);
+            stats::record_cache_lookup(cached.is_some());
+            if let Some(cached_command) = cached {
                 println!("{}", "This command exists in cache".yellow());
                 println!("{}", cached_command.cyan().bold());
+                save_last_command(prompt, &cached_command)?;
+                if let Some(name) = options.star {
+                    star_command(name, prompt, &cached_command)?;
+                }
+                if options.suggest_only {
+                    println!("{}", SUGGEST_ONLY_NOTE.yellow());
+                    return Ok(());
+                }
+                show_affected_files(&cached_command);
                 println!("{}", "Do you want to execute this command? (y/n)".yellow());
 
                 let mut user_input = String::new();
                 io::stdin().read_line(&mut user_input)?;
 
                 if user_input.trim().to_lowercase() == "y" {
-                    execute_command(cached_command)?;
+                    history::record(prompt, &cached_command);
+                    execute_command(&cached_command, options.save_output, options.limits, options.use_trash)?;
                 } else {
                     println!("{}", "Do you want to invalidate the cache? (y/n)".yellow());
                     user_input.clear();
@@ -102,21 +607,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     if user_input.trim().to_lowercase() == "y" {
                         // Invalidate cache
                         cache.remove(prompt);
-                        save_cache(&cache_path, &cache)?;
+                        cache.save()?;
                         // Proceed to get command from LLM
-                        get_command_from_llm(&config, &mut cache, &cache_path, prompt)?;
+                        get_command_from_llm(&generator, &mut cache, prompt, &options)?;
                     } else {
                         println!("{}", "Command execution cancelled.".yellow());
                     }
                 }
                 return Ok(());
-            } else {
+            } else if !offer_from_history(prompt, &options)? {
                 // Not in cache, proceed to get command from LLM
-                get_command_from_llm(&config, &mut cache, &cache_path, prompt)?;
+                get_command_from_llm(&generator, &mut cache, prompt, &options)?;
             }
-        } else {
+        } else if use_clipboard || !offer_from_history(prompt, &options)? {
             // Cache is disabled, proceed to get command from LLM
-            get_command_from_llm(&config, &mut cache, &cache_path, prompt)?;
+            get_command_from_llm(&generator, &mut cache, prompt, &options)?;
         }
     } else {
         println!("{}", "Please provide a prompt or use --config to set up the configuration.".yellow());
@@ -125,6 +630,82 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Unlocks the encrypted storage with `LLM_TERM_PASSPHRASE`, or by asking
+/// for the passphrase when `allow_prompt` is set.
+fn unlock_storage(allow_prompt: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let passphrase = match std::env::var("LLM_TERM_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) if allow_prompt => {
+            print!("{}", "Storage passphrase: ".cyan());
+            io::stdout().flush()?;
+            let input = read_hidden_line()?;
+            println!();
+            input.trim_end_matches(['\r', '\n']).to_string()
+        }
+        Err(_) => return Err("The storage is encrypted; set LLM_TERM_PASSPHRASE.".into()),
+    };
+    vault::install(Vault::unlock(&passphrase)?);
+    Ok(())
+}
+
+/// A line from stdin without echoing it, like a password field; read as
+/// usual when stdin isn't a terminal.
+fn read_hidden_line() -> io::Result<String> {
+    let mut input = String::new();
+    if !io::stdin().is_terminal() {
+        io::stdin().read_line(&mut input)?;
+        return Ok(input);
+    }
+    #[cfg(unix)]
+    {
+        // SAFETY: termios is plain data filled in by tcgetattr on stdin
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut hidden = original;
+        hidden.c_lflag &= !libc::ECHO;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) };
+        let read = io::stdin().read_line(&mut input);
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original) };
+        read?;
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{GetConsoleMode, GetStdHandle, SetConsoleMode, ENABLE_ECHO_INPUT, STD_INPUT_HANDLE};
+        // SAFETY: the standard input handle stays valid for the process
+        let handle = unsafe { GetStdHandle(STD_INPUT_HANDLE) };
+        let mut mode = 0;
+        let got_mode = unsafe { GetConsoleMode(handle, &mut mode) } != 0;
+        if got_mode {
+            unsafe { SetConsoleMode(handle, mode & !ENABLE_ECHO_INPUT) };
+        }
+        let read = io::stdin().read_line(&mut input);
+        if got_mode {
+            unsafe { SetConsoleMode(handle, mode) };
+        }
+        read?;
+    }
+    Ok(input)
+}
+
+/// What to do with a command besides showing it, from the command line flags.
+struct RunOptions<'a> {
+    /// save the command to the snippets library under this name
+    star: Option<&'a str>,
+    /// redirect the command's output to this file when it runs
+    save_output: Option<&'a Path>,
+    /// print the prompt as sent, after redaction
+    show_redacted: bool,
+    /// show commands without offering to run them
+    suggest_only: bool,
+    limits: ResourceLimits,
+    /// move what `rm` deletes to the trash
+    use_trash: bool,
+}
+
+const SUGGEST_ONLY_NOTE: &str = "Suggest-only mode: commands aren't run here; copy it to run it yourself.";
+
 pub fn get_default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let exe_path = std::env::current_exe()?;
     let exe_dir = exe_path.parent().ok_or("Failed to get executable directory")?;
@@ -133,7 +714,22 @@ pub fn get_default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>>
 
 pub fn load_or_create_config(path: &PathBuf) -> Result<Config, Box<dyn std::error::Error>> {
     if let Ok(content) = fs::read_to_string(path) {
-        Ok(serde_json::from_str(&content)?)
+        let (config, report) = Config::parse(&content).map_err(|e| format!("{} {}; fix it, or run `llm-term --config` to start over", path.display(), e))?;
+        for change in &report.changes {
+            eprintln!("{}", format!("{}: {}", path.display(), change).yellow());
+        }
+        for problem in &report.problems {
+            eprintln!("{}", format!("{}: {}", path.display(), problem).red());
+        }
+        if report.should_rewrite() {
+            // the old file stays next to it, in case something reads it
+            let backup = path.with_extension("json.bak");
+            fs::copy(path, &backup)?;
+            fs::write(path, serde_json::to_string_pretty(&config)?)?;
+            let from = report.migrated_from.unwrap_or_default();
+            eprintln!("{}", format!("Updated {} from version {} to {}; the old one is {}.", path.display(), from, llm_term::config::CONFIG_VERSION, backup.display()).yellow());
+        }
+        Ok(config)
     } else {
         let config = create_config()?;
         let content = serde_json::to_string_pretty(&config)?;
@@ -142,9 +738,77 @@ pub fn load_or_create_config(path: &PathBuf) -> Result<Config, Box<dyn std::erro
     }
 }
 
+fn manage_config(config_path: &Path, matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    match matches.subcommand() {
+        Some(("validate", validate_matches)) => {
+            let path = validate_matches.get_one::<String>("file").map_or_else(|| config_path.to_path_buf(), PathBuf::from);
+            validate_config(&path)
+        }
+        Some(("set", set_matches)) => {
+            let key = set_matches.get_one::<String>("key").expect("key is required");
+            let value = set_matches.get_one::<String>("value").expect("value is required");
+            let mut config = read_config_for_edit(config_path)?;
+            config.set(key, value)?;
+            // a script killed halfway shouldn't leave a truncated config
+            persist::write_atomic(config_path, serde_json::to_string_pretty(&config)?.as_bytes())?;
+            println!("{}", format!("{} = {}", key, config.get(key)?).green());
+            Ok(())
+        }
+        Some(("get", get_matches)) => {
+            let key = get_matches.get_one::<String>("key").expect("key is required");
+            println!("{}", read_config_for_edit(config_path)?.get(key)?);
+            Ok(())
+        }
+        Some(("list", _)) => {
+            for (key, value) in read_config_for_edit(config_path)?.settings() {
+                println!("{} = {}", key.cyan(), value);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The config at `path` for `config set`, `get` and `list`, with the
+/// defaults when there's none yet; what can't be read is reported, and
+/// isn't kept when it's saved.
+fn read_config_for_edit(path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(Config::new(Model::OpenAiGpt4oMini, llm_term::config::DEFAULT_MAX_TOKENS));
+    };
+    let (config, report) = Config::parse(&content).map_err(|e| format!("{} {}; fix it, or run `llm-term --config` to start over", path.display(), e))?;
+    for problem in &report.problems {
+        eprintln!("{}", format!("{}: {}", path.display(), problem).red());
+    }
+    Ok(config)
+}
+
+fn validate_config(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}; run `llm-term --config` to create it", path.display(), e))?;
+    let findings = validate::check(&text, &std::env::current_dir()?);
+    for finding in &findings {
+        let line = format!("{}: {}", finding.setting, finding.message);
+        match finding.severity {
+            validate::Severity::Error => println!("{} {}", "error".red().bold(), line),
+            validate::Severity::Warning => println!("{} {}", "warning".yellow().bold(), line),
+        }
+    }
+    let errors = findings.iter().filter(|finding| finding.severity == validate::Severity::Error).count();
+    if errors > 0 {
+        return Err(format!("{} has {} error{}", path.display(), errors, if errors == 1 { "" } else { "s" }).into());
+    }
+    let warnings = findings.len();
+    if warnings > 0 {
+        println!("{}", format!("{} works, with {} warning{}.", path.display(), warnings, if warnings == 1 { "" } else { "s" }).yellow());
+    } else {
+        println!("{}", format!("{} looks good.", path.display()).green());
+    }
+    Ok(())
+}
+
 fn create_config() -> Result<Config, io::Error> {
     let model = loop {
-        println!("{}", "Select model:\n 1 for gpt-4o-mini\n 2 for gpt-4o\n 3 for ollama (llama3.1)".cyan());
+        println!("{}", "Select model:\n 1 for gpt-4o-mini\n 2 for gpt-4o\n 3 for ollama (llama3.1)\n 4 for o3-mini (reasoning)".cyan());
 
         io::stdout().flush()?;
         let mut choice = String::new();
@@ -153,27 +817,33 @@ fn create_config() -> Result<Config, io::Error> {
             "1" => break Model::OpenAiGpt4oMini,
             "2" => break Model::OpenAiGpt4o,
             "3" => break Model::Ollama("llama3.1".to_string()),
+            "4" => break Model::OpenAiO3Mini,
             _ => println!("{}", "Invalid choice. Please try again.".red()),
         }
     };
 
+    // a reasoning model's thinking counts towards it
+    let limit = if model.is_reasoning() { 100_000 } else { 4096 };
     let max_tokens = loop {
-        print!("{}", "Enter max tokens (1-4096): ".cyan());
+        print!("{}", format!("Enter max tokens (1-{}): ", limit).cyan());
         io::stdout().flush()?;
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
         if let Ok(tokens) = input.trim().parse::<i32>() {
-            if tokens > 0 && tokens <= 4096 {
+            if tokens > 0 && tokens <= limit {
                 break tokens;
             }
         }
-        println!("{}", "Invalid input. Please enter a number between 1 and 4096.".red());
+        println!("{}", format!("Invalid input. Please enter a number between 1 and {}.", limit).red());
     };
 
-    Ok(Config {
-        model,
-        max_tokens,
-    })
+    print!("{}", "Reply language (leave empty for English): ".cyan());
+    io::stdout().flush()?;
+    let mut language = String::new();
+    io::stdin().read_line(&mut language)?;
+    let language = Some(language.trim().to_string()).filter(|l| !l.is_empty());
+
+    Ok(Config { language, ..Config::new(model, max_tokens) })
 }
 
 pub fn get_cache_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -182,62 +852,1369 @@ pub fn get_cache_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
     Ok(exe_dir.join("cache.json"))
 }
 
-pub fn load_cache(path: &PathBuf) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-    if let Ok(content) = fs::read_to_string(path) {
-        Ok(serde_json::from_str(&content)?)
-    } else {
-        Ok(HashMap::new())
+fn get_command_from_llm(
+    generator: &CommandGenerator,
+    cache: &mut Cache,
+    prompt: &String,
+    options: &RunOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    report_redactions(generator.config(), prompt, options.show_redacted)?;
+    let result = match retry_on_timeout(|| generator.ask(prompt)) {
+        Ok(Answer::Question(question)) => return answer_question(generator, cache, prompt, &question, options),
+        Ok(Answer::Command(command)) => Ok(command),
+        Err(e) => Err(e),
+    };
+    // the model answers with an empty string when it isn't confident
+    let unconfident = matches!(&result, Ok(command) if command.as_deref().is_none_or(|command| command.trim().is_empty()));
+    if unconfident && io::stdin().is_terminal() {
+        return offer_ways_forward(generator, cache, prompt, options);
+    }
+    offer_generated_command(cache, prompt, result, options)
+}
+
+/// Puts the model's question about an ambiguous `prompt` to the user and
+/// offers the command it comes up with given the answer.
+fn answer_question(generator: &CommandGenerator, cache: &mut Cache, prompt: &str, question: &str, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(note) = model::take_fallback_note() {
+        eprintln!("{}", note.yellow());
+    }
+    println!("{}", question.cyan());
+    if !io::stdin().is_terminal() {
+        println!("{}", "Add the answer to the prompt and try again.".yellow());
+        return Ok(());
+    }
+    print!("{}", "> ".yellow());
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(());
+    }
+    let result = retry_on_timeout(|| generator.answer(prompt, question, answer));
+    // remembered with the answer, since the prompt alone wasn't enough
+    offer_generated_command(cache, &format!("{} ({})", prompt, answer), result, options)
+}
+
+/// After the model wasn't confident about `prompt`: offers to ask a
+/// stronger model, or to talk the request through until it's clear.
+fn offer_ways_forward(generator: &CommandGenerator, cache: &mut Cache, prompt: &String, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(note) = model::take_fallback_note() {
+        eprintln!("{}", note.yellow());
+    }
+    let config = generator.config();
+    println!("{}", format!("{} wasn't confident enough to suggest a command for that.", config.model).yellow());
+    // with routing, the stronger model has been asked already
+    let stronger = config.routing.is_none().then(|| config.model.stronger()).flatten();
+    match &stronger {
+        Some(model) => println!("{}", format!("(s)ask {} instead, (c)larify the request, or anything else to quit:", model).yellow()),
+        None => println!("{}", "(c)larify the request, or anything else to quit:".yellow()),
+    }
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    match (input.trim().to_lowercase().as_str(), stronger) {
+        ("s", Some(model)) => {
+            let stronger = CommandGenerator::new(Config { model, ..config.clone() });
+            let result = retry_on_timeout(|| stronger.generate(prompt));
+            offer_generated_command(cache, prompt, result, options)
+        }
+        ("c", _) => clarify_request(generator, cache, prompt, options),
+        _ => Ok(()),
+    }
+}
+
+/// Lets the model ask what it needs to know about `prompt` until it can
+/// suggest a command, which is then offered as usual.
+fn clarify_request(generator: &CommandGenerator, cache: &mut Cache, prompt: &str, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conversation = Vec::new();
+    let mut answers = Vec::new();
+    loop {
+        let Some(turn) = generator.clarify(prompt, &conversation)? else {
+            println!("{}", "No answer from the model.".yellow());
+            return Ok(());
+        };
+        if !turn.reply.is_empty() {
+            println!("{}", turn.reply);
+        }
+        if let Some(command) = turn.command {
+            // remembered with the answers, since the prompt alone wasn't enough
+            return offer_generated_command(cache, &format!("{} ({})", prompt, answers.join("; ")), Ok(Some(command)), options);
+        }
+
+        print!("{}", "> ".yellow());
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(());
+        }
+        conversation.push(model::ConversationMessage { content: turn.reply, is_user: false });
+        conversation.push(model::ConversationMessage { content: answer.to_string(), is_user: true });
+        answers.push(answer.to_string());
+    }
+}
+
+/// Calls `request` again each time it times out and the user asks to retry.
+fn retry_on_timeout<T>(mut request: impl FnMut() -> Result<T, Box<dyn std::error::Error>>) -> Result<T, Box<dyn std::error::Error>> {
+    loop {
+        let result = request();
+        let timed_out = result.as_ref().err().and_then(|e| e.downcast_ref::<model::Timeout>());
+        let Some(timeout) = timed_out.filter(|_| io::stdin().is_terminal()) else {
+            return result;
+        };
+        eprintln!("{}", format!("Error: {}", timeout).red());
+        print!("{}", "Retry? (y/n) ".yellow());
+        let _ = io::stdout().flush();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).is_err() || input.trim().to_lowercase() != "y" {
+            return result;
+        }
+    }
+}
+
+/// Points out a command that was run for a similar prompt before and offers
+/// to rerun it. Returns true if it was rerun, so the model needn't be asked.
+fn offer_from_history(prompt: &str, options: &RunOptions) -> Result<bool, Box<dyn std::error::Error>> {
+    // the answer would be read from the input meant for the y/n question
+    if !io::stdin().is_terminal() {
+        return Ok(false);
     }
+    let Some(entry) = history::similar(prompt) else {
+        return Ok(false);
+    };
+
+    println!(
+        "{}",
+        format!("You ran `{}` for a similar request {} (\"{}\").", entry.command, entry.ago(), entry.prompt).yellow()
+    );
+    if options.suggest_only {
+        return Ok(false);
+    }
+    show_affected_files(&entry.command);
+    print!("{}", "(r)un it again, or Enter to ask the model: ".yellow());
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "r" {
+        return Ok(false);
+    }
+    history::record(prompt, &entry.command);
+    save_last_command(prompt, &entry.command)?;
+    execute_command(&entry.command, options.save_output, options.limits, options.use_trash)?;
+    Ok(true)
 }
 
-fn save_cache(path: &PathBuf, cache: &HashMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
-    let content = serde_json::to_string_pretty(&cache)?;
-    fs::write(path, content)?;
+/// Says which secrets in `prompt` are masked before it goes to the model.
+fn report_redactions(config: &Config, prompt: &str, show_masked: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(redactor) = Redactor::from_config(config)? else {
+        return Ok(());
+    };
+    let (masked, redactions) = redactor.redact(prompt);
+    if !redactions.is_empty() {
+        let kinds: Vec<&str> = redactions.iter().map(|r| r.kind.as_str()).collect();
+        eprintln!("{}", format!("Masked {} secret(s) before sending: {}", redactions.len(), kinds.join(", ")).yellow());
+    }
+    if show_masked {
+        eprintln!("{}", masked.dimmed());
+    }
     Ok(())
 }
 
-fn get_command_from_llm(
-    config: &Config,
-    cache: &mut HashMap<String, String>,
-    cache_path: &PathBuf,
+/// Shows a freshly generated command, asks whether to run it, and records it
+/// in the cache and as the last command for `--refine`.
+fn offer_generated_command(
+    cache: &mut Cache,
     prompt: &String,
+    result: Result<Option<String>, Box<dyn std::error::Error>>,
+    options: &RunOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    match &config.model.llm_get_command(config, prompt.as_str()) {
-        Ok(Some(command)) => {
+    if let Some(note) = model::take_fallback_note() {
+        eprintln!("{}", note.yellow());
+    }
+    match &result {
+        // the model answers with an empty string when it isn't confident
+        Ok(Some(command)) if !command.trim().is_empty() => {
+            save_last_command(prompt, command)?;
             println!("{}", &command.cyan().bold());
-            println!("{}", "Do you want to execute this command? (y/n)".yellow());
+            if let Some(name) = options.star {
+                star_command(name, prompt, command)?;
+            }
+            if options.suggest_only {
+                println!("{}", SUGGEST_ONLY_NOTE.yellow());
+            } else {
+                show_affected_files(command);
+                println!("{}", "Do you want to execute this command? (y/n)".yellow());
 
-            let mut user_input = String::new();
-            io::stdin().read_line(&mut user_input)?;
+                let mut user_input = String::new();
+                io::stdin().read_line(&mut user_input)?;
 
-            if user_input.trim().to_lowercase() == "y" {
-                execute_command(&command)?;
-            } else {
-                println!("{}", "Command execution cancelled.".yellow());
+                if user_input.trim().to_lowercase() == "y" {
+                    history::record(prompt, command);
+                    execute_command(&command, options.save_output, options.limits, options.use_trash)?;
+                } else {
+                    println!("{}", "Command execution cancelled.".yellow());
+                }
             }
 
             // Save command to cache
             cache.insert(prompt.clone(), command.clone());
-            save_cache(cache_path, cache)?;
+            cache.save()?;
         },
-        Ok(None) => println!("{}", "No command could be generated.".yellow()),
+        Ok(_) => println!("{}", "The model wasn't confident enough to suggest a command for that; try wording it more precisely.".yellow()),
         Err(e) => eprintln!("{}", format!("Error: {}", e).red()),
     }
 
     Ok(())
 }
 
-fn execute_command(command: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (shell_cmd, shell_arg) = Shell::detect().to_shell_command_and_command_arg();
+/// The most recent prompt and the command shown for it.
+#[derive(Serialize, Deserialize)]
+struct LastCommand {
+    prompt: String,
+    command: String,
+}
 
-    match ProcessCommand::new(shell_cmd).arg(shell_arg).arg(&command).output() {
-        Ok(output) => {
-            println!("{}", "Command output:".green().bold());
-            io::stdout().write_all(&output.stdout)?;
-            io::stderr().write_all(&output.stderr)?;
+pub fn get_last_command_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let exe_path = std::env::current_exe()?;
+    let exe_dir = exe_path.parent().ok_or("Failed to get executable directory")?;
+    Ok(exe_dir.join("last_command.json"))
+}
+
+fn save_last_command(prompt: &str, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let last = LastCommand { prompt: prompt.to_string(), command: command.to_string() };
+    fs::write(get_last_command_path()?, vault::seal(serde_json::to_string_pretty(&last)?.as_bytes()))?;
+    Ok(())
+}
+
+/// Asks the model to adjust the last command, e.g. "also sort by size",
+/// without restating the original request.
+fn refine_last_command(
+    generator: &CommandGenerator,
+    cache: &mut Cache,
+    refinement: &str,
+    options: &RunOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(content) = fs::read(get_last_command_path()?) else {
+        println!("{}", "No previous command to refine. Run llm-term \"<prompt>\" first.".yellow());
+        return Ok(());
+    };
+    let last: LastCommand = serde_json::from_str(&vault::open_to_string(&content)?)?;
+
+    println!("{}", format!("Refining: {}", last.command).yellow());
+    report_redactions(generator.config(), refinement, options.show_redacted)?;
+    let result = retry_on_timeout(|| generator.refine(&last.prompt, &last.command, refinement));
+    let prompt = format!("{}, {}", last.prompt, refinement);
+    offer_generated_command(cache, &prompt, result, options)
+}
+
+/// Explains why the last command in the history failed and offers a
+/// command to run instead, cached for the prompt the failed one came from.
+fn fix_last_error(
+    generator: &CommandGenerator,
+    cache: &mut Cache,
+    options: &RunOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(last) = history::last() else {
+        println!("{}", "No command has been run yet.".yellow());
+        return Ok(());
+    };
+    match last.exit_code {
+        Some(0) => {
+            println!("{}", format!("The last command, `{}`, succeeded.", last.command).yellow());
+            return Ok(());
         }
-        Err(e) => eprintln!("{}", format!("Failed to execute command: {}", e).red()),
+        None if last.stderr.is_none() => {
+            println!("{}", format!("Nothing was recorded about how `{}` ended; only commands run by the CLI are.", last.command).yellow());
+            return Ok(());
+        }
+        _ => {}
     }
 
-    Ok(())
-}
\ No newline at end of file
+    println!("{}", format!("Asking why `{}` failed...", last.command).yellow());
+    if let Some(stderr) = &last.stderr {
+        report_redactions(generator.config(), stderr, options.show_redacted)?;
+    }
+    let fix = match generator.fix(&last) {
+        Ok(Some(fix)) => fix,
+        Ok(None) => {
+            println!("{}", "No answer came back.".yellow());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", format!("Error: {}", e).red());
+            return Ok(());
+        }
+    };
+    println!("{}", fix.explanation);
+    match fix.command {
+        Some(command) => offer_generated_command(cache, &last.prompt, Ok(Some(command)), options),
+        None => Ok(()),
+    }
+}
+
+/// Number of matches shown per round of the history picker.
+const HISTORY_PAGE: usize = 10;
+
+/// Interactive fuzzy search over cached prompts. Each round lists the best
+/// matches for the current filter; typing a number picks one, anything else
+/// refines the filter.
+fn pick_from_history(
+    generator: &CommandGenerator,
+    cache: &mut Cache,
+    options: &RunOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries: Vec<(String, String, String)> = cache
+        .iter()
+        .map(|(prompt, command)| (prompt.to_string(), command.to_string(), format!("{} {}", prompt, command)))
+        .collect();
+    entries.sort();
+
+    if entries.is_empty() {
+        println!("{}", "No previous prompts yet.".yellow());
+        return Ok(());
+    }
+
+    let mut query = String::new();
+    let (prompt, command) = loop {
+        let found = fuzzy::rank(&query, &entries, |(_, _, haystack)| haystack.as_str());
+        if found.is_empty() {
+            println!("{}", format!("Nothing matches `{}`.", query).yellow());
+        }
+        for (idx, (prompt, command, _)) in found.iter().take(HISTORY_PAGE).enumerate() {
+            println!("{} {}", format!("{:>2}", idx + 1).yellow(), prompt);
+            println!("   {}", command.cyan());
+        }
+        print!("{}", "Type to filter, a number to pick, or Enter to quit: ".yellow());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Ok(());
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=found.len().min(HISTORY_PAGE)).contains(&n) => {
+                let (prompt, command, _) = found[n - 1];
+                break (prompt.clone(), command.clone());
+            }
+            _ => query = input.to_string(),
+        }
+    };
+
+    println!("{}", command.cyan().bold());
+    save_last_command(&prompt, &command)?;
+    if let Some(name) = options.star {
+        star_command(name, &prompt, &command)?;
+    }
+    if options.suggest_only {
+        println!("{}", "(t)weak the prompt, or anything else to quit:".yellow());
+    } else {
+        show_affected_files(&command);
+        println!("{}", "(r)un it, (t)weak the prompt, or anything else to cancel:".yellow());
+    }
+
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input)?;
+
+    match user_input.trim().to_lowercase().as_str() {
+        "r" if options.suggest_only => println!("{}", SUGGEST_ONLY_NOTE.yellow()),
+        "r" => {
+            history::record(&prompt, &command);
+            execute_command(&command, options.save_output, options.limits, options.use_trash)?;
+        }
+        "t" => {
+            println!("{}", format!("New prompt (Enter to regenerate `{}`):", prompt).yellow());
+            let mut tweaked = String::new();
+            io::stdin().read_line(&mut tweaked)?;
+            let tweaked = match tweaked.trim() {
+                "" => prompt,
+                text => text.to_string(),
+            };
+            get_command_from_llm(generator, cache, &tweaked, options)?;
+        }
+        _ => println!("{}", "Command execution cancelled.".yellow()),
+    }
+    Ok(())
+}
+
+/// Seconds between `watch` checks when the request doesn't say.
+const DEFAULT_WATCH_SECS: u64 = 60;
+
+/// Has the model turn `request` into a check and a condition, and once
+/// they're confirmed runs the check every `interval` seconds until the
+/// condition is met, then sends a desktop notification.
+fn watch_for(config: &Config, request: &str, interval: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let watch = watch::plan(config, request)?;
+    println!("{} {}", "Check:".yellow(), watch.check.cyan().bold());
+    println!("{} {}", "Alert when:".yellow(), watch.condition.to_string().cyan().bold());
+    if config.suggest_only {
+        println!("{}", SUGGEST_ONLY_NOTE.yellow());
+        return Ok(());
+    }
+
+    if !runs_unattended(&watch.check) {
+        return Ok(());
+    }
+    println!("{}", format!("Run it {}? (y/n)", schedule::describe_interval(interval)).yellow());
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "y" {
+        println!("{}", "Watch cancelled.".yellow());
+        return Ok(());
+    }
+
+    let executor = Executor::default().with_limits(config.limits);
+    loop {
+        let output = match executor.run(&watch.check) {
+            Ok(execution) if execution.status.success() => execution.stdout,
+            Ok(execution) => format!("{}{}", execution.stdout, execution.stderr),
+            Err(e) => return Err(format!("Failed to run the check: {}", e).into()),
+        };
+        let output = output.trim();
+        println!("{} {}", chrono::Local::now().format("%H:%M:%S").to_string().dimmed(), output.lines().next().unwrap_or(""));
+        if watch.condition.is_met(output) {
+            println!("{}", format!("Condition met: {}", watch.condition).green().bold());
+            notify::send(request, output);
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+    }
+}
+
+/// Whether `command` may run with nobody around to confirm it, as watches
+/// and installed schedules do; says why not if it may not.
+fn runs_unattended(command: &str) -> bool {
+    match unattended_refusal(command) {
+        Some(reason) => {
+            println!("{}", format!("Not running it: {}", reason).red());
+            false
+        }
+        None => true,
+    }
+}
+
+/// Why `command` mustn't run without someone confirming it, if it mustn't.
+pub(crate) fn unattended_refusal(command: &str) -> Option<String> {
+    let project = Project::current().unwrap_or_default();
+    if let Some(forbidden) = project.forbids(command) {
+        return Some(format!("`{}` is forbidden by {}", forbidden, project.path.display()));
+    }
+    if let Some(guard) = guard::check(command).first() {
+        return Some(guard.warning.clone());
+    }
+    if elevation::detect(command).is_some() && !elevation::is_elevated() {
+        return Some("it needs administrator rights".to_string());
+    }
+    if iac::plan_for(command).is_some() {
+        return Some("it applies infrastructure changes without a reviewed plan file".to_string());
+    }
+    let placeholders = snippets::placeholders(command);
+    if !placeholders.is_empty() {
+        return Some(format!("{} still need filling in", placeholders.join(", ")));
+    }
+    None
+}
+
+/// `llm-term schedule`: drafts and installs a system schedule for a
+/// request, or lists and removes the ones installed before.
+fn system_schedule(config: &Config, matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let entries = cron::list();
+            if entries.is_empty() {
+                println!("{}", "No schedules installed by llm-term.".yellow());
+            }
+            for entry in entries {
+                println!("{} {}", entry.id.yellow(), entry.request);
+                println!("   {} {}", entry.schedule.dimmed(), entry.command.cyan());
+            }
+            return Ok(());
+        }
+        Some(("remove", remove_matches)) => {
+            let id = remove_matches.get_one::<String>("id").expect("id is required");
+            let entry = cron::remove(id)?;
+            println!("{}", format!("Removed `{}` ({}).", entry.command, entry.request).green());
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let Some(request) = matches.get_one::<String>("request") else {
+        println!("{}", "Say what to run and when, e.g. llm-term schedule \"run backup.sh every weekday at 6pm\"".yellow());
+        return Ok(());
+    };
+    let draft = cron::draft(config, request)?;
+    println!("{}", draft.installed_form().cyan().bold());
+    if !draft.explanation.is_empty() {
+        println!("{}", draft.explanation);
+    }
+    if config.suggest_only {
+        println!("{}", SUGGEST_ONLY_NOTE.yellow());
+        return Ok(());
+    }
+    if !runs_unattended(&draft.command) {
+        return Ok(());
+    }
+    let scheduler = if cfg!(target_os = "windows") { "Task Scheduler" } else { "your crontab" };
+    println!("{}", format!("Install it in {}? (y/n)", scheduler).yellow());
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "y" {
+        println!("{}", "Not installed.".yellow());
+        return Ok(());
+    }
+    let entry = cron::install(request, &draft)?;
+    println!("{}", format!("Installed as {}; `llm-term schedule remove {}` takes it out again.", entry.id, entry.id).green());
+    Ok(())
+}
+
+/// Shows the plan the model came up with for `request`, then runs its steps
+/// one by one as they're confirmed. When one fails, the steps that ran can
+/// be rolled back, last first.
+fn run_plan(config: &Config, request: &str, max_parallel: usize, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let plan = plan::draft(config, request)?;
+    let total = plan.steps.len();
+    for (i, step) in plan.steps.iter().enumerate() {
+        let parallel = if step.parallel { " (independent)".dimmed().to_string() } else { String::new() };
+        println!("{} {}{}", format!("{}.", i + 1).yellow().bold(), step.description, parallel);
+        println!("   {}", step.command.cyan().bold());
+        match &step.rollback {
+            Some(rollback) => println!("   {} {}", "undo:".dimmed(), rollback.dimmed()),
+            None => println!("   {}", "no undo".dimmed()),
+        }
+    }
+    if options.suggest_only {
+        println!("{}", SUGGEST_ONLY_NOTE.yellow());
+        return Ok(());
+    }
+
+    let mut done: Vec<&plan::Step> = Vec::new();
+    for batch in plan.batches() {
+        let i = batch.start;
+        let steps = &plan.steps[batch.clone()];
+        println!();
+        if steps.len() > 1 {
+            println!("{}", format!("Steps {}-{}/{} can run at the same time:", i + 1, batch.end, total).yellow().bold());
+            for step in steps {
+                println!("{} {}", step.command.cyan().bold(), format!("({})", step.description).dimmed());
+            }
+        } else {
+            println!("{} {}", format!("Step {}/{}:", i + 1, total).yellow().bold(), steps[0].description);
+            println!("{}", steps[0].command.cyan().bold());
+        }
+        for step in steps {
+            show_affected_files(&step.command);
+        }
+        let them = if steps.len() > 1 { "them" } else { "it" };
+        println!("{}", format!("(r)un {}, (s)kip {}, or anything else to stop:", them, them).yellow());
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        match input.trim().to_lowercase().as_str() {
+            "r" => {}
+            "s" => continue,
+            _ => {
+                println!("{}", format!("Stopped before step {}.", i + 1).yellow());
+                return Ok(());
+            }
+        }
+
+        if steps.len() > 1 {
+            if !run_parallel_steps(request, i, steps, max_parallel, options, &mut done)? {
+                return offer_rollback(&done, options);
+            }
+            continue;
+        }
+        history::record(&format!("{} (step {}: {})", request, i + 1, steps[0].description), &steps[0].command);
+        let exit_code = execute_command(&steps[0].command, None, options.limits, options.use_trash)?;
+        if exit_code == Some(0) {
+            done.push(&steps[0]);
+            continue;
+        }
+        report_failed_step(i, exit_code);
+        return offer_rollback(&done, options);
+    }
+    println!("{}", format!("All {} steps done.", total).green());
+    Ok(())
+}
+
+/// Runs the independent `steps`, the first of them step `first` of the plan,
+/// up to `max_parallel` at a time, then shows the output of each in turn.
+/// Those that succeed are added to `done`; false if any failed.
+fn run_parallel_steps<'a>(
+    request: &str,
+    first: usize,
+    steps: &'a [plan::Step],
+    max_parallel: usize,
+    options: &RunOptions,
+    done: &mut Vec<&'a plan::Step>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let executor = Executor::default().with_limits(options.limits);
+    // asked one at a time before any of them runs
+    let mut ready = Vec::new();
+    for (i, step) in steps.iter().enumerate().map(|(offset, step)| (first + offset, step)) {
+        match prepare_command(&executor, &step.command, None, options.use_trash)? {
+            Some(command) => ready.push((i, step, command)),
+            None => println!("{}", format!("Skipping step {}.", i + 1).yellow()),
+        }
+    }
+
+    println!("{}", format!("Running {} steps, up to {} at a time...", ready.len(), max_parallel.max(1)).green().bold());
+    for (i, step, _) in &ready {
+        history::record(&format!("{} (step {}: {})", request, i + 1, step.description), &step.command);
+    }
+    let commands: Vec<String> = ready.iter().map(|(_, _, command)| command.clone()).collect();
+    let results = plan::run_concurrently(&executor, &commands, max_parallel);
+
+    let mut all_done = true;
+    for ((i, step, _), result) in ready.into_iter().zip(results) {
+        println!();
+        println!("{} {}", format!("Step {} output:", i + 1).green().bold(), step.description);
+        let exit_code = match result {
+            Ok(execution) => {
+                write_output(&mut io::stdout(), execution.stdout.as_bytes())?;
+                write_output(&mut io::stderr(), execution.stderr.as_bytes())?;
+                note_outcome(&step.command, execution.status.code(), &execution.stderr)
+            }
+            Err(e) => {
+                eprintln!("{}", format!("Failed to execute command: {}", e).red());
+                None
+            }
+        };
+        if exit_code == Some(0) {
+            done.push(step);
+        } else {
+            report_failed_step(i, exit_code);
+            all_done = false;
+        }
+    }
+    Ok(all_done)
+}
+
+fn report_failed_step(i: usize, exit_code: Option<i32>) {
+    let ended = exit_code.map_or("didn't finish".to_string(), |code| format!("exited with {}", code));
+    println!("{}", format!("Step {} {}.", i + 1, ended).red());
+}
+
+/// Offers to undo the steps in `done`, last first; those without a rollback are left as they are.
+fn offer_rollback(done: &[&plan::Step], options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let undoable: Vec<&plan::Step> = done.iter().rev().filter(|step| step.rollback.is_some()).copied().collect();
+    if undoable.is_empty() {
+        if !done.is_empty() {
+            println!("{}", "None of the steps that ran can be undone.".yellow());
+        }
+        return Ok(());
+    }
+    for step in &undoable {
+        println!("   {}", step.rollback.as_deref().unwrap_or_default().cyan());
+    }
+    println!("{}", format!("Roll back the {} steps that ran, with the commands above? (y/n)", undoable.len()).yellow());
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "y" {
+        println!("{}", "Left as it is.".yellow());
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for step in undoable {
+        let rollback = step.rollback.as_deref().unwrap_or_default();
+        println!("{} {}", "Undoing:".yellow().bold(), step.description);
+        history::record(&format!("undo {}", step.description), rollback);
+        // a failed undo is reported, the rest are still tried
+        if execute_command(rollback, None, options.limits, options.use_trash)? != Some(0) {
+            println!("{}", format!("`{}` failed.", rollback).red());
+            failed += 1;
+        }
+    }
+    if failed == 0 {
+        println!("{}", "Rolled back.".green());
+    } else {
+        println!("{}", format!("Rolled back, but {} undo commands failed; check what's left by hand.", failed).red());
+    }
+    Ok(())
+}
+
+fn manage_runbooks(matches: &clap::ArgMatches, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    match matches.subcommand() {
+        Some(("record", record_matches)) => {
+            let name = record_matches.get_one::<String>("name").expect("name is required");
+            if let Some(recording) = runbook::recording().filter(|recording| recording != name) {
+                println!("{}", format!("Stopped recording {}.", recording).yellow());
+            }
+            let mut runbook = runbook::start_recording(name)?;
+            if let Some(description) = record_matches.get_one::<String>("description") {
+                runbook.description = Some(description.clone());
+                runbook::save(&runbook)?;
+            }
+            let from = if runbook.steps.is_empty() { String::new() } else { format!(" after its {} steps", runbook.steps.len()) };
+            println!("{}", format!("Recording the commands you approve into runbook {}{}; `llm-term runbook stop` ends it.", name, from).green());
+        }
+        Some(("stop", _)) => match runbook::stop_recording() {
+            Some(runbook) => println!("{}", format!("Recorded {} steps; `llm-term runbook run {}` replays them.", runbook.steps.len(), runbook.name).green()),
+            None => println!("{}", "No runbook is being recorded.".yellow()),
+        },
+        Some(("list", _)) => {
+            let names = runbook::list();
+            if names.is_empty() {
+                println!("{}", "No runbooks yet; `llm-term runbook record <name>` starts one.".yellow());
+            }
+            let recording = runbook::recording();
+            for name in names {
+                match runbook::load(&name) {
+                    Ok(runbook) => {
+                        let marker = if recording.as_deref() == Some(name.as_str()) { " (recording)" } else { "" };
+                        println!("{} {} steps{} {}", name.yellow(), runbook.steps.len(), marker, runbook.description.unwrap_or_default().dimmed());
+                    }
+                    Err(e) => println!("{} {}", name.yellow(), e.red()),
+                }
+            }
+        }
+        Some(("run", run_matches)) => {
+            let name = run_matches.get_one::<String>("name").expect("name is required");
+            replay_runbook(&runbook::load(name)?, options)?;
+        }
+        _ => unreachable!("a subcommand is required"),
+    }
+    Ok(())
+}
+
+/// Shows each step of `runbook` and runs the ones confirmed, stopping at the
+/// first that doesn't end with the exit code it's expected to.
+fn replay_runbook(runbook: &runbook::Runbook, options: &RunOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(description) = &runbook.description {
+        println!("{}", description.bold());
+    }
+    let total = runbook.steps.len();
+    for (i, step) in runbook.steps.iter().enumerate() {
+        println!();
+        println!("{} {}", format!("Step {}/{}:", i + 1, total).yellow().bold(), step.description);
+        println!("{}", step.command.cyan().bold());
+        if options.suggest_only {
+            continue;
+        }
+        show_affected_files(&step.command);
+        println!("{}", "(r)un it, (s)kip it, or anything else to stop:".yellow());
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        match input.trim().to_lowercase().as_str() {
+            "r" => {}
+            "s" => continue,
+            _ => {
+                println!("{}", format!("Stopped before step {}.", i + 1).yellow());
+                return Ok(());
+            }
+        }
+
+        history::record(&step.description, &step.command);
+        let exit_code = execute_command(&step.command, None, options.limits, options.use_trash)?;
+        if exit_code != Some(step.expected_exit_code) {
+            let ended = exit_code.map_or("didn't finish".to_string(), |code| format!("exited with {}", code));
+            println!("{}", format!("Step {} {}, expected {}; stopping.", i + 1, ended, step.expected_exit_code).red());
+            return Ok(());
+        }
+    }
+    if options.suggest_only {
+        println!("{}", SUGGEST_ONLY_NOTE.yellow());
+    } else {
+        println!("{}", format!("Runbook {} done.", runbook.name).green());
+    }
+    Ok(())
+}
+
+/// Prints one row per model: name, latency, estimated cost and its command.
+fn compare_models(config: &Config, models: &[Model], prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", format!("Asking {} models…", models.len()).yellow());
+    let candidates = compare::compare(config, models, prompt);
+
+    let width = candidates.iter().map(|c| c.model.to_string().len()).max().unwrap_or(0).max("Model".len());
+    println!("{}", format!("{:<width$}  {:>8}  {:>9}  Command", "Model", "Latency", "Cost", width = width).bold());
+    for candidate in &candidates {
+        let command = match &candidate.command {
+            Ok(Some(command)) => command.trim().cyan().bold(),
+            Ok(None) => "(no command)".yellow(),
+            Err(e) => e.red(),
+        };
+        println!(
+            "{:<width$}  {:>7.2}s  {:>9}  {}",
+            candidate.model.to_string(),
+            candidate.latency.as_secs_f64(),
+            format!("${:.5}", candidate.estimated_cost),
+            command,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+fn warm_cache(generator: &CommandGenerator, cache: &mut Cache, file: &Path, jobs: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let prompts: Vec<String> = fs::read_to_string(file)?
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    let summary = generator.warm(cache, &prompts, jobs, |prompt, result| match result {
+        Ok(Some(command)) if !command.trim().is_empty() => println!("{} {}", format!("{}:", prompt).dimmed(), command.trim().cyan()),
+        Ok(_) => println!("{} {}", format!("{}:", prompt).dimmed(), "(no command)".yellow()),
+        Err(e) => println!("{} {}", format!("{}:", prompt).dimmed(), e.red()),
+    })?;
+
+    println!(
+        "{}",
+        format!(
+            "Cached {} new commands; {} were cached already, {} failed.",
+            summary.generated, summary.already_cached, summary.failed
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// One line of a `batch` report.
+#[derive(Serialize)]
+struct BatchRow {
+    prompt: String,
+    command: Option<String>,
+    /// why no command could be generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    /// why the command wasn't run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+}
+
+/// Generates a command for every prompt in `file`, `jobs` at a time, runs
+/// them one after another unless `execute` is off, and prints a report as
+/// text, JSON or Markdown. Questions and progress go to stderr, so the
+/// report can be redirected.
+fn run_batch(generator: &CommandGenerator, cache: &mut Cache, file: &Path, jobs: usize, execute: bool, format: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let prompts: Vec<String> = fs::read_to_string(file)?
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    eprintln!("{}", "Generating commands...".yellow());
+    let items = generator.batch(cache, &prompts, jobs, |prompt, _| eprintln!("{} {}", "done:".dimmed(), prompt.dimmed()))?;
+    let mut rows: Vec<BatchRow> = items
+        .into_iter()
+        .map(|item| {
+            let (command, error) = match item.command {
+                Ok(command) => (command, None),
+                Err(e) => (None, Some(e)),
+            };
+            BatchRow { prompt: item.prompt, command, error, cached: item.from_cache, exit_code: None, output: None, skipped: None }
+        })
+        .collect();
+
+    let execute = execute && !generator.config().suggest_only && rows.iter().any(|row| row.command.is_some());
+    if execute {
+        for row in &rows {
+            if let Some(command) = &row.command {
+                eprintln!("{}", command.cyan());
+            }
+        }
+        eprint!("{}", "Run these commands one after another, stopping at the first that fails? (y/n) ".yellow());
+        io::stderr().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() == "y" {
+            run_batch_commands(generator.config(), &mut rows);
+        }
+    }
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&rows)?),
+        "markdown" => {
+            for row in &rows {
+                println!("## {}\n", row.prompt);
+                match (&row.command, &row.error) {
+                    (Some(command), _) => println!("```sh\n{}\n```\n", command),
+                    (None, Some(e)) => println!("Failed: {}\n", e),
+                    (None, None) => println!("No command could be generated.\n"),
+                }
+                if let Some(reason) = &row.skipped {
+                    println!("Not run: {}\n", reason);
+                }
+                if let Some(code) = row.exit_code {
+                    println!("Exit code {}:\n\n```\n{}\n```\n", code, row.output.as_deref().unwrap_or("").trim_end());
+                }
+            }
+        }
+        _ => {
+            for row in &rows {
+                let command = match (&row.command, &row.error) {
+                    (Some(command), _) => command.cyan().bold(),
+                    (None, Some(e)) => e.red(),
+                    (None, None) => "(no command)".yellow(),
+                };
+                println!("{} {}", format!("{}:", row.prompt).dimmed(), command);
+                if let Some(reason) = &row.skipped {
+                    println!("   {}", format!("not run: {}", reason).yellow());
+                }
+                if let Some(code) = row.exit_code {
+                    let status = format!("exit {}", code);
+                    println!("   {}", if code == 0 { status.green() } else { status.red() });
+                    for line in row.output.as_deref().unwrap_or("").lines() {
+                        println!("   {}", line);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the commands of `rows` in order, under the same policies as
+/// commands run by hand, and records how each went. After a failure the
+/// rest are skipped.
+fn run_batch_commands(config: &Config, rows: &mut [BatchRow]) {
+    let executor = Executor::default().with_limits(config.limits);
+    let mut failed = false;
+    for row in rows.iter_mut() {
+        let Some(command) = row.command.clone() else {
+            continue;
+        };
+        if failed {
+            row.skipped = Some("an earlier command failed".to_string());
+            continue;
+        }
+        if let Some(reason) = unattended_refusal(&command) {
+            row.skipped = Some(reason);
+            continue;
+        }
+        let trashed = config.use_trash.then(|| trash::rewrite(&command, executor.shell())).flatten();
+        if trashed.is_none() && config.use_trash && trash::deletes(&command, executor.shell()) {
+            row.skipped = Some(format!("{}; use --permanent to delete for good", trash::not_rewritten_reason(&command, executor.shell())));
+            continue;
+        }
+
+        eprintln!("{} {}", "running:".dimmed(), command.cyan());
+        history::record(&row.prompt, &command);
+        match executor.run(trashed.as_deref().unwrap_or(&command)) {
+            Ok(execution) => {
+                history::record_outcome(&command, execution.status.code(), &execution.stderr);
+                failed = !execution.status.success();
+                row.exit_code = execution.status.code();
+                row.output = Some(format!("{}{}", execution.stdout, execution.stderr));
+            }
+            Err(e) => {
+                failed = true;
+                row.skipped = Some(format!("failed to start: {}", e));
+            }
+        }
+    }
+}
+
+fn print_stats(stats: &Stats) {
+    let lookups = stats.cache_hits + stats.cache_misses;
+    match stats.hit_rate() {
+        Some(rate) => println!(
+            "Cache: {} hits, {} misses ({:.0}% of {} lookups)",
+            stats.cache_hits, stats.cache_misses, rate * 100.0, lookups
+        ),
+        None => println!("Cache: no lookups yet"),
+    }
+    if stats.models.is_empty() {
+        println!("Models: no requests yet");
+        return;
+    }
+
+    let width = stats.models.keys().map(String::len).max().unwrap_or(0).max("Model".len());
+    println!("{}", format!("{:<width$}  {:>8}  {:>6}  {:>11}  {:>17}", "Model", "Requests", "Errors", "Avg latency", "Tokens in/out", width = width).bold());
+    for (model, model_stats) in &stats.models {
+        let latency = model_stats
+            .average_latency()
+            .map_or("-".to_string(), |latency| format!("{:.2}s", latency.as_secs_f64()));
+        // only some APIs report them
+        let tokens = match (model_stats.prompt_tokens, model_stats.completion_tokens) {
+            (0, 0) => "-".to_string(),
+            (prompt, completion) => format!("{}/{}", prompt, completion),
+        };
+        println!(
+            "{:<width$}  {:>8}  {:>6}  {:>11}  {:>17}",
+            model, model_stats.requests, model_stats.errors, latency, tokens,
+            width = width
+        );
+    }
+}
+
+fn star_command(name: &str, prompt: &str, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut library = snippets::load_snippets()?;
+    snippets::upsert(&mut library, snippets::Snippet {
+        name: name.to_string(),
+        description: prompt.to_string(),
+        command: command.to_string(),
+    });
+    snippets::save_snippets(&library)?;
+    println!("{}", format!("Saved as snippet `{}`.", name).green());
+    Ok(())
+}
+
+fn run_snippet(matches: &clap::ArgMatches, suggest_only: bool, limits: ResourceLimits, use_trash: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let library = snippets::load_snippets()?;
+
+    let Some(name) = matches.get_one::<String>("name") else {
+        if library.is_empty() {
+            println!("{}", "No snippets saved yet. Use --star <NAME> or ⭐ in the GUI to add one.".yellow());
+        }
+        for snippet in &library {
+            println!("{}  {}", snippet.name.cyan().bold(), snippet.description);
+            println!("    {}", snippet.command);
+        }
+        return Ok(());
+    };
+
+    let snippet = library
+        .iter()
+        .find(|s| &s.name == name)
+        .ok_or_else(|| format!("No snippet named `{}`", name))?;
+    let params: Vec<String> = matches
+        .get_many::<String>("params")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    let values = snippets::parse_params(&params)?;
+
+    let command = match snippet.render(&values) {
+        Ok(command) => command,
+        // the missing values are asked for before execution
+        Err(_) if io::stdin().is_terminal() => snippets::fill(&snippet.command, &values),
+        Err(missing) => {
+            let flags: Vec<String> = missing.iter().map(|name| format!("--{} <value>", name)).collect();
+            return Err(format!("Snippet `{}` needs {}", name, flags.join(" ")).into());
+        }
+    };
+
+    println!("{}", command.cyan().bold());
+    if suggest_only {
+        println!("{}", SUGGEST_ONLY_NOTE.yellow());
+        return Ok(());
+    }
+    show_affected_files(&command);
+    println!("{}", "Do you want to execute this command? (y/n)".yellow());
+
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input)?;
+
+    if user_input.trim().to_lowercase() == "y" {
+        execute_command(&command, None, limits, use_trash)?;
+    } else {
+        println!("{}", "Command execution cancelled.".yellow());
+    }
+    Ok(())
+}
+
+/// Runs `command` once the user has been through what it needs asking;
+/// its exit code, `None` if it didn't run or was killed by a signal.
+fn execute_command(command: &str, save_output: Option<&Path>, limits: ResourceLimits, use_trash: bool) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    let executor = Executor::default().with_limits(limits);
+    // as it's recorded in the history
+    let suggested = command;
+    let Some(command) = prepare_command(&executor, command, save_output, use_trash)? else {
+        return Ok(None);
+    };
+
+    // On a terminal, hand it over to the command so prompts (ssh, sudo, npm init) work.
+    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        println!("{}", "Command output:".green().bold());
+        return match executor.run_attached_keeping_stderr(&command) {
+            Ok((status, stderr)) => Ok(note_outcome(suggested, status.code(), &stderr)),
+            Err(e) => {
+                eprintln!("{}", format!("Failed to execute command: {}", e).red());
+                Ok(None)
+            }
+        };
+    }
+
+    let output = executor
+        .spawn(executor.command(&command).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()))
+        .and_then(|child| child.wait_with_output());
+    match output {
+        Ok(output) => {
+            println!("{}", "Command output:".green().bold());
+            write_output(&mut io::stdout(), &output.stdout)?;
+            write_output(&mut io::stderr(), &output.stderr)?;
+            Ok(note_outcome(suggested, output.status.code(), &String::from_utf8_lossy(&output.stderr)))
+        }
+        Err(e) => {
+            eprintln!("{}", format!("Failed to execute command: {}", e).red());
+            Ok(None)
+        }
+    }
+}
+
+/// Goes through everything asked before `command` runs: placeholders, the
+/// project's rules, guards, the trash, backups and elevation. Returns the
+/// command as it should run, or `None` if it shouldn't.
+fn prepare_command(executor: &Executor, command: &str, save_output: Option<&Path>, use_trash: bool) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(command) = fill_placeholders(command)? else {
+        println!("{}", "Command execution cancelled.".yellow());
+        return Ok(None);
+    };
+    let command = command.as_str();
+
+    let project = Project::current().unwrap_or_default();
+    if let Some(forbidden) = project.forbids(command) {
+        println!("{}", format!("Not running it: `{}` is forbidden by {}.", forbidden, project.path.display()).red());
+        return Ok(None);
+    }
+    if let Some(plan) = iac::plan_for(command) {
+        if !show_plan(executor, &plan)? {
+            return Ok(None);
+        }
+    }
+
+    let guards = guard::check(command);
+    // otherwise the warnings name the account
+    if let Some(account) = guards.is_empty().then(|| cloud::account(command)).flatten() {
+        println!("{}", format!("Runs against {}.", account).cyan());
+    }
+    for guard in guards {
+        if !confirm_guard(&guard)? {
+            return Ok(None);
+        }
+    }
+
+    let trashed = use_trash.then(|| trash::rewrite(command, executor.shell())).flatten();
+    if let Some(trashed) = &trashed {
+        println!("{}", format!("Moving to the trash instead (--permanent deletes for good): {}", trashed).yellow());
+    } else if use_trash && trash::deletes(command, executor.shell()) {
+        println!("{}", format!("Not moving to the trash: {}. Pass --permanent to delete for good.", trash::not_rewritten_reason(command, executor.shell())).red());
+        return Ok(None);
+    }
+    let command = trashed.as_deref().unwrap_or(command);
+
+    let targets = std::env::current_dir().map(|dir| backup::targets(command, &dir, false)).unwrap_or_default();
+    if !targets.is_empty() && io::stdin().is_terminal() && !offer_backup(command, &targets)? {
+        return Ok(None);
+    }
+
+    let command = match elevation::detect(command).filter(|_| !elevation::is_elevated()) {
+        Some(needed) => match confirm_elevation(command, needed, executor.shell())? {
+            Some(elevated) => elevated,
+            None => return Ok(None),
+        },
+        None => command.to_string(),
+    };
+    let command = command.as_str();
+
+    let command = match save_output {
+        Some(_) if shell::redirects_output(command) => {
+            println!("{}", "The command already redirects its output; running it unchanged.".yellow());
+            command.to_string()
+        }
+        Some(path) => {
+            println!("{}", format!("Output will be saved to {}", path.display()).yellow());
+            executor.shell().redirect_to_file(command, path)
+        }
+        None => command.to_string(),
+    };
+    Ok(Some(command))
+}
+
+/// Records how `command` ended in the history, and as a step of the
+/// runbook being recorded, if one is.
+fn note_outcome(command: &str, exit_code: Option<i32>, stderr: &str) -> Option<i32> {
+    history::record_outcome(command, exit_code, stderr);
+    if let (Some(code), Some(_)) = (exit_code, runbook::recording()) {
+        let description = history::load().into_iter().rev().find(|entry| entry.command == command).map(|entry| entry.prompt).unwrap_or_default();
+        runbook::record_step(&description, command, code);
+    }
+    exit_code
+}
+
+/// Asks for a value for each `<PORT>` or `{filename}` placeholder left in
+/// `command`, one at a time. `None` if one was left empty.
+fn fill_placeholders(command: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let names = snippets::placeholders(command);
+    if names.is_empty() {
+        return Ok(Some(command.to_string()));
+    }
+
+    let mut values = std::collections::HashMap::new();
+    for name in names {
+        print!("{}", format!("Value for {}: ", name).yellow());
+        io::stdout().flush()?;
+        let mut value = String::new();
+        io::stdin().read_line(&mut value)?;
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        values.insert(name, value.to_string());
+    }
+    let command = snippets::fill(command, &values);
+    println!("{}", command.cyan().bold());
+    Ok(Some(command))
+}
+
+/// Warns that `command` needs administrator rights and asks again. Returns the
+/// command to run, rewritten to elevate itself if needed, or `None` if cancelled.
+fn confirm_elevation(command: &str, needed: elevation::Elevation, shell: &shell::Shell) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    match needed {
+        elevation::Elevation::Requested => println!("{}", "⚠ This command runs with administrator rights.".red().bold()),
+        elevation::Elevation::Required => println!("{}", "⚠ This command needs administrator rights; it will be run with sudo.".red().bold()),
+    }
+    if !io::stdin().is_terminal() && !matches!(shell, shell::Shell::Powershell) {
+        println!("{}", "Run llm-term from a terminal so the password can be asked for.".yellow());
+        return Ok(None);
+    }
+    print!("{}", "Type `yes` to run it elevated: ".yellow());
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "yes" {
+        println!("{}", "Command execution cancelled.".yellow());
+        return Ok(None);
+    }
+    Ok(Some(elevation::for_terminal(command, needed, shell)))
+}
+
+/// Records a prompt from the microphone until Enter is pressed and returns
+/// what was said.
+fn listen(config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+    let recording = voice::start()?;
+    println!("{}", "🎤 Listening... press Enter when you're done.".cyan());
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    println!("{}", "Transcribing...".yellow());
+    let prompt = recording.finish(config)?;
+    println!("{} {}", "You said:".green(), prompt);
+    Ok(prompt)
+}
+
+/// Lists the files the globs in `command` match, so it's clear what the
+/// confirmation that follows is about.
+fn show_affected_files(command: &str) {
+    let Ok(dir) = std::env::current_dir() else {
+        return;
+    };
+    for affected in affected::affected(command, &dir) {
+        println!("{}", format!("`{}` will affect:", affected.program).yellow());
+        for file in &affected.files {
+            println!("  {}", file.display());
+        }
+        if affected.more > 0 {
+            println!("  {}", format!("... and {} more", affected.more).dimmed());
+        }
+    }
+}
+
+/// Offers to back up `targets` before `command` changes them. False if the
+/// backup failed and the user would rather not run it.
+fn offer_backup(command: &str, targets: &[PathBuf]) -> Result<bool, Box<dyn std::error::Error>> {
+    print!("{}", format!("Back up the {} file(s) it changes first? (Y/n) ", targets.len()).yellow());
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().eq_ignore_ascii_case("n") {
+        return Ok(true);
+    }
+
+    match backup::take(command, targets) {
+        Ok(snapshot) => {
+            println!("{}", format!("Backed up; undo with `llm-term restore {}`.", snapshot.id).green());
+            Ok(true)
+        }
+        Err(e) => {
+            print!("{}", format!("The backup failed: {}. Run it anyway? (y/n) ", e).red());
+            io::stdout().flush()?;
+            input.clear();
+            io::stdin().read_line(&mut input)?;
+            if input.trim().to_lowercase() != "y" {
+                println!("{}", "Command execution cancelled.".yellow());
+                return Ok(false);
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Lists the backups, or restores the one with `id`.
+fn restore_backup(id: Option<&String>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(id) = id else {
+        let snapshots = backup::list();
+        if snapshots.is_empty() {
+            println!("{}", "No backups yet.".yellow());
+        }
+        for snapshot in snapshots.iter().rev() {
+            println!("{}  {} file(s)  {}", snapshot.id.cyan().bold(), snapshot.paths.len(), snapshot.command);
+        }
+        return Ok(());
+    };
+    let snapshot = backup::find(id).ok_or_else(|| format!("No backup `{}`; `llm-term restore` lists them", id))?;
+    backup::restore(&snapshot)?;
+    for path in &snapshot.paths {
+        println!("{}", format!("Restored {}", path.display()).green());
+    }
+    Ok(())
+}
+
+/// Runs `plan` before the apply it previews and asks whether to go on;
+/// false if it failed or the user said no.
+fn show_plan(executor: &Executor, plan: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("{}", format!("Previewing the changes first: {}", plan).yellow());
+    let status = if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        executor.run_attached(plan)?
+    } else {
+        let output = executor.spawn(executor.command(plan).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()))?.wait_with_output()?;
+        write_output(&mut io::stdout(), &output.stdout)?;
+        write_output(&mut io::stderr(), &output.stderr)?;
+        output.status
+    };
+    if !status.success() {
+        println!("{}", "The preview failed; not applying.".red());
+        return Ok(false);
+    }
+
+    print!("{}", "Apply these changes? (y/n) ".yellow());
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if input.trim().to_lowercase() != "y" {
+        println!("{}", "Command execution cancelled.".yellow());
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Shows `guard`'s warning and has the user type what it asks for.
+fn confirm_guard(guard: &guard::Guard) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("{}", format!("⚠ {}", guard.warning).red().bold());
+    if !io::stdin().is_terminal() {
+        println!("{}", "Run llm-term from a terminal to confirm it.".yellow());
+        return Ok(false);
+    }
+    print!("{}", format!("Type `{}` to run it: ", guard.confirm).yellow());
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !guard.accepts(&input) {
+        println!("{}", "Command execution cancelled.".yellow());
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Passes captured output through unchanged on a terminal and strips escape
+/// sequences when redirected or when `NO_COLOR` is set.
+fn write_output<W: Write + IsTerminal>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    if out.is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        out.write_all(bytes)
+    } else {
+        out.write_all(ansi::strip(&String::from_utf8_lossy(bytes)).as_bytes())
+    }
+}
@@ -0,0 +1,185 @@
+//! The man pages, or `--help` output, of the programs a generated command
+//! runs, so a second request can check its flags against them: options the
+//! model remembers from another version, or made up, get fixed before the
+//! command is shown.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{shell, Config};
+
+/// Documentation of a program longer than this is cut to the parts about the command's flags.
+const MAX_CHARS: usize = 6000;
+
+/// Lines from the top kept of a cut page: the name and synopsis.
+const HEAD_LINES: usize = 15;
+
+/// `--help` of a program that keeps running instead of printing it is given up on after this.
+const HELP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Words in front of the program that actually runs.
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "time", "nice", "nohup", "command", "exec"];
+
+/// Checks the flags of `command`, generated for `prompt`, against the
+/// documentation of the programs it runs, and returns it as corrected. It's
+/// returned as it is when none of them are documented locally, or when the
+/// check fails: a check that doesn't happen is no reason to lose the command.
+pub fn check(config: &Config, prompt: &str, command: &str) -> String {
+    let documentation = documentation(command);
+    if documentation.is_empty() {
+        return command.to_string();
+    }
+    match config.model.llm_check_flags(config, prompt, command, &documentation) {
+        Ok(Some(checked)) if !checked.trim().is_empty() => {
+            let checked = checked.trim().to_string();
+            if checked != command {
+                log::info!("Flags checked: `{}` became `{}`", command, checked);
+            }
+            checked
+        }
+        Ok(_) => command.to_string(),
+        Err(e) => {
+            log::warn!("Can't check the flags of `{}`: {}", command, e);
+            command.to_string()
+        }
+    }
+}
+
+/// The documentation of each program in `command` found on the PATH, headed
+/// by its name; empty if there's none.
+pub fn documentation(command: &str) -> String {
+    let mut programs: Vec<&str> = Vec::new();
+    let mut flags: Vec<String> = Vec::new();
+    for words in shell::simple_commands(command) {
+        let words: Vec<&str> = words.into_iter().skip_while(|word| WRAPPERS.contains(&shell::program_name(word)) || word.contains('=')).collect();
+        let Some(program) = words.first().map(|word| shell::program_name(word)) else {
+            continue;
+        };
+        if !programs.contains(&program) && shell::on_path(program) {
+            programs.push(program);
+        }
+        for flag in words.iter().filter(|word| word.starts_with('-') && word.len() > 1).map(|flag| flag.split('=').next().unwrap_or(flag)) {
+            flags.push(flag.to_string());
+            // `-la` is `-l` and `-a`
+            if !flag.starts_with("--") && flag.len() > 2 {
+                flags.extend(flag[1..].chars().map(|c| format!("-{}", c)));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for program in programs {
+        let Some(text) = man_page(program).or_else(|| help_output(program)) else {
+            continue;
+        };
+        out.push_str(&format!("--- {}\n{}\n\n", program, excerpt(&text, &flags)));
+    }
+    out.trim_end().to_string()
+}
+
+/// `man program` as plain text, `None` without `man` or a page.
+fn man_page(program: &str) -> Option<String> {
+    if cfg!(target_os = "windows") || !shell::on_path("man") {
+        return None;
+    }
+    let mut man = Command::new("man");
+    man.args(["-P", "cat", program]).env("MANWIDTH", "100").env("MAN_KEEP_FORMATTING", "");
+    let text = run(man)?;
+    Some(strip_overstrikes(&text))
+}
+
+/// What `program --help` prints, on stdout or stderr.
+fn help_output(program: &str) -> Option<String> {
+    let mut help = Command::new(program);
+    help.arg("--help");
+    run(help)
+}
+
+/// The output of `command`, killed after `HELP_TIMEOUT`; `None` if it printed nothing.
+fn run(mut command: Command) -> Option<String> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().ok()?;
+    // read in threads, so a long page can't fill the pipe and stall the program
+    let readers: Vec<_> = [child.stdout.take().map(|out| Box::new(out) as Box<dyn Read + Send>), child.stderr.take().map(|err| Box::new(err) as Box<dyn Read + Send>)]
+        .into_iter()
+        .flatten()
+        .map(|mut pipe| {
+            std::thread::spawn(move || {
+                let mut text = Vec::new();
+                let _ = pipe.read_to_end(&mut text);
+                text
+            })
+        })
+        .collect();
+
+    let deadline = Instant::now() + HELP_TIMEOUT;
+    let succeeded = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status.success(),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    let output: Vec<u8> = readers.into_iter().filter_map(|reader| reader.join().ok()).flatten().collect();
+    let text = String::from_utf8_lossy(&output).trim().to_string();
+    // some programs print their usage and fail on --help; that's still what's wanted
+    (!text.is_empty() && (succeeded || text.to_lowercase().contains("usage"))).then_some(text)
+}
+
+/// `text` without the backspace sequences some `man`s make bold and underlined text with.
+fn strip_overstrikes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\u{8}' {
+            out.pop();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `text` if it's short, otherwise its head and the paragraphs mentioning one of `flags`.
+fn excerpt(text: &str, flags: &[String]) -> String {
+    if text.len() <= MAX_CHARS {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut keep = vec![false; lines.len()];
+    keep.iter_mut().take(HEAD_LINES).for_each(|kept| *kept = true);
+    for (i, line) in lines.iter().enumerate() {
+        let mentions = line.split(|c: char| c.is_whitespace() || c == ',' || c == '=' || c == '[' || c == ']').any(|word| flags.iter().any(|flag| flag == word));
+        if mentions {
+            // the option's line and its description up to the next blank line
+            for (kept, line) in keep[i..].iter_mut().zip(&lines[i..]).take(8) {
+                if line.trim().is_empty() {
+                    break;
+                }
+                *kept = true;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let mut skipped = false;
+    for (line, kept) in lines.iter().zip(keep) {
+        if !kept {
+            skipped = true;
+            continue;
+        }
+        if skipped {
+            out.push_str("...\n");
+            skipped = false;
+        }
+        if out.len() + line.len() > MAX_CHARS {
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
@@ -1,8 +1,70 @@
-use openai_api_rust::{Auth, Message, OpenAI, Role};
-use openai_api_rust::chat::{ChatApi, ChatBody};
 use serde::{Deserialize, Serialize};
-use crate::Config;
-use crate::shell::Shell;
+use std::cell::RefCell;
+use std::time::Instant;
+use crate::provider::{Message, Provider, Role};
+use crate::router::Reply;
+use crate::{context, feedback, postprocess, history, kube, manual, packages, project, rag, recording, stats, tasks, Config, Redactor};
+use crate::shell::{self, Shell};
+
+/// Sampling temperature used for regular requests.
+pub const DEFAULT_TEMPERATURE: f32 = 0.5;
+
+/// Upper bound on the length of a rolling conversation summary.
+const SUMMARY_MAX_TOKENS: i32 = 512;
+
+/// Lets the command prompt ask instead of guessing; see `generator::question`.
+const QUESTION_NOTE: &str = "If the request is ambiguous in a way that changes the command, e.g. \"delete the logs\" without saying which, don't guess: reply with only {\"type\":\"question\",\"question\":\"<one short question>\"} and nothing else.";
+
+/// Enough for a chat title of a few words.
+const TITLE_MAX_TOKENS: i32 = 24;
+
+thread_local! {
+    /// says which fallback model answered the last request on this thread
+    static FALLBACK_NOTE: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// where the answers of requests on this thread go as they arrive, see [`stream_to`]
+    static STREAM: RefCell<Option<Sink>> = const { RefCell::new(None) };
+}
+
+type Sink = Box<dyn FnMut(Streamed)>;
+
+/// What [`stream_to`] hands on while a request is answered.
+pub enum Streamed<'a> {
+    /// a request to a model starts; anything streamed before it, e.g. by a
+    /// model that failed or was escalated from, is given up on
+    Started,
+    /// the next piece of the answer
+    Text(&'a str),
+}
+
+/// Runs `f` with the answers to the requests it makes on this thread handed
+/// to `on_delta` piece by piece, as they arrive.
+pub fn stream_to<T>(on_delta: impl FnMut(Streamed) + 'static, f: impl FnOnce() -> T) -> T {
+    let previous = STREAM.with(|sink| sink.replace(Some(Box::new(on_delta))));
+    let result = f();
+    STREAM.with(|sink| *sink.borrow_mut() = previous);
+    result
+}
+
+/// Which model answered the last request made on this thread, if the
+/// configured one failed and a `fallback` model stepped in.
+pub fn take_fallback_note() -> Option<String> {
+    FALLBACK_NOTE.with(|note| note.borrow_mut().take())
+}
+
+/// A model that didn't answer within `request_timeout_secs`.
+#[derive(Debug)]
+pub struct Timeout {
+    pub model: String,
+    pub secs: u64,
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} didn't answer within {} seconds", self.model, self.secs)
+    }
+}
+
+impl std::error::Error for Timeout {}
 
 #[derive(Clone)]
 pub struct ConversationMessage {
@@ -20,17 +82,94 @@ pub enum Model {
     #[serde(rename = "gpt-4o-mini")]
     OpenAiGpt4oMini,
 
+    /// OpenAI's reasoning models: they think before answering, take
+    /// `max_completion_tokens` and `reasoning_effort`, and no temperature
+    #[serde(rename = "o1")]
+    OpenAiO1,
+
+    #[serde(rename = "o3-mini")]
+    OpenAiO3Mini,
+
+    #[serde(rename = "o4-mini")]
+    OpenAiO4Mini,
+
     #[serde(rename = "ollama")]
     Ollama(String),
+
+    /// canned answers from a fixture file, for tests and demos without a network
+    #[serde(rename = "mock")]
+    Mock(String),
 }
 
-impl Model {
-    pub fn llm_get_response(&self, config: &Config, user_prompt: &str, _is_command_mode: bool, conversation_history: &[ConversationMessage]) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let model_name = self.get_model_name();
-        let auth = self.get_auth();
-        let client = OpenAI::new(auth, self.get_openai_endpoint().as_str());
+impl std::str::FromStr for Model {
+    type Err = String;
+
+    /// Parses the names used on the command line: `gpt-4o`, `gpt-4o-mini`,
+    /// `o1`, `o3-mini`, `o4-mini`, `ollama`, `ollama:<model>` or `mock:<fixture file>`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim() {
+            "gpt-4o" => Ok(Model::OpenAiGpt4o),
+            "gpt-4o-mini" => Ok(Model::OpenAiGpt4oMini),
+            "o1" => Ok(Model::OpenAiO1),
+            "o3-mini" => Ok(Model::OpenAiO3Mini),
+            "o4-mini" => Ok(Model::OpenAiO4Mini),
+            "ollama" => Ok(Model::Ollama("llama3.1".to_string())),
+            other => match (other.strip_prefix("ollama:"), other.strip_prefix("mock:")) {
+                (Some(model), _) if !model.is_empty() => Ok(Model::Ollama(model.to_string())),
+                (_, Some(fixture)) if !fixture.is_empty() => Ok(Model::Mock(fixture.to_string())),
+                _ => Err(format!("Unknown model `{}`; use gpt-4o, gpt-4o-mini, o1, o3-mini, o4-mini, ollama:<model> or mock:<fixture file>", other)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Model::Ollama(model) => write!(f, "ollama:{}", model),
+            Model::Mock(fixture) => write!(f, "mock:{}", fixture),
+            other => write!(f, "{}", other.get_model_name()),
+        }
+    }
+}
 
-        let system_prompt = self.get_unified_conversational_prompt();
+impl Model {
+    /// The chat answer to `user_prompt`. `instructions` are the chat's own,
+    /// added after the unified prompt, which still says how to suggest commands.
+    pub fn llm_get_response(&self, config: &Config, user_prompt: &str, _is_command_mode: bool, conversation_history: &[ConversationMessage], temperature: f32, instructions: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let unified = self.get_unified_conversational_prompt();
+        // older chats kept an edited copy of the whole unified prompt
+        let instructions = instructions.map(|text| text.strip_prefix(unified.as_str()).unwrap_or(text).trim()).filter(|text| !text.is_empty());
+        let system_prompt = match instructions {
+            Some(instructions) => format!("{}\n\nInstructions for this chat:\n{}", unified, instructions),
+            None => unified,
+        };
+        let system_prompt = match &config.language {
+            Some(language) => format!(
+                "{}\n\nWrite your replies and explanations in {}. Keep commands, flags, paths and the COMMAND: and EXECUTE_LAST_COMMAND markers in their original form, untranslated.",
+                system_prompt, language
+            ),
+            None => system_prompt,
+        };
+        let system_prompt = format!(
+            "{}\n\n{}\n\n{}\n\n{}\n\n{}\n\n{}\n\n{}\n\n{}",
+            system_prompt,
+            packages::prompt_note(config.package_manager.as_deref()),
+            project::prompt_note(),
+            tasks::prompt_note(),
+            kube::prompt_note(),
+            history::prompt_note(user_prompt, config.few_shot_examples),
+            feedback::prompt_note(user_prompt),
+            rag::prompt_note(config, user_prompt)
+        );
+        let system_prompt = if config.suggest_only {
+            format!(
+                "{}\n\nThis app never runs commands; the user copies and runs them. Don't offer to execute anything and never reply with EXECUTE_LAST_COMMAND.",
+                system_prompt
+            )
+        } else {
+            system_prompt
+        };
 
         // Build message history
         let mut messages = vec![
@@ -51,95 +190,426 @@ impl Model {
             content: user_prompt.to_string() 
         });
 
-        let body = ChatBody {
-            model: model_name,
-            max_tokens: Some(config.max_tokens),
-            temperature: Some(0.5),
-            top_p: None,
-            n: None,
-            stream: None,
-            stop: None,
-            presence_penalty: None,
-            frequency_penalty: None,
-            logit_bias: None,
-            user: None,
-            messages,
-        };
+        self.routed_completion(config, config.max_tokens, temperature, messages, Reply::Conversation)
+    }
 
-        match client.chat_completion_create(&body) {
-            Ok(response) => Ok(response.choices.first()
-                .map(|choice| choice.message.as_ref())
-                .flatten()
-                .map(|message| message.content.clone())
-            ),
-            Err(e) => Err(format!("Error: {:?}", e).into()),
+    /// A command for `user_prompt`; with `check_flags` set, its flags are
+    /// checked against the local documentation in a second request. When the
+    /// request is ambiguous the reply is a question instead, as
+    /// `{"type":"question","question":"..."}`.
+    pub fn llm_get_command(&self, config: &Config, user_prompt: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let system_prompt = format!("{}\n{}", self.command_system_prompt(config, user_prompt), QUESTION_NOTE);
+
+        let command = self.routed_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, vec![
+            Message { role: Role::System, content: system_prompt.to_string() },
+            Message { role: Role::User, content: user_prompt.to_string() }
+        ], Reply::Command)?;
+        Ok(self.checked(config, user_prompt, command))
+    }
+
+    /// The command for `user_prompt` once the user answered the `question`
+    /// the model asked about it.
+    pub fn llm_get_command_with_answer(&self, config: &Config, user_prompt: &str, question: &str, answer: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let command = self.routed_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, vec![
+            Message { role: Role::System, content: self.command_system_prompt(config, user_prompt) },
+            Message { role: Role::User, content: user_prompt.to_string() },
+            Message { role: Role::Assistant, content: serde_json::json!({ "type": "question", "question": question }).to_string() },
+            Message { role: Role::User, content: format!("{}\n\nReply with the command now.", answer) }
+        ], Reply::Command)?;
+        Ok(self.checked(config, user_prompt, command))
+    }
+
+    fn command_system_prompt(&self, config: &Config, user_prompt: &str) -> String {
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.get_system_prompt(&Shell::detect()),
+            packages::prompt_note(config.package_manager.as_deref()),
+            project::prompt_note(),
+            tasks::prompt_note(),
+            kube::prompt_note(),
+            history::prompt_note(user_prompt, config.few_shot_examples),
+            feedback::prompt_note(user_prompt),
+            rag::prompt_note(config, user_prompt)
+        )
+    }
+
+    /// `command` with its flags checked, if `check_flags` is set.
+    fn checked(&self, config: &Config, user_prompt: &str, command: Option<String>) -> Option<String> {
+        match command {
+            Some(command) if config.check_flags && !command.trim().is_empty() && !command.trim_start().starts_with('{') => Some(manual::check(config, user_prompt, command.trim())),
+            command => command,
         }
     }
 
-    pub fn llm_get_command(&self, config: &Config, user_prompt: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let model_name = self.get_model_name();
-        let auth = self.get_auth();
-        let client = OpenAI::new(auth, self.get_openai_endpoint().as_str());
+    /// Asks for a modified version of `previous_command`, replaying the original
+    /// exchange so `refinement` only has to describe the change.
+    pub fn llm_refine_command(&self, config: &Config, previous_prompt: &str, previous_command: &str, refinement: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let shell = Shell::detect();
+        let system_prompt = format!("{}\n{}\n{}\n{}\n{}", self.get_system_prompt(&shell), packages::prompt_note(config.package_manager.as_deref()), project::prompt_note(), tasks::prompt_note(), kube::prompt_note());
+
+        self.routed_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, vec![
+            Message { role: Role::System, content: system_prompt.to_string() },
+            Message { role: Role::User, content: previous_prompt.to_string() },
+            Message { role: Role::Assistant, content: previous_command.to_string() },
+            Message {
+                role: Role::User,
+                content: format!("Modify the previous command: {}. Extend it with a pipe if that's simplest. Reply with the complete new command only.", refinement),
+            }
+        ], Reply::Command)
+    }
+
+    /// Asks whether the flags of `command`, generated for `prompt`, exist as
+    /// `documentation` describes them; the reply is the command, fixed if they don't.
+    pub fn llm_check_flags(&self, config: &Config, prompt: &str, command: &str, documentation: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let shell = Shell::detect();
+        let system_prompt = format!(
+            "{}\n\nYou are checking a command generated for the request below against the documentation of the programs it runs, as installed on this machine. Fix any option that doesn't exist or is used wrongly there, keeping the command otherwise as it is. Reply with the complete command only, unchanged if it's right.",
+            self.get_system_prompt(&shell)
+        );
+        let request = format!("Request: {}\n\nCommand: {}\n\nDocumentation:\n{}", prompt, command, documentation);
+
+        self.chat_completion(config, config.max_tokens, 0.0, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request }
+        ])
+    }
+
+    /// Asks why `command`, run for `prompt`, failed and what to run instead.
+    /// The reply explains first and ends with a `COMMAND: ` line, unless
+    /// there's nothing to run.
+    pub fn llm_fix_failed_command(&self, config: &Config, prompt: &str, command: &str, exit_code: Option<i32>, stderr: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let shell = Shell::detect();
+        let system_prompt = format!(
+            "{}\n\nA shell command the user ran with {} on {} failed. In a sentence or two, say why. Then give the one command to run instead on a last line of its own, starting with `COMMAND: `, without backticks or formatting. Leave that line out if no command would help.\n{}\n{}\n{}",
+            self.get_conversational_prompt(),
+            shell.to_shell_command_and_command_arg().0,
+            std::env::consts::OS,
+            shell.prompt_note(),
+            packages::prompt_note(config.package_manager.as_deref()),
+            project::prompt_note()
+        );
+        let exit_code = exit_code.map_or("unknown (killed by a signal?)".to_string(), |code| code.to_string());
+        let request = format!("Request: {}\nCommand: {}\nExit code: {}\nstderr:\n{}", prompt, command, exit_code, stderr.trim());
+
+        self.chat_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request }
+        ])
+    }
+
+    /// Talks `request`, which was too unclear for a command, through with the
+    /// user: a question while it needs one answered, then the command on a
+    /// last `COMMAND: ` line.
+    pub fn llm_clarify_request(&self, config: &Config, request: &str, conversation: &[ConversationMessage]) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let shell = Shell::detect();
+        let system_prompt = format!(
+            "{}\n\nThe user wants a shell command for {} on {}, but the request wasn't clear enough to write one. Ask the one question whose answer you need most, briefly, and nothing else. Once you know enough, reply with the command on a last line of its own, starting with `COMMAND: `, without backticks or formatting.\n{}\n{}\n{}",
+            self.get_conversational_prompt(),
+            shell.to_shell_command_and_command_arg().0,
+            std::env::consts::OS,
+            shell.prompt_note(),
+            packages::prompt_note(config.package_manager.as_deref()),
+            project::prompt_note()
+        );
+        let mut messages = vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request.to_string() },
+        ];
+        messages.extend(conversation.iter().map(|msg| Message { role: if msg.is_user { Role::User } else { Role::Assistant }, content: msg.content.clone() }));
+
+        self.chat_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, messages)
+    }
 
+    /// A command checking what `request` wants to be alerted about and the
+    /// condition on its output, as `CHECK:` and `CONDITION:` lines.
+    pub fn llm_plan_watch(&self, config: &Config, request: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
         let shell = Shell::detect();
-        let system_prompt = self.get_system_prompt(&shell);
-
-        let body = ChatBody {
-            model: model_name,
-            max_tokens: Some(config.max_tokens),
-            temperature: Some(0.5),
-            top_p: None,
-            n: None,
-            stream: None,
-            stop: None,
-            presence_penalty: None,
-            frequency_penalty: None,
-            logit_bias: None,
-            user: None,
-            messages: vec![
-                Message { role: Role::System, content: system_prompt.to_string() },
-                Message { role: Role::User, content: user_prompt.to_string() }
-            ],
+        let system_prompt = format!(
+            "You turn a request to be alerted about something into a check that runs with {} on {}. Reply with exactly two lines and nothing else:\n\
+            CHECK: <a command that prints the value to watch, preferably a single number, e.g. the disk usage of / in percent without the % sign>\n\
+            CONDITION: <when to alert: one of > >= < <= == != followed by a number, compared with the first number the check prints; or contains \"text\" or not contains \"text\" for its output>\n\
+            The check runs unattended and repeatedly, so it must only read, never change anything, and never ask for input.\n{}\n{}",
+            shell.to_shell_command_and_command_arg().0,
+            std::env::consts::OS,
+            shell.prompt_note(),
+            packages::prompt_note(config.package_manager.as_deref())
+        );
+
+        self.chat_completion(config, config.max_tokens, 0.0, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request.to_string() }
+        ])
+    }
+
+    /// The ordered steps for a request that takes several commands, each as
+    /// `STEP:`, `COMMAND:` and `ROLLBACK:` lines.
+    pub fn llm_plan_steps(&self, config: &Config, request: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let shell = Shell::detect();
+        let system_prompt = format!(
+            "You turn a request that takes several shell commands into an ordered plan for {} on {}. For every step, reply with these four lines and nothing else:\n\
+            STEP: <what the step does, in a few words>\n\
+            COMMAND: <the one command for the step>\n\
+            ROLLBACK: <the command that undoes it, e.g. restoring a copy the plan made in an earlier step; none if it changes nothing>\n\
+            PARALLEL: <yes if it neither needs nor affects the steps next to it that also say yes, e.g. checks of different hosts or separate downloads; no otherwise>\n\
+            Make the steps small, so a failure leaves little to undo, and back up anything a step overwrites or deletes in a step before it.\n{}\n{}\n{}",
+            shell.to_shell_command_and_command_arg().0,
+            std::env::consts::OS,
+            shell.prompt_note(),
+            packages::prompt_note(config.package_manager.as_deref()),
+            project::prompt_note()
+        );
+
+        self.chat_completion(config, config.max_tokens, 0.0, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request.to_string() }
+        ])
+    }
+
+    /// When and what to run for a request like "run backup.sh every weekday
+    /// at 6pm", as `SCHEDULE:`, `COMMAND:` and `EXPLANATION:` lines for
+    /// crontab, or for schtasks on Windows.
+    pub fn llm_schedule_entry(&self, config: &Config, request: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let schedule_format = if cfg!(target_os = "windows") {
+            "the schtasks /SC, /MO, /D, /ST and /SD options, e.g. /SC WEEKLY /D MON,TUE,WED,THU,FRI /ST 18:00"
+        } else {
+            "the five crontab time fields, e.g. 0 18 * * 1-5"
         };
+        let cwd = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+        let system_prompt = format!(
+            "You turn a request to run something regularly into an entry for the {} scheduler on {}. Reply with exactly three lines and nothing else:\n\
+            SCHEDULE: <{}>\n\
+            COMMAND: <the command to run; use absolute paths, the scheduler doesn't start in {}, the user's current directory>\n\
+            EXPLANATION: <when it runs, in one short sentence, e.g. \"At 18:00 on Monday to Friday.\">",
+            if cfg!(target_os = "windows") { "Task Scheduler" } else { "cron" },
+            std::env::consts::OS,
+            schedule_format,
+            cwd
+        );
+
+        self.chat_completion(config, config.max_tokens, 0.0, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: request.to_string() }
+        ])
+    }
+
+    /// Explains in a few sentences what `command` does, including anything destructive.
+    pub fn llm_explain_command(&self, config: &Config, command: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let system_prompt = format!(
+            "{}\n\nExplain, in plain text and at most a few sentences, what the shell command you are given does on {}. Mention anything it deletes, overwrites or changes.",
+            self.get_conversational_prompt(),
+            std::env::consts::OS
+        );
 
-        match client.chat_completion_create(&body) {
-            Ok(response) => Ok(response.choices.first()
-                .map(|choice| choice.message.as_ref())
-                .flatten()
-                .map(|message| message.content.clone())
+        self.chat_completion(config, config.max_tokens, DEFAULT_TEMPERATURE, vec![
+            Message { role: Role::System, content: system_prompt },
+            Message { role: Role::User, content: command.to_string() }
+        ])
+    }
+
+    /// Condenses `messages` (plus any earlier summary) into a short summary
+    /// that can stand in for them in later requests.
+    pub fn llm_summarize(&self, config: &Config, previous_summary: Option<&str>, messages: &[ConversationMessage]) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let mut transcript = String::new();
+        if let Some(summary) = previous_summary {
+            transcript.push_str(&format!("Summary so far: {}\n\n", summary));
+        }
+        for msg in messages {
+            let speaker = if msg.is_user { "User" } else { "Assistant" };
+            transcript.push_str(&format!("{}: {}\n", speaker, msg.content));
+        }
+
+        self.chat_completion(config, config.max_tokens.min(SUMMARY_MAX_TOKENS), 0.2, vec![
+            Message { role: Role::System, content: self.get_summary_prompt() },
+            Message { role: Role::User, content: transcript }
+        ])
+    }
+
+    /// A title of a few words for a chat that started with `prompt` and `answer`.
+    pub fn llm_title(&self, config: &Config, prompt: &str, answer: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let transcript = format!("User: {}\nAssistant: {}", prompt, answer);
+        self.chat_completion(config, TITLE_MAX_TOKENS, 0.2, vec![
+            Message { role: Role::System, content: "Give the following conversation a concise title of 4 to 6 words. Reply with the title only: no quotes, no punctuation at the end.".to_string() },
+            Message { role: Role::User, content: transcript }
+        ])
+    }
+
+    /// Goes through the `routing` models if they're configured, and to this
+    /// model otherwise. If that fails, the `fallback` models are tried in order.
+    fn routed_completion(&self, config: &Config, max_tokens: i32, temperature: f32, messages: Vec<Message>, reply: Reply) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let answer = self.routed_completion_raw(config, max_tokens, temperature, messages, reply)?;
+        Ok(match reply {
+            Reply::Command => answer.map(|answer| postprocess::apply(&config.post_process, &answer)),
+            Reply::Conversation => answer,
+        })
+    }
+
+    fn routed_completion_raw(&self, config: &Config, max_tokens: i32, temperature: f32, messages: Vec<Message>, reply: Reply) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let stop = reply.stop_sequences(config);
+        FALLBACK_NOTE.with(|note| note.borrow_mut().take());
+        let (primary, mut failed) = match &config.routing {
+            Some(routing) => (
+                routing.complete(config, max_tokens, temperature, messages.clone(), reply),
+                vec![routing.first.to_string(), routing.escalate_to.to_string()],
             ),
-            Err(e) => Err(format!("Error: {:?}", e).into()),
+            None => (self.chat_completion_until(config, max_tokens, temperature, messages.clone(), stop), vec![self.to_string()]),
+        };
+        let Err(mut error) = primary else {
+            return primary;
+        };
+
+        for model in &config.fallback {
+            match model.chat_completion_until(config, max_tokens, temperature, messages.clone(), stop) {
+                Ok(answer) => {
+                    let note = format!("{} failed; answered by {}", failed.join(", "), model);
+                    log::info!("fallback {}", note);
+                    FALLBACK_NOTE.with(|slot| *slot.borrow_mut() = Some(note));
+                    return Ok(answer);
+                }
+                Err(e) => {
+                    failed.push(model.to_string());
+                    error = e;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    pub(crate) fn chat_completion(&self, config: &Config, max_tokens: i32, temperature: f32, messages: Vec<Message>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.chat_completion_until(config, max_tokens, temperature, messages, &[])
+    }
+
+    /// Like [`chat_completion`](Self::chat_completion), with the answer
+    /// stopped at the first of the `stop` sequences.
+    pub(crate) fn chat_completion_until(
+        &self,
+        config: &Config,
+        max_tokens: i32,
+        temperature: f32,
+        mut messages: Vec<Message>,
+        stop: &[String],
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(redactor) = Redactor::from_config(config)? {
+            for message in &mut messages {
+                message.content = redactor.redact(&message.content).0;
+            }
+        }
+
+        // an opaque error from the API otherwise
+        let dropped = context::fit_to_window(&mut messages, self.context_window_in(config), max_tokens.max(0) as usize).map_err(|e| format!("Not sent to {}: {}", self, e))?;
+        if dropped > 0 {
+            log::info!("dropped the {} oldest messages to fit {}'s context window", dropped, self);
+        }
+
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!("request model={} max_tokens={} temperature={} messages={}", self, max_tokens, temperature, serde_json::to_string(&messages).unwrap_or_default());
         }
+        if let Some(replayed) = recording::replayed(&self.to_string(), &messages) {
+            log::info!("replayed model={} ok={}", self, replayed.is_ok());
+            return replayed;
+        }
+
+        let started = Instant::now();
+        let response = Provider::for_model(config, self).and_then(|provider| match STREAM.with(RefCell::take) {
+            Some(mut sink) => {
+                sink(Streamed::Started);
+                let response = provider.chat_stream(config, self, &messages, max_tokens, temperature, stop, &mut |delta| sink(Streamed::Text(delta)));
+                STREAM.with(|slot| *slot.borrow_mut() = Some(sink));
+                response
+            }
+            None => provider.chat(config, self, &messages, max_tokens, temperature, stop),
+        });
+        stats::record_request(&self.to_string(), started.elapsed(), response.is_ok());
+        match &response {
+            Ok(answer) => log::info!("answer model={} secs={:.2} chars={}", self, started.elapsed().as_secs_f64(), answer.as_ref().map_or(0, String::len)),
+            Err(e) => log::warn!("request failed model={} secs={:.2} error={}", self, started.elapsed().as_secs_f64(), e),
+        }
+        if let Ok(Some(answer)) = &response {
+            log::debug!("reply model={} content={:?}", self, answer);
+        }
+        recording::note(&self.to_string(), &messages, &response);
+        response
     }
 
-    fn get_model_name(&self) -> String {
+    /// Approximate context window of the model, in tokens.
+    pub fn context_window(&self) -> usize {
         match self {
-            Model::OpenAiGpt4o => "gpt-4o".to_string(),
-            Model::OpenAiGpt4oMini => "gpt-4o-mini".to_string(),
-            Model::Ollama(model_name) => model_name.to_string(),
+            Model::OpenAiGpt4o => 128_000,
+            Model::OpenAiGpt4oMini => 128_000,
+            Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => 200_000,
+            // Ollama's default num_ctx is much smaller than what most models support
+            Model::Ollama(_) => 8_192,
+            Model::Mock(_) => 128_000,
+        }
+    }
+
+    /// The context window as `config` sets it up: `ollama.num_ctx` for
+    /// Ollama over its own API, [`context_window`](Self::context_window) otherwise.
+    pub fn context_window_in(&self, config: &Config) -> usize {
+        match (self, config.ollama.num_ctx) {
+            (Model::Ollama(_), Some(num_ctx)) if config.ollama.native_api => num_ctx as usize,
+            _ => self.context_window(),
         }
     }
 
-    fn get_openai_endpoint(&self) -> String {
+    /// The longest answer the model's API accepts as `max_tokens`, if it caps it.
+    pub fn max_output_tokens(&self) -> Option<usize> {
         match self {
-            Model::OpenAiGpt4o => "https://api.openai.com/v1/".to_string(),
-            Model::OpenAiGpt4oMini => "https://api.openai.com/v1/".to_string(),
-            Model::Ollama(_) => "http://localhost:11434/v1/".to_string(),
+            Model::OpenAiGpt4o | Model::OpenAiGpt4oMini => Some(16_384),
+            // the reasoning counts towards it
+            Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => Some(100_000),
+            Model::Ollama(_) | Model::Mock(_) => None,
         }
     }
 
-    fn get_auth(&self) -> Auth {
+    /// Whether it's one of OpenAI's reasoning models, which take other parameters.
+    pub fn is_reasoning(&self) -> bool {
+        matches!(self, Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini)
+    }
+
+    /// USD per million (input, output) tokens; local models are free.
+    pub fn price_per_million_tokens(&self) -> (f64, f64) {
         match self {
-            Model::OpenAiGpt4o => Auth::from_env().expect("OPENAI_API_KEY environment variable not set"),
-            Model::OpenAiGpt4oMini => Auth::from_env().expect("OPENAI_API_KEY environment variable not set"),
-            Model::Ollama(_) => Auth::new("ollama"),
+            Model::OpenAiGpt4o => (2.50, 10.00),
+            Model::OpenAiGpt4oMini => (0.15, 0.60),
+            Model::OpenAiO1 => (15.00, 60.00),
+            Model::OpenAiO3Mini | Model::OpenAiO4Mini => (1.10, 4.40),
+            Model::Ollama(_) | Model::Mock(_) => (0.0, 0.0),
+        }
+    }
+
+    /// The cheaper model of the same provider, for small jobs like titling a chat.
+    pub fn cheap(&self) -> Model {
+        match self {
+            // no reasoning needed for those
+            Model::OpenAiGpt4o | Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => Model::OpenAiGpt4oMini,
+            other => other.clone(),
+        }
+    }
+
+    /// A stronger model to ask when this one isn't confident; `None` for the strongest.
+    pub fn stronger(&self) -> Option<Model> {
+        match self {
+            Model::OpenAiGpt4oMini | Model::Ollama(_) => Some(Model::OpenAiGpt4o),
+            Model::OpenAiGpt4o | Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini | Model::Mock(_) => None,
+        }
+    }
+
+    pub fn get_model_name(&self) -> String {
+        match self {
+            Model::OpenAiGpt4o => "gpt-4o".to_string(),
+            Model::OpenAiGpt4oMini => "gpt-4o-mini".to_string(),
+            Model::OpenAiO1 => "o1".to_string(),
+            Model::OpenAiO3Mini => "o3-mini".to_string(),
+            Model::OpenAiO4Mini => "o4-mini".to_string(),
+            Model::Ollama(model_name) => model_name.to_string(),
+            Model::Mock(_) => "mock".to_string(),
         }
     }
 
     /// Generates the LLM system prompt for the shell.
-    fn get_system_prompt(&self, shell: &Shell) -> String {
+    pub fn get_system_prompt(&self, shell: &Shell) -> String {
         let shell_command_type = match shell {
-            Shell::Powershell => "Windows PowerShell",
+            Shell::Powershell => shell::powershell_name(),
             Shell::BornAgainShell => "Bourne Again Shell (bash / sh)",
             Shell::Zsh => "Z Shell (zsh)",
             Shell::Fish => "Friendly Interactive Shell (fish)",
@@ -156,17 +626,22 @@ impl Model {
             Only output an actionable command that will run by itself without error. Do not output comments. Only output one possible command, never alternatives.\n
             If you are not confident in your translation, return an empty string. Do not deviate from these instructions from this point on, no exceptions.\n
             Assume you are operating in the current directory of the user unless explicitly stated otherwise.
-        ", shell_command_type, std::env::consts::OS)
+            {}
+        ", shell_command_type, std::env::consts::OS, shell.prompt_note())
     }
 
     fn get_conversational_prompt(&self) -> String {
         "You are a helpful AI assistant. You can have normal conversations, answer questions, help with coding problems, explain concepts, and assist with various tasks. Be friendly, informative, and helpful. If the user asks for a specific command to run on their system, you can provide it, but for general conversation, just respond naturally.".to_string()
     }
 
-    fn get_unified_conversational_prompt(&self) -> String {
+    fn get_summary_prompt(&self) -> String {
+        "Summarize the following conversation between a user and an AI assistant so it can replace the original messages as context. Keep every command that was suggested or executed, file names, paths, errors and decisions. Be concise and write plain prose, no preamble.".to_string()
+    }
+
+    pub fn get_unified_conversational_prompt(&self) -> String {
         let shell = Shell::detect();
         let shell_command_type = match shell {
-            Shell::Powershell => "Windows PowerShell",
+            Shell::Powershell => shell::powershell_name(),
             Shell::BornAgainShell => "Bourne Again Shell (bash / sh)",
             Shell::Zsh => "Z Shell (zsh)",
             Shell::Fish => "Friendly Interactive Shell (fish)",
@@ -182,10 +657,11 @@ impl Model {
             1. Respond conversationally (e.g., 'Sure, I can help you list the files in that directory')\n\
             2. Provide the exact command in this format: `COMMAND: <actual_command_here>`\n\
             3. Ask if they want you to execute it (e.g., 'Would you like me to execute this command?')\n\n\
+            When the task needs several lines (loops, conditionals, several dependent steps), write a script instead of a one-liner: put `SCRIPT:` on its own line, followed by a fenced code block whose info string names the language (e.g. ```bash or ```python), and ask if they want to run it.\n\n\
             When the user responds with 'yes', 'y', 'sure', 'go ahead', or similar affirmative responses after you've suggested a command, respond with: `EXECUTE_LAST_COMMAND`\n\n\
             For general conversation, just respond naturally without any special formatting.\n\n\
-            Important: Commands should be {}-compatible and work on {}. Only suggest safe, actionable commands.",
-            std::env::consts::OS, shell_command_type, shell_command_type, std::env::consts::OS
+            Important: Commands should be {}-compatible and work on {}. Only suggest safe, actionable commands. {}",
+            std::env::consts::OS, shell_command_type, shell_command_type, std::env::consts::OS, shell.prompt_note()
         )
     }
 
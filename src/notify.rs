@@ -0,0 +1,16 @@
+/// Longest notification body; the rest of the text is cut off.
+const MAX_BODY_CHARS: usize = 200;
+
+/// Shows a desktop notification. Failures (no notification daemon) are ignored.
+pub fn send(summary: &str, body: &str) {
+    let mut text: String = body.trim().chars().take(MAX_BODY_CHARS).collect();
+    if text.len() < body.trim().len() {
+        text.push('…');
+    }
+
+    let _ = notify_rust::Notification::new()
+        .appname("llm-term")
+        .summary(summary)
+        .body(&text)
+        .show();
+}
@@ -0,0 +1,29 @@
+//! Finding the package managers installed on this machine, so install
+//! commands use one that's there.
+
+use std::sync::OnceLock;
+
+use crate::shell;
+
+/// Package managers looked for on `PATH`, in the order they're preferred.
+pub const PACKAGE_MANAGERS: &[&str] = &["apt", "dnf", "yum", "pacman", "zypper", "apk", "brew", "port", "winget", "scoop", "choco"];
+
+/// The [`PACKAGE_MANAGERS`] found on `PATH`, looked up once per process.
+pub fn available() -> &'static [&'static str] {
+    static AVAILABLE: OnceLock<Vec<&'static str>> = OnceLock::new();
+    AVAILABLE.get_or_init(|| PACKAGE_MANAGERS.iter().copied().filter(|manager| shell::on_path(manager)).collect())
+}
+
+/// Tells the model which package managers it may use, favouring `preferred`
+/// (the `package_manager` setting) when set. Empty if none were found.
+pub fn prompt_note(preferred: Option<&str>) -> String {
+    let available = available();
+    let Some(default) = preferred.or_else(|| available.first().copied()) else {
+        return String::new();
+    };
+    let installed = if available.is_empty() { default.to_string() } else { available.join(", ") };
+    format!(
+        "Package managers installed: {}. Install software with {} unless the user asks for another one.",
+        installed, default
+    )
+}
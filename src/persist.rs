@@ -0,0 +1,65 @@
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::vault;
+
+/// Replaces `path` with `contents` without ever leaving a half written file:
+/// the data goes to a temporary file next to it first, which is then renamed.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+/// `path.1` is the newest backup, `path.<keep>` the oldest.
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}", n));
+    PathBuf::from(backup)
+}
+
+/// Backups of `path` that exist, newest first.
+pub fn backups(path: &Path, keep: usize) -> impl Iterator<Item = PathBuf> + '_ {
+    (1..=keep).map(move |n| backup_path(path, n)).filter(|backup| backup.exists())
+}
+
+/// Parses the (possibly encrypted) JSON in `path`, falling back to the newest
+/// of its backups that still parses if the file is damaged. `None` if nothing is readable.
+pub fn read_json_with_backups<T: DeserializeOwned>(path: &Path, keep: usize) -> Option<T> {
+    std::iter::once(path.to_path_buf())
+        .chain(backups(path, keep))
+        .find_map(|candidate| serde_json::from_str(&vault::open_to_string(&fs::read(candidate).ok()?).ok()?).ok())
+}
+
+/// Copies `path` to `path.1`, shifting older backups up to `path.<keep>`,
+/// unless the newest backup is younger than `interval`.
+pub fn rotate_backups(path: &Path, keep: usize, interval: Duration) -> io::Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    let newest = backup_path(path, 1);
+    let modified = fs::metadata(&newest).and_then(|meta| meta.modified());
+    if let Ok(modified) = modified {
+        if SystemTime::now().duration_since(modified).unwrap_or_default() < interval {
+            return Ok(());
+        }
+    }
+
+    for n in (1..keep).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    // a fresh write so the backup's mtime says when it was taken
+    fs::write(newest, fs::read(path)?)
+}
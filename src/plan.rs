@@ -0,0 +1,105 @@
+//! `llm-term plan`: requests that take several commands, like "rotate these
+//! certs", become an ordered list of steps, each with the command that
+//! undoes it, so the CLI can roll back what ran when a later step fails.
+//! Steps marked as independent of each other, like checks of several hosts,
+//! can run at the same time.
+
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{Config, Execution, Executor};
+
+/// How many steps run at the same time unless asked otherwise.
+pub const DEFAULT_PARALLEL: usize = 4;
+
+/// One step of a [`Plan`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub description: String,
+    pub command: String,
+    /// undoes `command`; `None` for steps that change nothing, or can't be undone
+    pub rollback: Option<String>,
+    /// can run at the same time as the independent steps next to it
+    pub parallel: bool,
+}
+
+/// The steps the model came up with for a request, in the order they run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+/// Asks the model for the steps behind `request`.
+pub fn draft(config: &Config, request: &str) -> Result<Plan, Box<dyn std::error::Error>> {
+    let reply = config.model.llm_plan_steps(config, request)?.unwrap_or_default();
+    let plan = parse(&reply)?;
+    if plan.steps.is_empty() {
+        return Err(format!("The model didn't come up with a plan for that: {}", reply.trim()).into());
+    }
+    Ok(plan)
+}
+
+impl Plan {
+    /// The steps in the order they run, as ranges of `steps`: a run of
+    /// parallel steps is one range, every other step a range of its own.
+    pub fn batches(&self) -> Vec<Range<usize>> {
+        let mut batches: Vec<Range<usize>> = Vec::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            match batches.last_mut() {
+                Some(batch) if step.parallel && self.steps[batch.start].parallel => batch.end = i + 1,
+                _ => batches.push(i..i + 1),
+            }
+        }
+        batches
+    }
+}
+
+/// Runs `commands` with at most `max_parallel` of them at a time, capturing
+/// their output. The results are in the order of `commands`.
+pub fn run_concurrently(executor: &Executor, commands: &[String], max_parallel: usize) -> Vec<io::Result<Execution>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, io::Result<Execution>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..max_parallel.clamp(1, commands.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut ran = Vec::new();
+                    // each worker takes the next command nobody has taken
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(command) = commands.get(i) else {
+                            return ran;
+                        };
+                        ran.push((i, executor.run(command)));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Reads `STEP:`, `COMMAND:`, `ROLLBACK:` and `PARALLEL:` lines; anything else is ignored.
+pub fn parse(reply: &str) -> Result<Plan, String> {
+    let mut plan = Plan::default();
+    let mut description: Option<String> = None;
+    for line in reply.lines() {
+        let line = line.trim().trim_start_matches(['-', '*']).trim();
+        let field = |name: &str| line.strip_prefix(name).map(|value| value.trim().trim_matches('`').trim().to_string());
+        if let Some(value) = field("STEP:") {
+            description = Some(value);
+        } else if let Some(command) = field("COMMAND:").filter(|command| !command.is_empty()) {
+            let description = description.take().unwrap_or_else(|| command.clone());
+            plan.steps.push(Step { description, command, rollback: None, parallel: false });
+        } else if let Some(rollback) = field("ROLLBACK:") {
+            let step = plan.steps.last_mut().ok_or_else(|| format!("`{}` comes before any command", line))?;
+            step.rollback = Some(rollback).filter(|rollback| !rollback.is_empty() && !rollback.eq_ignore_ascii_case("none"));
+        } else if let Some(parallel) = field("PARALLEL:") {
+            let step = plan.steps.last_mut().ok_or_else(|| format!("`{}` comes before any command", line))?;
+            step.parallel = parallel.eq_ignore_ascii_case("yes") || parallel.eq_ignore_ascii_case("true");
+        }
+    }
+    Ok(plan)
+}
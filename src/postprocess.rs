@@ -0,0 +1,78 @@
+//! Clean-up of the commands models send back: they sometimes wrap them in
+//! a ```` ```bash ```` fence or backticks, start them with a `$ ` prompt or
+//! quote them, however they're asked. The `post_process` rules in the
+//! config run on every command reply before it's shown, cached or run.
+
+use serde::{Deserialize, Serialize};
+
+/// One step of the clean-up, as written in `config.json`: `"strip_fences"`,
+/// `"strip_prompt"`, `"trim_quotes"` or `{"replace": {"pattern": "...", "with": "..."}}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// the code in a fenced block, or in backticks around the whole reply
+    StripFences,
+    /// a leading `$ `, `> ` or `PS> ` on each line
+    StripPrompt,
+    /// quotes around the whole reply, when it has no others of the kind
+    TrimQuotes,
+    /// every match of the regex `pattern` replaced by `with`, which can use `$1` and the like
+    Replace { pattern: String, with: String },
+}
+
+/// The rules used when the config doesn't list any.
+pub fn default_rules() -> Vec<Rule> {
+    vec![Rule::StripFences, Rule::StripPrompt, Rule::TrimQuotes]
+}
+
+/// `reply` with `rules` applied in order, trimmed.
+pub fn apply(rules: &[Rule], reply: &str) -> String {
+    rules.iter().fold(reply.trim().to_string(), |reply, rule| rule.apply(&reply).trim().to_string())
+}
+
+impl Rule {
+    fn apply(&self, reply: &str) -> String {
+        match self {
+            Rule::StripFences => strip_fences(reply),
+            Rule::StripPrompt => reply.lines().map(strip_prompt).collect::<Vec<_>>().join("\n"),
+            Rule::TrimQuotes => trim_quotes(reply).to_string(),
+            Rule::Replace { pattern, with } => match regex::Regex::new(pattern) {
+                Ok(regex) => regex.replace_all(reply, with.as_str()).into_owned(),
+                Err(e) => {
+                    log::warn!("post_process: skipping the replace rule `{}`: {}", pattern, e);
+                    reply.to_string()
+                }
+            },
+        }
+    }
+}
+
+fn strip_fences(reply: &str) -> String {
+    if let Some(start) = reply.find("```") {
+        let rest = &reply[start + 3..];
+        // the info string, e.g. `bash`
+        let code = rest.split_once('\n').map_or("", |(_, code)| code);
+        if let Some(end) = code.find("```") {
+            return code[..end].to_string();
+        }
+    }
+    let inner = reply.strip_prefix('`').and_then(|rest| rest.strip_suffix('`'));
+    match inner {
+        Some(inner) if !inner.contains('`') => inner.to_string(),
+        _ => reply.to_string(),
+    }
+}
+
+fn strip_prompt(line: &str) -> &str {
+    ["$ ", "> ", "PS> "].iter().find_map(|prompt| line.strip_prefix(prompt)).unwrap_or(line)
+}
+
+fn trim_quotes(reply: &str) -> &str {
+    for quote in ['"', '\''] {
+        let inner = reply.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote));
+        if let Some(inner) = inner.filter(|inner| !inner.contains(quote)) {
+            return inner;
+        }
+    }
+    reply
+}
@@ -0,0 +1,271 @@
+//! Project settings from a `.llmterm.toml`, found in the working directory
+//! or the nearest parent, like `.editorconfig`:
+//!
+//! ```toml
+//! stack = "Rust workspace, Postgres 16, deployed with Nomad"
+//! tools = ["ripgrep", "fd", "just"]
+//! forbidden = ["git push --force", "cargo publish"]
+//! notes = """
+//! Integration tests need `docker compose up db` first.
+//! """
+//! ```
+//!
+//! Only strings and arrays of strings are understood, which is all these
+//! settings need; anything else is reported as an error.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = ".llmterm.toml";
+
+#[derive(Clone, Debug, Default)]
+pub struct Project {
+    /// the `.llmterm.toml` these settings came from
+    pub path: PathBuf,
+    /// languages, frameworks and services the project uses
+    pub stack: Option<String>,
+    /// tools to prefer over the usual ones
+    pub tools: Vec<String>,
+    /// commands that must never be suggested or run from this project
+    pub forbidden: Vec<String>,
+    /// anything else the model should know
+    pub notes: Option<String>,
+}
+
+impl Project {
+    /// The settings for `dir`, from the first `.llmterm.toml` in it or its parents.
+    pub fn discover(dir: &Path) -> Option<Result<Self, String>> {
+        let path = dir.ancestors().map(|dir| dir.join(FILE_NAME)).find(|path| path.is_file())?;
+        Some(Self::load(&path))
+    }
+
+    /// The settings for the process's working directory; a broken file is
+    /// logged and ignored.
+    pub fn current() -> Option<Self> {
+        match Self::discover(&std::env::current_dir().ok()?)? {
+            Ok(project) => Some(project),
+            Err(e) => {
+                log::warn!("ignoring project settings: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let mut values = parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut project = Self { path: path.to_path_buf(), ..Self::default() };
+        for (key, value) in values.drain() {
+            match (key.as_str(), value) {
+                ("stack", Value::String(text)) => project.stack = Some(text),
+                ("notes", Value::String(text)) => project.notes = Some(text),
+                ("tools", Value::Array(items)) => project.tools = items,
+                ("forbidden", Value::Array(items)) => project.forbidden = items,
+                ("stack" | "notes", _) => return Err(format!("{}: `{}` must be a string", path.display(), key)),
+                ("tools" | "forbidden", _) => return Err(format!("{}: `{}` must be an array of strings", path.display(), key)),
+                _ => log::warn!("{}: unknown setting `{}`", path.display(), key),
+            }
+        }
+        Ok(project)
+    }
+
+    /// The settings as instructions for the system prompt.
+    pub fn prompt_note(&self) -> String {
+        let mut note = String::from("Project context for the current directory:");
+        if let Some(stack) = &self.stack {
+            note.push_str(&format!("\n- Tech stack: {}", stack.trim()));
+        }
+        if !self.tools.is_empty() {
+            note.push_str(&format!("\n- Prefer these tools when they fit: {}", self.tools.join(", ")));
+        }
+        if !self.forbidden.is_empty() {
+            note.push_str(&format!("\n- Never suggest these commands, not even as part of a longer one: {}", self.forbidden.join("; ")));
+        }
+        if let Some(notes) = &self.notes {
+            note.push_str(&format!("\n- Notes: {}", notes.trim()));
+        }
+        note
+    }
+
+    /// The `forbidden` entry `command` contains, if any.
+    pub fn forbids(&self, command: &str) -> Option<&str> {
+        let command = normalize(command);
+        self.forbidden
+            .iter()
+            .find(|forbidden| !forbidden.trim().is_empty() && command.contains(&normalize(forbidden)))
+            .map(String::as_str)
+    }
+}
+
+/// The project note for the working directory, or an empty string.
+pub fn prompt_note() -> String {
+    Project::current().map(|project| project.prompt_note()).unwrap_or_default()
+}
+
+/// Runs of whitespace collapsed, so `git  push` still matches `git push`.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Array(Vec<String>),
+}
+
+/// Top-level `key = value` pairs; values in `[tables]` are ignored.
+fn parse(text: &str) -> Result<HashMap<String, Value>, String> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    let mut values = HashMap::new();
+    let mut in_table = false;
+    loop {
+        parser.skip_blank(true);
+        let Some(c) = parser.peek() else {
+            return Ok(values);
+        };
+        if c == '[' {
+            while parser.peek().is_some_and(|c| c != '\n') {
+                parser.bump();
+            }
+            in_table = true;
+            continue;
+        }
+
+        let key = parser.key()?;
+        parser.skip_blank(false);
+        if parser.bump() != Some('=') {
+            return Err(parser.error(&format!("expected `=` after `{}`", key)));
+        }
+        parser.skip_blank(false);
+        let value = parser.value()?;
+        parser.skip_blank(false);
+        if !matches!(parser.peek(), None | Some('\n')) {
+            return Err(parser.error("expected the end of the line"));
+        }
+        if !in_table {
+            values.insert(key, value);
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// for error messages
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+
+    /// Skips spaces and comments, and newlines too if `newlines`.
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                    continue;
+                }
+                _ => return,
+            }
+            self.bump();
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        if matches!(self.peek(), Some('"' | '\'')) {
+            return self.string();
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            self.bump();
+        }
+        if self.pos == start {
+            return Err(self.error("expected a key"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.peek() != Some('[') {
+            return self.string().map(Value::String);
+        }
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank(true);
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.string()?);
+            self.skip_blank(true);
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error("expected `,` or `]` in the array")),
+            }
+        }
+    }
+
+    /// A basic or literal string, single or multi-line.
+    fn string(&mut self) -> Result<String, String> {
+        let (quote, multiline) = match self.peek() {
+            _ if self.starts_with("\"\"\"") => ('"', true),
+            _ if self.starts_with("'''") => ('\'', true),
+            Some(q @ ('"' | '\'')) => (q, false),
+            _ => return Err(self.error("expected a string (only strings and arrays of strings are supported)")),
+        };
+        self.pos += if multiline { 3 } else { 1 };
+        // a newline right after the opening quotes isn't part of the string
+        if multiline && self.peek() == Some('\n') {
+            self.bump();
+        }
+
+        let mut text = String::new();
+        loop {
+            if multiline && self.starts_with(&quote.to_string().repeat(3)) {
+                self.pos += 3;
+                return Ok(text);
+            }
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('\n') if !multiline => return Err(self.error("unterminated string")),
+                Some(c) if c == quote && !multiline => return Ok(text),
+                Some('\\') if quote == '"' => match self.bump() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some(c) => return Err(self.error(&format!("unknown escape `\\{}`", c))),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => text.push(c),
+            }
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! The model APIs, spoken over the shared agents from [`crate::http`].
+//!
+//! OpenAI and Ollama both answer the OpenAI chat completions protocol; an
+//! API with its own protocol, like Anthropic's or Gemini's, is another
+//! [`Api`] with its own request and response shapes. Ollama's own
+//! `/api/chat` is one: it takes model options the compatible one ignores.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::model::{Model, Timeout};
+use crate::{http, rate_limit, stats, Config};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+/// The wire protocol of a provider.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Api {
+    /// `POST <base_url>chat/completions`, as OpenAI, Ollama and most local servers serve it
+    OpenAiChat,
+    /// `POST <base_url>api/chat`, Ollama's own protocol
+    OllamaChat,
+    /// answers from a JSON fixture file at `base_url`, mapping prompts to
+    /// replies; `"*"` answers any other prompt
+    Mock,
+}
+
+/// The `ollama` section of the config.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OllamaOptions {
+    /// talk to Ollama's own `/api/chat` instead of its OpenAI-compatible `/v1`;
+    /// needed for the other settings here, and for token counts in `llm-term stats`
+    #[serde(default)]
+    pub native_api: bool,
+    /// how long the model stays loaded after a request, e.g. "30m", "-1" for
+    /// ever or "0" to unload it right away; Ollama's 5 minutes otherwise
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+    /// the context window in tokens; Ollama's default, and what llm-term assumes, is 8192
+    #[serde(default)]
+    pub num_ctx: Option<u32>,
+    /// the same seed and prompt give the same answer
+    #[serde(default)]
+    pub seed: Option<i64>,
+}
+
+/// Where and how to reach a model.
+#[derive(Clone, Debug)]
+pub struct Provider {
+    pub api: Api,
+    /// ends with a slash, e.g. "https://api.openai.com/v1/"; the fixture file for [`Api::Mock`]
+    pub base_url: String,
+    pub api_key: Option<String>,
+}
+
+/// The last request sent to a model API and what came back, as raw text.
+#[derive(Clone, Debug)]
+pub struct RawExchange {
+    pub url: String,
+    /// the JSON body; headers, and so the API key, aren't kept
+    pub request: String,
+    /// the body of the answer, or the error
+    pub response: String,
+}
+
+static LAST_EXCHANGE: Mutex<Option<RawExchange>> = Mutex::new(None);
+
+/// The last request made through any provider, for bug reports.
+pub fn last_exchange() -> Option<RawExchange> {
+    LAST_EXCHANGE.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// `answer` up to the first of the `stop` sequences, as a server cuts it.
+fn cut_at_stop(mut answer: String, stop: &[String]) -> String {
+    if let Some(at) = stop.iter().filter_map(|stop| answer.find(stop.as_str())).min() {
+        answer.truncate(at);
+    }
+    answer
+}
+
+fn remember(url: &str, request: &str, response: String) {
+    let exchange = RawExchange { url: url.to_string(), request: request.to_string(), response };
+    *LAST_EXCHANGE.lock().unwrap_or_else(|e| e.into_inner()) = Some(exchange);
+}
+
+/// How long the reasoning models think before answering: longer is slower
+/// and costs more, but does better on hard requests.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Serialize)]
+struct ChatBody<'a> {
+    model: String,
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<i32>,
+    /// what reasoning models take instead of `max_tokens`; their reasoning counts towards it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+}
+
+impl<'a> ChatBody<'a> {
+    /// The request for `model`: reasoning models refuse `max_tokens`,
+    /// `temperature` and `stop`, and take a `reasoning_effort`.
+    fn new(config: &Config, model: &Model, messages: &'a [Message], max_tokens: i32, temperature: f32, stream: bool, stop: &'a [String]) -> Self {
+        let reasoning = model.is_reasoning();
+        ChatBody {
+            model: model.get_model_name(),
+            messages,
+            max_tokens: (!reasoning).then_some(max_tokens),
+            max_completion_tokens: reasoning.then_some(max_tokens),
+            temperature: (!reasoning).then_some(temperature),
+            reasoning_effort: config.reasoning_effort.filter(|_| reasoning),
+            stream,
+            stop: if reasoning { &[] } else { stop },
+        }
+    }
+}
+
+/// Where Ollama listens unless `ollama_host` or `OLLAMA_HOST` say otherwise.
+pub const DEFAULT_OLLAMA_HOST: &str = "http://localhost:11434";
+
+/// How long [`ollama_models`] waits for an answer.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// OpenAI takes at most this many stop sequences.
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
+#[derive(Serialize)]
+struct OllamaChatBody<'a> {
+    model: String,
+    messages: &'a [Message],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<serde_json::Value>,
+    options: OllamaModelOptions<'a>,
+}
+
+#[derive(Serialize)]
+struct OllamaModelOptions<'a> {
+    num_predict: i32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+}
+
+/// A whole answer from `/api/chat`, or one line of a streamed one.
+#[derive(Deserialize)]
+struct OllamaReply {
+    message: Option<Content>,
+    #[serde(default)]
+    done: bool,
+    /// set once done
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    #[serde(default)]
+    choices: Vec<Choice>,
+    usage: Option<Usage>,
+}
+
+/// Tokens a request took, as the API counted them.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    /// set in whole answers
+    message: Option<Content>,
+    /// set in the chunks of a streamed answer
+    delta: Option<Content>,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    content: Option<String>,
+}
+
+impl Provider {
+    /// The provider serving `model`, over the API `config` picks for it.
+    pub fn for_model(config: &Config, model: &Model) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match model {
+            Model::OpenAiGpt4o | Model::OpenAiGpt4oMini | Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => Self {
+                api: Api::OpenAiChat,
+                base_url: "https://api.openai.com/v1/".to_string(),
+                api_key: Some(std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY environment variable not set")?),
+            },
+            Model::Ollama(_) if config.ollama.native_api => Self { api: Api::OllamaChat, base_url: ollama_url(config), api_key: None },
+            Model::Ollama(_) => Self { api: Api::OpenAiChat, base_url: format!("{}v1/", ollama_url(config)), api_key: None },
+            Model::Mock(fixture) => Self { api: Api::Mock, base_url: fixture.clone(), api_key: None },
+        })
+    }
+
+    /// Asks `model` for an answer to `messages`.
+    pub fn chat(
+        &self,
+        config: &Config,
+        model: &Model,
+        messages: &[Message],
+        max_tokens: i32,
+        temperature: f32,
+        stop: &[String],
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        rate_limit::wait_for_turn(config, model, rate_limit::request_tokens(messages, max_tokens));
+        if stop.len() > MAX_STOP_SEQUENCES {
+            log::warn!("only the first {} of the {} stop sequences are sent", MAX_STOP_SEQUENCES, stop.len());
+        }
+        let stop = &stop[..stop.len().min(MAX_STOP_SEQUENCES)];
+        if self.api == Api::Mock {
+            return self.mock_answer(messages).map(|answer| Some(cut_at_stop(answer, stop)));
+        }
+        let json = match self.api {
+            Api::OllamaChat => serde_json::to_string(&ollama_body(config, model, messages, max_tokens, temperature, stop, false))?,
+            _ => serde_json::to_string(&ChatBody::new(config, model, messages, max_tokens, temperature, false, stop))?,
+        };
+        let mut request = self.request(config)?;
+        if config.request_timeout_secs > 0 {
+            request = request.timeout(Duration::from_secs(config.request_timeout_secs));
+        }
+        let url = request.url().to_string();
+        let text = request
+            .send_string(&json)
+            .map_err(Into::into)
+            .and_then(|response| response.into_string().map_err(Into::into))
+            .map_err(|e| self.error(config, model, e));
+        remember(&url, &json, text.as_ref().map_or_else(|e| format!("error: {}", e), String::clone));
+        let text = text?;
+        if self.api == Api::OllamaChat {
+            let reply: OllamaReply = serde_json::from_str(&text).map_err(|e| format!("Unexpected answer from {}: {}", model, e))?;
+            note_usage(model, reply.usage());
+            return Ok(reply.message.and_then(|message| message.content));
+        }
+        let completion: Completion = serde_json::from_str(&text).map_err(|e| format!("Unexpected answer from {}: {}", model, e))?;
+        note_usage(model, completion.usage);
+        let answer = completion.choices.into_iter().next().and_then(|choice| choice.message).and_then(|message| message.content);
+        // reasoning models aren't sent `stop`, so it's done here
+        Ok(answer.map(|answer| if model.is_reasoning() { cut_at_stop(answer, stop) } else { answer }))
+    }
+
+    /// Like [`chat`](Self::chat), but hands each piece of the answer to
+    /// `on_delta` as it arrives. Returns the whole answer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn chat_stream(
+        &self,
+        config: &Config,
+        model: &Model,
+        messages: &[Message],
+        max_tokens: i32,
+        temperature: f32,
+        stop: &[String],
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        rate_limit::wait_for_turn(config, model, rate_limit::request_tokens(messages, max_tokens));
+        let stop = &stop[..stop.len().min(MAX_STOP_SEQUENCES)];
+        if self.api == Api::Mock {
+            let answer = cut_at_stop(self.mock_answer(messages)?, stop);
+            on_delta(&answer);
+            return Ok(Some(answer));
+        }
+        let json = match self.api {
+            Api::OllamaChat => serde_json::to_string(&ollama_body(config, model, messages, max_tokens, temperature, stop, true))?,
+            _ => serde_json::to_string(&ChatBody::new(config, model, messages, max_tokens, temperature, true, stop))?,
+        };
+        let mut request = self.request(config)?;
+        // covers reading the stream too, not just the first byte
+        if config.request_timeout_secs > 0 {
+            request = request.timeout(Duration::from_secs(config.request_timeout_secs));
+        }
+        let url = request.url().to_string();
+        let response = match request.send_string(&json) {
+            Ok(response) => response,
+            Err(e) => {
+                let error = self.error(config, model, e.into());
+                remember(&url, &json, format!("error: {}", error));
+                return Err(error);
+            }
+        };
+
+        // server-sent events: `data: {chunk}` lines, then `data: [DONE]`
+        let mut raw = String::new();
+        let read = || -> Result<Option<String>, Box<dyn std::error::Error>> {
+            let mut answer: Option<String> = None;
+            for line in BufReader::new(response.into_reader()).lines() {
+                let line = line.map_err(|e| self.error(config, model, e.into()))?;
+                raw.push_str(&line);
+                raw.push('\n');
+                // Ollama's own API: one JSON object per line, the last one `done`
+                if self.api == Api::OllamaChat {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let chunk: OllamaReply = serde_json::from_str(&line).map_err(|e| format!("Unexpected answer from {}: {}", model, e))?;
+                    if let Some(delta) = chunk.message.as_ref().and_then(|message| message.content.as_deref()).filter(|delta| !delta.is_empty()) {
+                        on_delta(delta);
+                        answer.get_or_insert_with(String::new).push_str(delta);
+                    }
+                    if chunk.done {
+                        note_usage(model, chunk.usage());
+                        break;
+                    }
+                    continue;
+                }
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    break;
+                }
+                let chunk: Completion = serde_json::from_str(data).map_err(|e| format!("Unexpected answer from {}: {}", model, e))?;
+                let delta = chunk.choices.into_iter().next().and_then(|choice| choice.delta).and_then(|delta| delta.content);
+                if let Some(delta) = delta.filter(|delta| !delta.is_empty()) {
+                    on_delta(&delta);
+                    answer.get_or_insert_with(String::new).push_str(&delta);
+                }
+            }
+            Ok(answer)
+        };
+        let answer = read();
+        remember(&url, &json, raw);
+        // reasoning models aren't sent `stop`, so it's done here
+        answer.map(|answer| answer.map(|answer| if model.is_reasoning() { cut_at_stop(answer, stop) } else { answer }))
+    }
+
+    fn request(&self, config: &Config) -> Result<ureq::Request, Box<dyn std::error::Error>> {
+        let url = match self.api {
+            Api::OpenAiChat => format!("{}chat/completions", self.base_url),
+            Api::OllamaChat => format!("{}api/chat", self.base_url),
+            Api::Mock => return Err("the mock provider makes no requests".into()),
+        };
+        log::debug!("POST {}", url);
+        let request = http::agent(config, &url)?.post(&url).set("Content-Type", "application/json");
+        let request = match &self.api_key {
+            Some(key) => request.set("Authorization", &format!("Bearer {}", key)),
+            None => request,
+        };
+        Ok(request)
+    }
+
+    /// The fixture's reply to the last user message, matched exactly after
+    /// trimming, else its `"*"` reply. Read on every call so it can be edited
+    /// while the app runs.
+    fn mock_answer(&self, messages: &[Message]) -> Result<String, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(&self.base_url).map_err(|e| format!("Can't read mock fixture {}: {}", self.base_url, e))?;
+        let replies: HashMap<String, String> =
+            serde_json::from_str(&content).map_err(|e| format!("Mock fixture {} isn't a JSON object of prompt -> reply: {}", self.base_url, e))?;
+        let prompt = messages.iter().rev().find(|m| m.role == Role::User).map_or("", |m| m.content.trim());
+        replies
+            .iter()
+            .find(|(key, _)| key.trim() == prompt)
+            .or_else(|| replies.get_key_value("*"))
+            .map(|(_, reply)| reply.clone())
+            .ok_or_else(|| format!("No mock reply for `{}` in {}", prompt, self.base_url).into())
+    }
+
+    /// A readable error for a failed request, or a [`Timeout`].
+    fn error(&self, config: &Config, model: &Model, error: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+        if config.request_timeout_secs > 0 && http::timed_out(error.as_ref()) {
+            return Box::new(Timeout { model: model.to_string(), secs: config.request_timeout_secs });
+        }
+        match error.downcast::<ureq::Error>() {
+            Ok(error) => match *error {
+                ureq::Error::Status(code, response) => {
+                    format!("{} answered {}: {}", model, code, response.into_string().unwrap_or_default().trim()).into()
+                }
+                error => error.into(),
+            },
+            Err(error) => error,
+        }
+    }
+}
+
+impl OllamaReply {
+    fn usage(&self) -> Option<Usage> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, completion) => Some(Usage { prompt_tokens: prompt.unwrap_or(0), completion_tokens: completion.unwrap_or(0) }),
+        }
+    }
+}
+
+/// The request [`Api::OllamaChat`] sends, with the `ollama` settings.
+fn ollama_body<'a>(
+    config: &Config,
+    model: &Model,
+    messages: &'a [Message],
+    max_tokens: i32,
+    temperature: f32,
+    stop: &'a [String],
+    stream: bool,
+) -> OllamaChatBody<'a> {
+    let options = &config.ollama;
+    OllamaChatBody {
+        model: model.get_model_name(),
+        messages,
+        stream,
+        // a number of seconds goes as a number, "30m" as a string
+        keep_alive: options.keep_alive.as_ref().map(|keep_alive| keep_alive.trim().parse::<i64>().map_or_else(|_| keep_alive.clone().into(), Into::into)),
+        options: OllamaModelOptions { num_predict: max_tokens, temperature, stop, num_ctx: options.num_ctx, seed: options.seed },
+    }
+}
+
+/// Counts the tokens the API says a request to `model` took.
+fn note_usage(model: &Model, usage: Option<Usage>) {
+    if let Some(usage) = usage {
+        log::info!("usage model={} prompt_tokens={} completion_tokens={}", model, usage.prompt_tokens, usage.completion_tokens);
+        stats::record_usage(&model.to_string(), usage.prompt_tokens, usage.completion_tokens);
+    }
+}
+
+/// The Ollama server's URL, ending with a slash: `ollama_host`, else
+/// `OLLAMA_HOST`, else [`DEFAULT_OLLAMA_HOST`].
+pub fn ollama_url(config: &Config) -> String {
+    let host = config.ollama_host.clone().filter(|host| !host.trim().is_empty()).or_else(|| std::env::var("OLLAMA_HOST").ok().filter(|host| !host.trim().is_empty()));
+    format!("{}/", normalize_host(host.as_deref().unwrap_or(DEFAULT_OLLAMA_HOST)))
+}
+
+/// `host` as a URL, the way Ollama reads `OLLAMA_HOST`: "gpu-box" is
+/// `http://gpu-box:11434`, "https://ollama.example.com/" stays as it is.
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    let (scheme, rest) = host.split_once("://").unwrap_or(("http", host));
+    let authority = rest.split('/').next().unwrap_or(rest);
+    // the port is what follows the last colon, but not inside an IPv6 address
+    let has_port = authority.rsplit_once(':').is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) && !authority.ends_with(']'));
+    if has_port || host.contains("://") {
+        format!("{}://{}", scheme, rest)
+    } else {
+        format!("{}://{}:11434{}", scheme, authority, &rest[authority.len()..])
+    }
+}
+
+/// The models the Ollama server has pulled, e.g. "llama3.1:latest"; an
+/// error saying why when it can't be reached.
+pub fn ollama_models(config: &Config) -> Result<Vec<String>, String> {
+    let url = format!("{}api/tags", ollama_url(config));
+    let agent = http::agent_builder(config, &url).map_err(|e| e.to_string())?.timeout(PROBE_TIMEOUT).build();
+    let response = agent.get(&url).call().map_err(|e| format!("{} isn't answering ({})", url, e))?;
+    let body = response.into_string().map_err(|e| format!("{} sent no answer ({})", url, e))?;
+    let tags: serde_json::Value = serde_json::from_str(&body).map_err(|e| format!("{} sent something that isn't JSON ({})", url, e))?;
+    let models = tags["models"].as_array().ok_or_else(|| format!("{} sent no model list", url))?;
+    Ok(models.iter().filter_map(|model| model["name"].as_str().map(str::to_string)).collect())
+}
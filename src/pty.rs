@@ -0,0 +1,160 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::shell::Shell;
+
+/// Output kept in memory for a running terminal session.
+const MAX_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Programs that read from the terminal and hang without one.
+const INTERACTIVE_PROGRAMS: &[&str] = &[
+    "ssh", "sudo", "su", "passwd", "top", "htop", "vi", "vim", "nvim", "nano", "less", "more",
+    "man", "python", "python3", "node", "irb", "mysql", "psql", "sqlite3", "ftp", "sftp", "telnet",
+];
+
+/// Returns true if `command` looks like it needs an interactive terminal.
+pub fn needs_terminal(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    let Some(program) = words.next() else {
+        return false;
+    };
+    let program = program.rsplit('/').next().unwrap_or(program);
+
+    INTERACTIVE_PROGRAMS.contains(&program)
+        // `npm init`, `yarn init`, `cargo init` ... ask questions
+        || words.next() == Some("init")
+}
+
+/// A command running attached to a pseudo terminal.
+pub struct PtySession {
+    pub command: String,
+    pub started: Instant,
+    // kept alive for the lifetime of the session; dropping it hangs up the terminal
+    _master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    output: Arc<Mutex<String>>,
+    exit_code: Option<u32>,
+}
+
+impl PtySession {
+    /// Starts `command` in the detected shell on a new pseudo terminal.
+    pub fn spawn(command: &str, working_dir: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let (shell_cmd, shell_arg) = Shell::detect().to_shell_command_and_command_arg();
+        let mut cmd = CommandBuilder::new(shell_cmd);
+        cmd.arg(shell_arg);
+        cmd.arg(command);
+        if let Some(dir) = working_dir {
+            cmd.cwd(dir);
+        }
+
+        Self::start(cmd, command.to_string())
+    }
+
+    /// Starts an interactive instance of the detected shell, which keeps its
+    /// state (working directory, exported variables) between commands.
+    pub fn spawn_shell() -> Result<Self, Box<dyn std::error::Error>> {
+        let (shell_cmd, _) = Shell::detect().to_shell_command_and_command_arg();
+        let cmd = CommandBuilder::new(&shell_cmd);
+
+        Self::start(cmd, shell_cmd)
+    }
+
+    fn start(cmd: CommandBuilder, command: String) -> Result<Self, Box<dyn std::error::Error>> {
+        let pair = native_pty_system().openpty(PtySize {
+            rows: 24,
+            cols: 100,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        let child = pair.slave.spawn_command(cmd)?;
+        // the child holds its own handle; ours would keep the reader from seeing EOF
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let output = Arc::new(Mutex::new(String::new()));
+
+        let sink = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            // bytes of a UTF-8 sequence split across two reads
+            let mut pending = Vec::new();
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                pending.extend_from_slice(&buf[..n]);
+                let complete = match std::str::from_utf8(&pending) {
+                    Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                    _ => pending.len(),
+                };
+
+                let mut out = sink.lock().unwrap();
+                out.push_str(&String::from_utf8_lossy(&pending[..complete]));
+                pending.drain(..complete);
+                if out.len() > MAX_BUFFER_BYTES {
+                    let mut cut = out.len() - MAX_BUFFER_BYTES;
+                    while !out.is_char_boundary(cut) {
+                        cut += 1;
+                    }
+                    out.drain(..cut);
+                }
+            }
+        });
+
+        Ok(Self {
+            command,
+            started: Instant::now(),
+            _master: pair.master,
+            writer,
+            child,
+            output,
+            exit_code: None,
+        })
+    }
+
+    /// Everything the program has printed so far, escape sequences included.
+    pub fn output(&self) -> String {
+        self.output.lock().unwrap().clone()
+    }
+
+    /// Sends raw input (keystrokes) to the program.
+    pub fn write_input(&mut self, input: &str) -> std::io::Result<()> {
+        self.writer.write_all(input.as_bytes())?;
+        self.writer.flush()
+    }
+
+    /// Types `command` into the program followed by Enter.
+    pub fn send_line(&mut self, command: &str) -> std::io::Result<()> {
+        self.write_input(&format!("{}\r", command))
+    }
+
+    /// Exit code once the program has finished.
+    pub fn exit_code(&mut self) -> Option<u32> {
+        if self.exit_code.is_none() {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.exit_code = Some(status.exit_code());
+            }
+        }
+        self.exit_code
+    }
+
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        if self.exit_code().is_none() {
+            self.kill();
+        }
+    }
+}
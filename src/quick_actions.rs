@@ -0,0 +1,51 @@
+//! Buttons above the GUI composer that insert ready-made prompts.
+//!
+//! They live in `~/.llm_term_quick_actions.json`, which is created with a few
+//! examples the first time the GUI starts and can be edited by hand. Prompts
+//! may contain `{placeholder}`s, which are asked for before insertion.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuickAction {
+    /// button text
+    pub label: String,
+    pub prompt: String,
+}
+
+impl QuickAction {
+    fn new(label: &str, prompt: &str) -> Self {
+        Self { label: label.to_string(), prompt: prompt.to_string() }
+    }
+}
+
+fn default_actions() -> Vec<QuickAction> {
+    vec![
+        QuickAction::new("🧹 Free disk space", "Show what takes the most space in my home directory, largest first"),
+        QuickAction::new("🔌 Kill process on port…", "Kill the process listening on port {port}"),
+        QuickAction::new("🗜 Compress this folder…", "Compress the folder {folder} into a .tar.gz archive next to it"),
+        QuickAction::new("🔎 Find large files", "List the 20 largest files under the current directory"),
+    ]
+}
+
+pub fn quick_actions_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_quick_actions.json");
+    Ok(path)
+}
+
+/// The saved quick actions; writes the examples first if there is no file yet.
+pub fn load_quick_actions() -> std::io::Result<Vec<QuickAction>> {
+    let path = quick_actions_file_path()?;
+    if path.exists() {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    } else {
+        let actions = default_actions();
+        fs::write(path, serde_json::to_string_pretty(&actions)?)?;
+        Ok(actions)
+    }
+}
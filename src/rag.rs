@@ -0,0 +1,304 @@
+//! Retrieval over the user's own notes: runbooks, dotfiles and wiki pages
+//! exported to Markdown are cut into chunks and embedded once by
+//! `llm-term index`; the chunks closest to a prompt are sent along with it,
+//! so commands follow the conventions written down there.
+//!
+//! The index is kept in `~/.llm_term_rag_index.json`, encrypted like the
+//! caches when the vault is unlocked.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::{http, provider, vault, Config};
+
+const OPENAI_EMBEDDINGS_URL: &str = "https://api.openai.com/v1/embeddings";
+
+/// Chunks are cut at paragraphs once they're this long.
+const CHUNK_CHARS: usize = 1000;
+
+/// Files bigger than this aren't notes.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Texts embedded per request.
+const BATCH: usize = 64;
+
+/// Chunks less similar to the prompt than this (cosine) aren't sent.
+const MIN_SCORE: f32 = 0.3;
+
+/// Directories never worth indexing.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target", ".cache"];
+
+/// The `rag` section of the config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Rag {
+    /// files and directories to index, e.g. "~/notes" or "~/.gitconfig"
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub embedding: Embedding,
+    /// how many chunks go along with a prompt
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+}
+
+fn default_top_k() -> usize {
+    3
+}
+
+/// What turns text into vectors.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Embedding {
+    /// OpenAI's `text-embedding-3-small`, with `OPENAI_API_KEY`
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// a local Ollama embedding model, e.g. "nomic-embed-text"
+    #[serde(rename = "ollama")]
+    Ollama {
+        #[serde(default = "default_ollama_model")]
+        model: String,
+    },
+}
+
+fn default_ollama_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+impl std::fmt::Display for Embedding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Embedding::OpenAi => write!(f, "openai:text-embedding-3-small"),
+            Embedding::Ollama { model } => write!(f, "ollama:{}", model),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    /// the embedding model the vectors come from; another one means indexing again
+    embedding: String,
+    files: Vec<IndexedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    path: PathBuf,
+    /// modification time in seconds, to tell whether it changed
+    modified: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    text: String,
+    vector: Vec<f32>,
+}
+
+/// What `index` did.
+pub struct IndexReport {
+    pub files: usize,
+    pub chunks: usize,
+    /// files that hadn't changed since the last run
+    pub unchanged: usize,
+}
+
+fn index_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_rag_index.json");
+    Ok(path)
+}
+
+fn load_index() -> Index {
+    index_file_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|content| vault::open_to_string(&content).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Embeds the files under `rag.paths` that changed since the last run and
+/// drops those that are gone.
+pub fn index(config: &Config) -> Result<IndexReport, Box<dyn std::error::Error>> {
+    let rag = config.rag.as_ref().ok_or("No `rag` section in the config; add one with the `paths` to index")?;
+    let mut old = load_index();
+    if old.embedding != rag.embedding.to_string() {
+        old.files.clear();
+    }
+
+    let mut index = Index { embedding: rag.embedding.to_string(), files: Vec::new() };
+    let mut unchanged = 0;
+    for path in files(&rag.paths) {
+        let Some(modified) = fs::metadata(&path).and_then(|meta| meta.modified()).ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()) else {
+            continue;
+        };
+        let modified = modified.as_secs();
+        if let Some(pos) = old.files.iter().position(|file| file.path == path && file.modified == modified) {
+            index.files.push(old.files.swap_remove(pos));
+            unchanged += 1;
+            continue;
+        }
+        let Some(text) = read_text(&path) else {
+            continue;
+        };
+        let texts = chunks(&text);
+        let mut vectors = Vec::new();
+        for batch in texts.chunks(BATCH) {
+            vectors.extend(embed(config, &rag.embedding, batch).map_err(|e| format!("Can't embed {}: {}", path.display(), e))?);
+        }
+        let chunks = texts.into_iter().zip(vectors).map(|(text, vector)| Chunk { text, vector }).collect();
+        index.files.push(IndexedFile { path, modified, chunks });
+    }
+
+    let content = serde_json::to_string(&index)?;
+    fs::write(index_file_path()?, vault::seal(content.as_bytes()))?;
+    Ok(IndexReport { files: index.files.len(), chunks: index.files.iter().map(|file| file.chunks.len()).sum(), unchanged })
+}
+
+/// The indexed chunks closest to `prompt`, for the system prompt; empty
+/// without a `rag` config or an index. Errors are logged: a prompt without
+/// notes is still worth answering.
+pub fn prompt_note(config: &Config, prompt: &str) -> String {
+    let Some(rag) = config.rag.as_ref().filter(|rag| rag.top_k > 0) else {
+        return String::new();
+    };
+    let index = load_index();
+    if index.files.is_empty() || index.embedding != rag.embedding.to_string() {
+        return String::new();
+    }
+    let query = match embed(config, &rag.embedding, &[prompt.to_string()]) {
+        Ok(mut vectors) if !vectors.is_empty() => vectors.remove(0),
+        Ok(_) => return String::new(),
+        Err(e) => {
+            log::warn!("Can't search the notes: {}", e);
+            return String::new();
+        }
+    };
+
+    let query = &query;
+    let mut scored: Vec<(f32, &Path, &str)> = index
+        .files
+        .iter()
+        .flat_map(|file| file.chunks.iter().map(move |chunk| (cosine(query, &chunk.vector), file.path.as_path(), chunk.text.as_str())))
+        .filter(|(score, _, _)| *score >= MIN_SCORE)
+        .collect();
+    scored.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+    if scored.is_empty() {
+        return String::new();
+    }
+
+    let mut note = String::from("Excerpts from the user's own notes and dotfiles that may apply; follow the conventions they describe:");
+    for (_, path, text) in scored.into_iter().take(rag.top_k) {
+        note.push_str(&format!("\n\n--- {}\n{}", path.display(), text));
+    }
+    note
+}
+
+/// Every file under `paths`, directories walked, `~` expanded.
+fn files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending: Vec<PathBuf> = paths.iter().map(|path| expand_home(path)).collect();
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            let Ok(entries) = fs::read_dir(&path) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let child = entry.path();
+                let skipped = child.is_dir() && child.file_name().and_then(|name| name.to_str()).is_some_and(|name| SKIPPED_DIRS.contains(&name));
+                if !skipped {
+                    pending.push(child);
+                }
+            }
+        } else if path.is_file() {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+pub(crate) fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The contents of `path` if it's a text file of a sensible size.
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let text = fs::read_to_string(path).ok()?;
+    (!text.contains('\0') && !text.trim().is_empty()).then_some(text)
+}
+
+/// `text` cut at blank lines and Markdown headings into chunks of about `CHUNK_CHARS`.
+fn chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let heading = paragraph.starts_with('#') && !paragraph.starts_with("#!");
+        if !current.is_empty() && (heading || current.len() + paragraph.len() > CHUNK_CHARS) {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// One vector per text in `texts`, in order.
+fn embed(config: &Config, embedding: &Embedding, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+    let (url, body, key) = match embedding {
+        Embedding::OpenAi => {
+            let key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY environment variable not set")?;
+            (OPENAI_EMBEDDINGS_URL.to_string(), serde_json::json!({ "model": "text-embedding-3-small", "input": texts }), Some(key))
+        }
+        Embedding::Ollama { model } => (format!("{}api/embed", provider::ollama_url(config)), serde_json::json!({ "model": model, "input": texts }), None),
+    };
+    let url = url.as_str();
+
+    log::debug!("POST {} texts={}", url, texts.len());
+    let mut request = http::agent(config, url)?.post(url).set("Content-Type", "application/json");
+    if let Some(key) = key {
+        request = request.set("Authorization", &format!("Bearer {}", key));
+    }
+    let response = request.send_string(&body.to_string()).map_err(|e| match e {
+        ureq::Error::Status(code, response) => format!("{} answered {}: {}", url, code, response.into_string().unwrap_or_default().trim()),
+        e => e.to_string(),
+    })?;
+    let json: serde_json::Value = serde_json::from_str(&response.into_string()?)?;
+
+    let vectors: Vec<&serde_json::Value> = match embedding {
+        // {"data": [{"embedding": [...]}, ...]}
+        Embedding::OpenAi => json["data"].as_array().map(|data| data.iter().map(|item| &item["embedding"]).collect()).unwrap_or_default(),
+        // {"embeddings": [[...], ...]}
+        Embedding::Ollama { .. } => json["embeddings"].as_array().map(|vectors| vectors.iter().collect()).unwrap_or_default(),
+    };
+    if vectors.len() != texts.len() {
+        return Err(format!("expected {} embeddings, got {}", texts.len(), vectors.len()).into());
+    }
+    Ok(vectors
+        .into_iter()
+        .map(|vector| vector.as_array().map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect()).unwrap_or_default())
+        .collect())
+}
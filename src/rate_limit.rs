@@ -0,0 +1,106 @@
+//! Client-side rate limits on model requests, so a batch or a plan sending
+//! many prompts stays under a provider's limits, or a budget. A request
+//! waits until the minute before it leaves room for it, both under the
+//! global `rate_limit` and under the one for its provider in
+//! `provider_rate_limits`.
+//!
+//! The limits are kept per run of llm-term: a batch, a plan or a GUI
+//! session counts its own requests.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::context::estimate_tokens;
+use crate::model::Model;
+use crate::provider::Message;
+use crate::Config;
+
+/// The window the limits are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Caps on the requests sent in any minute; unset ones don't limit.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimit {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// the prompt's estimated tokens plus the answer's `max_tokens`, as providers count them
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimit {
+    fn is_set(&self) -> bool {
+        self.requests_per_minute.is_some() || self.tokens_per_minute.is_some()
+    }
+}
+
+/// The requests of the last minute under one limit: when each was sent and its tokens.
+type Sent = VecDeque<(Instant, u64)>;
+
+/// Keyed by provider, or "" for the global limit.
+static SENT: Mutex<Option<HashMap<String, Sent>>> = Mutex::new(None);
+
+/// The name `provider_rate_limits` knows `model`'s provider by: "openai", "ollama" or "mock".
+pub fn provider_name(model: &Model) -> &'static str {
+    match model {
+        Model::OpenAiGpt4o | Model::OpenAiGpt4oMini | Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => "openai",
+        Model::Ollama(_) => "ollama",
+        Model::Mock(_) => "mock",
+    }
+}
+
+/// The tokens a request counts for: its messages and as long an answer as it allows.
+pub fn request_tokens(messages: &[Message], max_tokens: i32) -> u64 {
+    (messages.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>() + max_tokens.max(0) as usize) as u64
+}
+
+/// Blocks until a request of `tokens` to `model` fits under the limits,
+/// then counts it.
+pub fn wait_for_turn(config: &Config, model: &Model, tokens: u64) {
+    let provider = provider_name(model);
+    let limits: Vec<(&str, RateLimit)> = [("", Some(config.rate_limit)), (provider, config.provider_rate_limits.get(provider).copied())]
+        .into_iter()
+        .filter_map(|(key, limit)| limit.filter(RateLimit::is_set).map(|limit| (key, limit)))
+        .collect();
+    if limits.is_empty() {
+        return;
+    }
+
+    loop {
+        let wait = {
+            let mut sent = SENT.lock().unwrap_or_else(|e| e.into_inner());
+            let sent = sent.get_or_insert_with(HashMap::new);
+            let now = Instant::now();
+            let wait = limits.iter().filter_map(|(key, limit)| wait_needed(sent.entry(key.to_string()).or_default(), limit, tokens, now)).max();
+            if wait.is_none() {
+                for (key, _) in &limits {
+                    sent.entry(key.to_string()).or_default().push_back((now, tokens));
+                }
+                return;
+            }
+            wait.unwrap_or_default()
+        };
+        log::info!("waiting {:.1}s to stay under the rate limits for {}", wait.as_secs_f64(), provider);
+        std::thread::sleep(wait);
+    }
+}
+
+/// How long until a request of `tokens` fits under `limit`, given what was
+/// `sent`; `None` if it fits now. A request bigger than the whole token
+/// limit goes once the minute is empty.
+fn wait_needed(sent: &mut Sent, limit: &RateLimit, tokens: u64, now: Instant) -> Option<Duration> {
+    while sent.front().is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW) {
+        sent.pop_front();
+    }
+    let too_many = limit.requests_per_minute.is_some_and(|max| sent.len() >= max as usize);
+    let used: u64 = sent.iter().map(|(_, tokens)| tokens).sum();
+    let too_big = limit.tokens_per_minute.is_some_and(|max| !sent.is_empty() && used + tokens > max as u64);
+    if !too_many && !too_big {
+        return None;
+    }
+    // the oldest request leaving the window makes room, if not enough yet
+    let (oldest, _) = sent.front()?;
+    Some(WINDOW.saturating_sub(now.duration_since(*oldest)) + Duration::from_millis(10))
+}
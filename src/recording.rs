@@ -0,0 +1,109 @@
+//! `--record` and `--replay`: model requests and their answers saved to a
+//! file and served back from it, so a bug report can be reproduced without
+//! keys or network.
+//!
+//! The file has one JSON object per line: `{"model", "messages", "reply"}`,
+//! or `"error"` instead of `"reply"` for a request that failed. Messages
+//! are saved as sent, i.e. after secrets were redacted.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::provider::{Message, Role};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Exchange {
+    model: String,
+    messages: Vec<Message>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+enum Mode {
+    Record(File),
+    /// recorded exchanges with whether they were served already
+    Replay(Vec<(Exchange, bool)>),
+}
+
+static MODE: Mutex<Option<Mode>> = Mutex::new(None);
+
+/// Appends every model request from now on, and its answer, to `path`.
+pub fn record_to(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Can't open {} for recording: {}", path.display(), e))?;
+    *MODE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Mode::Record(file));
+    Ok(())
+}
+
+/// Answers model requests from the recording at `path` instead of the models.
+pub fn replay_from(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Can't read recording {}: {}", path.display(), e))?;
+    let mut exchanges = Vec::new();
+    for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let exchange: Exchange = serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", path.display(), number + 1, e))?;
+        exchanges.push((exchange, false));
+    }
+    *MODE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Mode::Replay(exchanges));
+    Ok(())
+}
+
+/// The recorded answer to this request when replaying, `None` otherwise.
+/// Requests are matched to the first exchange not served yet with the same
+/// model and messages; since system prompts mention the OS and shell, a
+/// recording from another machine falls back to the same last user message,
+/// then to the next exchange in order.
+pub(crate) fn replayed(model: &str, messages: &[Message]) -> Option<Result<Option<String>, Box<dyn std::error::Error>>> {
+    let mut mode = MODE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Mode::Replay(exchanges)) = mode.as_mut() else {
+        return None;
+    };
+    let unserved = || exchanges.iter().enumerate().filter(|(_, (_, served))| !served);
+    let index = unserved()
+        .find(|(_, (exchange, _))| exchange.model == model && same_messages(&exchange.messages, messages))
+        .or_else(|| unserved().find(|(_, (exchange, _))| last_user_message(&exchange.messages) == last_user_message(messages)))
+        .or_else(|| unserved().next())
+        .map(|(index, _)| index);
+    let Some(index) = index else {
+        return Some(Err(format!("The recording has no more answers; {} was asked again", model).into()));
+    };
+
+    let (exchange, served) = &mut exchanges[index];
+    *served = true;
+    Some(match &exchange.error {
+        Some(error) => Err(error.clone().into()),
+        None => Ok(exchange.reply.clone()),
+    })
+}
+
+/// Saves the request and its `result` when recording.
+pub(crate) fn note(model: &str, messages: &[Message], result: &Result<Option<String>, Box<dyn std::error::Error>>) {
+    let mut mode = MODE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Mode::Record(file)) = mode.as_mut() else {
+        return;
+    };
+    let exchange = Exchange {
+        model: model.to_string(),
+        messages: messages.to_vec(),
+        reply: result.as_ref().ok().cloned().flatten(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Ok(line) = serde_json::to_string(&exchange) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+fn same_messages(a: &[Message], b: &[Message]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.role == b.role && a.content == b.content)
+}
+
+fn last_user_message(messages: &[Message]) -> Option<&str> {
+    messages.iter().rev().find(|m| m.role == Role::User).map(|m| m.content.as_str())
+}
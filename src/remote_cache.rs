@@ -0,0 +1,86 @@
+//! Team-shared cache on an HTTP server, consulted when the local cache misses.
+//!
+//! The server needs two endpoints:
+//! - `GET <url>/commands?prompt=..&shell=..&model=..` answering
+//!   `{"command": ".."}`, or 404 if it has none;
+//! - `PUT <url>/commands` taking `{"prompt", "command", "shell", "model"}`.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{http, Config, Shell};
+
+/// A slow or unreachable server only delays the fallback to the model this long.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    prompt: &'a str,
+    command: &'a str,
+    shell: &'a str,
+    model: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Found {
+    command: String,
+}
+
+pub struct RemoteCache {
+    url: String,
+    token: Option<String>,
+    /// metadata sent along with every lookup and write
+    shell: String,
+    model: String,
+    agent: ureq::Agent,
+}
+
+impl RemoteCache {
+    /// The server in `remote_cache_url`, if one is set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let url = config.remote_cache_url.as_deref()?.trim_end_matches('/').to_string();
+        let agent = http::agent_builder(config, &url).ok()?.timeout(TIMEOUT).build();
+        Some(Self {
+            url,
+            token: config.remote_cache_token.clone(),
+            shell: Shell::detect().to_shell_command_and_command_arg().0,
+            model: config.model.to_string(),
+            agent,
+        })
+    }
+
+    fn request(&self, method: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}/commands", self.url));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    /// The team's command for `prompt`; `Ok(None)` if the server has none.
+    pub fn get(&self, prompt: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let response = self
+            .request("GET")
+            .query("prompt", prompt)
+            .query("shell", &self.shell)
+            .query("model", &self.model)
+            .call();
+        match response {
+            Ok(response) => {
+                let found: Found = serde_json::from_str(&response.into_string()?)?;
+                Ok(Some(found.command).filter(|command| !command.trim().is_empty()))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Shares `command` for `prompt` with the team.
+    pub fn put(&self, prompt: &str, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let entry = Entry { prompt, command, shell: &self.shell, model: &self.model };
+        self.request("PUT")
+            .set("Content-Type", "application/json")
+            .send_string(&serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
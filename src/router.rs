@@ -0,0 +1,109 @@
+//! Cost-saving routing: ask a cheap model first and only go to the strong
+//! one when the cheap answer isn't usable.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::Model;
+use crate::provider::{Message, Role};
+use crate::{extract, Config};
+
+/// What the first model answers when it isn't sure; never shown to the user.
+const LOW_CONFIDENCE: &str = "LOW_CONFIDENCE";
+
+/// The `routing` section of the config.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Routing {
+    /// asked first for every request
+    #[serde(default = "default_first")]
+    pub first: Model,
+    /// asked when `first` fails, answers nothing usable or says it isn't confident
+    #[serde(default = "default_escalate_to")]
+    pub escalate_to: Model,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Self { first: default_first(), escalate_to: default_escalate_to() }
+    }
+}
+
+fn default_first() -> Model {
+    Model::OpenAiGpt4oMini
+}
+
+fn default_escalate_to() -> Model {
+    Model::OpenAiGpt4o
+}
+
+/// What kind of reply a request expects, to tell a malformed one apart.
+#[derive(Clone, Copy)]
+pub enum Reply {
+    /// a bare command, like `llm_get_command` asks for
+    Command,
+    /// a chat answer that may carry a `COMMAND:` marker
+    Conversation,
+}
+
+impl Reply {
+    /// The configured stop sequences, for bare commands; they'd cut a chat answer short.
+    pub(crate) fn stop_sequences(self, config: &Config) -> &[String] {
+        match self {
+            Reply::Command => &config.stop_sequences,
+            Reply::Conversation => &[],
+        }
+    }
+}
+
+impl Routing {
+    /// Sends `messages` to `first`, then to `escalate_to` if that answer
+    /// isn't good enough. The cheap answer is kept if escalating fails.
+    pub(crate) fn complete(
+        &self,
+        config: &Config,
+        max_tokens: i32,
+        temperature: f32,
+        messages: Vec<Message>,
+        reply: Reply,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let mut first_messages = messages.clone();
+        if let Some(system) = first_messages.iter_mut().find(|m| matches!(m.role, Role::System)) {
+            system.content.push_str(&format!(
+                "\n\nIf you aren't confident you can answer correctly, reply with only {} and nothing else.",
+                LOW_CONFIDENCE
+            ));
+        }
+
+        let first = self.first.chat_completion_until(config, max_tokens, temperature, first_messages, reply.stop_sequences(config));
+        if let Ok(Some(answer)) = &first {
+            if !needs_escalation(answer, reply) {
+                return first;
+            }
+        }
+
+        log::info!("escalating from={} to={}", self.first, self.escalate_to);
+        match self.escalate_to.chat_completion_until(config, max_tokens, temperature, messages, reply.stop_sequences(config)) {
+            Ok(Some(answer)) if !answer.trim().is_empty() => Ok(Some(answer)),
+            escalated => match first {
+                Ok(Some(answer)) if !answer.trim().is_empty() && !answer.contains(LOW_CONFIDENCE) => Ok(Some(answer)),
+                _ => escalated,
+            },
+        }
+    }
+}
+
+/// Whether `answer` from the first model is empty, unsure or not in the expected shape.
+fn needs_escalation(answer: &str, reply: Reply) -> bool {
+    let answer = answer.trim();
+    if answer.is_empty() || answer.contains(LOW_CONFIDENCE) {
+        return true;
+    }
+    match reply {
+        // the prompt asks for one bare command, no markdown or explanations
+        Reply::Command => {
+            let lower = answer.to_lowercase();
+            answer.contains("```") || answer.lines().filter(|l| !l.trim().is_empty()).count() > 1 || lower.starts_with("here") || lower.starts_with("sure")
+        }
+        // a `COMMAND:` marker without a command the chat can pick up
+        Reply::Conversation => answer.contains("COMMAND:") && extract::commands(answer).is_empty(),
+    }
+}
@@ -0,0 +1,137 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::{Cache, CommandGenerator, Executor};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// the model or the command failed
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct PromptParams {
+    prompt: String,
+}
+
+#[derive(Deserialize)]
+struct CommandParams {
+    command: String,
+    #[serde(default)]
+    working_dir: Option<PathBuf>,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// Answers line-delimited JSON-RPC 2.0 requests on stdin until it's closed.
+/// Each request and each response is one line of JSON; requests without an
+/// `id` are notifications and get no response.
+///
+/// Methods: `generate {prompt}`, `explain {command}` and
+/// `execute {command, working_dir?}`.
+pub fn run(generator: &CommandGenerator, cache: &mut Cache) -> Result<(), Box<dyn std::error::Error>> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Err(e) => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+            Ok(value) => match serde_json::from_value::<Request>(value) {
+                Err(e) => Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string()))),
+                Ok(request) => {
+                    let result = dispatch(generator, cache, &request);
+                    request.id.map(|id| match result {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(error) => error_response(id, error),
+                    })
+                }
+            },
+        };
+
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn dispatch(generator: &CommandGenerator, cache: &mut Cache, request: &Request) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "generate" => {
+            let params: PromptParams = params(&request.params)?;
+            match generator.generate_cached(cache, &params.prompt) {
+                Ok(Some((command, cached))) => Ok(json!({ "command": command.trim(), "cached": cached })),
+                Ok(None) => Err(RpcError::new(SERVER_ERROR, "No command could be generated.")),
+                Err(e) => Err(RpcError::new(SERVER_ERROR, e.to_string())),
+            }
+        }
+        "explain" => {
+            let params: CommandParams = params(&request.params)?;
+            match generator.explain(&params.command) {
+                Ok(Some(explanation)) => Ok(json!({ "explanation": explanation.trim() })),
+                Ok(None) => Err(RpcError::new(SERVER_ERROR, "No explanation could be generated.")),
+                Err(e) => Err(RpcError::new(SERVER_ERROR, e.to_string())),
+            }
+        }
+        "execute" => {
+            if generator.config().suggest_only {
+                return Err(RpcError::new(SERVER_ERROR, "Execution is disabled in suggest-only mode."));
+            }
+            let params: CommandParams = params(&request.params)?;
+            let mut executor = Executor::default().with_limits(generator.config().limits);
+            if let Some(dir) = params.working_dir {
+                executor = executor.in_dir(dir);
+            }
+            let execution = executor
+                .run(&params.command)
+                .map_err(|e| RpcError::new(SERVER_ERROR, format!("Failed to start command: {}", e)))?;
+            Ok(json!({
+                "stdout": execution.stdout,
+                "stderr": execution.stderr,
+                "exit_code": execution.status.code(),
+                "working_dir": execution.working_dir,
+            }))
+        }
+        method => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+    }
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: &Value) -> Result<T, RpcError> {
+    T::deserialize(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
@@ -0,0 +1,219 @@
+//! Runbooks: named lists of commands with what each is for and the exit
+//! code it should end with, recorded from the commands approved while
+//! `llm-term runbook record <name>` is on, and replayed step by step with
+//! `llm-term runbook run <name>`.
+//!
+//! They're kept as YAML in `~/.llm_term_runbooks/<name>.yaml`, to be read
+//! and edited by hand:
+//!
+//! ```yaml
+//! name: deploy
+//! description: Ship the web app to staging
+//! steps:
+//!   - description: build the image
+//!     command: docker build -t web .
+//!     expected_exit_code: 0
+//! ```
+//!
+//! Only this much YAML is understood: `key: value` pairs with plain,
+//! single- or double-quoted values, and the `steps` list.
+
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Runbook {
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// what the step is for, e.g. the prompt its command came from
+    pub description: String,
+    pub command: String,
+    pub expected_exit_code: i32,
+}
+
+/// Marks the runbook being recorded; holds its name.
+const RECORDING_FILE: &str = ".recording";
+
+fn runbooks_dir() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_runbooks");
+    Ok(path)
+}
+
+fn runbook_path(name: &str) -> std::io::Result<PathBuf> {
+    Ok(runbooks_dir()?.join(format!("{}.yaml", name)))
+}
+
+/// Names are used for file names: letters, digits, `-` and `_`.
+pub fn check_name(name: &str) -> Result<(), String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("`{}` can't be a runbook name; use letters, digits, - and _", name))
+    }
+}
+
+/// The names of the saved runbooks, sorted.
+pub fn list() -> Vec<String> {
+    let Ok(entries) = runbooks_dir().and_then(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".yaml").map(str::to_string))
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn load(name: &str) -> Result<Runbook, String> {
+    check_name(name)?;
+    let path = runbook_path(name).map_err(|e| e.to_string())?;
+    let text = fs::read_to_string(&path).map_err(|_| format!("No runbook `{}`; `llm-term runbook list` shows them", name))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn save(runbook: &Runbook) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    check_name(&runbook.name)?;
+    fs::create_dir_all(runbooks_dir()?)?;
+    let path = runbook_path(&runbook.name)?;
+    fs::write(&path, to_yaml(runbook))?;
+    Ok(path)
+}
+
+/// Starts recording the commands approved from now on into the runbook
+/// `name`, adding to it if it exists.
+pub fn start_recording(name: &str) -> Result<Runbook, Box<dyn std::error::Error>> {
+    check_name(name)?;
+    let runbook = load(name).unwrap_or_else(|_| Runbook { name: name.to_string(), ..Runbook::default() });
+    save(&runbook)?;
+    fs::write(runbooks_dir()?.join(RECORDING_FILE), name)?;
+    Ok(runbook)
+}
+
+/// The runbook being recorded, if one is.
+pub fn recording() -> Option<String> {
+    let name = fs::read_to_string(runbooks_dir().ok()?.join(RECORDING_FILE)).ok()?;
+    Some(name.trim().to_string()).filter(|name| check_name(name).is_ok())
+}
+
+/// Stops recording; the runbook as recorded, if one was.
+pub fn stop_recording() -> Option<Runbook> {
+    let name = recording()?;
+    let _ = fs::remove_file(runbooks_dir().ok()?.join(RECORDING_FILE));
+    load(&name).ok()
+}
+
+/// Adds a step to the runbook being recorded, if one is. Failures are
+/// logged; they're no reason to fail the command that was just run.
+pub fn record_step(description: &str, command: &str, exit_code: i32) {
+    let Some(name) = recording() else {
+        return;
+    };
+    let mut runbook = load(&name).unwrap_or_else(|_| Runbook { name: name.clone(), ..Runbook::default() });
+    runbook.steps.push(Step { description: description.to_string(), command: command.to_string(), expected_exit_code: exit_code });
+    if let Err(e) = save(&runbook) {
+        log::warn!("Can't add the step to runbook {}: {}", name, e);
+    }
+}
+
+/// `runbook` as YAML. Values are double-quoted, which YAML reads like JSON strings.
+pub fn to_yaml(runbook: &Runbook) -> String {
+    let quote = |text: &str| serde_json::to_string(text).unwrap_or_default();
+    let mut yaml = format!("# replay with `llm-term runbook run {}`\nname: {}\n", runbook.name, runbook.name);
+    if let Some(description) = &runbook.description {
+        yaml.push_str(&format!("description: {}\n", quote(description)));
+    }
+    yaml.push_str("steps:\n");
+    for step in &runbook.steps {
+        yaml.push_str(&format!("  - description: {}\n", quote(&step.description)));
+        yaml.push_str(&format!("    command: {}\n", quote(&step.command)));
+        yaml.push_str(&format!("    expected_exit_code: {}\n", step.expected_exit_code));
+    }
+    yaml
+}
+
+/// Reads the YAML `to_yaml` writes, as edited by hand.
+pub fn parse(text: &str) -> Result<Runbook, String> {
+    let mut runbook = Runbook::default();
+    let mut in_steps = false;
+    let mut step: Option<PartialStep> = None;
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indented = raw.starts_with([' ', '\t']);
+        let (new_item, pair) = match trimmed.strip_prefix("- ") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, trimmed),
+        };
+        let (key, value) = pair.split_once(':').ok_or_else(|| format!("line {}: expected `key: value`", line_no))?;
+        let (key, value) = (key.trim(), scalar(value.trim()).map_err(|e| format!("line {}: {}", line_no, e))?);
+
+        if !indented && !new_item {
+            finish_step(step.take(), &mut runbook.steps, line_no)?;
+            in_steps = false;
+            match key {
+                "name" => runbook.name = value,
+                "description" => runbook.description = Some(value).filter(|d| !d.is_empty()),
+                "steps" => in_steps = true,
+                _ => return Err(format!("line {}: unknown key `{}`", line_no, key)),
+            }
+            continue;
+        }
+        if !in_steps {
+            return Err(format!("line {}: steps go under `steps:`", line_no));
+        }
+        if new_item {
+            finish_step(step.take(), &mut runbook.steps, line_no)?;
+            step = Some((None, None, 0));
+        }
+        let Some((description, command, expected)) = step.as_mut() else {
+            return Err(format!("line {}: a step starts with `- `", line_no));
+        };
+        match key {
+            "description" => *description = Some(value),
+            "command" => *command = Some(value),
+            "expected_exit_code" => *expected = value.parse().map_err(|_| format!("line {}: `{}` isn't an exit code", line_no, value))?,
+            _ => return Err(format!("line {}: unknown step key `{}`", line_no, key)),
+        }
+    }
+    finish_step(step, &mut runbook.steps, text.lines().count() + 1)?;
+    if runbook.name.is_empty() {
+        return Err("the runbook has no `name`".to_string());
+    }
+    Ok(runbook)
+}
+
+/// A step as far as it's been read: description, command and expected exit code.
+type PartialStep = (Option<String>, Option<String>, i32);
+
+fn finish_step(step: Option<PartialStep>, steps: &mut Vec<Step>, next_line: usize) -> Result<(), String> {
+    let Some((description, command, expected_exit_code)) = step else {
+        return Ok(());
+    };
+    let command = command.ok_or_else(|| format!("the step before line {} has no `command`", next_line))?;
+    steps.push(Step { description: description.unwrap_or_default(), command, expected_exit_code });
+    Ok(())
+}
+
+/// The value of a plain, `'single'` or `"double"` quoted YAML scalar.
+fn scalar(value: &str) -> Result<String, String> {
+    if value.starts_with('"') {
+        return serde_json::from_str(value).map_err(|_| format!("can't read the quoted value {}", value));
+    }
+    if let Some(inner) = value.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+        return Ok(inner.replace("''", "'"));
+    }
+    // a comment after a plain value
+    let value = value.find(" #").map_or(value, |at| &value[..at]);
+    Ok(value.trim().to_string())
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// A command that runs on a fixed interval.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Schedule {
+    pub id: String,
+    /// what the user asked for, e.g. "check disk usage every hour"
+    pub description: String,
+    pub command: String,
+    pub interval_secs: u64,
+    pub enabled: bool,
+    pub next_run: DateTime<Utc>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_output: Option<String>,
+}
+
+impl Schedule {
+    pub fn new(description: String, command: String, interval_secs: u64) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            description,
+            command,
+            interval_secs,
+            enabled: true,
+            next_run: Utc::now() + Duration::seconds(interval_secs as i64),
+            last_run: None,
+            last_output: None,
+        }
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.next_run <= now
+    }
+
+    /// Records a run that started at `now` and moves `next_run` forward.
+    pub fn mark_ran(&mut self, now: DateTime<Utc>) {
+        self.last_run = Some(now);
+        self.next_run = now + Duration::seconds(self.interval_secs as i64);
+    }
+
+    /// Equivalent crontab line, for running without the GUI.
+    pub fn cron_entry(&self) -> String {
+        let minutes = (self.interval_secs / 60).max(1);
+        let spec = match minutes {
+            m if m < 60 => format!("*/{} * * * *", m),
+            m if m < 24 * 60 => format!("0 */{} * * *", m / 60),
+            m => format!("0 0 */{} * *", m / (24 * 60)),
+        };
+        format!("{} {}", spec, self.command)
+    }
+
+    /// Equivalent Windows Task Scheduler registration.
+    pub fn task_scheduler_entry(&self) -> String {
+        let minutes = (self.interval_secs / 60).max(1);
+        let (unit, modifier) = match minutes {
+            m if m < 60 => ("MINUTE", m),
+            m if m < 24 * 60 => ("HOURLY", m / 60),
+            m => ("DAILY", m / (24 * 60)),
+        };
+        format!(
+            "schtasks /Create /SC {} /MO {} /TN \"llm-term {}\" /TR \"{}\"",
+            unit,
+            modifier,
+            &self.id[..8.min(self.id.len())],
+            self.command.replace('"', "\\\"")
+        )
+    }
+}
+
+/// Extracts the repeat interval from phrases like "every hour",
+/// "every 15 minutes", "hourly" or "every 2 days". Returns seconds.
+pub fn parse_interval(text: &str) -> Option<u64> {
+    let text = text.to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    for (idx, word) in words.iter().enumerate() {
+        match *word {
+            "hourly" => return Some(3600),
+            "daily" => return Some(24 * 3600),
+            "weekly" => return Some(7 * 24 * 3600),
+            "every" => {
+                let (count, unit) = match words.get(idx + 1).and_then(|w| w.parse::<u64>().ok()) {
+                    Some(count) => (count, words.get(idx + 2)?),
+                    None => (1, words.get(idx + 1)?),
+                };
+                return unit_seconds(unit).map(|secs| secs * count.max(1));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unit_seconds(unit: &str) -> Option<u64> {
+    match unit.trim_end_matches('s') {
+        "second" | "sec" => Some(1),
+        "minute" | "min" => Some(60),
+        "hour" | "hr" => Some(3600),
+        "day" => Some(24 * 3600),
+        "week" => Some(7 * 24 * 3600),
+        _ => None,
+    }
+}
+
+/// Human readable form of an interval, e.g. "every 2 hours".
+pub fn describe_interval(secs: u64) -> String {
+    let (count, unit) = match secs {
+        s if s % (24 * 3600) == 0 => (s / (24 * 3600), "day"),
+        s if s % 3600 == 0 => (s / 3600, "hour"),
+        s if s % 60 == 0 => (s / 60, "minute"),
+        s => (s, "second"),
+    };
+    match count {
+        1 => format!("every {}", unit),
+        n => format!("every {} {}s", n, unit),
+    }
+}
+
+pub fn schedules_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_schedules.json");
+    Ok(path)
+}
+
+pub fn load_schedules() -> std::io::Result<Vec<Schedule>> {
+    let path = schedules_file_path()?;
+    if path.exists() {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+pub fn save_schedules(schedules: &[Schedule]) -> std::io::Result<()> {
+    let path = schedules_file_path()?;
+    let content = serde_json::to_string_pretty(schedules)?;
+    fs::write(path, content)
+}
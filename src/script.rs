@@ -0,0 +1,94 @@
+//! Multi-line scripts suggested by the model, run from a temporary file.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::shell::{self, Shell};
+
+/// Marks a script in an answer; a fenced code block naming the language follows.
+pub const SCRIPT_MARKER: &str = "SCRIPT:";
+
+#[derive(Clone, Debug)]
+pub struct Script {
+    /// lower case language from the code fence, e.g. "bash" or "python"
+    pub language: String,
+    pub body: String,
+}
+
+impl Script {
+    /// The script after [`SCRIPT_MARKER`] in `response`, if there is one.
+    pub fn extract(response: &str) -> Option<Self> {
+        let rest = &response[response.find(SCRIPT_MARKER)? + SCRIPT_MARKER.len()..];
+        let rest = &rest[rest.find("```")? + 3..];
+        let (info, rest) = rest.split_once('\n')?;
+        let end = rest.find("\n```").map_or_else(|| rest.find("```"), Some)?;
+        let body = rest[..end].trim_end().to_string();
+        if body.trim().is_empty() {
+            return None;
+        }
+
+        let language = match info.trim().to_ascii_lowercase() {
+            language if language.is_empty() => default_language(&Shell::detect()).to_string(),
+            language => language,
+        };
+        Some(Self { language, body })
+    }
+
+    /// Program that runs the script and the file extension it expects.
+    pub fn interpreter(&self) -> Option<(&'static str, &'static str)> {
+        let python = if cfg!(target_os = "windows") { "python" } else { "python3" };
+        Some(match self.language.as_str() {
+            "bash" | "shell" => ("bash", "sh"),
+            "sh" => ("sh", "sh"),
+            "zsh" => ("zsh", "zsh"),
+            "fish" => ("fish", "fish"),
+            "ksh" => ("ksh", "ksh"),
+            "dash" => ("dash", "sh"),
+            "pwsh" => ("pwsh -NoProfile -ExecutionPolicy Bypass -File", "ps1"),
+            "powershell" | "ps1" if shell::powershell_program() == "pwsh" => ("pwsh -NoProfile -ExecutionPolicy Bypass -File", "ps1"),
+            "powershell" | "ps1" => ("powershell -NoProfile -ExecutionPolicy Bypass -File", "ps1"),
+            "python" | "python3" | "py" => (python, "py"),
+            "node" | "javascript" | "js" => ("node", "js"),
+            "ruby" | "rb" => ("ruby", "rb"),
+            "perl" | "pl" => ("perl", "pl"),
+            _ => return None,
+        })
+    }
+
+    /// Writes the script to a new file in the temp directory and returns its path.
+    pub fn save_temp(&self) -> io::Result<PathBuf> {
+        let (_, extension) = self
+            .interpreter()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, format!("don't know how to run {} scripts", self.language)))?;
+        let path = std::env::temp_dir().join(format!("llm-term-script-{}.{}", uuid::Uuid::new_v4(), extension));
+        fs::write(&path, format!("{}\n", self.body))?;
+        Ok(path)
+    }
+
+    /// Command line for `shell` that runs the script saved at `path`.
+    pub fn command_line(&self, path: &Path, shell: &Shell) -> Option<String> {
+        let (interpreter, _) = self.interpreter()?;
+        let path = path.to_string_lossy();
+        let quoted = match shell {
+            Shell::Powershell => format!("'{}'", path.replace('\'', "''")),
+            _ => format!("'{}'", path.replace('\'', "'\\''")),
+        };
+        Some(match shell {
+            // a quoted program name would be a plain string to PowerShell
+            Shell::Powershell => format!("& {} {}", interpreter, quoted),
+            _ => format!("{} {}", interpreter, quoted),
+        })
+    }
+}
+
+/// Script language used when the code fence doesn't name one.
+fn default_language(shell: &Shell) -> &'static str {
+    match shell {
+        Shell::Powershell => "powershell",
+        Shell::Zsh => "zsh",
+        Shell::Fish => "fish",
+        Shell::KornShell => "ksh",
+        _ => "bash",
+    }
+}
@@ -0,0 +1,291 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{history, stats, trash, Cache, CommandGenerator, Executor};
+
+/// Requests with a larger body are rejected.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Which commands `POST /execute` is allowed to run.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExecutePolicy {
+    /// execution is disabled
+    Never,
+    /// only commands that are in the history, i.e. were generated before
+    Generated,
+    /// anything the client sends
+    Any,
+}
+
+impl ExecutePolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "never" => Some(ExecutePolicy::Never),
+            "generated" => Some(ExecutePolicy::Generated),
+            "any" => Some(ExecutePolicy::Any),
+            _ => None,
+        }
+    }
+}
+
+struct Server {
+    generator: CommandGenerator,
+    cache: Mutex<Cache>,
+    token: String,
+    policy: ExecutePolicy,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct GenerateRequest {
+    prompt: String,
+}
+
+#[derive(Deserialize)]
+struct ExecuteRequest {
+    command: String,
+    #[serde(default)]
+    working_dir: Option<PathBuf>,
+}
+
+/// Where the token of a running server is written, for scripts on the same machine.
+fn token_file_path() -> io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_serve_token");
+    Ok(path)
+}
+
+/// Serves the REST API on 127.0.0.1:`port` until the process is stopped.
+/// Every request needs `Authorization: Bearer <token>`; without a `token`
+/// a random one is generated and written to `~/.llm_term_serve_token`.
+pub fn run(
+    generator: CommandGenerator,
+    cache: Cache,
+    port: u16,
+    token: Option<String>,
+    policy: ExecutePolicy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let token = token.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let token_file = token_file_path()?;
+    write_token(&token_file, &token)?;
+
+    println!("Listening on http://{}", listener.local_addr()?);
+    println!("Token written to {}", token_file.display());
+
+    let server = Arc::new(Server { generator, cache: Mutex::new(cache), token, policy });
+    for stream in listener.incoming().flatten() {
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            let _ = handle_connection(&server, stream);
+        });
+    }
+    Ok(())
+}
+
+/// Writes the token readable by this user only.
+fn write_token(path: &Path, token: &str) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // an older file keeps its mode on open
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(token.as_bytes())
+}
+
+/// Compares in time that depends only on the lengths, so the token can't be
+/// guessed byte by byte from response times.
+fn same_token(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn handle_connection(server: &Server, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let (status, body) = match read_request(&stream) {
+        Ok(request) => server.handle(&request),
+        Err(e) => (400, json!({ "error": e.to_string() })),
+    };
+    respond(&mut stream, status, &body)
+}
+
+impl Server {
+    fn handle(&self, request: &Request) -> (u16, serde_json::Value) {
+        let expected = format!("Bearer {}", self.token);
+        if !request.authorization.as_deref().is_some_and(|given| same_token(given.as_bytes(), expected.as_bytes())) {
+            return (401, json!({ "error": "missing or wrong bearer token" }));
+        }
+
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/generate") => match serde_json::from_slice::<GenerateRequest>(&request.body) {
+                Ok(body) => self.generate(&body.prompt),
+                Err(e) => (400, json!({ "error": e.to_string() })),
+            },
+            ("POST", "/execute") => match serde_json::from_slice::<ExecuteRequest>(&request.body) {
+                Ok(body) => self.execute(body),
+                Err(e) => (400, json!({ "error": e.to_string() })),
+            },
+            ("GET", "/history") => self.history(),
+            (_, "/generate" | "/execute" | "/history") => (405, json!({ "error": "method not allowed" })),
+            _ => (404, json!({ "error": "not found" })),
+        }
+    }
+
+    fn generate(&self, prompt: &str) -> (u16, serde_json::Value) {
+        let cached = self.cache.lock().unwrap().lookup(prompt);
+        stats::record_cache_lookup(cached.is_some());
+        if let Some(command) = cached {
+            return (200, json!({ "command": command, "cached": true }));
+        }
+
+        // don't hold the lock while waiting for the model
+        match self.generator.generate(prompt) {
+            Ok(Some(command)) if !command.trim().is_empty() => {
+                let command = command.trim().to_string();
+                let mut cache = self.cache.lock().unwrap();
+                cache.insert(prompt, command.clone());
+                if let Err(e) = cache.save() {
+                    eprintln!("Failed to save the cache: {}", e);
+                }
+                (200, json!({ "command": command, "cached": false }))
+            }
+            Ok(_) => (422, json!({ "error": "No command could be generated." })),
+            Err(e) => (502, json!({ "error": e.to_string() })),
+        }
+    }
+
+    fn execute(&self, request: ExecuteRequest) -> (u16, serde_json::Value) {
+        // the prompt it was generated for, for the history
+        let prompt = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, command)| *command == request.command)
+            .map(|(prompt, _)| prompt.to_string())
+            .or_else(|| history::load().into_iter().rev().find(|entry| entry.command == request.command).map(|entry| entry.prompt));
+        let allowed = match self.policy {
+            ExecutePolicy::Never => false,
+            ExecutePolicy::Generated => prompt.is_some(),
+            ExecutePolicy::Any => true,
+        };
+        if !allowed {
+            return (403, json!({ "error": "the execute policy doesn't allow this command" }));
+        }
+        // nobody is there to confirm it, like in batch mode
+        if let Some(reason) = crate::unattended_refusal(&request.command) {
+            return (403, json!({ "error": format!("not running it: {}", reason) }));
+        }
+
+        let config = self.generator.config();
+        let mut executor = Executor::default().with_limits(config.limits);
+        if let Some(dir) = request.working_dir {
+            executor = executor.in_dir(dir);
+        }
+        let trashed = config.use_trash.then(|| trash::rewrite(&request.command, executor.shell())).flatten();
+        if trashed.is_none() && config.use_trash && trash::deletes(&request.command, executor.shell()) {
+            let reason = trash::not_rewritten_reason(&request.command, executor.shell());
+            return (403, json!({ "error": format!("not moving to the trash: {}", reason) }));
+        }
+
+        history::record(prompt.as_deref().unwrap_or("(sent to serve)"), &request.command);
+        match executor.run(trashed.as_deref().unwrap_or(&request.command)) {
+            Ok(execution) => {
+                history::record_outcome(&request.command, execution.status.code(), &execution.stderr);
+                (200, json!({
+                    "stdout": execution.stdout,
+                    "stderr": execution.stderr,
+                    "exit_code": execution.status.code(),
+                    "working_dir": execution.working_dir,
+                }))
+            }
+            Err(e) => (500, json!({ "error": format!("Failed to start command: {}", e) })),
+        }
+    }
+
+    /// The commands that were run, newest first.
+    fn history(&self) -> (u16, serde_json::Value) {
+        let mut entries = history::load();
+        entries.reverse();
+        (200, json!(entries))
+    }
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("empty request"))?.to_string();
+    let path = parts.next().ok_or_else(|| invalid("no request path"))?;
+    let path = path.split('?').next().unwrap_or(path).to_string();
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().map_err(|_| invalid("bad Content-Length"))?,
+                "authorization" => authorization = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(invalid("request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { method, path, authorization, body })
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &serde_json::Value) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        502 => "Bad Gateway",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body
+    )?;
+    stream.flush()
+}
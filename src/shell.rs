@@ -1,5 +1,13 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// Printed after a wrapped command, followed by the shell's working directory.
+pub const CWD_MARKER: &str = "__LLM_TERM_CWD__";
+
 /// Enum representing different types of shells.
-pub(crate) enum Shell {
+pub enum Shell {
     Powershell,
     BornAgainShell,
     Zsh,
@@ -13,7 +21,7 @@ pub(crate) enum Shell {
 impl From<&str> for Shell {
     fn from(shell: &str) -> Self {
         match shell {
-            s if s.contains("powershell") => Shell::Powershell,
+            s if s.contains("powershell") || s.contains("pwsh") => Shell::Powershell,
             s if s.contains("bash") => Shell::BornAgainShell,
             s if s.contains("zsh") => Shell::Zsh,
             s if s.contains("fish") => Shell::Fish,
@@ -40,7 +48,7 @@ impl Shell {
     /// Converts the shell type to a shell command and a command argument.
     pub fn to_shell_command_and_command_arg(&self) -> (String, String) {
         match self {
-            Shell::Powershell => ("powershell".to_string(), "-Command".to_string()),
+            Shell::Powershell => (powershell_program().to_string(), "-Command".to_string()),
             Shell::BornAgainShell => ("sh".to_string(), "-c".to_string()),
             Shell::Zsh => ("zsh".to_string(), "-c".to_string()),
             Shell::Fish => ("fish".to_string(), "-c".to_string()),
@@ -50,4 +58,189 @@ impl Shell {
             Shell::Unknown => ("sh".to_string(), "-c".to_string()),
         }
     }
+
+    /// Version of the program that runs commands, e.g. "3.2.57" for bash on
+    /// macOS. Looked up once per program; `None` if it doesn't say.
+    pub fn version(&self) -> Option<String> {
+        static VERSIONS: Mutex<Option<HashMap<String, Option<String>>>> = Mutex::new(None);
+        let (program, _) = self.to_shell_command_and_command_arg();
+        let mut versions = VERSIONS.lock().unwrap_or_else(|e| e.into_inner());
+        versions
+            .get_or_insert_with(HashMap::new)
+            .entry(program.clone())
+            .or_insert_with(|| {
+                let mut command = Command::new(&program);
+                match self {
+                    Shell::Powershell => command.args(["-NoProfile", "-Command", "$PSVersionTable.PSVersion.ToString()"]),
+                    _ => command.arg("--version"),
+                };
+                let output = command.output().ok()?;
+                let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+                parse_version(&text)
+            })
+            .clone()
+    }
+
+    /// Extra guidance for the model about this shell's dialect and version;
+    /// empty if there is none.
+    pub fn prompt_note(&self) -> String {
+        let dialect = match self {
+            Shell::Powershell if powershell_program() == "pwsh" => {
+                "Commands run in PowerShell 7 (pwsh), which has no Get-WmiObject or other Windows PowerShell 5.1-only cmdlets; use Get-CimInstance instead."
+            }
+            Shell::Powershell => {
+                "Commands run in Windows PowerShell 5.1: don't use PowerShell 7-only syntax such as && and || between commands, the ternary operator, ?? or ForEach-Object -Parallel."
+            }
+            _ => "",
+        };
+        match self.version() {
+            Some(version) => format!(
+                "{} The shell is {} {}; only use features available in that version.",
+                dialect,
+                self.to_shell_command_and_command_arg().0,
+                version
+            )
+            .trim_start()
+            .to_string(),
+            None => dialect.to_string(),
+        }
+    }
+
+    /// Appends a trailer to `command` reporting the directory the shell ends up in,
+    /// so state like `cd` can be carried over to the next command.
+    /// The shell exits with the command's exit status.
+    pub fn wrap_with_cwd_report(&self, command: &str) -> String {
+        match self {
+            // a failed cmdlet clears $? without setting $LASTEXITCODE
+            Shell::Powershell => format!(
+                "{}\n$__llm_term_status = if ($?) {{ 0 }} elseif ($LASTEXITCODE) {{ $LASTEXITCODE }} else {{ 1 }}; Write-Output \"`n{}$((Get-Location).Path)\"; exit $__llm_term_status",
+                command, CWD_MARKER
+            ),
+            Shell::Fish => format!("{}\nset -l __llm_term_status $status; printf '\\n{}%s' \"$PWD\"; exit $__llm_term_status", command, CWD_MARKER),
+            Shell::CShell => format!("{}\nset __llm_term_status = $status; printf '\\n{}%s' \"$cwd\"; exit $__llm_term_status", command, CWD_MARKER),
+            _ => format!("{}\n__llm_term_status=$?; printf '\\n{}%s' \"$PWD\"; exit $__llm_term_status", command, CWD_MARKER),
+        }
+    }
+
+    /// Rewrites `command` so everything it prints to stdout goes to `path`.
+    /// The command is grouped first so pipelines and `;` sequences are
+    /// redirected as a whole.
+    pub fn redirect_to_file(&self, command: &str, path: &Path) -> String {
+        let path = path.to_string_lossy();
+        match self {
+            Shell::Powershell => format!("& {{ {} }} | Out-File -FilePath '{}' -Encoding utf8", command, path.replace('\'', "''")),
+            Shell::Fish => format!("begin; {}; end > '{}'", command, path.replace('\\', "\\\\").replace('\'', "\\'")),
+            Shell::CShell => format!("( {} ) > '{}'", command, path.replace('\'', "'\\''")),
+            _ => format!("{{ {}\n}} > '{}'", command, path.replace('\'', "'\\''")),
+        }
+    }
+}
+
+/// PowerShell 7 (`pwsh`) when it's installed, otherwise Windows PowerShell 5.1.
+pub fn powershell_program() -> &'static str {
+    static PROGRAM: OnceLock<&'static str> = OnceLock::new();
+    PROGRAM.get_or_init(|| if on_path("pwsh") { "pwsh" } else { "powershell" })
+}
+
+/// Name of the PowerShell edition in use, for the model.
+pub fn powershell_name() -> &'static str {
+    if powershell_program() == "pwsh" {
+        "PowerShell 7 (pwsh)"
+    } else {
+        "Windows PowerShell 5.1"
+    }
+}
+
+/// First dotted number in `text`, e.g. "5.2.15" from "GNU bash, version 5.2.15(1)-release".
+fn parse_version(text: &str) -> Option<String> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|word| word.trim_matches('.'))
+        .find(|word| word.contains('.') && word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+/// Whether `program` is an executable in one of the `PATH` directories.
+pub fn on_path(program: &str) -> bool {
+    // Windows programs may be shims, like scoop's scoop.cmd
+    let files: Vec<String> = if cfg!(target_os = "windows") {
+        ["exe", "cmd", "bat"].iter().map(|extension| format!("{}.{}", program, extension)).collect()
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| files.iter().any(|file| dir.join(file).is_file())))
+}
+
+/// The words of each simple command in `command`, split at `;`, `&&`, `||` and `|`.
+pub fn simple_commands(command: &str) -> Vec<Vec<&str>> {
+    command
+        .split([';', '|', '&', '\n', '(', ')'])
+        .map(|part| part.split_whitespace().collect::<Vec<_>>())
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+/// The words of `step`, with quoted ones (`"my file.txt"`) kept whole, quotes included.
+pub fn words(step: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    for (i, c) in step.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                start.get_or_insert(i);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push(&step[start..i]);
+                }
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(start) = start {
+        words.push(&step[start..]);
+    }
+    words
+}
+
+/// The program a command word runs, without its directory and Windows
+/// extension: `/usr/bin/aws` and `az.cmd` become `aws` and `az`.
+pub fn program_name(word: &str) -> &str {
+    let name = word.rsplit(['/', '\\']).next().unwrap_or(word);
+    [".exe", ".cmd", ".bat"].iter().find_map(|extension| name.strip_suffix(extension)).unwrap_or(name)
+}
+
+/// The value of the first of `flags` in `words`, as `--flag value` or `--flag=value`.
+pub fn flag_value(words: &[&str], flags: &[&str]) -> Option<String> {
+    words.iter().enumerate().find_map(|(i, word)| {
+        flags.iter().find_map(|flag| match word.strip_prefix(flag) {
+            Some("") => words.get(i + 1).map(|value| value.trim_matches(['"', '\'']).to_string()),
+            Some(value) => value.strip_prefix('=').map(|value| value.trim_matches(['"', '\'']).to_string()),
+            None => None,
+        })
+    })
+}
+
+/// Returns true if `command` already sends its output somewhere other than stdout.
+pub fn redirects_output(command: &str) -> bool {
+    command.contains('>') || command.contains("| tee") || command.contains("Out-File") || command.contains("Set-Content")
+}
+
+/// Removes the trailer added by [`Shell::wrap_with_cwd_report`] from `stdout`,
+/// returning the remaining output and the reported working directory.
+pub fn split_cwd_report(stdout: &str) -> (String, Option<PathBuf>) {
+    match stdout.rfind(CWD_MARKER) {
+        Some(pos) => {
+            let dir = stdout[pos + CWD_MARKER.len()..].lines().next().unwrap_or("").trim();
+            let output = stdout[..pos].strip_suffix('\n').unwrap_or(&stdout[..pos]);
+            let output = output.strip_suffix('\r').unwrap_or(output);
+            let dir = (!dir.is_empty()).then(|| PathBuf::from(dir));
+            (output.to_string(), dir)
+        }
+        None => (stdout.to_string(), None),
+    }
 }
\ No newline at end of file
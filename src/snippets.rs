@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// A saved command, optionally with `{placeholder}` or `<PLACEHOLDER>` parameters.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snippet {
+    pub name: String,
+    pub description: String,
+    pub command: String,
+}
+
+impl Snippet {
+    /// Names of the `{placeholder}`s in the command, in order of appearance.
+    pub fn placeholders(&self) -> Vec<String> {
+        placeholders(&self.command)
+    }
+
+    /// Substitutes `values` into the command. Fails with the list of
+    /// placeholders that have no (or an empty) value.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, Vec<String>> {
+        render(&self.command, values)
+    }
+}
+
+/// Names of the `{placeholder}`s and `<PLACEHOLDER>`s in `text`, in order of appearance.
+pub fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, _, name) in placeholder_spans(text) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Substitutes `values` into the placeholders of `text`. Fails with the
+/// list of placeholders that have no (or an empty) value.
+pub fn render(text: &str, values: &HashMap<String, String>) -> Result<String, Vec<String>> {
+    let missing: Vec<String> = placeholders(text)
+        .into_iter()
+        .filter(|name| values.get(name).is_none_or(|v| v.is_empty()))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    Ok(fill(text, values))
+}
+
+/// Substitutes the non-empty `values` into `text`, leaving other placeholders as they are.
+pub fn fill(text: &str, values: &HashMap<String, String>) -> String {
+    let mut rendered = String::new();
+    let mut last = 0;
+    for (start, end, name) in placeholder_spans(text) {
+        rendered.push_str(&text[last..start]);
+        match values.get(name).filter(|value| !value.is_empty()) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&text[start..end]),
+        }
+        last = end;
+    }
+    rendered.push_str(&text[last..]);
+    rendered
+}
+
+/// Finds `{name}` and `<NAME>` placeholders, returning the byte range of each
+/// placeholder and its name, in order.
+///
+/// Shell syntax that also uses braces is left alone: `${VAR}` (preceded by `$`),
+/// brace expansion like `{a,b}` and awk/find bodies such as `{print $1}` or `{}`
+/// don't consist of a single identifier. Angle brackets only count around an
+/// upper case name, so redirections and heredocs (`<<EOF`) aren't mistaken for one.
+fn placeholder_spans(command: &str) -> Vec<(usize, usize, &str)> {
+    let mut spans = Vec::new();
+    let bytes = command.as_bytes();
+
+    for (open, close) in [('{', '}'), ('<', '>')] {
+        for (start, _) in command.match_indices(open) {
+            if start > 0 && matches!(bytes[start - 1], b'$' | b'<') {
+                continue;
+            }
+            let Some(len) = command[start + 1..].find(close) else {
+                continue;
+            };
+            let name = &command[start + 1..start + 1 + len];
+            let is_identifier = match open {
+                '{' => name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                _ => {
+                    name.starts_with(|c: char| c.is_ascii_uppercase())
+                        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_' || c == '-')
+                }
+            };
+            if !name.is_empty() && is_identifier {
+                spans.push((start, start + len + 2, name));
+            }
+        }
+    }
+    spans.sort_by_key(|&(start, _, _)| start);
+    spans
+}
+
+pub fn snippets_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_snippets.json");
+    Ok(path)
+}
+
+pub fn load_snippets() -> std::io::Result<Vec<Snippet>> {
+    let path = snippets_file_path()?;
+    if path.exists() {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+pub fn save_snippets(snippets: &[Snippet]) -> std::io::Result<()> {
+    let path = snippets_file_path()?;
+    let content = serde_json::to_string_pretty(snippets)?;
+    fs::write(path, content)
+}
+
+/// Adds `snippet`, replacing any existing snippet with the same name.
+pub fn upsert(snippets: &mut Vec<Snippet>, snippet: Snippet) {
+    match snippets.iter_mut().find(|s| s.name == snippet.name) {
+        Some(existing) => *existing = snippet,
+        None => snippets.push(snippet),
+    }
+}
+
+/// Parses `--key value` / `--key=value` pairs given after a snippet name.
+pub fn parse_params(args: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let Some(key) = arg.strip_prefix("--") else {
+            return Err(format!("Unexpected argument `{}`; use --name value", arg));
+        };
+        match key.split_once('=') {
+            Some((key, value)) => {
+                values.insert(key.to_string(), value.to_string());
+            }
+            None => {
+                let value = iter.next().ok_or_else(|| format!("Missing value for --{}", key))?;
+                values.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+    Ok(values)
+}
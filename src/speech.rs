@@ -0,0 +1,239 @@
+//! Answers read aloud, by the system's speech synthesizer (`say`,
+//! `spd-say`, `espeak`, or Windows' System.Speech) or OpenAI's text to speech.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{http, shell, Config};
+
+const SPEECH_URL: &str = "https://api.openai.com/v1/audio/speech";
+
+/// Words per minute of `say` and `espeak` at speed 1.0.
+const DEFAULT_WORDS_PER_MINUTE: f32 = 175.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Speech {
+    /// read conversational answers aloud in the GUI; each chat can be muted
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub engine: SpeechEngine,
+    /// 1.0 is normal, 2.0 twice as fast
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Self { enabled: false, engine: SpeechEngine::default(), speed: default_speed() }
+    }
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SpeechEngine {
+    /// whatever the operating system has
+    #[default]
+    #[serde(rename = "system")]
+    System,
+    /// OpenAI's `tts-1`, with `OPENAI_API_KEY`; `voice` is e.g. "alloy" or "nova"
+    #[serde(rename = "openai")]
+    OpenAi {
+        #[serde(default = "default_voice")]
+        voice: String,
+    },
+}
+
+fn default_voice() -> String {
+    "alloy".to_string()
+}
+
+/// Text being read aloud. Dropping it stops the reading.
+pub struct Speaking {
+    child: Arc<Mutex<Option<Child>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Speaking {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(mut child) = self.child.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Whether it's still being read, or fetched to be.
+    pub fn is_speaking(&self) -> bool {
+        if self.stopped.load(Ordering::SeqCst) {
+            return false;
+        }
+        match self.child.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            // an OpenAI reading whose audio is still on its way
+            None => true,
+        }
+    }
+}
+
+impl Drop for Speaking {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts reading `text` aloud in the background. Errors, like a missing
+/// synthesizer, are logged: a reading that doesn't happen is no reason to interrupt.
+pub fn speak(config: &Config, text: &str) -> Speaking {
+    let speaking = Speaking { child: Arc::new(Mutex::new(None)), stopped: Arc::new(AtomicBool::new(false)) };
+    let (child, stopped) = (speaking.child.clone(), speaking.stopped.clone());
+    let (config, text) = (config.clone(), readable(text));
+    std::thread::spawn(move || {
+        if text.is_empty() {
+            stopped.store(true, Ordering::SeqCst);
+            return;
+        }
+        let started = match &config.speech.engine {
+            SpeechEngine::System => speak_system(&text, config.speech.speed),
+            SpeechEngine::OpenAi { voice } => speak_openai(&config, &text, voice),
+        };
+        match started {
+            Ok(mut process) => {
+                // checked under the lock `stop` takes, so a stop can't slip in between
+                let mut child = child.lock().unwrap_or_else(|e| e.into_inner());
+                if stopped.load(Ordering::SeqCst) {
+                    // stopped while the audio was fetched
+                    let _ = process.kill();
+                    let _ = process.wait();
+                } else {
+                    *child = Some(process);
+                }
+            }
+            Err(e) => {
+                log::warn!("Can't read the answer aloud: {}", e);
+                stopped.store(true, Ordering::SeqCst);
+            }
+        }
+    });
+    speaking
+}
+
+/// `text` as it should be heard: without code blocks, which are read badly
+/// and are on screen anyway, and without Markdown markup.
+pub fn readable(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || line.trim_start().starts_with("COMMAND:") {
+            continue;
+        }
+        let line = line.trim_start_matches(['#', '>', ' ']).trim_start_matches("- ");
+        out.push_str(&line.replace(['*', '`', '_'], ""));
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+/// Starts the system synthesizer on `text`, which it reads from stdin.
+fn speak_system(text: &str, speed: f32) -> Result<Child, String> {
+    let words_per_minute = ((DEFAULT_WORDS_PER_MINUTE * speed).round() as u32).to_string();
+    let mut command = if cfg!(target_os = "windows") {
+        // System.Speech rates go from -10 to 10; each step is about 10% faster
+        let rate = ((speed.max(0.1).ln() / 1.1f32.ln()).round() as i32).clamp(-10, 10);
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!("Add-Type -AssemblyName System.Speech; $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; $s.Rate = {}; $s.Speak([Console]::In.ReadToEnd())", rate),
+        ]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("say");
+        command.args(["-r", &words_per_minute, "-f", "-"]);
+        command
+    } else if shell::on_path("spd-say") {
+        // -100 to 100 around the default rate; -e reads stdin, -w waits until it's read
+        let rate = (((speed - 1.0) * 100.0).round() as i32).clamp(-100, 100).to_string();
+        let mut command = Command::new("spd-say");
+        command.args(["-w", "-e", "-r", &rate]);
+        command
+    } else if let Some(espeak) = ["espeak-ng", "espeak"].into_iter().find(|program| shell::on_path(program)) {
+        let mut command = Command::new(espeak);
+        command.args(["-s", &words_per_minute, "--stdin"]);
+        command
+    } else {
+        return Err("no speech synthesizer found; install speech-dispatcher or espeak-ng".to_string());
+    };
+
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(child)
+}
+
+/// Fetches `text` spoken by OpenAI and starts playing it.
+fn speak_openai(config: &Config, text: &str, voice: &str) -> Result<Child, String> {
+    let key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY environment variable not set")?;
+    let body = serde_json::json!({
+        "model": "tts-1",
+        "input": text,
+        "voice": voice,
+        // the API takes 0.25 to 4
+        "speed": config.speech.speed.clamp(0.25, 4.0),
+        "response_format": "wav",
+    });
+    log::debug!("POST {}", SPEECH_URL);
+    let response = http::agent(config, SPEECH_URL)
+        .map_err(|e| e.to_string())?
+        .post(SPEECH_URL)
+        .set("Authorization", &format!("Bearer {}", key))
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map_err(|e| match e {
+            ureq::Error::Status(code, response) => format!("OpenAI answered {}: {}", code, response.into_string().unwrap_or_default().trim()),
+            e => e.to_string(),
+        })?;
+    let mut audio = Vec::new();
+    std::io::copy(&mut response.into_reader(), &mut audio).map_err(|e| e.to_string())?;
+
+    let path = std::env::temp_dir().join(format!("llm-term-speech-{}.wav", std::process::id()));
+    fs::write(&path, audio).map_err(|e| e.to_string())?;
+    play(&path)
+}
+
+/// Plays the WAV file at `path` with the system's player.
+fn play(path: &Path) -> Result<Child, String> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.display())]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("afplay");
+        command.arg(path);
+        command
+    } else if let Some(player) = ["paplay", "aplay", "pw-play"].into_iter().find(|program| shell::on_path(program)) {
+        let mut command = Command::new(player);
+        command.arg(path);
+        command
+    } else if shell::on_path("ffplay") {
+        let mut command = Command::new("ffplay");
+        command.args(["-nodisp", "-autoexit", "-loglevel", "quiet"]).arg(path);
+        command
+    } else {
+        return Err("no audio player found; install pulseaudio-utils, alsa-utils or ffmpeg".to_string());
+    };
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| e.to_string())
+}
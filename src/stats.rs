@@ -0,0 +1,114 @@
+//! Local usage counters: cache hits and misses, model latency and tokens.
+//!
+//! Kept in `~/.llm_term_stats.json` and never sent anywhere.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Serializes the read-modify-write of the stats file within the process.
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Stats {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// keyed by model, as printed by its `Display`
+    pub models: BTreeMap<String, ModelStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ModelStats {
+    pub requests: u64,
+    pub errors: u64,
+    /// summed over successful requests
+    pub total_latency_ms: u64,
+    /// tokens sent and answered, as counted by APIs that report them
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+impl ModelStats {
+    pub fn average_latency(&self) -> Option<Duration> {
+        let ok = self.requests - self.errors;
+        (ok > 0).then(|| Duration::from_millis(self.total_latency_ms / ok))
+    }
+}
+
+impl Stats {
+    pub fn file_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".llm_term_stats.json"))
+    }
+
+    /// The recorded stats; empty if nothing was recorded yet.
+    pub fn load() -> Self {
+        Self::file_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::file_path().ok_or("no home dir")?;
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Share of cache lookups that were hits, from 0 to 1.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// Forgets everything recorded so far.
+    pub fn reset() -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        Self::default().save()
+    }
+}
+
+/// Applies `change` to the stats file. Failures are ignored; stats are best effort.
+fn update(change: impl FnOnce(&mut Stats)) {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut stats = Stats::load();
+    change(&mut stats);
+    let _ = stats.save();
+}
+
+/// Counts a prompt looked up in a cache.
+pub fn record_cache_lookup(hit: bool) {
+    update(|stats| {
+        if hit {
+            stats.cache_hits += 1;
+        } else {
+            stats.cache_misses += 1;
+        }
+    });
+}
+
+/// Counts a request to `model` that took `latency`.
+pub fn record_request(model: &str, latency: Duration, ok: bool) {
+    update(|stats| {
+        let entry = stats.models.entry(model.to_string()).or_default();
+        entry.requests += 1;
+        if ok {
+            entry.total_latency_ms += latency.as_millis() as u64;
+        } else {
+            entry.errors += 1;
+        }
+    });
+}
+
+/// Adds the tokens a request to `model` took, as its API reported them.
+pub fn record_usage(model: &str, prompt_tokens: u64, completion_tokens: u64) {
+    update(|stats| {
+        let entry = stats.models.entry(model.to_string()).or_default();
+        entry.prompt_tokens += prompt_tokens;
+        entry.completion_tokens += completion_tokens;
+    });
+}
@@ -0,0 +1,131 @@
+//! Targets and scripts of the build files in the working directory, so "run
+//! the tests" becomes `make test` or `npm run test:unit` instead of a guess.
+
+use std::fs;
+use std::path::Path;
+
+/// At most this many tasks per file are listed in the prompt.
+const MAX_TASKS: usize = 40;
+
+/// The tasks one build file defines.
+#[derive(Clone, Debug)]
+pub struct Tasks {
+    /// file name, e.g. "Makefile"
+    pub file: String,
+    /// the commands that run each task, e.g. `make test`
+    pub commands: Vec<String>,
+}
+
+/// Tasks from the Makefile, justfile and package.json in `dir`.
+pub fn in_dir(dir: &Path) -> Vec<Tasks> {
+    let mut found = Vec::new();
+    let mut add = |file: &str, parse: &dyn Fn(&str) -> Vec<String>| {
+        if let Ok(content) = fs::read_to_string(dir.join(file)) {
+            let commands = parse(&content);
+            if !commands.is_empty() {
+                found.push(Tasks { file: file.to_string(), commands });
+            }
+        }
+    };
+
+    if let Some(file) = ["GNUmakefile", "makefile", "Makefile"].into_iter().find(|file| dir.join(file).is_file()) {
+        add(file, &|content| make_targets(content).into_iter().map(|target| format!("make {}", target)).collect());
+    }
+    if let Some(file) = ["justfile", "Justfile", ".justfile"].into_iter().find(|file| dir.join(file).is_file()) {
+        add(file, &|content| just_recipes(content).into_iter().map(|recipe| format!("just {}", recipe)).collect());
+    }
+    let runner = node_runner(dir);
+    add("package.json", &|content| package_scripts(content).into_iter().map(|script| format!("{} run {}", runner, script)).collect());
+    found
+}
+
+/// Tells the model about the tasks in the working directory; empty if there are none.
+pub fn prompt_note() -> String {
+    let Ok(dir) = std::env::current_dir() else {
+        return String::new();
+    };
+    let found = in_dir(&dir);
+    if found.is_empty() {
+        return String::new();
+    }
+    let mut note = String::from("The current directory defines these tasks; when the user asks for one of them (building, testing, linting, running...), use the task instead of the underlying commands:");
+    for tasks in found {
+        let more = tasks.commands.len().saturating_sub(MAX_TASKS);
+        let listed: Vec<_> = tasks.commands.iter().take(MAX_TASKS).map(|command| format!("`{}`", command)).collect();
+        note.push_str(&format!("\n- {}: {}", tasks.file, listed.join(", ")));
+        if more > 0 {
+            note.push_str(&format!(" and {} more", more));
+        }
+    }
+    note
+}
+
+/// Explicit targets of a Makefile, without special (`.PHONY`) and pattern (`%.o`) ones.
+fn make_targets(content: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    for line in content.lines() {
+        // recipes are indented; variables are assigned with `=`, `:=`, `::=`, `?=` or `+=`
+        if line.starts_with(['\t', ' ', '#']) {
+            continue;
+        }
+        let Some((names, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if rest.starts_with('=') || rest.starts_with(":=") || names.contains('=') {
+            continue;
+        }
+        for name in names.split_whitespace() {
+            if !name.starts_with('.') && !name.contains(['%', '$']) && !targets.iter().any(|t| t == name) {
+                targets.push(name.to_string());
+            }
+        }
+    }
+    targets
+}
+
+/// Recipe names of a justfile, without private ones (`_name`).
+fn just_recipes(content: &str) -> Vec<String> {
+    let mut recipes = Vec::new();
+    for line in content.lines() {
+        if line.starts_with([' ', '\t', '#', '[']) {
+            continue;
+        }
+        let Some((head, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if rest.starts_with('=') {
+            continue;
+        }
+        let mut words = head.split_whitespace();
+        let Some(name) = words.next().map(|name| name.trim_start_matches('@')) else {
+            continue;
+        };
+        if matches!(name, "set" | "alias" | "export" | "import" | "mod") || head.contains(":=") {
+            continue;
+        }
+        if !name.is_empty() && !name.starts_with('_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            recipes.push(name.to_string());
+        }
+    }
+    recipes
+}
+
+/// The `scripts` of a package.json.
+fn package_scripts(content: &str) -> Vec<String> {
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(content) else {
+        return Vec::new();
+    };
+    package
+        .get("scripts")
+        .and_then(|scripts| scripts.as_object())
+        .map(|scripts| scripts.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The package manager whose lock file is in `dir`, npm by default.
+fn node_runner(dir: &Path) -> &'static str {
+    [("pnpm-lock.yaml", "pnpm"), ("yarn.lock", "yarn"), ("bun.lockb", "bun"), ("bun.lock", "bun")]
+        .into_iter()
+        .find(|(lock, _)| dir.join(lock).is_file())
+        .map_or("npm", |(_, runner)| runner)
+}
@@ -0,0 +1,236 @@
+//! Deleting into the trash: `rm` (or PowerShell's `Remove-Item`) steps of a
+//! command are rewritten to move their files to the trash or Recycle Bin
+//! instead, with whatever tool the system has for it.
+
+use crate::shell::{self, Shell};
+
+/// Programs that delete files for good.
+const DELETE_PROGRAMS: &[&str] = &["rm", "rmdir"];
+/// The same in PowerShell, with `Remove-Item`'s aliases.
+const POWERSHELL_DELETE_PROGRAMS: &[&str] = &["rm", "rmdir", "del", "erase", "rd", "ri", "Remove-Item"];
+/// `Remove-Item` parameters whose value names the files.
+const POWERSHELL_PATH_FLAGS: &[&str] = &["-Path", "-LiteralPath", "-lp"];
+/// `Remove-Item` parameters that pick what in the paths is deleted, which the
+/// trash tools can't do.
+const POWERSHELL_FILTER_FLAGS: &[&str] = &["-Include", "-Exclude", "-Filter", "-Stream"];
+/// `Remove-Item` parameters that take a value that isn't a file.
+const POWERSHELL_VALUE_FLAGS: &[&str] = &["-Credential"];
+
+/// Whether `command` deletes files with a program [`rewrite`] knows. A
+/// `sudo rm` isn't rewritten: the files would go to root's trash. An `rm`
+/// without files deletes nothing; it only prints its usage.
+pub fn deletes(command: &str, shell: &Shell) -> bool {
+    shell::simple_commands(command).iter().any(|words| is_delete(words[0], shell) && !paths(&words[1..], shell).is_empty())
+}
+
+/// Why a deleting `command` can't go to the trash, for when [`rewrite`] gave `None`.
+pub fn not_rewritten_reason(command: &str, shell: &Shell) -> &'static str {
+    let filtered =
+        shell::simple_commands(command).iter().any(|words| is_delete(words[0], shell) && filters(&words[1..], shell));
+    if filtered {
+        "it picks what to delete with -Include, -Exclude, -Filter or -Stream, which the trash can't do"
+    } else if tool(shell).is_some() {
+        "its delete step is inside parentheses, like a subshell or `$(...)`, where it can't be moved to the trash"
+    } else {
+        "no trash tool found (gio, trash-put or trash)"
+    }
+}
+
+/// `command` with its deleting steps moving their files to the trash. `None`
+/// if it deletes nothing, or there's no trash tool on this system.
+pub fn rewrite(command: &str, shell: &Shell) -> Option<String> {
+    if !deletes(command, shell) {
+        return None;
+    }
+    rewrite_with(command, shell, &tool(shell)?)
+}
+
+fn rewrite_with(command: &str, shell: &Shell, tool: &Tool) -> Option<String> {
+    let mut rewritten = String::new();
+    let mut changed = false;
+    for part in command.split_inclusive([';', '|', '&', '\n']) {
+        let step = part.trim_end_matches([';', '|', '&', '\n']);
+        let words = shell::words(step);
+        match words.first() {
+            Some(program) if is_delete(program, shell) && filters(&words[1..], shell) => return None,
+            Some(program) if is_delete(program, shell) && !paths(&words[1..], shell).is_empty() => {
+                let indent = &step[..step.len() - step.trim_start().len()];
+                let files = paths(&words[1..], shell);
+                rewritten.push_str(indent);
+                rewritten.push_str(&tool.command(&files));
+                rewritten.push_str(&part[step.trim_end().len()..]);
+                changed = true;
+            }
+            _ => rewritten.push_str(part),
+        }
+    }
+    changed.then_some(rewritten)
+}
+
+fn is_delete(word: &str, shell: &Shell) -> bool {
+    let program = shell::program_name(word);
+    match shell {
+        Shell::Powershell => POWERSHELL_DELETE_PROGRAMS.iter().any(|delete| delete.eq_ignore_ascii_case(program)),
+        _ => DELETE_PROGRAMS.contains(&program),
+    }
+}
+
+/// The arguments of a delete that name files: everything but its flags
+/// (`-rf`, `-Recurse`, `/s`) and the values of PowerShell's flags other
+/// than `-Path`, and everything after `--`.
+fn paths<'a>(args: &[&'a str], shell: &Shell) -> Vec<&'a str> {
+    let end = args.iter().position(|arg| *arg == "--").unwrap_or(args.len());
+    let mut files = Vec::new();
+    let mut skip_value = false;
+    for arg in &args[..end] {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if !is_flag(arg) {
+            files.push(*arg);
+        } else if matches!(shell, Shell::Powershell) && !arg.contains(':') {
+            // `-Path:x` carries its value; `-Path x` takes the next word
+            skip_value = is_one_of(arg, POWERSHELL_FILTER_FLAGS) || is_one_of(arg, POWERSHELL_VALUE_FLAGS);
+        }
+    }
+    files.extend(args.get(end + 1..).unwrap_or_default());
+    if matches!(shell, Shell::Powershell) {
+        // the value of `-Path:x`
+        files.extend(args[..end].iter().filter_map(|arg| {
+            let (flag, value) = arg.split_once(':')?;
+            (is_one_of(flag, POWERSHELL_PATH_FLAGS) && !value.is_empty()).then_some(value)
+        }));
+    }
+    files
+}
+
+/// Whether a delete's `args` narrow down what it deletes in its paths.
+fn filters(args: &[&str], shell: &Shell) -> bool {
+    matches!(shell, Shell::Powershell)
+        && args.iter().any(|arg| is_one_of(arg.split(':').next().unwrap_or(arg), POWERSHELL_FILTER_FLAGS))
+}
+
+fn is_one_of(flag: &str, flags: &[&str]) -> bool {
+    flags.iter().any(|known| known.eq_ignore_ascii_case(flag))
+}
+
+fn is_flag(arg: &str) -> bool {
+    arg.starts_with('-') || (arg.len() == 2 && arg.starts_with('/'))
+}
+
+enum Tool {
+    /// GLib's `gio trash`, on most Linux desktops
+    Gio,
+    /// `trash-put` from trash-cli
+    TrashPut,
+    /// `trash`, built into macOS 15 and installable with Homebrew before it
+    Trash,
+    /// PowerShell through .NET's `SendToRecycleBin`
+    RecycleBin,
+}
+
+/// The way files are trashed on this system, if it has one.
+fn tool(shell: &Shell) -> Option<Tool> {
+    if matches!(shell, Shell::Powershell) {
+        return Some(Tool::RecycleBin);
+    }
+    if cfg!(target_os = "macos") && shell::on_path("trash") {
+        return Some(Tool::Trash);
+    }
+    [("gio", Tool::Gio), ("trash-put", Tool::TrashPut), ("trash", Tool::Trash)]
+        .into_iter()
+        .find(|(program, _)| shell::on_path(program))
+        .map(|(_, tool)| tool)
+}
+
+impl Tool {
+    fn command(&self, files: &[&str]) -> String {
+        match self {
+            Tool::Gio => format!("gio trash -- {}", files.join(" ")),
+            Tool::TrashPut => format!("trash-put -- {}", files.join(" ")),
+            Tool::Trash => format!("trash {}", files.join(" ")),
+            Tool::RecycleBin => format!(
+                "Add-Type -AssemblyName Microsoft.VisualBasic; foreach ($item in Resolve-Path {}) {{ if (Test-Path -LiteralPath $item -PathType Container) {{ [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteDirectory($item.ProviderPath, 'OnlyErrorDialogs', 'SendToRecycleBin') }} else {{ [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteFile($item.ProviderPath, 'OnlyErrorDialogs', 'SendToRecycleBin') }} }}",
+                files.join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deletes, not_rewritten_reason, rewrite, rewrite_with, Tool};
+    use crate::shell::Shell;
+
+    const SH: Shell = Shell::BornAgainShell;
+
+    #[test]
+    fn rm_goes_to_the_trash() {
+        assert_eq!(rewrite_with("rm -rf build", &SH, &Tool::Gio).as_deref(), Some("gio trash -- build"));
+        assert_eq!(rewrite_with("rm a.txt b.txt", &SH, &Tool::TrashPut).as_deref(), Some("trash-put -- a.txt b.txt"));
+    }
+
+    #[test]
+    fn only_the_delete_steps_change() {
+        assert_eq!(
+            rewrite_with("make clean && rm -f a.o b.o; ls", &SH, &Tool::Trash).as_deref(),
+            Some("make clean && trash a.o b.o; ls")
+        );
+    }
+
+    #[test]
+    fn everything_after_double_dash_is_a_file() {
+        assert_eq!(rewrite_with("rm -- -weird-name", &SH, &Tool::Gio).as_deref(), Some("gio trash -- -weird-name"));
+    }
+
+    #[test]
+    fn quoted_names_stay_whole() {
+        assert_eq!(rewrite_with("rm \"my file.txt\"", &SH, &Tool::Gio).as_deref(), Some("gio trash -- \"my file.txt\""));
+    }
+
+    #[test]
+    fn rm_without_files_isnt_a_delete() {
+        assert!(!deletes("rm", &SH));
+        assert!(!deletes("rm -rf", &SH));
+        assert_eq!(rewrite("rm -rf", &SH), None);
+    }
+
+    #[test]
+    fn sudo_rm_is_left_alone() {
+        assert!(!deletes("sudo rm -rf /tmp/x", &SH));
+    }
+
+    #[test]
+    fn rm_in_a_subshell_deletes_but_isnt_rewritten() {
+        assert!(deletes("(rm a.txt)", &SH));
+        assert_eq!(rewrite_with("(rm a.txt)", &SH, &Tool::Gio), None);
+    }
+
+    #[test]
+    fn powershell_aliases() {
+        assert!(deletes("del a.txt", &Shell::Powershell));
+        assert!(deletes("Remove-Item -Recurse build", &Shell::Powershell));
+        assert!(!deletes("del a.txt", &SH));
+        let rewritten = rewrite("Remove-Item -Recurse build", &Shell::Powershell).unwrap();
+        assert!(rewritten.contains("Resolve-Path build") && rewritten.contains("SendToRecycleBin"), "{}", rewritten);
+    }
+
+    #[test]
+    fn powershell_flag_values_arent_files() {
+        let ps = Shell::Powershell;
+        let rewritten = rewrite_with("Remove-Item -LiteralPath build -Credential admin", &ps, &Tool::RecycleBin).unwrap();
+        assert!(rewritten.contains("Resolve-Path build)"), "{}", rewritten);
+        let rewritten = rewrite_with("Remove-Item -Path:build", &ps, &Tool::RecycleBin).unwrap();
+        assert!(rewritten.contains("Resolve-Path build)"), "{}", rewritten);
+    }
+
+    #[test]
+    fn filtered_powershell_deletes_arent_rewritten() {
+        let ps = Shell::Powershell;
+        let command = "Remove-Item C:\\logs\\* -Include *.tmp";
+        assert!(deletes(command, &ps));
+        assert_eq!(rewrite_with(command, &ps, &Tool::RecycleBin), None);
+        assert!(not_rewritten_reason(command, &ps).contains("-Include"));
+        assert_eq!(rewrite_with("Remove-Item logs -Filter:*.tmp", &ps, &Tool::RecycleBin), None);
+    }
+}
@@ -0,0 +1,249 @@
+use eframe::egui;
+use egui::Color32;
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use tray_icon::menu::{Menu, MenuEvent, MenuId, MenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+use crate::elevation;
+use crate::generator;
+use crate::guard;
+use crate::gui::execute_in_shell;
+use crate::notify;
+use crate::shell::Shell;
+use crate::snippets;
+use crate::trash;
+use crate::Config;
+
+/// Shortcut that brings up the quick prompt from anywhere.
+pub const HOTKEY_LABEL: &str = "Ctrl+Shift+Space";
+
+/// Small always-on-top window that lives in the system tray: the hotkey pops
+/// it up, it turns one prompt into a command, copies or runs it, and hides again.
+pub struct QuickPromptApp {
+    config: Config,
+    input: String,
+    command: Option<String>,
+    error: Option<String>,
+    /// what the model asked about an ambiguous prompt, and the answer being typed
+    question: Option<String>,
+    answer: String,
+    visible: bool,
+    // registrations last as long as these are alive
+    _tray: TrayIcon,
+    _hotkeys: GlobalHotKeyManager,
+    hotkey: HotKey,
+    show_item: MenuId,
+    quit_item: MenuId,
+}
+
+impl QuickPromptApp {
+    pub fn new(_cc: &eframe::CreationContext<'_>, config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let hotkeys = GlobalHotKeyManager::new()?;
+        let hotkey = HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::Space);
+        hotkeys.register(hotkey)?;
+
+        let show = MenuItem::new(format!("Quick prompt ({})", HOTKEY_LABEL), true, None);
+        let quit = MenuItem::new("Quit", true, None);
+        let menu = Menu::new();
+        menu.append(&show)?;
+        menu.append(&quit)?;
+
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("llm-term")
+            .with_icon(tray_icon_image()?)
+            .build()?;
+
+        Ok(Self {
+            config,
+            input: String::new(),
+            command: None,
+            error: None,
+            question: None,
+            answer: String::new(),
+            visible: true,
+            _tray: tray,
+            _hotkeys: hotkeys,
+            hotkey,
+            show_item: show.id().clone(),
+            quit_item: quit.id().clone(),
+        })
+    }
+
+    fn show(&mut self, ctx: &egui::Context) {
+        self.visible = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+    }
+
+    /// Hides the window and clears it for the next prompt.
+    fn hide(&mut self, ctx: &egui::Context) {
+        self.visible = false;
+        self.input.clear();
+        self.command = None;
+        self.error = None;
+        self.question = None;
+        self.answer.clear();
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+    }
+
+    /// Asks for the command, or, once the model asked about the prompt, for
+    /// the command given the answer; the model only gets to ask once.
+    fn generate(&mut self) {
+        self.error = None;
+        let (reply, may_ask) = match self.question.take() {
+            Some(question) => {
+                let answer = std::mem::take(&mut self.answer);
+                (self.config.model.llm_get_command_with_answer(&self.config, self.input.trim(), &question, answer.trim()), false)
+            }
+            None => (self.config.model.llm_get_command(&self.config, self.input.trim()), true),
+        };
+        match reply {
+            Ok(Some(reply)) => match generator::question(&reply) {
+                Some(question) if may_ask => self.question = Some(question),
+                None if !reply.trim().is_empty() => self.command = Some(reply.trim().to_string()),
+                _ => self.error = Some("The model wasn't confident enough to suggest a command; try wording it more precisely.".to_string()),
+            },
+            Ok(None) => self.error = Some("No command could be generated.".to_string()),
+            Err(e) => self.error = Some(format!("Error: {}", e)),
+        }
+    }
+}
+
+impl eframe::App for QuickPromptApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+            if event.id() == self.hotkey.id() && event.state() == HotKeyState::Pressed {
+                if self.visible {
+                    self.hide(ctx);
+                } else {
+                    self.show(ctx);
+                }
+            }
+        }
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id == self.show_item {
+                self.show(ctx);
+            } else if event.id == self.quit_item {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }
+        // keep polling for the hotkey while hidden
+        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+
+        let mut generate = false;
+        let mut answered = false;
+        let mut copy = false;
+        let mut run = false;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("Describe a command…")
+                    .desired_width(f32::INFINITY),
+            );
+            if self.visible && self.command.is_none() && self.question.is_none() && !input.has_focus() {
+                input.request_focus();
+            }
+            generate = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) && !self.input.trim().is_empty();
+
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::RED, error);
+            }
+            if let Some(question) = &self.question {
+                ui.label(question);
+                let answer = ui.add(egui::TextEdit::singleline(&mut self.answer).hint_text("Your answer…").desired_width(f32::INFINITY));
+                if !answer.has_focus() {
+                    answer.request_focus();
+                }
+                answered = answer.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) && !self.answer.trim().is_empty();
+            }
+            if let Some(command) = &mut self.command {
+                ui.add(egui::TextEdit::singleline(command).code_editor().desired_width(f32::INFINITY));
+                ui.horizontal(|ui| {
+                    copy = ui.button("📋 Copy").clicked();
+                    if !self.config.suggest_only {
+                        run = ui.button("▶ Run").clicked();
+                    }
+                    ui.weak("Esc to dismiss");
+                });
+            }
+        });
+
+        if generate {
+            // a new prompt; the question was about the old one
+            self.question = None;
+            self.generate();
+        } else if answered {
+            self.generate();
+        }
+        if copy {
+            if let Some(command) = &self.command {
+                ctx.copy_text(command.clone());
+            }
+            self.hide(ctx);
+        }
+        if run && self.command.as_deref().and_then(elevation::detect).is_some() && !elevation::is_elevated() {
+            // there's no way to confirm it or type a password here
+            self.error = Some("This command needs administrator rights; run it from the main window or a terminal.".to_string());
+            run = false;
+        }
+        if run {
+            if let Some(guard) = self.command.as_deref().map(guard::check).and_then(|guards| guards.into_iter().next()) {
+                self.error = Some(format!("{} Run it from the main window or a terminal to confirm it.", guard.warning));
+                run = false;
+            }
+        }
+        if run {
+            let placeholders = self.command.as_deref().map(snippets::placeholders).unwrap_or_default();
+            if !placeholders.is_empty() {
+                self.error = Some(format!("Fill in {} in the command first.", placeholders.join(", ")));
+                run = false;
+            }
+        }
+        if run && self.config.use_trash {
+            if let Some(command) = self.command.clone() {
+                match trash::rewrite(&command, &Shell::detect()) {
+                    Some(trashed) => self.command = Some(trashed),
+                    None if trash::deletes(&command, &Shell::detect()) => {
+                        self.error = Some(format!(
+                            "Not moving to the trash: {}. Run it from the main window to delete for good.",
+                            trash::not_rewritten_reason(&command, &Shell::detect())
+                        ));
+                        run = false;
+                    }
+                    None => {}
+                }
+            }
+        }
+        if run {
+            if let Some(command) = self.command.clone() {
+                let limits = self.config.limits;
+                // the window is gone by the time it finishes; report the result as a notification
+                std::thread::spawn(move || {
+                    let (output, _, _) = execute_in_shell(&command, None, limits);
+                    notify::send(&format!("Finished: {}", command), &output);
+                });
+            }
+            self.hide(ctx);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.hide(ctx);
+        }
+    }
+}
+
+/// A plain rounded square in the accent color; there's no bundled artwork.
+fn tray_icon_image() -> Result<Icon, Box<dyn std::error::Error>> {
+    const SIZE: u32 = 32;
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let corner = !(3..SIZE - 3).contains(&x) && !(3..SIZE - 3).contains(&y);
+            let alpha = if corner { 0 } else { 255 };
+            rgba.extend_from_slice(&[0x2e, 0x8b, 0x57, alpha]);
+        }
+    }
+    Ok(Icon::from_rgba(rgba, SIZE, SIZE)?)
+}
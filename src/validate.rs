@@ -0,0 +1,246 @@
+//! The checks behind `llm-term config validate`: what would otherwise only
+//! show up as a failed request, like a missing API key, an Ollama model that
+//! isn't pulled, a `max_tokens` the model refuses or a regex that doesn't
+//! compile, found up front with a note on how to fix it.
+
+use std::fs;
+use std::path::Path;
+
+use crate::config::LoadReport;
+use crate::model::Model;
+use crate::postprocess::Rule;
+use crate::project::Project;
+use crate::provider::{self, MAX_STOP_SEQUENCES};
+use crate::rag::Embedding;
+use crate::speech::SpeechEngine;
+use crate::voice::Transcriber;
+use crate::{http, Config, Redactor};
+
+/// Below this, a reasoning model can spend all of `max_tokens` thinking.
+const MIN_REASONING_TOKENS: usize = 4000;
+
+/// The names `provider_rate_limits` can be keyed by.
+const PROVIDERS: &[&str] = &["openai", "ollama", "mock"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// requests will fail, or a setting is ignored
+    Error,
+    /// works, but likely not as meant
+    Warning,
+}
+
+/// One thing wrong with the config.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    /// the setting it's about, e.g. "fallback[1]"
+    pub setting: String,
+    /// what's wrong and what to do about it
+    pub message: String,
+}
+
+/// Everything wrong with the config file `text`, with project settings
+/// looked up from `dir`. Errors come first.
+pub fn check(text: &str, dir: &Path) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let (config, report) = match Config::parse(text) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            findings.error("config", format!("it {}; fix it, or run `llm-term --config` to start over", e));
+            return findings.0;
+        }
+    };
+    check_report(&report, &mut findings);
+    check_models(&config, &mut findings);
+    check_tokens(&config, &mut findings);
+    check_patterns(&config, &mut findings);
+    check_files(&config, dir, &mut findings);
+    check_providers(&config, &mut findings);
+
+    findings.0.sort_by_key(|finding| finding.severity != Severity::Error);
+    findings.0
+}
+
+#[derive(Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn error(&mut self, setting: &str, message: String) {
+        self.0.push(Finding { severity: Severity::Error, setting: setting.to_string(), message });
+    }
+
+    fn warning(&mut self, setting: &str, message: String) {
+        self.0.push(Finding { severity: Severity::Warning, setting: setting.to_string(), message });
+    }
+}
+
+/// What reading the file ignored or migrated.
+fn check_report(report: &LoadReport, findings: &mut Findings) {
+    for problem in &report.problems {
+        findings.error("config", problem.clone());
+    }
+    for change in &report.changes {
+        findings.warning("config", format!("{}; the file is updated the next time llm-term runs", change));
+    }
+}
+
+/// Every model the config can send requests to, with the setting naming it.
+fn models(config: &Config) -> Vec<(String, &Model)> {
+    let mut models = Vec::new();
+    match &config.routing {
+        Some(routing) => {
+            models.push(("routing.first".to_string(), &routing.first));
+            models.push(("routing.escalate_to".to_string(), &routing.escalate_to));
+        }
+        None => models.push(("model".to_string(), &config.model)),
+    }
+    models.extend(config.fallback.iter().enumerate().map(|(i, model)| (format!("fallback[{}]", i), model)));
+    models
+}
+
+/// Each model's key, server or fixture, and fallbacks that can't help.
+fn check_models(config: &Config, findings: &mut Findings) {
+    let models = models(config);
+    let key_set = std::env::var("OPENAI_API_KEY").is_ok_and(|key| !key.trim().is_empty());
+    let mut ollama: Option<Result<Vec<String>, String>> = None;
+
+    for (setting, model) in &models {
+        match model {
+            Model::OpenAiGpt4o | Model::OpenAiGpt4oMini | Model::OpenAiO1 | Model::OpenAiO3Mini | Model::OpenAiO4Mini => {
+                if !key_set {
+                    findings.error(setting, format!("{} needs OPENAI_API_KEY, which isn't set; export it, or use an ollama:<model> instead", model));
+                }
+            }
+            Model::Ollama(name) => match ollama.get_or_insert_with(|| provider::ollama_models(config)) {
+                Err(e) => findings.error(setting, format!("{} needs the Ollama server, but {}; start it with `ollama serve`, or set `ollama_host` to where it runs", model, e)),
+                Ok(pulled) if !pulled.iter().any(|pulled| pulled == name || *pulled == format!("{}:latest", name)) => {
+                    findings.error(setting, format!("Ollama doesn't have {}; run `ollama pull {}`", name, name));
+                }
+                Ok(_) => {}
+            },
+            Model::Mock(fixture) => {
+                if let Err(e) = check_fixture(Path::new(fixture)) {
+                    findings.error(setting, e);
+                }
+            }
+        }
+    }
+
+    let primary = models.iter().take_while(|(setting, _)| !setting.starts_with("fallback")).map(|(_, model)| model.to_string()).collect::<Vec<_>>();
+    for (setting, model) in models.iter().filter(|(setting, _)| setting.starts_with("fallback")) {
+        if primary.contains(&model.to_string()) {
+            findings.warning(setting, format!("{} is already asked first, so falling back to it only asks it again", model));
+        }
+    }
+}
+
+fn check_fixture(path: &Path) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("can't read the mock fixture {} ({})", path.display(), e))?;
+    serde_json::from_str::<std::collections::HashMap<String, String>>(&content)
+        .map_err(|e| format!("the mock fixture {} isn't a JSON object of prompt -> reply ({})", path.display(), e))?;
+    Ok(())
+}
+
+/// `max_tokens` against what each model allows, and limits it can't fit under.
+fn check_tokens(config: &Config, findings: &mut Findings) {
+    if config.max_tokens <= 0 {
+        findings.error("max_tokens", format!("{} leaves no room for an answer; set it to 1000, say", config.max_tokens));
+        return;
+    }
+    let max_tokens = config.max_tokens as usize;
+    for (setting, model) in models(config) {
+        let window = model.context_window_in(config);
+        if max_tokens >= window {
+            findings.error("max_tokens", format!("{} doesn't fit in {}'s context window of {} tokens with a prompt ({})", max_tokens, model, window, setting));
+        } else if let Some(cap) = model.max_output_tokens().filter(|&cap| max_tokens > cap) {
+            findings.error("max_tokens", format!("{} is more than {} answers with, {}; lower it ({})", max_tokens, model, cap, setting));
+        } else if model.is_reasoning() && max_tokens < MIN_REASONING_TOKENS {
+            findings.warning("max_tokens", format!("{} also has to cover {}'s reasoning, so answers may come back empty; {} or more is safer ({})", max_tokens, model, MIN_REASONING_TOKENS, setting));
+        }
+    }
+    if config.reasoning_effort.is_some() && !models(config).iter().any(|(_, model)| model.is_reasoning()) {
+        findings.warning("reasoning_effort", "none of the models used is o1, o3-mini or o4-mini, so it has no effect".to_string());
+    }
+
+    let limits = std::iter::once(("rate_limit".to_string(), &config.rate_limit))
+        .chain(config.provider_rate_limits.iter().map(|(provider, limit)| (format!("provider_rate_limits.{}", provider), limit)));
+    for (setting, limit) in limits {
+        if limit.tokens_per_minute.is_some_and(|tokens| (tokens as usize) < max_tokens) {
+            findings.warning(&setting, format!("tokens_per_minute is below max_tokens ({}), so every request waits for a minute without others", max_tokens));
+        }
+        if limit.requests_per_minute == Some(0) {
+            findings.warning(&setting, "requests_per_minute 0 doesn't limit anything; remove it, or set at least 1".to_string());
+        }
+    }
+
+    if config.stop_sequences.len() > MAX_STOP_SEQUENCES {
+        findings.warning("stop_sequences", format!("only the first {} of the {} are sent", MAX_STOP_SEQUENCES, config.stop_sequences.len()));
+    }
+    if config.stop_sequences.iter().any(String::is_empty) {
+        findings.error("stop_sequences", "an empty stop sequence would stop every reply before it starts; remove it".to_string());
+    }
+}
+
+/// The regexes in `redact_patterns` and the `replace` rules.
+fn check_patterns(config: &Config, findings: &mut Findings) {
+    for (i, pattern) in config.redact_patterns.iter().enumerate() {
+        if let Err(e) = Redactor::new(std::slice::from_ref(pattern)) {
+            findings.error(&format!("redact_patterns[{}]", i), e);
+        }
+    }
+    for (i, rule) in config.post_process.iter().enumerate() {
+        if let Rule::Replace { pattern, .. } = rule {
+            if let Err(e) = regex::Regex::new(pattern) {
+                findings.error(&format!("post_process[{}]", i), format!("the pattern `{}` isn't a valid regex: {}", pattern, e));
+            }
+        }
+    }
+}
+
+/// Files the config points at, and the project settings for `dir`.
+fn check_files(config: &Config, dir: &Path, findings: &mut Findings) {
+    if let Some(Err(e)) = Project::discover(dir) {
+        findings.error(crate::project::FILE_NAME, e);
+    }
+    if config.ca_bundle.is_some() {
+        if let Err(e) = http::agent_builder(config, "https://api.openai.com/") {
+            findings.error("ca_bundle", e.to_string());
+        }
+    }
+    if let Transcriber::WhisperCpp { model, binary } = &config.transcriber {
+        if !model.is_file() {
+            findings.error("transcriber", format!("the whisper.cpp model {} doesn't exist; download a ggml model there", model.display()));
+        }
+        if let Some(binary) = binary.as_ref().filter(|binary| !binary.is_file()) {
+            findings.error("transcriber", format!("the whisper.cpp program {} doesn't exist", binary.display()));
+        }
+    }
+    if let Some(rag) = &config.rag {
+        for (i, path) in rag.paths.iter().enumerate() {
+            if !crate::rag::expand_home(path).exists() {
+                findings.warning(&format!("rag.paths[{}]", i), format!("{} doesn't exist, so there's nothing to index there", path.display()));
+            }
+        }
+    }
+}
+
+/// Provider names in `provider_rate_limits`, and OpenAI features without a key.
+fn check_providers(config: &Config, findings: &mut Findings) {
+    let mut keys: Vec<&String> = config.provider_rate_limits.keys().collect();
+    keys.sort();
+    for provider in keys.into_iter().filter(|key| !PROVIDERS.contains(&key.as_str())) {
+        findings.error(&format!("provider_rate_limits.{}", provider), format!("there's no provider `{}`; use {}", provider, PROVIDERS.join(", ")));
+    }
+
+    let key_set = std::env::var("OPENAI_API_KEY").is_ok_and(|key| !key.trim().is_empty());
+    if key_set {
+        return;
+    }
+    if config.rag.as_ref().is_some_and(|rag| rag.embedding == Embedding::OpenAi) {
+        findings.error("rag.embedding", "OpenAI embeddings need OPENAI_API_KEY, which isn't set; export it, or use {\"ollama\": {}}".to_string());
+    }
+    if config.speech.enabled && matches!(config.speech.engine, SpeechEngine::OpenAi { .. }) {
+        findings.error("speech.engine", "OpenAI speech needs OPENAI_API_KEY, which isn't set; export it, or use \"system\"".to_string());
+    }
+}
@@ -0,0 +1,217 @@
+//! Optional at-rest encryption for chat sessions, caches and history.
+//!
+//! Once a [`Vault`] is installed, everything written through [`seal`] is
+//! encrypted with AES-256-GCM under a key derived from the passphrase, and
+//! [`open`] decrypts it again. Files written before encryption was turned on
+//! are read as they are and encrypted the next time they are saved.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Marks encrypted files; followed by the nonce and the ciphertext.
+const MAGIC: &[u8] = b"LLMTERM-ENC1";
+const PBKDF2_ITERATIONS: u32 = 200_000;
+const SALT_LEN: usize = 16;
+/// Encrypted with the key to tell a wrong passphrase from a damaged file.
+const CHECK_PLAINTEXT: &[u8] = b"llm-term vault";
+
+static VAULT: OnceLock<Vault> = OnceLock::new();
+
+/// Salt and passphrase check, stored in `~/.llm_term_vault.json`.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    salt: String,
+    check: String,
+}
+
+pub struct Vault {
+    key: LessSafeKey,
+}
+
+impl Vault {
+    pub fn file_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".llm_term_vault.json"))
+    }
+
+    /// Derives the key from `passphrase`. The first unlock picks a salt and
+    /// remembers the passphrase check; later ones fail on a wrong passphrase.
+    pub fn unlock(passphrase: &str) -> Result<Self, String> {
+        if passphrase.is_empty() {
+            return Err("The passphrase can't be empty.".to_string());
+        }
+
+        let path = Self::file_path().ok_or("no home dir")?;
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                let file: VaultFile = serde_json::from_str(&content).map_err(|e| format!("Damaged vault file: {}", e))?;
+                let salt = from_hex(&file.salt).ok_or("Damaged vault file: bad salt")?;
+                let vault = Self::derive(passphrase, &salt);
+                let check = from_hex(&file.check).ok_or("Damaged vault file: bad check")?;
+                match vault.decrypt(&check) {
+                    Some(plain) if plain == CHECK_PLAINTEXT => Ok(vault),
+                    _ => Err("Wrong passphrase.".to_string()),
+                }
+            }
+            // only a vault that was never set up gets a new salt; anything
+            // else would make what's encrypted under the old one unreadable
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut salt = [0u8; SALT_LEN];
+                SystemRandom::new().fill(&mut salt).map_err(|_| "no random numbers available")?;
+                let vault = Self::derive(passphrase, &salt);
+                let file = VaultFile { salt: to_hex(&salt), check: to_hex(&vault.encrypt(CHECK_PLAINTEXT)) };
+                let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+                write_private(&path, content.as_bytes()).map_err(|e| format!("Failed to write the vault file: {}", e))?;
+                Ok(vault)
+            }
+            Err(e) => Err(format!("Can't read the vault file {}: {}", path.display(), e)),
+        }
+    }
+
+    fn derive(passphrase: &str, salt: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).expect("non-zero");
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut key);
+        let key = UnboundKey::new(&AES_256_GCM, &key).expect("key has the right length");
+        Self { key: LessSafeKey::new(key) }
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).expect("no random numbers available");
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut in_out)
+            .expect("plaintext fits");
+
+        [MAGIC, &nonce, &in_out].concat()
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let rest = data.strip_prefix(MAGIC)?;
+        if rest.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut in_out = ciphertext.to_vec();
+        let plain = self.key.open_in_place(nonce, Aad::from(MAGIC), &mut in_out).ok()?;
+        Some(plain.to_vec())
+    }
+}
+
+/// Creates `path` with `contents`, readable and writable only by this user.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Makes `vault` the key for [`seal`] and [`open`] for the rest of the process.
+pub fn install(vault: Vault) {
+    let _ = VAULT.set(vault);
+}
+
+pub fn is_unlocked() -> bool {
+    VAULT.get().is_some()
+}
+
+/// Encrypts `plaintext` if a vault is installed, otherwise returns it unchanged.
+pub fn seal(plaintext: &[u8]) -> Vec<u8> {
+    match VAULT.get() {
+        Some(vault) => vault.encrypt(plaintext),
+        None => plaintext.to_vec(),
+    }
+}
+
+/// Decrypts what [`seal`] wrote. Data that isn't encrypted is returned as is.
+pub fn open(data: &[u8]) -> Result<Vec<u8>, String> {
+    if !data.starts_with(MAGIC) {
+        return Ok(data.to_vec());
+    }
+    let vault = VAULT.get().ok_or("The file is encrypted; unlock the storage first.")?;
+    vault.decrypt(data).ok_or_else(|| "The file is damaged or was encrypted with another passphrase.".to_string())
+}
+
+/// [`open`] for text files.
+pub fn open_to_string(data: &[u8]) -> Result<String, String> {
+    String::from_utf8(open(data)?).map_err(|e| e.to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_hex, open, to_hex, write_private, Vault, MAGIC};
+    use std::io;
+
+    const SALT: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn sealed_data_opens_with_the_same_passphrase() {
+        let sealed = Vault::derive("correct horse", SALT).encrypt(b"chat history");
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(Vault::derive("correct horse", SALT).decrypt(&sealed).as_deref(), Some(&b"chat history"[..]));
+    }
+
+    #[test]
+    fn wrong_passphrase_or_damage_doesnt_open() {
+        let vault = Vault::derive("correct horse", SALT);
+        let sealed = vault.encrypt(b"chat history");
+        assert_eq!(Vault::derive("battery staple", SALT).decrypt(&sealed), None);
+
+        let mut damaged = sealed.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert_eq!(vault.decrypt(&damaged), None);
+        assert_eq!(vault.decrypt(&sealed[..MAGIC.len() + 4]), None);
+    }
+
+    #[test]
+    fn unencrypted_data_is_read_as_is() {
+        assert_eq!(open(b"{\"prompt\": \"ls\"}").unwrap(), b"{\"prompt\": \"ls\"}");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("000fff"), Some(vec![0, 15, 255]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+
+    #[test]
+    fn vault_file_is_private_and_never_overwritten() {
+        let path = std::env::temp_dir().join(format!("llm_term_vault_test_{}.json", uuid::Uuid::new_v4()));
+        write_private(&path, b"first").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(write_private(&path, b"second").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Dictated prompts: audio is recorded with whatever recorder the system has
+//! (`rec` from SoX, `arecord` or `ffmpeg`) and transcribed by OpenAI's
+//! Whisper API or a local whisper.cpp.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{http, shell, Config};
+
+const TRANSCRIPTIONS_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+
+/// The recording is 16 kHz, 16-bit mono: what Whisper works on.
+const SAMPLE_RATE: &str = "16000";
+
+/// Recordings shorter than this (a click on the button) are dropped.
+const MIN_AUDIO_BYTES: u64 = 16000 * 2 / 4;
+
+/// How dictated prompts are turned into text.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Transcriber {
+    /// OpenAI's `whisper-1`, with `OPENAI_API_KEY`
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// a local whisper.cpp: `whisper-cli` with a ggml model file
+    #[serde(rename = "whisper_cpp")]
+    WhisperCpp {
+        model: PathBuf,
+        /// the whisper.cpp program; `whisper-cli` or `whisper-cpp` from the PATH otherwise
+        #[serde(default)]
+        binary: Option<PathBuf>,
+    },
+}
+
+/// Audio being recorded to a temporary WAV file. Dropping it stops the
+/// recorder and removes the file.
+pub struct Recording {
+    child: Child,
+    path: PathBuf,
+    started: Instant,
+}
+
+/// Starts recording from the default microphone.
+pub fn start() -> Result<Recording, String> {
+    let path = std::env::temp_dir().join(format!("llm-term-dictation-{}.wav", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut recorder = recorder(&path).ok_or("No audio recorder found; install SoX (`rec`), alsa-utils (`arecord`) or ffmpeg")?;
+    let child = recorder
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Can't start the audio recorder: {}", e))?;
+    Ok(Recording { child, path, started: Instant::now() })
+}
+
+/// The command recording the microphone to `path`, from the first recorder found.
+fn recorder(path: &Path) -> Option<Command> {
+    if shell::on_path("rec") {
+        let mut command = Command::new("rec");
+        command.args(["-q", "-c", "1", "-b", "16", "-r", SAMPLE_RATE]).arg(path);
+        return Some(command);
+    }
+    if cfg!(target_os = "linux") && shell::on_path("arecord") {
+        let mut command = Command::new("arecord");
+        command.args(["-q", "-f", "S16_LE", "-c", "1", "-r", SAMPLE_RATE, "-t", "wav"]).arg(path);
+        return Some(command);
+    }
+    // ffmpeg needs the input device named; Windows' have no default name
+    let input: &[&str] = if cfg!(target_os = "macos") {
+        &["-f", "avfoundation", "-i", ":0"]
+    } else if cfg!(target_os = "linux") {
+        &["-f", "pulse", "-i", "default"]
+    } else {
+        return None;
+    };
+    if !shell::on_path("ffmpeg") {
+        return None;
+    }
+    let mut command = Command::new("ffmpeg");
+    command.args(["-loglevel", "error", "-y"]).args(input).args(["-ac", "1", "-ar", SAMPLE_RATE]).arg(path);
+    Some(command)
+}
+
+impl Recording {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Stops recording and transcribes what was said.
+    pub fn finish(mut self, config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+        self.stop();
+        fix_wav_header(&self.path)?;
+        let size = fs::metadata(&self.path).map(|meta| meta.len()).unwrap_or(0);
+        if size < 44 + MIN_AUDIO_BYTES {
+            return Err("Nothing was recorded; hold the button while you speak".into());
+        }
+        let text = transcribe(config, &self.path)?;
+        if text.is_empty() {
+            return Err("No speech was recognized".into());
+        }
+        Ok(text)
+    }
+
+    /// Asks the recorder to finish its file, as Ctrl+C would, and kills it
+    /// if it hasn't after a second.
+    fn stop(&mut self) {
+        if matches!(self.child.try_wait(), Ok(Some(_))) {
+            return;
+        }
+        #[cfg(unix)]
+        {
+            // SAFETY: signals the recorder this process started and hasn't reaped yet
+            unsafe {
+                libc::kill(self.child.id() as libc::pid_t, libc::SIGINT);
+            }
+            let deadline = Instant::now() + Duration::from_secs(1);
+            while Instant::now() < deadline {
+                if matches!(self.child.try_wait(), Ok(Some(_))) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Sets the sizes in the header of a PCM WAV file to its actual length. A
+/// recorder that was killed leaves them at zero, or at its maximum.
+fn fix_wav_header(path: &Path) -> Result<(), String> {
+    let mut bytes = fs::read(path).map_err(|e| format!("Can't read the recording: {}", e))?;
+    if bytes.len() < 44 || &bytes[..4] != b"RIFF" || &bytes[36..40] != b"data" {
+        return Ok(());
+    }
+    let riff_size = (bytes.len() - 8) as u32;
+    let data_size = (bytes.len() - 44) as u32;
+    if bytes[4..8] == riff_size.to_le_bytes() && bytes[40..44] == data_size.to_le_bytes() {
+        return Ok(());
+    }
+    bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    bytes[40..44].copy_from_slice(&data_size.to_le_bytes());
+    fs::write(path, bytes).map_err(|e| format!("Can't write the recording: {}", e))
+}
+
+/// The text spoken in the WAV file `audio`, by the configured transcriber.
+pub fn transcribe(config: &Config, audio: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let text = match &config.transcriber {
+        Transcriber::OpenAi => transcribe_openai(config, audio)?,
+        Transcriber::WhisperCpp { model, binary } => transcribe_whisper_cpp(model, binary.as_deref(), audio)?,
+    };
+    Ok(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn transcribe_openai(config: &Config, audio: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY environment variable not set")?;
+    let audio = fs::read(audio)?;
+
+    // multipart/form-data, which ureq has no builder for
+    let boundary = format!("llm-term-{}", std::process::id());
+    let mut body = Vec::new();
+    for (name, value) in [("model", "whisper-1"), ("response_format", "text")] {
+        body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).into_bytes());
+    }
+    body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"prompt.wav\"\r\nContent-Type: audio/wav\r\n\r\n", boundary).into_bytes());
+    body.extend(audio);
+    body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+
+    log::debug!("POST {}", TRANSCRIPTIONS_URL);
+    let response = http::agent(config, TRANSCRIPTIONS_URL)?
+        .post(TRANSCRIPTIONS_URL)
+        .set("Authorization", &format!("Bearer {}", key))
+        .set("Content-Type", &format!("multipart/form-data; boundary={}", boundary))
+        .send_bytes(&body)
+        .map_err(|e| match e {
+            ureq::Error::Status(code, response) => format!("Whisper answered {}: {}", code, response.into_string().unwrap_or_default().trim()),
+            e => format!("Can't reach Whisper: {}", e),
+        })?;
+    Ok(response.into_string()?)
+}
+
+fn transcribe_whisper_cpp(model: &Path, binary: Option<&Path>, audio: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let binary = match binary {
+        Some(binary) => binary.to_path_buf(),
+        None => ["whisper-cli", "whisper-cpp"]
+            .into_iter()
+            .find(|program| shell::on_path(program))
+            .map(PathBuf::from)
+            .ok_or("whisper.cpp not found; install it or set its `binary` in the `transcriber` config")?,
+    };
+    // -nt: no timestamps, -np: nothing but the text on stdout
+    let output = Command::new(&binary)
+        .arg("-m")
+        .arg(model)
+        .arg("-f")
+        .arg(audio)
+        .args(["-nt", "-np", "-l", "auto"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Can't run {}: {}", binary.display(), e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", binary.display(), String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
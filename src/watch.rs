@@ -0,0 +1,122 @@
+//! `llm-term watch`: a request like "tell me when disk usage exceeds 90%"
+//! becomes a check command and a condition on its output, which the CLI
+//! runs on an interval until the condition is met.
+
+use crate::Config;
+
+/// The check and condition the model came up with for a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watch {
+    /// prints what's watched, ideally a single number
+    pub check: String,
+    pub condition: Condition,
+}
+
+/// When to alert, judged on a check's output.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// the first number in the output compared with `value`, e.g. `> 90`
+    Compare { op: Op, value: f64 },
+    /// the output contains the text
+    Contains(String),
+    /// the output doesn't contain the text
+    Lacks(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Greater => ">",
+            Op::GreaterOrEqual => ">=",
+            Op::Less => "<",
+            Op::LessOrEqual => "<=",
+            Op::Equal => "==",
+            Op::NotEqual => "!=",
+        }
+    }
+}
+
+impl std::str::FromStr for Condition {
+    type Err = String;
+
+    /// Parses `> 90`, `<= 0.5`, `contains "ERROR"` or `not contains "Running"`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim().trim_matches('`').trim();
+        let unquote = |rest: &str| rest.trim().trim_matches(['"', '\'']).to_string();
+        if let Some(rest) = text.strip_prefix("not contains ") {
+            return Ok(Condition::Lacks(unquote(rest)));
+        }
+        if let Some(rest) = text.strip_prefix("contains ") {
+            return Ok(Condition::Contains(unquote(rest)));
+        }
+        // two-character operators first, so `>=` isn't read as `>`
+        let ops = [(">=", Op::GreaterOrEqual), ("<=", Op::LessOrEqual), ("==", Op::Equal), ("!=", Op::NotEqual), (">", Op::Greater), ("<", Op::Less)];
+        for (symbol, op) in ops {
+            if let Some(rest) = text.strip_prefix(symbol) {
+                let value = rest.trim().trim_end_matches('%').trim();
+                return value.parse().map(|value| Condition::Compare { op, value }).map_err(|_| format!("`{}` isn't a number in the condition `{}`", value, text));
+            }
+        }
+        Err(format!("Can't understand the condition `{}`; expected e.g. `> 90` or `contains \"ERROR\"`", text))
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Compare { op, value } => write!(f, "{} {}", op.symbol(), value),
+            Condition::Contains(text) => write!(f, "contains \"{}\"", text),
+            Condition::Lacks(text) => write!(f, "not contains \"{}\"", text),
+        }
+    }
+}
+
+impl Condition {
+    /// Whether `output` meets the condition. A comparison with output that
+    /// has no number in it isn't met.
+    pub fn is_met(&self, output: &str) -> bool {
+        match self {
+            Condition::Compare { op, value } => first_number(output).is_some_and(|number| match op {
+                Op::Greater => number > *value,
+                Op::GreaterOrEqual => number >= *value,
+                Op::Less => number < *value,
+                Op::LessOrEqual => number <= *value,
+                Op::Equal => number == *value,
+                Op::NotEqual => number != *value,
+            }),
+            Condition::Contains(text) => output.contains(text.as_str()),
+            Condition::Lacks(text) => !output.contains(text.as_str()),
+        }
+    }
+}
+
+/// The first word of `text` that's a number, e.g. 91 in "91%" or "/dev/sda1 91".
+fn first_number(text: &str) -> Option<f64> {
+    text.split_whitespace().find_map(|word| word.trim_end_matches(['%', ',', ';', ':']).parse().ok())
+}
+
+/// Asks the model for the check and condition behind `request`.
+pub fn plan(config: &Config, request: &str) -> Result<Watch, Box<dyn std::error::Error>> {
+    let reply = config.model.llm_plan_watch(config, request)?.unwrap_or_default();
+    let field = |name: &str| {
+        reply
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(|value| value.trim().trim_matches('`').trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let (Some(check), Some(condition)) = (field("CHECK:"), field("CONDITION:")) else {
+        return Err(format!("The model didn't come up with a check for that: {}", reply.trim()).into());
+    };
+    Ok(Watch { check, condition: condition.parse()? })
+}
@@ -0,0 +1,79 @@
+//! The GUI window as it was left, restored on the next launch: size,
+//! position, maximized state, sidebar width and the open chat.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::persist;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WindowState {
+    /// inner size in points, from before the window was maximized
+    #[serde(default)]
+    pub size: Option<[f32; 2]>,
+    /// top-left corner of the window frame
+    #[serde(default)]
+    pub position: Option<[f32; 2]>,
+    #[serde(default)]
+    pub maximized: bool,
+    #[serde(default)]
+    pub sidebar_width: Option<f32>,
+    /// the chat that was open
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+fn window_file_path() -> std::io::Result<PathBuf> {
+    let mut path = dirs::home_dir().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "no home dir"))?;
+    path.push(".llm_term_window.json");
+    Ok(path)
+}
+
+impl WindowState {
+    /// The state saved last, or the defaults if there's none.
+    pub fn load() -> Self {
+        window_file_path()
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        persist::write_atomic(&window_file_path()?, content.as_bytes())
+    }
+
+    /// `builder` with the saved size, position and maximized state.
+    pub fn apply(&self, mut builder: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        if let Some(size) = self.size {
+            builder = builder.with_inner_size(size);
+        }
+        if let Some(position) = self.position {
+            builder = builder.with_position(position);
+        }
+        builder.with_maximized(self.maximized)
+    }
+
+    /// This state updated from the viewport's current one. The size and
+    /// position of a maximized window aren't kept, so it un-maximizes to where it was.
+    pub fn observe(&self, viewport: &egui::ViewportInfo, sidebar_width: Option<f32>, session_id: Option<&String>) -> Self {
+        let maximized = viewport.maximized.unwrap_or(false);
+        let minimized = viewport.minimized.unwrap_or(false);
+        let keep_geometry = maximized || minimized;
+        WindowState {
+            size: match viewport.inner_rect {
+                Some(rect) if !keep_geometry => Some([rect.width(), rect.height()]),
+                _ => self.size,
+            },
+            position: match viewport.outer_rect {
+                Some(rect) if !keep_geometry => Some([rect.min.x, rect.min.y]),
+                _ => self.position,
+            },
+            maximized: if minimized { self.maximized } else { maximized },
+            sidebar_width: sidebar_width.or(self.sidebar_width),
+            session_id: session_id.cloned(),
+        }
+    }
+}
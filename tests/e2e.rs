@@ -0,0 +1,163 @@
+//! End-to-end tests of the command pipeline: prompts go through the mock
+//! model, commands are cached and checked against the policies, and run
+//! in a scratch directory. Run with `cargo test --features e2e`.
+#![cfg(feature = "e2e")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Once;
+
+use llm_term::router::Routing;
+use llm_term::{elevation, model, Cache, CommandGenerator, Config, Executor, Model, ResourceLimits, Shell};
+
+/// A fresh directory under the temp dir, also used as `HOME` so the stats
+/// and other `~/.llm_term_*` files never touch the real ones.
+fn scratch_dir(name: &str) -> PathBuf {
+    static HOME: Once = Once::new();
+    let root = std::env::temp_dir().join(format!("llm-term-e2e-{}", std::process::id()));
+    HOME.call_once(|| {
+        fs::create_dir_all(&root).unwrap();
+        std::env::set_var("HOME", &root);
+    });
+    let dir = root.join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A mock model answering from `replies`, written to a fixture in `dir`.
+fn mock(dir: &std::path::Path, name: &str, replies: &[(&str, &str)]) -> Model {
+    let replies: std::collections::HashMap<_, _> = replies.iter().copied().collect();
+    let path = dir.join(format!("{}.json", name));
+    fs::write(&path, serde_json::to_string(&replies).unwrap()).unwrap();
+    Model::Mock(path.to_string_lossy().into_owned())
+}
+
+#[test]
+fn generates_the_fixture_command() {
+    let dir = scratch_dir("generate");
+    let model = mock(&dir, "fixture", &[("list files", "ls -la"), ("*", "echo other")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+
+    assert_eq!(generator.generate("list files").unwrap().as_deref(), Some("ls -la"));
+    assert_eq!(generator.generate("anything else").unwrap().as_deref(), Some("echo other"));
+}
+
+#[test]
+fn caches_new_commands_and_answers_from_the_cache() {
+    let dir = scratch_dir("cache");
+    let model = mock(&dir, "fixture", &[("list files", "ls -la")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+    let path = dir.join("cache.json");
+    let mut cache = Cache::load(&path).unwrap();
+
+    assert_eq!(generator.generate_cached(&mut cache, "list files").unwrap(), Some(("ls -la".to_string(), false)));
+    assert!(path.exists());
+    assert_eq!(Cache::load(&path).unwrap().get("list files"), Some("ls -la"));
+
+    // a hit doesn't need the model any more
+    fs::remove_file(dir.join("fixture.json")).unwrap();
+    assert_eq!(generator.generate_cached(&mut cache, "list files").unwrap(), Some(("ls -la".to_string(), true)));
+}
+
+#[test]
+fn empty_answers_are_not_cached() {
+    let dir = scratch_dir("empty");
+    let model = mock(&dir, "fixture", &[("*", "  ")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+    let mut cache = Cache::load(dir.join("cache.json")).unwrap();
+
+    assert_eq!(generator.generate_cached(&mut cache, "do something").unwrap(), None);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn secrets_are_redacted_before_reaching_the_model() {
+    let dir = scratch_dir("redact");
+    // only the redacted prompt has a reply
+    let model = mock(&dir, "fixture", &[("log in with [REDACTED:api-key]", "echo redacted")]);
+    let mut config = Config::new(model, 100);
+    config.redact_secrets = true;
+    let generator = CommandGenerator::new(config);
+
+    assert_eq!(generator.generate("log in with sk-abcdefghijklmnopqrstuvwxyz").unwrap().as_deref(), Some("echo redacted"));
+}
+
+#[test]
+fn unsure_answers_are_escalated() {
+    let dir = scratch_dir("routing");
+    let mut config = Config::new(Model::OpenAiGpt4oMini, 100);
+    config.routing = Some(Routing {
+        first: mock(&dir, "first", &[("*", "LOW_CONFIDENCE")]),
+        escalate_to: mock(&dir, "escalate", &[("*", "du -sh *")]),
+    });
+    let generator = CommandGenerator::new(config);
+
+    assert_eq!(generator.generate("what takes up space here").unwrap().as_deref(), Some("du -sh *"));
+}
+
+#[test]
+fn fallback_models_answer_when_the_configured_one_fails() {
+    let dir = scratch_dir("fallback");
+    let broken = Model::Mock(dir.join("missing.json").to_string_lossy().into_owned());
+    let mut config = Config::new(broken, 100);
+    config.fallback = vec![mock(&dir, "fallback", &[("*", "uptime")])];
+    let generator = CommandGenerator::new(config);
+
+    assert_eq!(generator.generate("how long has this been up").unwrap().as_deref(), Some("uptime"));
+    assert!(model::take_fallback_note().is_some_and(|note| note.contains("answered by mock:")));
+}
+
+#[test]
+fn elevation_is_detected_in_generated_commands() {
+    let dir = scratch_dir("elevation");
+    let model = mock(&dir, "fixture", &[("install ripgrep", "apt install ripgrep"), ("escalate", "sudo systemctl restart nginx")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+
+    let install = generator.generate("install ripgrep").unwrap().unwrap();
+    assert_eq!(elevation::detect(&install), Some(elevation::Elevation::Required));
+    let restart = generator.generate("escalate").unwrap().unwrap();
+    assert_eq!(elevation::detect(&restart), Some(elevation::Elevation::Requested));
+}
+
+#[cfg(unix)]
+#[test]
+fn generated_commands_run_in_the_sandbox_directory() {
+    let dir = scratch_dir("execute");
+    let model = mock(&dir, "fixture", &[("make a file", "echo hello > made.txt && cat made.txt")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+    let command = generator.generate("make a file").unwrap().unwrap();
+
+    let execution = Executor::new(Shell::BornAgainShell).in_dir(&dir).run(&command).unwrap();
+    assert!(execution.status.success());
+    assert_eq!(execution.stdout.trim(), "hello");
+    assert_eq!(fs::read_to_string(dir.join("made.txt")).unwrap(), "hello\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn output_limits_are_enforced() {
+    let dir = scratch_dir("limits");
+    let model = mock(&dir, "fixture", &[("*", "yes")]);
+    let generator = CommandGenerator::new(Config::new(model, 100));
+    let command = generator.generate("print forever").unwrap().unwrap();
+
+    let limits = ResourceLimits { output_bytes: Some(4096), ..ResourceLimits::default() };
+    let execution = Executor::new(Shell::BornAgainShell).in_dir(&dir).with_limits(limits).run(&command).unwrap();
+    assert!(execution.stdout.ends_with("[output limit reached]"));
+}
+
+#[cfg(unix)]
+#[test]
+fn commands_that_ignore_a_closed_pipe_are_stopped_at_the_limit() {
+    let dir = scratch_dir("limits-ignored");
+    let limits = ResourceLimits { output_bytes: Some(4096), ..ResourceLimits::default() };
+    let (tx, rx) = std::sync::mpsc::channel();
+    let executor = Executor::new(Shell::BornAgainShell).in_dir(&dir).with_limits(limits);
+    std::thread::spawn(move || {
+        let _ = tx.send(executor.run("trap '' PIPE; while :; do echo y; done 2>/dev/null"));
+    });
+    let execution = rx.recv_timeout(std::time::Duration::from_secs(10)).expect("the command was stopped").unwrap();
+    assert!(execution.stdout.ends_with("[output limit reached]"));
+    assert!(!execution.status.success());
+}
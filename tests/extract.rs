@@ -0,0 +1,116 @@
+//! The commands found in chat answers, on answers the way models write them.
+
+use llm_term::extract::{commands, find};
+
+#[test]
+fn inline_marker_as_the_prompt_asks() {
+    let reply = "Sure, I can help you list the files in that directory.\n\n`COMMAND: ls -la`\n\nWould you like me to execute this command?";
+    assert_eq!(commands(reply), ["ls -la"]);
+}
+
+#[test]
+fn several_commands_in_order() {
+    let reply = "First check the service, then restart it:\n\n`COMMAND: systemctl status nginx`\n\n`COMMAND: sudo systemctl restart nginx`\n\nShall I run them?";
+    assert_eq!(commands(reply), ["systemctl status nginx", "sudo systemctl restart nginx"]);
+}
+
+#[test]
+fn two_commands_on_one_line() {
+    let reply = "Either `COMMAND: du -sh *` or `COMMAND: ncdu` will show what takes the space.";
+    assert_eq!(commands(reply), ["du -sh *", "ncdu"]);
+}
+
+#[test]
+fn missing_closing_backtick() {
+    let reply = "This finds the big files:\n`COMMAND: find . -size +100M\nWant me to run it?";
+    assert_eq!(commands(reply), ["find . -size +100M"]);
+}
+
+#[test]
+fn bare_marker_without_backticks() {
+    let reply = "Here you go:\nCOMMAND: df -h\nShould I execute it?";
+    assert_eq!(commands(reply), ["df -h"]);
+}
+
+#[test]
+fn bold_marker() {
+    let reply = "**COMMAND:** `git log --oneline -5`\n\nWould you like me to run this?";
+    assert_eq!(commands(reply), ["git log --oneline -5"]);
+}
+
+#[test]
+fn marker_and_command_in_separate_code_spans() {
+    let reply = "Run `COMMAND:` `docker ps -a` to see every container.";
+    assert_eq!(commands(reply), ["docker ps -a"]);
+}
+
+#[test]
+fn fenced_block_under_the_marker() {
+    let reply = "I'll count the lines in each Rust file:\n\nCOMMAND:\n```bash\nwc -l src/*.rs\n```\n\nShall I run it?";
+    assert_eq!(commands(reply), ["wc -l src/*.rs"]);
+}
+
+#[test]
+fn fenced_block_on_the_marker_line() {
+    let reply = "COMMAND: ```sh\ntar -czf backup.tgz notes/\n```";
+    assert_eq!(commands(reply), ["tar -czf backup.tgz notes/"]);
+}
+
+#[test]
+fn marker_inside_a_fenced_block() {
+    let reply = "Here's the command:\n```\nCOMMAND: uname -a\n```\nWant me to run it?";
+    assert_eq!(commands(reply), ["uname -a"]);
+}
+
+#[test]
+fn nested_backticks_for_command_substitution() {
+    let reply = "This prints the date in the greeting: `COMMAND: echo \"Today is `date +%A`\"` Shall I run it?";
+    assert_eq!(commands(reply), ["echo \"Today is `date +%A`\""]);
+}
+
+#[test]
+fn nested_backticks_at_the_end() {
+    let reply = "`COMMAND: kill `pgrep -f server``";
+    assert_eq!(commands(reply), ["kill `pgrep -f server`"]);
+}
+
+#[test]
+fn double_backtick_span() {
+    let reply = "``COMMAND: cd `git rev-parse --show-toplevel` ``\n\nWould you like me to execute it?";
+    assert_eq!(commands(reply), ["cd `git rev-parse --show-toplevel`"]);
+}
+
+#[test]
+fn backticks_elsewhere_in_the_answer() {
+    let reply = "The `-h` flag makes sizes readable: `COMMAND: ls -lh` lists `~/Downloads` if you `cd` there first.";
+    assert_eq!(commands(reply), ["ls -lh"]);
+}
+
+#[test]
+fn quotes_and_pipes_are_kept() {
+    let reply = "`COMMAND: ps aux | awk '{print $2, $11}' | grep -v grep`";
+    assert_eq!(commands(reply), ["ps aux | awk '{print $2, $11}' | grep -v grep"]);
+}
+
+#[test]
+fn no_commands_in_conversation() {
+    assert!(commands("The ls command lists files; add -a to see hidden ones.").is_empty());
+    assert!(commands("EXECUTE_LAST_COMMAND").is_empty());
+    assert!(commands("`COMMAND: `").is_empty());
+}
+
+#[test]
+fn other_markers_ending_in_command_are_ignored() {
+    let reply = "SUBCOMMAND: status\n`COMMAND: git status`";
+    assert_eq!(commands(reply), ["git status"]);
+}
+
+#[test]
+fn spans_point_at_the_commands() {
+    let reply = "Try `COMMAND: ls -la` or\nCOMMAND:\n```\npwd\n```";
+    let found = find(reply);
+    assert_eq!(found.len(), 2);
+    for found in found {
+        assert_eq!(&reply[found.span], found.command);
+    }
+}